@@ -0,0 +1,624 @@
+//! Arbitrage opportunities between exchanges (same pair, two venues) and within an exchange
+//! (three markets forming a cycle), net of taker fees. Pairs are standardized across exchanges
+//! (see `types::Pair`) so that their quotes can be compared directly.
+
+use actix::{Actor, Context, Handler, Recipient};
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::{HashMap, HashSet};
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::helpers;
+use crate::types::*;
+
+/// Buying on an exchange and selling on another one
+#[derive(Debug, Clone)]
+pub struct CrossExchangeOpportunity {
+    /// UNIX timestamp in ms of the most recent quote used
+    pub event_ms: i64,
+    pub pair: Pair,
+    pub buy_exchange: Exchange,
+    pub sell_exchange: Exchange,
+    /// Average prices of the levels taken, before fees
+    pub buy_price: Price,
+    pub sell_price: Price,
+    /// Quantity that can be traded profitably, None when the quotes have no volume (tickers)
+    pub quantity: Option<Volume>,
+    /// Net profit in quote currency, None when the quantity is unknown
+    pub profit: Option<Amount>,
+    /// Net profit over the cost, 0.01 for 1%
+    pub profit_rate: BigDecimal,
+}
+
+/// An order of a triangular cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriangularLeg {
+    pub pair: Pair,
+    pub side: OrderSide,
+    /// Best ask for a buy, best bid for a sell
+    pub price: Price,
+}
+
+/// Converting an asset through three markets of an exchange back to itself
+#[derive(Debug, Clone)]
+pub struct TriangularOpportunity {
+    /// UNIX timestamp in ms of the most recent quote used
+    pub event_ms: i64,
+    pub exchange: Exchange,
+    /// Asset the cycle starts and ends with
+    pub start: Asset,
+    pub legs: Vec<TriangularLeg>,
+    /// Largest amount of `start` the best levels can take, None when they have no volume
+    pub start_quantity: Option<Amount>,
+    /// Net gain over the amount converted, 0.01 for 1%
+    pub profit_rate: BigDecimal,
+}
+
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub enum ArbitrageOpportunity {
+    CrossExchange(CrossExchangeOpportunity),
+    Triangular(TriangularOpportunity),
+}
+
+/// What identifies an opportunity while its terms change
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum OpportunityKey {
+    CrossExchange(Pair, Exchange, Exchange),
+    Triangular(Exchange, Vec<(Pair, OrderSide)>),
+}
+
+impl OpportunityKey {
+    /// Whether the opportunity is scanned again on a quote update of the pair on the exchange
+    fn is_scanned_with(&self, exchange: Exchange, pair: Pair) -> bool {
+        match self {
+            OpportunityKey::CrossExchange(p, _, _) => *p == pair,
+            OpportunityKey::Triangular(e, legs) => *e == exchange && legs.iter().any(|(p, _)| *p == pair),
+        }
+    }
+
+    fn involves(&self, exchange: Exchange) -> bool {
+        match self {
+            OpportunityKey::CrossExchange(_, buy, sell) => *buy == exchange || *sell == exchange,
+            OpportunityKey::Triangular(e, _) => *e == exchange,
+        }
+    }
+}
+
+impl ArbitrageOpportunity {
+    fn key(&self) -> OpportunityKey {
+        match self {
+            ArbitrageOpportunity::CrossExchange(o) => OpportunityKey::CrossExchange(o.pair, o.buy_exchange, o.sell_exchange),
+            ArbitrageOpportunity::Triangular(o) => OpportunityKey::Triangular(o.exchange, o.legs.iter().map(|l| (l.pair, l.side)).collect()),
+        }
+    }
+
+    /// Same prices, quantities and profit, whatever the time of the quotes
+    fn has_same_terms(&self, other: &ArbitrageOpportunity) -> bool {
+        match (self, other) {
+            (ArbitrageOpportunity::CrossExchange(a), ArbitrageOpportunity::CrossExchange(b)) => {
+                (&a.buy_price, &a.sell_price, &a.quantity, &a.profit_rate) == (&b.buy_price, &b.sell_price, &b.quantity, &b.profit_rate)
+            }
+            (ArbitrageOpportunity::Triangular(a), ArbitrageOpportunity::Triangular(b)) => {
+                (&a.legs, &a.start_quantity, &a.profit_rate) == (&b.legs, &b.start_quantity, &b.profit_rate)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Opportunities last sent, so that an opportunity is only sent again when its terms change
+#[derive(Debug, Default)]
+struct ReportedOpportunities {
+    reported: HashMap<OpportunityKey, ArbitrageOpportunity>,
+}
+
+impl ReportedOpportunities {
+    /// Opportunities found on a quote update of the pair on the exchange that are new or changed.
+    /// The ones that are no longer found are forgotten, they are sent again if they come back.
+    fn update(&mut self, exchange: Exchange, pair: Pair, found: Vec<ArbitrageOpportunity>) -> Vec<ArbitrageOpportunity> {
+        let found_keys: HashSet<OpportunityKey> = found.iter().map(ArbitrageOpportunity::key).collect();
+        self.reported.retain(|key, _| !key.is_scanned_with(exchange, pair) || found_keys.contains(key));
+        let mut changed = vec![];
+        for o in found {
+            let key = o.key();
+            if self.reported.get(&key).map_or(true, |reported| !reported.has_same_terms(&o)) {
+                self.reported.insert(key, o.clone());
+                changed.push(o);
+            }
+        }
+        changed
+    }
+
+    fn forget_exchange(&mut self, exchange: Exchange) {
+        self.reported.retain(|key, _| !key.involves(exchange));
+    }
+}
+
+/// What the scanner polls with `ArbitrageScanner::poll`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteSource {
+    /// Best bid and ask only, opportunities have no quantity
+    Ticker,
+    Orderbook,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbitrageSettings {
+    /// Taker fee rate by exchange (0.001 for 0.1%), missing exchanges have no fee
+    pub taker_fees: HashMap<Exchange, BigDecimal>,
+    /// Opportunities with a lower net profit rate are not reported
+    pub min_profit_rate: BigDecimal,
+    /// Quotes older than this are left out, they are all used when None
+    pub max_age_ms: Option<i64>,
+}
+
+impl Default for ArbitrageSettings {
+    fn default() -> Self {
+        ArbitrageSettings { taker_fees: HashMap::new(), min_profit_rate: BigDecimal::zero(), max_age_ms: None }
+    }
+}
+
+/// Levels of a market, the levels of tickers have no volume
+#[derive(Debug, Clone)]
+struct MarketQuote {
+    timestamp: i64,
+    asks: Vec<(Price, Option<Volume>)>,
+    bids: Vec<(Price, Option<Volume>)>,
+}
+
+/// Latest quotes of every exchange, fed by polling or by live events
+#[derive(Debug)]
+pub struct ArbitrageScanner {
+    settings: ArbitrageSettings,
+    quotes: HashMap<Pair, HashMap<Exchange, MarketQuote>>,
+}
+
+impl ArbitrageScanner {
+    pub fn new(settings: ArbitrageSettings) -> ArbitrageScanner {
+        ArbitrageScanner { settings, quotes: HashMap::new() }
+    }
+
+    /// Follow the levels of a book, they are sorted best first whatever the order of the exchange
+    pub fn update_book(&mut self, exchange: Exchange, book: &Orderbook) {
+        let levels = |levels: &[(Price, Volume)]| -> Vec<(Price, Option<Volume>)> { levels.iter().map(|(p, v)| (p.clone(), Some(v.clone()))).collect() };
+        let mut asks = levels(&book.asks);
+        let mut bids = levels(&book.bids);
+        asks.sort_by(|a, b| a.0.cmp(&b.0));
+        bids.sort_by(|a, b| b.0.cmp(&a.0));
+        self.insert(exchange, book.pair, MarketQuote { timestamp: book.timestamp, asks, bids });
+    }
+
+    pub fn update_ticker(&mut self, exchange: Exchange, ticker: &Ticker) {
+        self.insert(exchange, ticker.pair, MarketQuote {
+            timestamp: ticker.timestamp,
+            asks: vec![(ticker.lowest_ask.clone(), None)],
+            bids: vec![(ticker.highest_bid.clone(), None)],
+        });
+    }
+
+    pub fn update_best_bid_offer(&mut self, exchange: Exchange, bbo: &BestBidOffer) {
+        self.insert(exchange, bbo.pair, MarketQuote {
+            timestamp: bbo.event_ms,
+            asks: vec![(bbo.ask.clone(), bbo.ask_volume.clone())],
+            bids: vec![(bbo.bid.clone(), bbo.bid_volume.clone())],
+        });
+    }
+
+    fn insert(&mut self, exchange: Exchange, pair: Pair, quote: MarketQuote) {
+        self.quotes.entry(pair).or_default().insert(exchange, quote);
+    }
+
+    pub fn remove(&mut self, exchange: Exchange, pair: Pair) {
+        if let Some(quotes) = self.quotes.get_mut(&pair) {
+            quotes.remove(&exchange);
+        }
+    }
+
+    pub fn remove_exchange(&mut self, exchange: Exchange) {
+        for quotes in self.quotes.values_mut() {
+            quotes.remove(&exchange);
+        }
+    }
+
+    /// Follow the quotes of a live event, return the pair they changed.
+    /// Books that are out of sync or of a feed that is down are left out.
+    pub fn handle_event(&mut self, exchange: Exchange, event: &LiveEvent) -> Option<Pair> {
+        match event {
+            LiveEvent::LiveOrderbook(book) => {
+                self.update_book(exchange, book);
+                Some(book.pair)
+            }
+            LiveEvent::LiveTicker(ticker) => {
+                self.update_ticker(exchange, ticker);
+                Some(ticker.pair)
+            }
+            LiveEvent::LiveBestBidOffer(bbo) => {
+                self.update_best_bid_offer(exchange, bbo);
+                Some(bbo.pair)
+            }
+            LiveEvent::LiveBookState(state) if state.state != BookState::Synced => {
+                self.remove(exchange, state.pair);
+                Some(state.pair)
+            }
+            LiveEvent::LiveFeedStatus(status) if status.status == FeedStatus::Disconnected || status.status == FeedStatus::Stale => {
+                self.remove_exchange(exchange);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn fee(&self, exchange: Exchange) -> BigDecimal {
+        self.settings.taker_fees.get(&exchange).cloned().unwrap_or_else(BigDecimal::zero)
+    }
+
+    fn is_fresh(&self, quote: &MarketQuote, now: i64) -> bool {
+        self.settings.max_age_ms.map_or(true, |max_age| now - quote.timestamp <= max_age)
+    }
+
+    fn fresh_quotes(&self, pair: Pair, now: i64) -> Vec<(Exchange, &MarketQuote)> {
+        self.quotes.get(&pair).map_or(vec![], |quotes| quotes.iter()
+            .filter(|(_, q)| self.is_fresh(q, now))
+            .map(|(e, q)| (*e, q))
+            .collect())
+    }
+
+    /// Opportunities to buy the pair on an exchange and sell it on another one
+    pub fn cross_exchange(&self, pair: Pair) -> Vec<CrossExchangeOpportunity> {
+        let quotes = self.fresh_quotes(pair, helpers::get_unix_timestamp_ms());
+        let mut opportunities = vec![];
+        for (buy_exchange, buy) in &quotes {
+            for (sell_exchange, sell) in &quotes {
+                if buy_exchange == sell_exchange {
+                    continue;
+                }
+                if let Some(o) = cross(pair, (*buy_exchange, buy, self.fee(*buy_exchange)), (*sell_exchange, sell, self.fee(*sell_exchange))) {
+                    if o.profit_rate >= self.settings.min_profit_rate {
+                        opportunities.push(o);
+                    }
+                }
+            }
+        }
+        opportunities
+    }
+
+    /// Cycles of three markets of the exchange, each cycle is reported once per direction
+    pub fn triangular(&self, exchange: Exchange) -> Vec<TriangularOpportunity> {
+        self.triangular_cycles(exchange, None)
+    }
+
+    /// Cycles of three markets of the exchange that trade the pair
+    pub fn triangular_with(&self, exchange: Exchange, pair: Pair) -> Vec<TriangularOpportunity> {
+        self.triangular_cycles(exchange, Some(pair))
+    }
+
+    fn triangular_cycles(&self, exchange: Exchange, pair: Option<Pair>) -> Vec<TriangularOpportunity> {
+        let now = helpers::get_unix_timestamp_ms();
+        let fee = self.fee(exchange);
+        let one = BigDecimal::from(1);
+        // Rate, capacity in the input asset, order and quote timestamp by conversion
+        let mut conversions: HashMap<(Asset, Asset), (BigDecimal, Option<Amount>, TriangularLeg, i64)> = HashMap::new();
+        for (pair, quotes) in &self.quotes {
+            let quote = match quotes.get(&exchange) {
+                Some(quote) if self.is_fresh(quote, now) => quote,
+                _ => continue,
+            };
+            let symbol = Symbol::from(*pair);
+            if let Some((bid, volume)) = quote.bids.first() {
+                let rate = bid.clone() * (one.clone() - fee.clone());
+                let leg = TriangularLeg { pair: *pair, side: OrderSide::Sell, price: bid.clone() };
+                conversions.insert((symbol.base.clone(), symbol.quote.clone()), (rate, volume.clone(), leg, quote.timestamp));
+            }
+            if let Some((ask, volume)) = quote.asks.first() {
+                if ask.is_zero() {
+                    continue;
+                }
+                let cost = ask.clone() * (one.clone() + fee.clone());
+                let capacity = volume.clone().map(|v| v * cost.clone());
+                let leg = TriangularLeg { pair: *pair, side: OrderSide::Buy, price: ask.clone() };
+                conversions.insert((symbol.quote.clone(), symbol.base.clone()), (one.clone() / cost, capacity, leg, quote.timestamp));
+            }
+        }
+
+        let assets: HashSet<Asset> = conversions.keys().map(|(from, _)| from.clone()).collect();
+        // Every rotation of a cycle is the same cycle, it starts with its smallest asset
+        let cycles: Vec<[Asset; 3]> = match pair {
+            None => {
+                let mut cycles = vec![];
+                for a in &assets {
+                    for b in &assets {
+                        for c in &assets {
+                            if a < b && a < c && b != c {
+                                cycles.push([a.clone(), b.clone(), c.clone()]);
+                            }
+                        }
+                    }
+                }
+                cycles
+            }
+            // The two assets of the pair follow each other in the cycles that trade it
+            Some(pair) => {
+                let symbol = Symbol::from(pair);
+                assets.iter()
+                    .filter(|c| **c != symbol.base && **c != symbol.quote)
+                    .flat_map(|c| vec![[symbol.base.clone(), symbol.quote.clone(), c.clone()], [symbol.quote.clone(), symbol.base.clone(), c.clone()]])
+                    .map(|cycle| {
+                        let first = (0..3).min_by_key(|i| &cycle[*i]).unwrap_or(0);
+                        [cycle[first].clone(), cycle[(first + 1) % 3].clone(), cycle[(first + 2) % 3].clone()]
+                    })
+                    .collect()
+            }
+        };
+
+        let mut opportunities = vec![];
+        for [a, b, c] in cycles {
+            let legs = match (conversions.get(&(a.clone(), b.clone())), conversions.get(&(b.clone(), c.clone())), conversions.get(&(c.clone(), a.clone()))) {
+                (Some(l1), Some(l2), Some(l3)) => [l1, l2, l3],
+                _ => continue,
+            };
+            if let Some(pair) = pair {
+                if !legs.iter().any(|l| l.2.pair == pair) {
+                    continue;
+                }
+            }
+            let rate = legs[0].0.clone() * legs[1].0.clone() * legs[2].0.clone();
+            let profit_rate = rate - one.clone();
+            if profit_rate <= BigDecimal::zero() || profit_rate < self.settings.min_profit_rate {
+                continue;
+            }
+            // Capacities in the start asset, through the rates of the previous legs
+            let mut start_quantity: Option<Amount> = None;
+            let mut through = one.clone();
+            let mut depth_known = true;
+            for (leg_rate, capacity, _, _) in legs.iter() {
+                match capacity {
+                    Some(capacity) => {
+                        let in_start = capacity.clone() / through.clone();
+                        start_quantity = Some(match start_quantity {
+                            Some(q) => std::cmp::min(q, in_start),
+                            None => in_start,
+                        });
+                    }
+                    None => depth_known = false,
+                }
+                through = through * leg_rate.clone();
+            }
+            opportunities.push(TriangularOpportunity {
+                event_ms: legs.iter().map(|l| l.3).max().unwrap_or_default(),
+                exchange,
+                start: a,
+                legs: legs.iter().map(|l| l.2.clone()).collect(),
+                start_quantity: if depth_known { start_quantity } else { None },
+                profit_rate,
+            });
+        }
+        opportunities
+    }
+
+    /// Every opportunity of the quotes known
+    pub fn scan(&self) -> Vec<ArbitrageOpportunity> {
+        let mut opportunities: Vec<ArbitrageOpportunity> = self.quotes.keys()
+            .flat_map(|pair| self.cross_exchange(*pair))
+            .map(ArbitrageOpportunity::CrossExchange)
+            .collect();
+        let exchanges: HashSet<Exchange> = self.quotes.values().flat_map(|quotes| quotes.keys().cloned()).collect();
+        for exchange in exchanges {
+            opportunities.extend(self.triangular(exchange).into_iter().map(ArbitrageOpportunity::Triangular));
+        }
+        opportunities
+    }
+
+    /// Fetch the ticker or the book of the pairs on every exchange then scan them.
+    /// Pairs that cannot be fetched are left out.
+    pub async fn poll(&mut self, apis: &mut [(Exchange, Box<dyn ExchangeApi>)], pairs: &[Pair], source: QuoteSource) -> Vec<ArbitrageOpportunity> {
+        for (exchange, api) in apis.iter_mut() {
+            for &pair in pairs {
+                let result = match source {
+                    QuoteSource::Ticker => api.ticker(pair).await.map(|ticker| self.update_ticker(*exchange, &ticker)),
+                    QuoteSource::Orderbook => api.orderbook(pair).await.map(|book| self.update_book(*exchange, &book)),
+                };
+                if let Err(e) = result {
+                    debug!("Arbitrage : {:?} {:?} left out : {}", exchange, pair, e);
+                    self.remove(*exchange, pair);
+                }
+            }
+        }
+        self.scan()
+    }
+
+    /// Scan the live events sent to the returned recipient, opportunities involving the pair of
+    /// every quote update are sent to `recipients` when they appear or their terms change.
+    /// Must be called from a running actix system.
+    pub fn start(self, recipients: Vec<Recipient<ArbitrageOpportunity>>) -> Recipient<LiveEventEnveloppe> {
+        ArbitrageActor { scanner: self, reported: ReportedOpportunities::default(), recipients }.start().recipient()
+    }
+}
+
+/// Match the asks of an exchange with the bids of another one while it is profitable
+fn cross(pair: Pair, buy: (Exchange, &MarketQuote, BigDecimal), sell: (Exchange, &MarketQuote, BigDecimal)) -> Option<CrossExchangeOpportunity> {
+    let one = BigDecimal::from(1);
+    let (buy_exchange, buy_quote, buy_fee) = buy;
+    let (sell_exchange, sell_quote, sell_fee) = sell;
+    let buy_factor = one.clone() + buy_fee;
+    let sell_factor = one.clone() - sell_fee;
+    let event_ms = std::cmp::max(buy_quote.timestamp, sell_quote.timestamp);
+
+    let (first_ask, first_bid) = (buy_quote.asks.first()?, sell_quote.bids.first()?);
+    let top_cost = first_ask.0.clone() * buy_factor.clone();
+    let top_proceeds = first_bid.0.clone() * sell_factor.clone();
+    if top_proceeds <= top_cost || top_cost.is_zero() {
+        return None;
+    }
+    if first_ask.1.is_none() || first_bid.1.is_none() {
+        return Some(CrossExchangeOpportunity {
+            event_ms,
+            pair,
+            buy_exchange,
+            sell_exchange,
+            buy_price: first_ask.0.clone(),
+            sell_price: first_bid.0.clone(),
+            quantity: None,
+            profit: None,
+            profit_rate: top_proceeds / top_cost - one,
+        });
+    }
+
+    let (mut ai, mut bi) = (0, 0);
+    let mut ask_left = first_ask.1.clone().unwrap_or_else(BigDecimal::zero);
+    let mut bid_left = first_bid.1.clone().unwrap_or_else(BigDecimal::zero);
+    let (mut quantity, mut bought, mut sold) = (BigDecimal::zero(), BigDecimal::zero(), BigDecimal::zero());
+    while ai < buy_quote.asks.len() && bi < sell_quote.bids.len() {
+        let (ask, bid) = (&buy_quote.asks[ai].0, &sell_quote.bids[bi].0);
+        if bid.clone() * sell_factor.clone() <= ask.clone() * buy_factor.clone() {
+            break;
+        }
+        let taken = std::cmp::min(ask_left.clone(), bid_left.clone());
+        quantity = quantity + taken.clone();
+        bought = bought + ask.clone() * taken.clone();
+        sold = sold + bid.clone() * taken.clone();
+        ask_left = ask_left - taken.clone();
+        bid_left = bid_left - taken;
+        if ask_left.is_zero() {
+            ai += 1;
+            ask_left = buy_quote.asks.get(ai).and_then(|l| l.1.clone()).unwrap_or_else(BigDecimal::zero);
+        }
+        if bid_left.is_zero() {
+            bi += 1;
+            bid_left = sell_quote.bids.get(bi).and_then(|l| l.1.clone()).unwrap_or_else(BigDecimal::zero);
+        }
+    }
+    if quantity.is_zero() {
+        return None;
+    }
+    let cost = bought.clone() * buy_factor;
+    let proceeds = sold.clone() * sell_factor;
+    Some(CrossExchangeOpportunity {
+        event_ms,
+        pair,
+        buy_exchange,
+        sell_exchange,
+        buy_price: bought / quantity.clone(),
+        sell_price: sold / quantity.clone(),
+        quantity: Some(quantity),
+        profit: Some(proceeds.clone() - cost.clone()),
+        profit_rate: proceeds / cost - one,
+    })
+}
+
+struct ArbitrageActor {
+    scanner: ArbitrageScanner,
+    reported: ReportedOpportunities,
+    recipients: Vec<Recipient<ArbitrageOpportunity>>,
+}
+
+impl Actor for ArbitrageActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for ArbitrageActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        let LiveEventEnveloppe(exchange, event) = msg;
+        let pair = self.scanner.handle_event(exchange, &event);
+        if let LiveEvent::LiveFeedStatus(_) = event {
+            // The quotes of the exchange may have been dropped, its opportunities are sent again when they come back
+            self.reported.forget_exchange(exchange);
+        }
+        let pair = match pair {
+            Some(pair) => pair,
+            None => return,
+        };
+        let mut opportunities: Vec<ArbitrageOpportunity> = self.scanner.cross_exchange(pair).into_iter()
+            .map(ArbitrageOpportunity::CrossExchange)
+            .collect();
+        opportunities.extend(self.scanner.triangular_with(exchange, pair).into_iter().map(ArbitrageOpportunity::Triangular));
+        for o in self.reported.update(exchange, pair, opportunities) {
+            for r in &self.recipients {
+                if let Err(e) = r.do_send(o.clone()) {
+                    warn!("Arbitrage : error sending an opportunity : {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod arbitrage_tests {
+    use super::*;
+    use crate::test_util::{book, decimal};
+
+    #[test]
+    fn should_find_cross_exchange_opportunities_net_of_fees() {
+        let mut settings = ArbitrageSettings::default();
+        settings.taker_fees.insert(Exchange::Kraken, decimal("0.01"));
+        let mut scanner = ArbitrageScanner::new(settings);
+        scanner.update_book(Exchange::Kraken, &book(Pair::ZEC_BTC, &[("100", "1"), ("101", "2")], &[("99", "1")]));
+        scanner.update_book(Exchange::Poloniex, &book(Pair::ZEC_BTC, &[("110", "1")], &[("105", "1"), ("102.5", "5")]));
+
+        let opportunities = scanner.cross_exchange(Pair::ZEC_BTC);
+        assert_eq!(opportunities.len(), 1);
+        let o = &opportunities[0];
+        assert_eq!((o.buy_exchange, o.sell_exchange), (Exchange::Kraken, Exchange::Poloniex));
+        // 1 at 100 (101 with fees) sold at 105, then 2 at 101 (102.01 with fees) sold at 102.5
+        assert_eq!(o.quantity, Some(decimal("3")));
+        assert_eq!(o.profit, Some(decimal("4.98")));
+    }
+
+    #[test]
+    fn should_follow_live_books() {
+        let mut scanner = ArbitrageScanner::new(ArbitrageSettings::default());
+        scanner.update_book(Exchange::Kraken, &book(Pair::ZEC_BTC, &[("101", "2"), ("100", "1")], &[("99", "1"), ("98", "1")]));
+        scanner.update_book(Exchange::Poloniex, &book(Pair::ZEC_BTC, &[("110", "1")], &[("95", "1"), ("105", "1")]));
+
+        let opportunities = scanner.cross_exchange(Pair::ZEC_BTC);
+        assert_eq!(opportunities.len(), 1);
+        assert_eq!((opportunities[0].buy_price.clone(), opportunities[0].sell_price.clone()), (decimal("100"), decimal("105")));
+        assert_eq!(opportunities[0].quantity, Some(decimal("1")));
+
+        // Levels are sorted whatever the order of the exchange
+        let unsorted = Orderbook { bids: vec![(decimal("95"), decimal("1")), (decimal("105"), decimal("1"))], ..book(Pair::ZEC_BTC, &[("110", "1")], &[]) };
+        scanner.update_book(Exchange::Poloniex, &unsorted);
+        assert_eq!(scanner.cross_exchange(Pair::ZEC_BTC)[0].sell_price, decimal("105"));
+    }
+
+    #[test]
+    fn should_find_triangular_opportunities() {
+        let mut scanner = ArbitrageScanner::new(ArbitrageSettings::default());
+        scanner.update_book(Exchange::Kraken, &book(Pair::BTC_EUR, &[("10000", "1")], &[("9990", "1")]));
+        scanner.update_book(Exchange::Kraken, &book(Pair::ETH_BTC, &[("0.02", "10")], &[("0.0199", "10")]));
+        scanner.update_book(Exchange::Kraken, &book(Pair::ETH_EUR, &[("205", "10")], &[("204", "10")]));
+
+        let opportunities = scanner.triangular(Exchange::Kraken);
+        assert_eq!(opportunities.len(), 1);
+        let o = &opportunities[0];
+        // BTC to ETH at 0.02, ETH to EUR at 204, EUR to BTC at 10000
+        assert_eq!(o.start, Asset::new("BTC"));
+        assert_eq!(o.legs.iter().map(|l| (l.pair, l.side)).collect::<Vec<_>>(),
+                   vec![(Pair::ETH_BTC, OrderSide::Buy), (Pair::ETH_EUR, OrderSide::Sell), (Pair::BTC_EUR, OrderSide::Buy)]);
+        assert_eq!(o.profit_rate, decimal("0.02"));
+        // 0.2 BTC buy the 10 ETH of the best ask
+        assert_eq!(o.start_quantity, Some(decimal("0.2")));
+
+        assert_eq!(scanner.triangular_with(Exchange::Kraken, Pair::ETH_EUR).len(), 1);
+        assert!(scanner.triangular_with(Exchange::Kraken, Pair::ZEC_BTC).is_empty());
+    }
+
+    #[test]
+    fn should_report_new_or_changed_opportunities_only() {
+        let mut scanner = ArbitrageScanner::new(ArbitrageSettings::default());
+        let mut reported = ReportedOpportunities::default();
+        let mut update = |scanner: &mut ArbitrageScanner, kraken_ask: &str| {
+            scanner.update_book(Exchange::Kraken, &book(Pair::ZEC_BTC, &[(kraken_ask, "1")], &[("99", "1")]));
+            scanner.update_book(Exchange::Poloniex, &book(Pair::ZEC_BTC, &[("110", "1")], &[("105", "1")]));
+            let found = scanner.cross_exchange(Pair::ZEC_BTC).into_iter().map(ArbitrageOpportunity::CrossExchange).collect();
+            reported.update(Exchange::Kraken, Pair::ZEC_BTC, found).len()
+        };
+
+        assert_eq!(update(&mut scanner, "100"), 1);
+        assert_eq!(update(&mut scanner, "100"), 0);
+        assert_eq!(update(&mut scanner, "101"), 1);
+        // Gone then back
+        assert_eq!(update(&mut scanner, "106"), 0);
+        assert_eq!(update(&mut scanner, "101"), 1);
+    }
+}
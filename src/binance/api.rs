@@ -21,6 +21,7 @@ use crate::error::*;
 use crate::helpers;
 use crate::types::Pair;
 use crate::binance::utils;
+use crate::utils::pair_or;
 use crate::types::*;
 use hyper::client::HttpConnector;
 use futures::{TryFutureExt};
@@ -29,6 +30,7 @@ use crate::helpers::json;
 use binance::api::Binance;
 use binance::market::Market;
 use binance::account::Account;
+use binance::userstream::UserStream;
 
 #[derive(Debug, Clone)]
 pub struct BinanceApi {
@@ -71,6 +73,10 @@ impl BinanceApi {
         Binance::new(self.api_key.clone(), self.api_secret.clone())
     }
 
+    pub fn user_stream(&self) -> UserStream {
+        Binance::new(self.api_key.clone(), self.api_secret.clone())
+    }
+
     /// The number of calls in a given period is limited. In order to avoid a ban we limit
     /// by default the number of api requests.
     /// This function sets or removes the limitation.
@@ -79,6 +85,152 @@ impl BinanceApi {
     pub fn set_burst(&mut self, burst: bool) {
         self.burst = burst
     }
+
+    fn block_or_continue(&self) {
+        if ! self.burst {
+            let threshold: u64 = 50; // 1200 request weight per minute
+            let offset: u64 = helpers::get_unix_timestamp_ms() as u64 - self.last_request as u64;
+            if offset < threshold {
+                let wait_ms = Duration::from_millis(threshold - offset);
+                thread::sleep(wait_ms);
+            }
+        }
+    }
+
+    async fn public_query(&mut self, method: &str, params: &mut HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        helpers::strip_empties(params);
+        let url = format!("{}{}?{}", utils::REST_BASE_URL, method, helpers::url_encode_hashmap(params));
+        let url: Uri = url.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
+
+        self.block_or_continue();
+        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        self.last_request = helpers::get_unix_timestamp_ms();
+        let reader = buf.reader();
+        // Most market data endpoints return an array
+        let result = json::deserialize_json_or_array_r(reader)?;
+        utils::parse_result(&result)
+    }
+
+    async fn private_query(&mut self, method: &str, http_method: Method, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        let api_key = self.api_key.clone().ok_or(ErrorKind::BadCredentials)?;
+        let api_secret = self.api_secret.clone().ok_or(ErrorKind::BadCredentials)?;
+
+        let timestamp = helpers::get_unix_timestamp_ms().to_string();
+        let mut query_params: HashMap<&str, &str> = params.clone();
+        query_params.insert("timestamp", &timestamp);
+        helpers::strip_empties(&mut query_params);
+        let query = helpers::url_encode_hashmap(&query_params);
+        let signature = utils::build_signature(&query, &api_secret)?;
+        let url = format!("{}{}?{}&signature={}", utils::REST_BASE_URL, method, query, signature);
+
+        self.block_or_continue();
+        let req: Result<Request<Body>> = Request::builder()
+            .method(http_method)
+            .uri(url)
+            .header("X-MBX-APIKEY", api_key)
+            .header(USER_AGENT, "coinnect")
+            .body(Body::empty())
+            .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
+        let buf = self.http_client.request(req?).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        self.last_request = helpers::get_unix_timestamp_ms();
+        let reader = buf.reader();
+        let result = json::deserialize_json_or_array_r(reader)?;
+        utils::parse_result(&result)
+    }
+
+    /// Place an order, the parameters are the ones of `POST /api/v3/order` (symbol, side, type,
+    /// timeInForce, quantity, price, stopPrice, newClientOrderId, ...)
+    ///
+    /// Sample output :
+    ///
+    /// ```json
+    /// {"symbol":"BTCUSDT","orderId":28,"orderListId":-1,"clientOrderId":"6gCrw2kRUAF9CvJDGP16IP",
+    /// "transactTime":1507725176595}
+    /// ```
+    pub async fn new_order(&mut self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        let mut order_params = params.clone();
+        order_params.insert("newOrderRespType", "ACK");
+        self.private_query("order", Method::POST, &order_params).await
+    }
+
+    /// Sample output :
+    ///
+    /// ```json
+    /// {"timezone":"UTC","serverTime":1565246363776,"rateLimits":[...],"exchangeFilters":[],
+    /// "symbols":[{"symbol":"ETHBTC","status":"TRADING","baseAsset":"ETH","quoteAsset":"BTC",
+    /// "filters":[...], ...}, ...]}
+    /// ```
+    pub async fn return_exchange_info(&mut self) -> Result<Map<String, Value>> {
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        self.public_query("exchangeInfo", &mut params).await
+    }
+
+    /// Sample output :
+    ///
+    /// ```json
+    /// {"symbol":"LTCBTC","bidPrice":"4.00000000","bidQty":"431.00000000","askPrice":"4.00000200",
+    /// "askQty":"9.00000000"}
+    /// ```
+    pub async fn return_book_ticker(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+        let pair_name = &pair_or(Exchange::Binance, &pair)?;
+
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("symbol", pair_name);
+        self.public_query("ticker/bookTicker", &mut params).await
+    }
+
+    /// Sample output :
+    ///
+    /// ```json
+    /// {"symbol":"BNBBTC","priceChange":"-94.99999800","lastPrice":"4.00000200",
+    /// "bidPrice":"4.00000000","askPrice":"4.00000200","volume":"8913.30000000", ...}
+    /// ```
+    pub async fn return_24h_ticker(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+        let pair_name = &pair_or(Exchange::Binance, &pair)?;
+
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("symbol", pair_name);
+        self.public_query("ticker/24hr", &mut params).await
+    }
+
+    /// Sample output :
+    ///
+    /// ```json
+    /// [{"a":26129,"p":"0.01633102","q":"4.70443515","f":27781,"l":27781,"T":1498793709153,"m":true,"M":true}]
+    /// ```
+    pub async fn return_agg_trades(&mut self, pair: Pair, start_time: Option<i64>) -> Result<Map<String, Value>> {
+        let pair_name = &pair_or(Exchange::Binance, &pair)?;
+        let start_time = start_time.map(|t| t.to_string()).unwrap_or_default();
+
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("symbol", pair_name);
+        params.insert("startTime", &start_time);
+        self.public_query("aggTrades", &mut params).await
+    }
+
+    /// Sample output :
+    ///
+    /// ```json
+    /// [[1499040000000,"0.01634790","0.80000000","0.01575800","0.01577100","148976.11427815",
+    /// 1499644799999,"2434.19055334",308,"1756.87402397","28.46694368","0"]]
+    /// ```
+    pub async fn return_klines(&mut self,
+                               pair: Pair,
+                               interval: &str,
+                               start_time: Option<i64>,
+                               end_time: Option<i64>)
+                               -> Result<Map<String, Value>> {
+        let pair_name = &pair_or(Exchange::Binance, &pair)?;
+        let start_time = start_time.map(|t| t.to_string()).unwrap_or_default();
+        let end_time = end_time.map(|t| t.to_string()).unwrap_or_default();
+
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("symbol", pair_name);
+        params.insert("interval", interval);
+        params.insert("startTime", &start_time);
+        params.insert("endTime", &end_time);
+        self.public_query("klines", &mut params).await
+    }
 }
 
 
@@ -14,21 +14,24 @@ use binance::api::Binance;
 use binance::market::Market;
 use crate::utils::pair_or;
 use bigdecimal::{BigDecimal, ToPrimitive};
+use serde_json::Value;
+use std::collections::HashMap;
+use crate::binance::models::Filters;
 
 #[async_trait]
 impl ExchangeApi for BinanceApi {
     async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
-        let market = self.market();
+        // The best bid and ask come from the book ticker, the 24h stats only give the last trade
+        let book_ticker = self.return_book_ticker(pair).await?;
+        let stats = self.return_24h_ticker(pair).await?;
 
-        let pair_str = pair_or(Exchange::Binance, &pair)?;
-        let result =   market.get_24h_price_stats(*pair_str).await?;
         Ok(Ticker {
             timestamp: helpers::get_unix_timestamp_ms(),
             pair,
-            last_trade_price: BigDecimal::from(result.last_price),
-            lowest_ask: BigDecimal::from(result.low_price),
-            highest_bid: BigDecimal::from(result.high_price),
-            volume: Some(BigDecimal::from(result.volume)),
+            last_trade_price: helpers::from_json_bigdecimal(&stats["lastPrice"], "lastPrice")?,
+            lowest_ask: helpers::from_json_bigdecimal(&book_ticker["askPrice"], "askPrice")?,
+            highest_bid: helpers::from_json_bigdecimal(&book_ticker["bidPrice"], "bidPrice")?,
+            volume: Some(helpers::from_json_bigdecimal(&stats["volume"], "volume")?),
         })
     }
 
@@ -36,7 +39,7 @@ impl ExchangeApi for BinanceApi {
         let market = self.market();
         let pair_str = pair_or(Exchange::Binance, &pair)?;
 
-        let book_ticker = market.get_depth(*pair_str).await?;
+        let book_ticker = market.get_depth(pair_str.as_str()).await?;
 
         Ok(Orderbook {
             timestamp: helpers::get_unix_timestamp_ms(),
@@ -52,49 +55,303 @@ impl ExchangeApi for BinanceApi {
                        quantity: Volume,
                        price: Option<Price>)
                        -> Result<OrderInfo> {
-        let pair_str = *pair_or(Exchange::Binance, &pair)?;
-        let account = self.account();
-        let quantity_f64 = quantity.as_f64()?;
-        let result = match order_type {
-            OrderType::BuyLimit => {
-                if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
-                }
-                account.limit_buy(pair_str, quantity_f64, price.unwrap().as_f64()?).await
+        self.place_order(OrderRequest::from((order_type, pair, quantity, price))).await
+    }
+
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        order.check_prices()?;
+        let pair_str = pair_or(Exchange::Binance, &order.pair)?;
+        if order.reduce_only {
+            return Err(ErrorKind::Unsupported("reduce-only orders".to_string()).into());
+        }
+
+        let side = match order.side {
+            OrderSide::Buy => "BUY",
+            OrderSide::Sell => "SELL",
+        };
+        let time_in_force = match order.time_in_force {
+            TimeInForce::GoodTillCancel => "GTC",
+            TimeInForce::ImmediateOrCancel => "IOC",
+            TimeInForce::FillOrKill => "FOK",
+            TimeInForce::GoodTillDate(_) => {
+                return Err(ErrorKind::Unsupported("time in force GoodTillDate".to_string()).into())
             }
-            OrderType::BuyMarket => account.market_buy(pair_str, quantity_f64).await,
-            OrderType::SellLimit => {
-                if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
-                }
-                account.limit_sell(pair_str, quantity_f64, price.unwrap().as_f64()?).await
+        };
+        // Only orders resting in the book take a time in force
+        let (order_type_str, time_in_force) = match (order.kind, order.price.is_some(), order.post_only) {
+            (OrderKind::Limit, _, true) if time_in_force == "GTC" => ("LIMIT_MAKER", ""),
+            (OrderKind::Limit, _, false) => ("LIMIT", time_in_force),
+            (OrderKind::Market, _, false) => ("MARKET", ""),
+            (OrderKind::Stop, _, false) => ("STOP_LOSS", ""),
+            (OrderKind::StopLimit, _, false) => ("STOP_LOSS_LIMIT", time_in_force),
+            (OrderKind::TakeProfit, false, false) => ("TAKE_PROFIT", ""),
+            (OrderKind::TakeProfit, true, false) => ("TAKE_PROFIT_LIMIT", time_in_force),
+            (OrderKind::Limit, _, true) => {
+                return Err(ErrorKind::Unsupported("post-only with a time in force".to_string()).into())
+            }
+            (_, _, true) => {
+                return Err(ErrorKind::Unsupported("post-only orders other than limit".to_string()).into())
             }
-            OrderType::SellMarket => account.market_sell(pair_str, quantity_f64).await,
         };
 
+        let quantity = order.quantity.to_string();
+        let price = match order_type_str {
+            "MARKET" | "STOP_LOSS" | "TAKE_PROFIT" => "".to_string(),
+            _ => order.price.as_ref().map(|p| p.to_string()).unwrap_or_default(),
+        };
+        let stop_price = order.stop_price.as_ref().map(|p| p.to_string()).unwrap_or_default();
+        let client_order_id = order.client_order_id.clone().unwrap_or_default();
+
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("symbol", &pair_str);
+        params.insert("side", side);
+        params.insert("type", order_type_str);
+        params.insert("timeInForce", time_in_force);
+        params.insert("quantity", &quantity);
+        params.insert("price", &price);
+        params.insert("stopPrice", &stop_price);
+        params.insert("newClientOrderId", &client_order_id);
+        let result = self.new_order(&params).await?;
+
+        let order_id = result["orderId"]
+            .as_u64()
+            .ok_or_else(|| ErrorKind::MissingField("orderId".to_string()))?;
+        let client_order_id = result["clientOrderId"]
+            .as_str()
+            .ok_or_else(|| ErrorKind::MissingField("clientOrderId".to_string()))?;
+
         Ok(OrderInfo {
             timestamp: helpers::get_unix_timestamp_ms(),
-            identifier: vec![result?.client_order_id],
+            identifier: vec![order_id.to_string(), client_order_id.to_string()],
         })
     }
 
-    /// Return the balances for each currency on the account
+    /// Return the balances for each currency on the account, without the funds locked in open
+    /// orders (see `BinanceApi::locked_balances`)
     async fn balances(&mut self) -> Result<Balances> {
+        Ok(self.account_balances().await?.0)
+    }
+
+    async fn cancel_order(&mut self, pair: Pair, order: &OrderInfo) -> Result<OrderInfo> {
+        let pair_str = pair_or(Exchange::Binance, &pair)?;
+        let order_id = exchange_order_id(order)?;
+
+        let result = self.account().cancel_order(pair_str.as_str(), order_id).await?;
+
+        Ok(OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![result.order_id.to_string()],
+        })
+    }
+
+    async fn order_status(&mut self, pair: Pair, order: &OrderInfo) -> Result<Order> {
+        let pair_str = pair_or(Exchange::Binance, &pair)?;
+        let order_id = exchange_order_id(order)?;
+
+        let result = self.account().order_status(pair_str.as_str(), order_id).await?;
+
+        let mut status = to_order(pair, result)?;
+        status.identifier = order.identifier.clone();
+        Ok(status)
+    }
+
+    async fn open_orders(&mut self, pair: Pair) -> Result<Vec<Order>> {
+        let pair_str = pair_or(Exchange::Binance, &pair)?;
+
+        let result = self.account().get_open_orders(pair_str.as_str()).await?;
+
+        result.into_iter().map(|o| to_order(pair, o)).collect()
+    }
+
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>> {
+        let result = self.return_agg_trades(pair, since).await?;
+        let trade_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut trades = Vec::new();
+        for trade in trade_array {
+            let timestamp = trade["T"]
+                .as_i64()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat("T".to_string()))?;
+            // When the buyer is the maker, the taker sold
+            let tt = match trade["m"].as_bool() {
+                Some(true) => TradeType::Sell,
+                Some(false) => TradeType::Buy,
+                None => TradeType::None,
+            };
+
+            trades.push(Trade {
+                id: trade["a"].as_u64().map(|id| id.to_string()),
+                timestamp,
+                pair,
+                price: helpers::from_json_bigdecimal(&trade["p"], "p")?,
+                volume: helpers::from_json_bigdecimal(&trade["q"], "q")?,
+                tt,
+            });
+        }
+        Ok(trades)
+    }
+
+    async fn candles(&mut self,
+                     pair: Pair,
+                     interval: Interval,
+                     start: Option<i64>,
+                     end: Option<i64>)
+                     -> Result<Vec<Candle>> {
+        let result = self.return_klines(pair, utils::get_interval_string(interval), start, end).await?;
+        let kline_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut candles = Vec::new();
+        for kline in kline_array {
+            let timestamp = kline[0]
+                .as_i64()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat("openTime".to_string()))?;
+
+            candles.push(Candle {
+                timestamp,
+                pair,
+                interval,
+                open: helpers::from_json_bigdecimal(&kline[1], "open")?,
+                high: helpers::from_json_bigdecimal(&kline[2], "high")?,
+                low: helpers::from_json_bigdecimal(&kline[3], "low")?,
+                close: helpers::from_json_bigdecimal(&kline[4], "close")?,
+                volume: helpers::from_json_bigdecimal(&kline[5], "volume")?,
+            });
+        }
+        Ok(candles)
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        let result = self.return_exchange_info().await?;
+        let symbol_array = result["symbols"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["symbols"])))?;
+
+        let mut symbols = SymbolMap::new();
+        for symbol_obj in symbol_array {
+            let (symbol, name) = parse_symbol(symbol_obj)?;
+            symbols.insert(symbol, name);
+        }
+        symbols.register(Exchange::Binance);
+        Ok(symbols)
+    }
+
+    async fn markets(&mut self) -> Result<Markets> {
+        let result = self.return_exchange_info().await?;
+        let symbol_array = result["symbols"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["symbols"])))?;
+
+        // Commissions are account wide, in basis points
+        let fee_tier = match self.api_key {
+            Some(_) => {
+                let account = self.account().get_account().await?;
+                Some(FeeTier {
+                    maker: BigDecimal::from(account.maker_commission as f64) / BigDecimal::from(10000),
+                    taker: BigDecimal::from(account.taker_commission as f64) / BigDecimal::from(10000),
+                })
+            }
+            None => None,
+        };
+
+        let mut markets = Markets::new();
+        for symbol_obj in symbol_array {
+            let (symbol, name) = parse_symbol(symbol_obj)?;
+            let mut market = MarketInfo::new(symbol.clone(), name);
+            market.fee_tier = fee_tier.clone();
+
+            let filters = symbol_obj["filters"]
+                .as_array()
+                .ok_or_else(|| ErrorKind::MissingField("filters".to_string()))?;
+            for filter in filters {
+                // Filters the models do not know about are not trading rules we surface
+                match serde_json::from_value::<Filters>(filter.clone()) {
+                    Ok(Filters::PriceFilter { tick_size, .. }) => {
+                        market.price_tick = Some(tick_size.parse::<BigDecimal>()?);
+                    }
+                    Ok(Filters::LotSize { min_qty, max_qty, step_size }) => {
+                        market.min_quantity = Some(min_qty.parse::<BigDecimal>()?);
+                        market.max_quantity = Some(max_qty.parse::<BigDecimal>()?);
+                        market.quantity_step = Some(step_size.parse::<BigDecimal>()?);
+                    }
+                    Ok(Filters::MinNotional { min_notional, .. }) => {
+                        market.min_notional = Some(min_notional.parse::<BigDecimal>()?);
+                    }
+                    _ => (),
+                }
+            }
+            markets.insert(symbol, market);
+        }
+        Ok(markets)
+    }
+}
+
+/// Return the symbol and the Binance name of an `exchangeInfo` symbol
+fn parse_symbol(symbol: &Value) -> Result<(Symbol, &str)> {
+    let name = symbol["symbol"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("symbol".to_string()))?;
+    let base = symbol["baseAsset"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("baseAsset".to_string()))?;
+    let quote = symbol["quoteAsset"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("quoteAsset".to_string()))?;
+    Ok((Symbol::new(base, quote), name))
+}
+
+impl BinanceApi {
+    /// Return the funds locked in open orders for each currency on the account
+    pub async fn locked_balances(&mut self) -> Result<Balances> {
+        Ok(self.account_balances().await?.1)
+    }
+
+    /// Free and locked balances of the account
+    async fn account_balances(&mut self) -> Result<(Balances, Balances)> {
         let result = self.account().get_account().await?;
 
-        let mut balances = Balances::new();
+        let mut free = Balances::new();
+        let mut locked = Balances::new();
 
         for balance in result.balances {
-            let currency = utils::get_currency_enum(balance.asset.as_str());
-
-            match currency {
-                Some(c) => {
-                    balances.insert(c, balance.free.parse::<BigDecimal>()?);
-                },
-                _ => ()
+            if let Some(c) = utils::get_currency_enum(balance.asset.as_str()) {
+                free.insert(c, balance.free.parse::<BigDecimal>()?);
+                locked.insert(c, balance.locked.parse::<BigDecimal>()?);
             }
         }
 
-        Ok(balances)
+        Ok((free, locked))
     }
 }
+
+/// Binance needs the numeric order id, which is the first numeric identifier of the order
+fn exchange_order_id(order: &OrderInfo) -> Result<u64> {
+    order.identifier
+        .iter()
+        .filter_map(|id| id.parse::<u64>().ok())
+        .next()
+        .ok_or_else(|| ErrorKind::InvalidFieldValue("orderId".to_string()).into())
+}
+
+fn to_order(pair: Pair, order: binance::model::Order) -> Result<Order> {
+    let order_type = utils::get_order_type(order.side.as_str(), order.type_name.as_str())
+        .ok_or_else(|| ErrorKind::InvalidFieldFormat("type".to_string()))?;
+    let price = match order_type {
+        OrderType::BuyMarket | OrderType::SellMarket => None,
+        _ => Some(BigDecimal::from(order.price)),
+    };
+
+    Ok(Order {
+        identifier: vec![order.order_id.to_string(), order.client_order_id],
+        timestamp: order.time as i64,
+        pair,
+        order_type,
+        status: utils::get_order_status(order.status.as_str()),
+        price,
+        quantity: order.orig_qty.parse::<BigDecimal>()?,
+        executed_quantity: order.executed_qty.parse::<BigDecimal>()?,
+    })
+}
@@ -5,6 +5,7 @@ pub mod credentials;
 pub mod utils;
 pub mod generic_api;
 pub mod streaming_api;
+pub mod user_streaming_api;
 pub mod models;
 
 pub use self::credentials::BinanceCreds;
@@ -1,5 +1,6 @@
-use crate::types::{Channel, LiveEvent, TradeType};
+use crate::types::{Channel, LiveEvent, TradeType, Pair, Ticker, BestBidOffer, Candle, Interval};
 use crate::types;
+use crate::exchange::Exchange;
 use bigdecimal::BigDecimal;
 
 pub static OUTBOUND_ACCOUNT_INFO: &'static str = "outboundAccountInfo";
@@ -11,6 +12,8 @@ pub static DEPTH_ORDERBOOK : &'static str = "depthUpdate";
 pub static PARTIAL_ORDERBOOK : &'static str = "lastUpdateId";
 
 pub static DAYTICKER: &'static str = "24hrTicker";
+/// Method of the depth snapshots in feed records
+pub static DEPTH_SNAPSHOT: &'static str = "depthSnapshot";
 
 #[derive(Debug, Serialize, Deserialize, Message)]
 #[serde(tag = "e")]
@@ -27,7 +30,7 @@ pub enum Event {
     #[serde(alias = "lastUpdateId")]
     OrderBook(OrderBook),
     #[serde(alias = "24hrTicker")]
-    DayTicker(Vec<DayTickerEvent>),
+    DayTicker(DayTickerEvent),
     #[serde(alias = "kline")]
     Kline(KlineEvent),
     #[serde(alias = "depthUpdate")]
@@ -37,13 +40,16 @@ pub enum Event {
 impl Into<LiveEvent> for Event {
     fn into(self) -> LiveEvent {
         match self {
-            Event::Trade(e) => LiveEvent::LiveTrade(types::LiveTrade{
-                amount: e.qty.parse::<f32>().unwrap(),
-                event_ms: e.event_time as i64,
-                price: e.price.parse::<BigDecimal>().unwrap(),
-                tt: TradeType::Sell,
-                pair: e.symbol,
-            }),
+            Event::Trade(e) => match crate::utils::pair_enum(Exchange::Binance, e.symbol.as_str()) {
+                Some(pair) => LiveEvent::LiveTrade(types::LiveTrade{
+                    amount: e.qty.parse::<f32>().unwrap(),
+                    event_ms: e.event_time as i64,
+                    price: e.price.parse::<BigDecimal>().unwrap(),
+                    tt: TradeType::Sell,
+                    pair,
+                }),
+                None => LiveEvent::Noop,
+            },
             Event::DepthOrderBook(e) => {
                 let asks = e.asks;
                 let bids = e.bids;
@@ -55,7 +61,7 @@ impl Into<LiveEvent> for Event {
                         (a.price.into(), a.qty.into())
                     }).collect(),
                     timestamp: e.event_time as i64,
-                    pair: *super::utils::get_pair_enum(e.symbol.as_str()).unwrap(),
+                    pair: crate::utils::pair_enum(Exchange::Binance, e.symbol.as_str()).unwrap(),
                 })
             },
             _ => LiveEvent::Noop
@@ -71,13 +77,17 @@ pub struct Subscription {
     id: i32,
 }
 
-pub fn subscription(c: Channel, currency_pairs: Vec<&str>, id: i32) -> Subscription {
+pub fn subscription(c: Channel, currency_pairs: Vec<String>, id: i32) -> Subscription {
     let channel_str = match c {
-        Channel::LiveTrades => "trade",
-        Channel::LiveOrders => "orders",
-        Channel::LiveOrderBook => "depth@100ms",
-        Channel::LiveDetailOrderBook => "depth@100ms",
-        Channel::LiveFullOrderBook => "depth@100ms",
+        Channel::LiveTrades => "trade".to_string(),
+        // The account streams are read with a listen key, see `user_streaming_api`
+        Channel::LiveOrders | Channel::UserData => "orders".to_string(),
+        Channel::LiveOrderBook => "depth@100ms".to_string(),
+        Channel::LiveDetailOrderBook => "depth@100ms".to_string(),
+        Channel::LiveFullOrderBook => "depth@100ms".to_string(),
+        Channel::LiveTicker => "ticker".to_string(),
+        Channel::LiveBestBidOffer => "bookTicker".to_string(),
+        Channel::LiveCandles(interval) => format!("kline_{}", super::utils::get_interval_string(interval)),
     };
     Subscription {
         method: String::from("SUBSCRIBE"),
@@ -86,6 +96,10 @@ pub fn subscription(c: Channel, currency_pairs: Vec<&str>, id: i32) -> Subscript
     }
 }
 
+pub fn unsubscription(c: Channel, currency_pairs: Vec<String>, id: i32) -> Subscription {
+    Subscription { method: String::from("UNSUBSCRIBE"), ..subscription(c, currency_pairs, id) }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerTime {
@@ -245,6 +259,27 @@ pub struct Asks {
     ignore: Vec<String>,
 }
 
+/// Depth snapshot fetched over REST, recorded with the stream frames so that replays can sync books
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DepthSnapshot {
+    pub symbol: String,
+    pub last_update_id: u64,
+    /// (price, quantity) levels
+    pub asks: Vec<(f64, f64)>,
+    pub bids: Vec<(f64, f64)>,
+}
+
+impl DepthSnapshot {
+    pub fn asks(&self) -> Vec<(types::Price, types::Volume)> {
+        self.asks.iter().map(|&(p, q)| (BigDecimal::from(p), BigDecimal::from(q))).collect()
+    }
+
+    pub fn bids(&self) -> Vec<(types::Price, types::Volume)> {
+        self.bids.iter().map(|&(p, q)| (BigDecimal::from(p), BigDecimal::from(q))).collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct UserDataStream {
@@ -401,7 +436,7 @@ pub struct OrderTradeEvent {
 
     #[serde(rename = "n")] pub commission: String,
 
-    #[serde(skip, rename = "N")] pub asset_commisioned: Option<String>,
+    #[serde(rename = "N")] pub asset_commisioned: Option<String>,
 
     #[serde(rename = "T")] pub trade_order_time: u64,
 
@@ -490,6 +525,48 @@ pub struct DayTickerEvent {
     #[serde(rename = "n")] pub num_trades: u64,
 }
 
+impl DayTickerEvent {
+    pub fn ticker(&self, pair: Pair) -> Option<Ticker> {
+        Some(Ticker {
+            timestamp: self.event_time as i64,
+            pair,
+            last_trade_price: self.current_close.parse::<BigDecimal>().ok()?,
+            lowest_ask: self.best_ask.parse::<BigDecimal>().ok()?,
+            highest_bid: self.best_bid.parse::<BigDecimal>().ok()?,
+            volume: self.quote_volume.parse::<BigDecimal>().ok(),
+        })
+    }
+}
+
+/// Top of book of the `bookTicker` streams, sent without an event type nor time
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BookTickerEvent {
+    #[serde(rename = "u")] pub update_id: u64,
+
+    #[serde(rename = "s")] pub symbol: String,
+
+    #[serde(rename = "b")] pub best_bid: String,
+
+    #[serde(rename = "B")] pub best_bid_qty: String,
+
+    #[serde(rename = "a")] pub best_ask: String,
+
+    #[serde(rename = "A")] pub best_ask_qty: String,
+}
+
+impl BookTickerEvent {
+    pub fn best_bid_offer(&self, pair: Pair, event_ms: i64) -> Option<BestBidOffer> {
+        Some(BestBidOffer {
+            event_ms,
+            pair,
+            bid: self.best_bid.parse::<BigDecimal>().ok()?,
+            ask: self.best_ask.parse::<BigDecimal>().ok()?,
+            bid_volume: self.best_bid_qty.parse::<BigDecimal>().ok(),
+            ask_volume: self.best_ask_qty.parse::<BigDecimal>().ok(),
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct KlineEvent {
@@ -564,6 +641,21 @@ pub struct Kline {
     #[serde(skip, rename = "B")] pub ignore_me: String,
 }
 
+impl Kline {
+    pub fn candle(&self, pair: Pair, interval: Interval) -> Option<Candle> {
+        Some(Candle {
+            timestamp: self.start_time,
+            pair,
+            interval,
+            open: self.open.parse::<BigDecimal>().ok()?,
+            high: self.high.parse::<BigDecimal>().ok()?,
+            low: self.low.parse::<BigDecimal>().ok()?,
+            close: self.close.parse::<BigDecimal>().ok()?,
+            volume: self.volume.parse::<BigDecimal>().ok()?,
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DepthOrderBookEvent {
@@ -1,113 +1,193 @@
-use futures::stream::{SplitSink, FuturesUnordered};
-use crate::exchange_bot::{ExchangeBot, WsHandler, DefaultWsActor};
-use crate::types::{Channel, Pair, LiveEventEnveloppe, LiveAggregatedOrderBook, LiveEvent, Orderbook};
+use crate::exchange_bot::{ExchangeBot, WsHandler, WsWriter, DefaultWsActor, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, add_channel_pairs, remove_channel_pairs};
+use crate::types::{Channel, Pair, LiveEventEnveloppe, LiveEvent, BookState, BookUpdate, BookSettings, LiveBooks, LiveCandles, Orderbook};
+use crate::recorder::FeedRecorder;
 use std::collections::{HashSet, HashMap};
 use crate::coinnect::Credentials;
-use std::rc::Rc;
-use std::cell::RefCell;
 use crate::error::*;
 use std::time::Duration;
-use actix::io::SinkWrite;
-use awc::ws::{Message, Codec};
-use actix_codec::Framed;
-use awc::BoxedSocket;
+use awc::ws::Message;
 use bytes::Bytes;
 use bytes::Buf;
-use crate::exchange::{Exchange, ExchangeApi};
+use crate::exchange::Exchange;
 use super::models::*;
 use crate::binance::BinanceApi;
-use futures::Future;
-use std::task::Poll;
-use futures_util::{FutureExt, TryFutureExt, StreamExt};
-use std::sync::{Arc, RwLock};
-use std::borrow::Borrow;
-use tokio::sync::mpsc::{self, Receiver};
-
-use std::{thread, ptr};
-use actix::{Addr, Recipient, Context, Actor, AsyncContext};
-use actix_web::error::Canceled;
-use tokio::runtime::Runtime;
+use actix::{Addr, Recipient};
 use async_trait::async_trait;
 use bigdecimal::BigDecimal;
+use crate::utils;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 static WEBSOCKET_URL: &'static str = "wss://stream.binance.com:9443/ws";
 
+/// Attempts to fetch a depth snapshot before the book is flagged as stale, snapshots are still
+/// fetched afterwards until the book is synced
+const SNAPSHOT_ATTEMPTS: u32 = 3;
+/// Longest delay between two snapshot attempts
+const MAX_SNAPSHOT_BACKOFF: Duration = Duration::from_secs(60);
+
 pub struct BinanceBot {
-    addr: Addr<DefaultWsActor>
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+    books: LiveBooks,
 }
 
 impl ExchangeBot for BinanceBot {
-    fn is_connected(&self) -> bool {
-        unimplemented!()
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.full_order_book(pair)
     }
 }
 
 #[derive(Clone)]
 pub struct BinanceStreamingApi {
-    books: Arc<RefCell<HashMap<Pair, LiveAggregatedOrderBook>>>,
+    books: LiveBooks,
+    candles: LiveCandles,
     pub channels: HashMap<Channel, HashSet<Pair>>,
     pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
-    api: Arc<BinanceApi>,
+    /// Depth snapshots are fetched with it, replays have none and use the recorded ones
+    api: Option<BinanceApi>,
+    /// Records the depth snapshots fetched when the feed is recorded
+    snapshots: Option<Rc<RefCell<FeedRecorder>>>,
 }
 
 impl BinanceStreamingApi {
-    /// Create a new bittrex exchange bot, unavailable channels and currencies are ignored
-    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BinanceBot> {
-        let mut map = channels.clone();
-        let order_book_pairs: &HashSet<Pair> = map.entry(Channel::LiveFullOrderBook).or_default();
-        let trade_pairs: &HashSet<Pair> = map.entry(Channel::LiveTrades).or_default();
-        let api = BinanceStreamingApi {
+    /// Create a new binance exchange bot, unavailable channels and currencies are ignored
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, book_settings: BookSettings, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BinanceBot> {
+        let books = LiveBooks::new(book_settings);
+        let monitor = FeedMonitor::new(Exchange::Binance, channels.clone(), recipients.clone());
+        let snapshots = connection.record.clone().map(|record| FeedRecorder::new("BinanceSnapshots", Exchange::Binance, record));
+        let api = BinanceStreamingApi::new_handler(channels, books.clone(), Some(BinanceApi::new(*creds)?), snapshots, recipients);
+        let addr = DefaultWsActor::new("BinanceStream", WEBSOCKET_URL, Some(Duration::from_secs(30)), connection, monitor.clone(), Box::new(api)).await?;
+
+        return Ok(BinanceBot { addr, books, monitor });
+    }
+
+    /// Create the handler of the feed without connecting it, books are synced with the depth
+    /// snapshots of `api` or with recorded ones when there is none, see `replay::FeedReplay`
+    pub fn new_handler(channels: HashMap<Channel, HashSet<Pair>>, books: LiveBooks, api: Option<BinanceApi>, snapshots: Option<FeedRecorder>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> BinanceStreamingApi {
+        books.flush_to(Exchange::Binance, recipients.clone());
+        BinanceStreamingApi {
             recipients,
-            books: Arc::new(RefCell::new(HashMap::new())),
+            books,
+            candles: LiveCandles::default(),
             channels,
-            api: Arc::new(BinanceApi::new(*creds).unwrap()),
-        };
-        api.refresh_order_books().await;
-        let addr = DefaultWsActor::new("BinanceStream", WEBSOCKET_URL, Some(Duration::from_secs(30)), Box::new(api)).await?;
-
-        return Ok(BinanceBot { addr });
+            api,
+            snapshots: snapshots.map(|recorder| Rc::new(RefCell::new(recorder))),
+        }
     }
 
-    async fn refresh_order_books(&self) {
-            let mut orderbooks_futs: Vec<Receiver<Result<Orderbook>>> = Vec::new();
-            let mut arc3 = &mut &self.api.as_ref().clone();
-            for &pair in self.channels.get(&Channel::LiveFullOrderBook).unwrap() {
-                let mut arc = arc3.clone();
-                let (mut tx, mut rx) = mpsc::channel::<Result<Orderbook>>(100);
-                orderbooks_futs.push(rx);
-                tokio::spawn(async move {
-                    let r = arc.orderbook(pair.clone()).await;
-                    let r = r.map_err(|e| {
-                        info!("Binance : error fetching order book for {:?} : {:?}", pair, e);
-                        e
+    /// Fetch a depth snapshot in the background, diffs received meanwhile are buffered by the
+    /// book and applied once the snapshot is in.
+    /// Failed attempts are retried with an exponential backoff until the book is synced or
+    /// unsubscribed, the book is flagged as stale after `SNAPSHOT_ATTEMPTS`.
+    fn resync(&self, pair: Pair) {
+        let api = match self.api.clone() {
+            Some(api) => api,
+            None => return,
+        };
+        let snapshots = self.snapshots.clone();
+        let books = self.books.clone();
+        let recipients = self.recipients.clone();
+        actix_rt::spawn(async move {
+            let mut attempt: u32 = 0;
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                if !books.contains(pair) {
+                    // Unsubscribed meanwhile
+                    return;
+                }
+                match fetch_snapshot(&api, pair).await {
+                    Ok(snapshot) => {
+                        if let (Some(recorder), Ok(payload)) = (snapshots.as_ref(), serde_json::to_value(&snapshot)) {
+                            recorder.borrow_mut().record_method(DEPTH_SNAPSHOT, &payload);
+                        }
+                        if apply_snapshot(&books, &recipients, pair, &snapshot) {
+                            return;
+                        }
+                        debug!("Binance : snapshot for {:?} does not connect with the buffered diffs", pair);
+                    }
+                    Err(e) => info!("Binance : error fetching order book for {:?} : {:?}", pair, e),
+                }
+                attempt += 1;
+                if attempt == SNAPSHOT_ATTEMPTS {
+                    books.with_book(pair, |agg| {
+                        if agg.state == BookState::Resyncing && agg.mark_stale().is_some() {
+                            broadcast(&recipients, LiveEvent::LiveBookState(agg.book_state()));
+                        }
                     });
-                    tx.send(r).await;
-                });
+                }
+                tokio::time::delay_for(backoff).await;
+                backoff = std::cmp::min(backoff * 2, MAX_SNAPSHOT_BACKOFF);
             }
+        });
+    }
 
-            let ret: FuturesUnordered<_> = orderbooks_futs.iter_mut().map(|rx| rx.recv()).collect();
-            let ret2 : Vec<Option<Result<Orderbook>>> = ret.collect().await;
-            for ob in ret2.into_iter().filter(|&ref o| o.as_ref().and_then(|r| r.as_ref().ok()).is_some()) {
-                let ob = ob.unwrap().unwrap();
-                let mut books = self.books.borrow_mut();
-                let default_book = LiveAggregatedOrderBook::default(ob.pair);
-                let mut agg = books.entry(ob.pair).or_insert(default_book);
-                agg.reset_asks(ob.asks.into_iter());
-                agg.reset_bids(ob.bids.into_iter());
-                let latest_order_book: Orderbook = agg.order_book();
-                self.broadcast(LiveEvent::LiveOrderbook(latest_order_book.clone()));
-            }
+    /// Return the pair of a symbol if it is subscribed on the channel
+    fn subscribed_pair(&self, channel: Channel, symbol: &str) -> Option<Pair> {
+        let pair = utils::pair_enum(Exchange::Binance, symbol)?;
+        if self.channels.get(&channel).map_or(false, |pairs| pairs.contains(&pair)) {
+            Some(pair)
+        } else {
+            None
+        }
     }
 
     fn broadcast(&self, v: LiveEvent) {
-        let vec = self.recipients.clone();
-        if vec.len() == 0 as usize {
-            println!("{:?}", v);
-        } else {
-            for r in &vec {
-                let le: LiveEvent = v.clone();
-                r.do_send(LiveEventEnveloppe(Exchange::Binance, le));
-            }
+        broadcast(&self.recipients, v)
+    }
+}
+
+async fn fetch_snapshot(api: &BinanceApi, pair: Pair) -> Result<DepthSnapshot> {
+    let pair_str = utils::pair_or(Exchange::Binance, &pair)?;
+    let market = api.market();
+    let depth = market.get_depth(pair_str.as_str()).await?;
+    Ok(DepthSnapshot {
+        symbol: pair_str,
+        last_update_id: depth.last_update_id,
+        asks: depth.asks.into_iter().map(|a| (a.price, a.qty)).collect(),
+        bids: depth.bids.into_iter().map(|a| (a.price, a.qty)).collect(),
+    })
+}
+
+/// Apply a snapshot to a resyncing book, return false if it does not connect with the buffered diffs
+fn apply_snapshot(books: &LiveBooks, recipients: &[Recipient<LiveEventEnveloppe>], pair: Pair, snapshot: &DepthSnapshot) -> bool {
+    books.with_book(pair, |agg| {
+        if agg.state == BookState::Synced {
+            // Synced meanwhile by the snapshot of another attempt
+            return true;
+        }
+        if agg.apply_snapshot(Some(snapshot.last_update_id), snapshot.asks().into_iter(), snapshot.bids().into_iter()).is_some() {
+            broadcast(recipients, LiveEvent::LiveBookState(agg.book_state()));
+        }
+        if agg.state != BookState::Synced {
+            return false;
+        }
+        if let Some(ob) = agg.latest_order_book() {
+            broadcast(recipients, LiveEvent::LiveOrderbook(ob));
+        }
+        true
+    })
+}
+
+fn broadcast(recipients: &[Recipient<LiveEventEnveloppe>], v: LiveEvent) {
+    if recipients.is_empty() {
+        debug!("{:?}", v);
+    } else {
+        for r in recipients {
+            let le: LiveEvent = v.clone();
+            r.do_send(LiveEventEnveloppe(Exchange::Binance, le));
         }
     }
 }
@@ -115,49 +195,162 @@ impl BinanceStreamingApi {
 #[async_trait]
 impl WsHandler for BinanceStreamingApi {
     #[cfg_attr(feature = "flame_it", flame)]
-    fn handle_in(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes) {
+    fn handle_in(&mut self, _w: &mut dyn WsWriter, msg: Bytes) {
         let v: Result<Event> = serde_json::from_slice(msg.bytes()).map_err(|e| ErrorKind::Json(e).into());
         if v.is_err() {
+            // Book tickers are the only payloads without an event type
+            if let Ok(t) = serde_json::from_slice::<BookTickerEvent>(msg.bytes()) {
+                let now = self.books.clock().now_ms();
+                if let Some(bbo) = self.subscribed_pair(Channel::LiveBestBidOffer, &t.symbol).and_then(|pair| t.best_bid_offer(pair, now)) {
+                    self.broadcast(LiveEvent::LiveBestBidOffer(bbo));
+                }
+                return;
+            }
             return trace!("Binance : error {:?} deserializing {:?}", v.err().unwrap(), msg);
         }
         match v {
             Ok(Event::DepthOrderBook(ob)) => {
-                let pair = super::utils::get_pair_enum(ob.symbol.as_str());
-                if pair.is_none() {
+                let current_pair = match utils::pair_enum(Exchange::Binance, ob.symbol.as_str()) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                if !self.books.contains(current_pair) {
                     return;
                 }
-                let current_pair = *pair.unwrap();
-                let mut books = self.books.borrow_mut();
-                let default_book = LiveAggregatedOrderBook::default(current_pair);
-                let mut agg = books.entry(current_pair).or_insert(default_book);
-                agg.update_asks(ob.asks.into_iter().map(|a| (BigDecimal::from(a.price), BigDecimal::from(a.qty))));
-                agg.update_bids(ob.bids.into_iter().map(|a| (BigDecimal::from(a.price), BigDecimal::from(a.qty))));
-                agg.latest_order_book().map(|ob| self.broadcast(LiveEvent::LiveOrderbook(ob)));
+                let update = BookUpdate {
+                    update_ids: Some((ob.first_update_id, ob.final_update_id)),
+                    asks: ob.asks.into_iter().map(|a| (BigDecimal::from(a.price), BigDecimal::from(a.qty))).collect(),
+                    bids: ob.bids.into_iter().map(|a| (BigDecimal::from(a.price), BigDecimal::from(a.qty))).collect(),
+                };
+                let (state_change, latest) = self.books.with_book(current_pair, |agg| {
+                    (agg.apply_update(update).map(|_| agg.book_state()), agg.latest_order_book())
+                });
+                if let Some(state) = state_change {
+                    let resync = state.state == BookState::Resyncing;
+                    self.broadcast(LiveEvent::LiveBookState(state));
+                    if resync {
+                        self.resync(current_pair);
+                    }
+                }
+                if let Some(ob) = latest {
+                    self.broadcast(LiveEvent::LiveOrderbook(ob));
+                }
             }
             Ok(Event::Trade(t)) => {
                 let le : LiveEvent = Event::Trade(t).into();
                 self.broadcast(le)
             },
+            Ok(Event::Kline(e)) => {
+                let kline = e.kline;
+                let interval = self.channels.keys().find_map(|c| match c {
+                    Channel::LiveCandles(i) if super::utils::get_interval_string(*i) == kline.interval => Some(*i),
+                    _ => None,
+                });
+                let candle = interval
+                    .and_then(|interval| self.subscribed_pair(Channel::LiveCandles(interval), &kline.symbol).map(|pair| (pair, interval)))
+                    .and_then(|(pair, interval)| kline.candle(pair, interval));
+                if let Some(candle) = candle {
+                    for c in self.candles.update_candle(candle, kline.is_final_bar) {
+                        self.broadcast(LiveEvent::LiveCandle(c));
+                    }
+                }
+            }
+            Ok(Event::DayTicker(t)) => {
+                if let Some(ticker) = self.subscribed_pair(Channel::LiveTicker, &t.symbol).and_then(|pair| t.ticker(pair)) {
+                    self.broadcast(LiveEvent::LiveTicker(ticker));
+                }
+            }
             _ => return
         }
     }
 
-    fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
-        let rc = self.books.clone();
-
-        let mut books = rc.borrow_mut();
-
-        let x = self.channels.get(&Channel::LiveFullOrderBook).unwrap();
-        for &pair in x {
-            books.insert(pair.clone(), LiveAggregatedOrderBook::default(pair.clone()));
+    fn handle_started(&mut self, w: &mut dyn WsWriter) {
+        let book_pairs: Vec<Pair> = self.channels.get(&Channel::LiveFullOrderBook).map_or(vec![], |pairs| pairs.iter().cloned().collect());
+        for &pair in &book_pairs {
+            if let Some(state) = self.books.with_book(pair, |agg| agg.start_resync().map(|_| agg.book_state())) {
+                self.broadcast(LiveEvent::LiveBookState(state));
+            }
         }
-//        ctx.spawn(self.clone().refresh_order_books());
 
         for (k, v) in &self.channels {
-            let pairs = v.into_iter().map(|pair| *super::utils::get_pair_string(&pair).unwrap()).collect();
+            let pairs: Vec<String> = v.iter().filter_map(|pair| match utils::pair_name(Exchange::Binance, pair) {
+                Some(currency_pair) => Some(currency_pair),
+                None => {
+                    warn!("Binance : pair {:?} is not supported", pair);
+                    None
+                }
+            }).collect();
+            if pairs.is_empty() {
+                continue;
+            }
             info!("Binance : connecting to {:?} for {:?}", k, &pairs);
             let result = serde_json::to_string(&subscription(k.clone(), pairs, 1)).unwrap();
             w.write(Message::Text(result));
         }
+
+        // Diffs are buffered from now on, the snapshots must be more recent than the first one
+        for pair in book_pairs {
+            self.resync(pair);
+        }
+    }
+
+    fn handle_subscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let (pairs, unsupported): (HashSet<Pair>, HashSet<Pair>) = pairs.into_iter()
+            .partition(|pair| utils::pair_name(Exchange::Binance, pair).is_some());
+        if !unsupported.is_empty() {
+            warn!("Binance : pairs {:?} are not supported", unsupported);
+        }
+        let new_pairs: Vec<Pair> = add_channel_pairs(&mut self.channels, channel.clone(), pairs.clone());
+        if new_pairs.is_empty() {
+            return pairs;
+        }
+        let book_pairs: Vec<Pair> = if channel == Channel::LiveFullOrderBook { new_pairs.clone() } else { vec![] };
+        for &pair in &book_pairs {
+            if let Some(state) = self.books.with_book(pair, |agg| agg.start_resync().map(|_| agg.book_state())) {
+                self.broadcast(LiveEvent::LiveBookState(state));
+            }
+        }
+        let currency_pairs = new_pairs.iter().filter_map(|pair| utils::pair_name(Exchange::Binance, pair)).collect();
+        w.write(Message::Text(serde_json::to_string(&subscription(channel, currency_pairs, 1)).unwrap()));
+        for pair in book_pairs {
+            self.resync(pair);
+        }
+        pairs
+    }
+
+    fn handle_unsubscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let removed = remove_channel_pairs(&mut self.channels, channel.clone(), pairs);
+        if channel == Channel::LiveFullOrderBook {
+            for pair in &removed {
+                self.books.remove(*pair);
+            }
+        }
+        if let Channel::LiveCandles(interval) = channel {
+            for pair in &removed {
+                self.candles.remove(*pair, interval);
+            }
+        }
+        let currency_pairs: Vec<String> = removed.iter().filter_map(|pair| utils::pair_name(Exchange::Binance, pair)).collect();
+        if !currency_pairs.is_empty() {
+            w.write(Message::Text(serde_json::to_string(&unsubscription(channel, currency_pairs, 1)).unwrap()));
+        }
+        removed.into_iter().collect()
+    }
+
+    fn handle_recorded(&mut self, method: &str, payload: &str) {
+        if method != DEPTH_SNAPSHOT {
+            return;
+        }
+        let snapshot: DepthSnapshot = match serde_json::from_str(payload) {
+            Ok(snapshot) => snapshot,
+            Err(e) => return trace!("Binance : error {:?} deserializing a recorded snapshot", e),
+        };
+        let pair = match self.subscribed_pair(Channel::LiveFullOrderBook, &snapshot.symbol) {
+            Some(pair) if self.books.contains(pair) => pair,
+            _ => return,
+        };
+        if !apply_snapshot(&self.books, &self.recipients, pair, &snapshot) {
+            debug!("Binance : recorded snapshot for {:?} does not connect with the buffered diffs", pair);
+        }
     }
 }
@@ -0,0 +1,223 @@
+use crate::exchange_bot::{ExchangeBot, WsHandler, WsWriter, DefaultWsActor, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, Reconnect};
+use crate::types::{Channel, Pair, LiveEventEnveloppe, LiveEvent, OrderUpdate, Fill, BalanceUpdate, OrderSide};
+use std::collections::{HashSet, HashMap};
+use crate::coinnect::Credentials;
+use crate::error::*;
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use std::time::Duration;
+use bytes::Bytes;
+use bytes::Buf;
+use crate::exchange::Exchange;
+use super::models::*;
+use crate::binance::BinanceApi;
+use actix::{Addr, Recipient};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+
+static WEBSOCKET_URL: &'static str = "wss://stream.binance.com:9443/ws";
+
+/// Listen keys expire after an hour without keepalive
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+pub struct BinanceUserBot {
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+    /// Kept alive while the bot lives
+    _listen_key: Rc<RefCell<String>>,
+}
+
+impl ExchangeBot for BinanceUserBot {
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+}
+
+/// Orders, fills and balances of the account, from the user data stream of a listen key
+pub struct BinanceUserStreamingApi {
+    pairs: HashSet<Pair>,
+    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    api: BinanceApi,
+    listen_key: Weak<RefCell<String>>,
+    /// Set once connected, to reconnect with a new listen key
+    addr: Rc<RefCell<Option<Addr<DefaultWsActor>>>>,
+    /// The listen key is checked again on reconnections
+    connected: bool,
+}
+
+impl BinanceUserStreamingApi {
+    /// Create a new binance user data bot, order updates and fills are only sent for `pairs`
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, pairs: HashSet<Pair>, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BinanceUserBot> {
+        let api = BinanceApi::new(*creds)?;
+        let listen_key = Rc::new(RefCell::new(api.user_stream().start().await?.listen_key));
+        let addr_cell = Rc::new(RefCell::new(None));
+        keep_alive(api.clone(), &listen_key, addr_cell.clone());
+        let mut channels = HashMap::new();
+        channels.insert(Channel::UserData, pairs.clone());
+        let monitor = FeedMonitor::new(Exchange::Binance, channels, recipients.clone());
+        let handler = BinanceUserStreamingApi { pairs, recipients, api, listen_key: Rc::downgrade(&listen_key), addr: addr_cell.clone(), connected: false };
+        let url = stream_url(&listen_key.borrow());
+        let addr = DefaultWsActor::new("BinanceUserStream", &url, Some(Duration::from_secs(30)), connection, monitor.clone(), Box::new(handler)).await?;
+        *addr_cell.borrow_mut() = Some(addr.clone());
+        Ok(BinanceUserBot { addr, monitor, _listen_key: listen_key })
+    }
+
+    fn broadcast(&self, v: LiveEvent) {
+        if self.recipients.is_empty() {
+            debug!("{:?}", v);
+        } else {
+            for r in &self.recipients {
+                r.do_send(LiveEventEnveloppe(Exchange::Binance, v.clone()));
+            }
+        }
+    }
+
+    fn order_events(&self, e: OrderTradeEvent) -> Vec<LiveEvent> {
+        let pair = match crate::utils::pair_enum(Exchange::Binance, e.symbol.as_str()) {
+            Some(pair) if self.pairs.contains(&pair) => pair,
+            _ => return vec![],
+        };
+        let side = if e.side == "BUY" { OrderSide::Buy } else { OrderSide::Sell };
+        let order_id = e.order_id.to_string();
+        let mut events = vec![LiveEvent::LiveOrderUpdate(OrderUpdate {
+            event_ms: e.event_time as i64,
+            order_id: order_id.clone(),
+            pair,
+            status: super::utils::get_order_status(&e.order_status),
+            side: Some(side),
+            // Market orders have a zero price
+            price: e.price.parse::<BigDecimal>().ok().filter(|p| *p != BigDecimal::from(0)),
+            quantity: e.qty.parse::<BigDecimal>().ok(),
+            executed_quantity: e.accumulated_qty_filled_trades.parse::<BigDecimal>().ok(),
+        })];
+        if e.execution_type == "TRADE" {
+            match (e.price_last_filled_trade.parse::<BigDecimal>(), e.qty_last_filled_trade.parse::<BigDecimal>()) {
+                (Ok(price), Ok(quantity)) => events.push(LiveEvent::LiveFill(Fill {
+                    event_ms: e.trade_order_time as i64,
+                    order_id,
+                    trade_id: e.trade_id.to_string(),
+                    pair,
+                    side,
+                    price,
+                    quantity,
+                    fee: e.commission.parse::<BigDecimal>().ok(),
+                    fee_currency: e.asset_commisioned.as_ref().and_then(|asset| super::utils::get_currency_enum(asset)),
+                })),
+                _ => trace!("Binance : invalid fill in {:?}", e),
+            }
+        }
+        events
+    }
+}
+
+fn stream_url(listen_key: &str) -> String {
+    format!("{}/{}", WEBSOCKET_URL, listen_key)
+}
+
+/// Extend the validity of the listen key until the bot is dropped, a new key is requested when
+/// the keepalive fails
+fn keep_alive(api: BinanceApi, listen_key: &Rc<RefCell<String>>, addr: Rc<RefCell<Option<Addr<DefaultWsActor>>>>) {
+    let listen_key = Rc::downgrade(listen_key);
+    actix_rt::spawn(async move {
+        loop {
+            tokio::time::delay_for(KEEPALIVE_INTERVAL).await;
+            let key = match listen_key.upgrade() {
+                Some(key) => key,
+                None => return,
+            };
+            let current = key.borrow().clone();
+            if let Err(e) = api.user_stream().keep_alive(&current).await {
+                warn!("Binance : error keeping the user data stream alive, requesting a new listen key : {:?}", e);
+                renew_listen_key(&api, &key, &addr).await;
+            }
+        }
+    });
+}
+
+/// Request a listen key, the stream reconnects if it is not the current one. Binance returns the
+/// current key while it is valid.
+async fn renew_listen_key(api: &BinanceApi, listen_key: &RefCell<String>, addr: &RefCell<Option<Addr<DefaultWsActor>>>) {
+    let new_key = match api.user_stream().start().await {
+        Ok(answer) => answer.listen_key,
+        Err(e) => return warn!("Binance : error requesting a listen key : {:?}", e),
+    };
+    if *listen_key.borrow() == new_key {
+        return;
+    }
+    let url = stream_url(&new_key);
+    *listen_key.borrow_mut() = new_key;
+    if let Some(addr) = addr.borrow().as_ref() {
+        addr.do_send(Reconnect(url));
+    }
+}
+
+#[async_trait]
+impl WsHandler for BinanceUserStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, _w: &mut dyn WsWriter, msg: Bytes) {
+        let v: Result<Event> = serde_json::from_slice(msg.bytes()).map_err(|e| ErrorKind::Json(e).into());
+        match v {
+            Ok(Event::AccountUpdate(e)) => {
+                for b in e.balance {
+                    let currency = match super::utils::get_currency_enum(&b.asset) {
+                        Some(currency) => currency,
+                        None => continue,
+                    };
+                    if let (Ok(free), Ok(locked)) = (b.free.parse::<BigDecimal>(), b.locked.parse::<BigDecimal>()) {
+                        self.broadcast(LiveEvent::LiveBalanceUpdate(BalanceUpdate { event_ms: e.event_time as i64, currency, free, locked }));
+                    }
+                }
+            }
+            Ok(Event::OrderTrade(e)) => {
+                for event in self.order_events(e) {
+                    self.broadcast(event);
+                }
+            }
+            Ok(_) => (),
+            Err(e) => trace!("Binance : error {:?} deserializing {:?}", e, msg),
+        }
+    }
+
+    /// The listen key is part of the url, there is nothing to subscribe to. It may have expired
+    /// while disconnected, a new one is requested on reconnections.
+    fn handle_started(&mut self, _w: &mut dyn WsWriter) {
+        info!("Binance : connected to the user data stream for {:?}", &self.pairs);
+        if !self.connected {
+            self.connected = true;
+            return;
+        }
+        let listen_key = match self.listen_key.upgrade() {
+            Some(listen_key) => listen_key,
+            None => return,
+        };
+        let api = self.api.clone();
+        let addr = self.addr.clone();
+        actix_rt::spawn(async move {
+            renew_listen_key(&api, &listen_key, &addr).await;
+        });
+    }
+
+    fn handle_subscribe(&mut self, _w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel != Channel::UserData {
+            warn!("Binance : channel {:?} is not available on the user data stream", channel);
+            return HashSet::new();
+        }
+        self.pairs.extend(pairs.iter().cloned());
+        pairs
+    }
+
+    fn handle_unsubscribe(&mut self, _w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel != Channel::UserData {
+            return HashSet::new();
+        }
+        pairs.into_iter().filter(|pair| self.pairs.remove(pair)).collect()
+    }
+}
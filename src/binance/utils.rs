@@ -10,11 +10,14 @@ use serde_json::value::Map;
 use crate::error::*;
 use crate::helpers;
 use crate::types::Currency;
+use crate::types::{Asset, Interval, OrderStatus, OrderType, Symbol};
 use crate::types::Pair;
 use crate::types::Pair::*;
 
 const PAIRS_BYTES : &[u8] = include_bytes!("./PAIRS");
 
+pub const REST_BASE_URL: &str = "https://api.binance.com/api/v3/";
+
 lazy_static! {
     //curl https://api.binance.com//api/v3/exchangeInfo | jq -r '.symbols | .[].baseAsset + "_" + .[].quoteAsset + "," '
     //Big warning, binance uses lower case for websockets, and upper case symbols for the rest api
@@ -27,9 +30,13 @@ lazy_static! {
     static ref PAIRS_STRING: BidirMap<Pair, &'static str> = {
         let mut m = BidirMap::new();
         for (pair, b_pair) in &*ALL_BINANCE_PAIRS {
-            serde_json::from_str(format!("\"{}\"", pair).as_str()).map(|p_enum : Pair | {
-                m.insert(p_enum, b_pair.as_str());
-            });
+            // Pairs without a variant are kept as `Pair::Listed`
+            match pair.parse::<Symbol>() {
+                Ok(symbol) => {
+                    m.insert(Pair::from(symbol), b_pair.as_str());
+                }
+                Err(_) => trace!("Binance pair {} cannot be parsed", pair),
+            }
         }
         m
     };
@@ -47,6 +54,79 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
+/// Return the normalized order status associated to the one used by Binance
+pub fn get_order_status(status: &str) -> OrderStatus {
+    match status {
+        "NEW" | "PENDING_CANCEL" => OrderStatus::Open,
+        "PARTIALLY_FILLED" => OrderStatus::PartiallyFilled,
+        "FILLED" => OrderStatus::Filled,
+        "CANCELED" => OrderStatus::Canceled,
+        "REJECTED" => OrderStatus::Rejected,
+        "EXPIRED" => OrderStatus::Expired,
+        _ => OrderStatus::Unknown,
+    }
+}
+
+/// Return the order type associated to the side and type used by Binance
+pub fn get_order_type(side: &str, type_name: &str) -> Option<OrderType> {
+    match (side, type_name) {
+        ("BUY", "MARKET") => Some(OrderType::BuyMarket),
+        ("SELL", "MARKET") => Some(OrderType::SellMarket),
+        ("BUY", _) => Some(OrderType::BuyLimit),
+        ("SELL", _) => Some(OrderType::SellLimit),
+        _ => None,
+    }
+}
+
+/// Sign the query string of a private request, Binance expects the lowercase hex HMAC-SHA256
+pub fn build_signature(query: &str, api_secret: &str) -> Result<String> {
+    const C: &'static [u8] = b"0123456789abcdef";
+
+    let mut mac = Hmac::<Sha256>::new(api_secret.as_bytes());
+
+    mac.input(query.as_bytes());
+    let result = mac.result();
+
+    let raw_signature = result.code();
+    let mut signature = Vec::with_capacity(raw_signature.len() * 2);
+    for &byte in raw_signature {
+        signature.push(C[(byte >> 4) as usize]);
+        signature.push(C[(byte & 0xf) as usize]);
+    }
+    Ok(String::from_utf8(signature)?)
+}
+
+/// Return the kline interval string used by Binance
+pub fn get_interval_string(interval: Interval) -> &'static str {
+    match interval {
+        Interval::OneMinute => "1m",
+        Interval::FiveMinutes => "5m",
+        Interval::FifteenMinutes => "15m",
+        Interval::ThirtyMinutes => "30m",
+        Interval::OneHour => "1h",
+        Interval::TwoHours => "2h",
+        Interval::FourHours => "4h",
+        Interval::OneDay => "1d",
+        Interval::OneWeek => "1w",
+    }
+}
+
+/// If the response holds an error code, return the error message found in it
+/// else return the response
+pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
+    match (response.get("code"), response.get("msg")) {
+        (Some(code), Some(msg)) => {
+            let msg = msg.as_str().unwrap_or_default();
+            match code.as_i64() {
+                Some(-1003) => Err(ErrorKind::RateLimitExceeded.into()),
+                Some(-1121) => Err(ErrorKind::PairUnsupported.into()),
+                _ => Err(ErrorKind::ExchangeSpecificError(msg.to_string()).into()),
+            }
+        }
+        _ => Ok(response.clone()),
+    }
+}
+
 /// Return the currency enum associated with the
 /// string used by Bitstamp. If no currency is found,
 /// return None
@@ -60,8 +140,7 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
 /// assert_eq!(Some(Currency::USD), currency);
 /// ```
 pub fn get_currency_enum(currency: &str) -> Option<Currency> {
-    let c : Option<Currency> = serde_json::from_str(currency).ok();
-    c
+    Asset::new(currency).currency()
 }
 
 /// Return the currency string associated with the
@@ -77,5 +156,5 @@ pub fn get_currency_enum(currency: &str) -> Option<Currency> {
 /// assert_eq!(currency, Some("USD".to_string()));
 /// ```
 pub fn get_currency_string(currency: Currency) -> Option<String> {
-    serde_json::to_string(&currency).ok()
+    Some(Asset::from(currency).to_string())
 }
@@ -2,9 +2,8 @@
 //! Please see examples for more informations.
 
 
-use hyper::{Client, Body, Request, Uri, Method};
+use hyper::{Body, Request, Method};
 use hyper::header::CONTENT_TYPE;
-use hyper_tls::HttpsConnector;
 
 use serde_json::Value;
 use serde_json::value::Map;
@@ -12,27 +11,29 @@ use serde_json::value::Map;
 use std::collections::HashMap;
 use std::thread;
 use std::time::Duration;
+use std::sync::Arc;
 
 use crate::coinnect::Credentials;
 use crate::exchange::Exchange;
 
 use crate::error::*;
-use crate::helpers;
+use crate::helpers::{self, json};
 use crate::types::Pair;
 use crate::bitstamp::utils;
+use crate::utils::pair_or;
 use crate::types::*;
-use hyper::client::HttpConnector;
-use futures::{TryFutureExt};
+use crate::transport::{self, HttpClient, HttpTransport};
 use bytes::buf::BufExt as _;
-use awc::http::StatusCode;
+use hyper::StatusCode;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BitstampApi {
     last_request: i64, // unix timestamp in ms, to avoid ban
     api_key: String,
     api_secret: String,
     customer_id: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    transport: Arc<dyn HttpTransport>,
+    base_url: String,
     burst: bool,
 }
 
@@ -44,15 +45,13 @@ impl BitstampApi {
             return Err(ErrorKind::InvalidConfigType(Exchange::Bitstamp, creds.exchange()).into());
         }
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
-
         Ok(BitstampApi {
                last_request: 0,
                api_key: creds.get("api_key").unwrap_or_default(),
                api_secret: creds.get("api_secret").unwrap_or_default(),
                customer_id: creds.get("customer_id").unwrap_or_default(),
-               http_client: ssl,
+               transport: transport::default_transport(),
+               base_url: utils::BASE_URL.to_string(),
                burst: false, // No burst by default
            })
     }
@@ -83,14 +82,14 @@ impl BitstampApi {
             .get("method")
             .ok_or_else(|| "Missing \"method\" field.")?;
         let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let string = utils::build_url(method, pair);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
+        let url = utils::build_url_from(&self.base_url, method, pair);
 
         self.block_or_continue();
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.get(&url).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
-        utils::deserialize_json_r(reader)
+        let reader = resp.body.reader();
+        // transactions returns an array
+        json::deserialize_json_or_array_r(reader)
     }
 
     ///
@@ -110,7 +109,7 @@ impl BitstampApi {
             .get("method")
             .ok_or_else(|| "Missing \"method\" field.")?;
         let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let url: String = utils::build_url(method, pair);
+        let url: String = utils::build_url_from(&self.base_url, method, pair);
 
         let nonce = utils::generate_nonce(None);
         let signature =
@@ -132,18 +131,17 @@ impl BitstampApi {
 
         let req: Result<Request<Body>> = Request::builder()
             .method(Method::POST)
-            .uri(url)
+            .uri(url.as_str())
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
             .body(post_data.into())
             .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
         let req2 = req.unwrap();
-        let resp = self.http_client.request(req2).await?;
-        let code = resp.status().clone();
+        let resp = self.transport.send(req2).await?;
+        let code = resp.status;
         if code.is_client_error() && code == StatusCode::FORBIDDEN {
             return Err(ErrorKind::BadCredentials.into());
         }
-        let buf = hyper::body::aggregate(resp.into_body()).await?;
-        let reader = buf.reader();
+        let reader = resp.body.reader();
         let result = utils::deserialize_json_r(reader);
         result
     }
@@ -161,10 +159,7 @@ impl BitstampApi {
     /// ... }
     /// ```
     pub async fn return_ticker(&mut self, pair: Pair) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bitstamp, &pair)?;
 
         let mut params: HashMap<&str, &str> = HashMap::new();
         params.insert("pair", pair_name);
@@ -179,11 +174,7 @@ impl BitstampApi {
     /// [0.00006900,408], ... ], "timestamp": "1234567890"}
     /// ```
     pub async fn return_order_book(&mut self, pair: Pair) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-
-        };
+        let pair_name = &pair_or(Exchange::Bitstamp, &pair)?;
 
         let mut params: HashMap<&str, &str> = HashMap::new();
         params.insert("method", "order_book");
@@ -200,10 +191,7 @@ impl BitstampApi {
     /// "total":"0.04978"}, ... ]
     /// ```
     pub async fn return_trade_history(&mut self, pair: Pair) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bitstamp, &pair)?;
 
         let mut params: HashMap<&str, &str> = HashMap::new();
         params.insert("pair", pair_name);
@@ -211,6 +199,19 @@ impl BitstampApi {
         self.public_query(&params).await
     }
 
+    /// Sample output :
+    ///
+    /// ```json
+    /// [{"base_decimals": 8, "minimum_order": "5.0 USD", "name": "BTC/USD", "counter_decimals": 2,
+    /// "trading": "Enabled", "url_symbol": "btcusd", "description": "Bitcoin / U.S. dollar"}, ...]
+    /// ```
+    pub async fn return_trading_pairs_info(&mut self) -> Result<Map<String, Value>> {
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("pair", "");
+        params.insert("method", "trading-pairs-info");
+        self.public_query(&params).await
+    }
+
 
     /// Returns all of your available balances.
     ///
@@ -226,6 +227,20 @@ impl BitstampApi {
         self.private_query(&params).await
     }
 
+    /// Token used to subscribe to the private websocket channels
+    ///
+    /// Sample output:
+    ///
+    /// ```json
+    /// {"token": "...", "user_id": 123, "valid_sec": 60}
+    /// ```
+    pub async fn websockets_token(&mut self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("method", "websockets_token");
+        params.insert("pair", "");
+        self.private_query(&params).await
+    }
+
     /// Add a buy limit order to the exchange
     /// limit_price	: If the order gets executed, a new sell order will be placed,
     /// with "limit_price" as its price.
@@ -238,10 +253,7 @@ impl BitstampApi {
                      price_limit: Option<Price>,
                      daily_order: Option<bool>)
                      -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bitstamp, &pair)?;
 
         let amount_string = amount.to_string();
         let price_string = price.to_string();
@@ -277,10 +289,7 @@ impl BitstampApi {
                       price_limit: Option<Price>,
                       daily_order: Option<bool>)
                       -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bitstamp, &pair)?;
 
         let amount_string = format!("{:.8}", amount);
         let price_string = if pair == Pair::BTC_USD {
@@ -313,10 +322,7 @@ impl BitstampApi {
     /// on the market conditions and that these conditions may be subject to sudden changes
     /// that cannot be foreseen.
     pub async fn buy_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bitstamp, &pair)?;
 
         let amount_string = amount.to_string();
 
@@ -334,10 +340,7 @@ impl BitstampApi {
     /// on the market conditions and that these conditions may be subject to sudden changes
     /// that cannot be foreseen.
     pub async fn sell_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bitstamp, &pair)?;
 
         let amount_string = amount.to_string();
 
@@ -351,6 +354,16 @@ impl BitstampApi {
     }
 }
 
+impl HttpClient for BitstampApi {
+    fn set_transport(&mut self, transport: Arc<dyn HttpTransport>) {
+        self.transport = transport;
+    }
+
+    fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = transport::trim_base_url(base_url);
+    }
+}
+
 
 #[cfg(test)]
 mod bitstamp_api_tests {
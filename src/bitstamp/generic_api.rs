@@ -2,7 +2,7 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Bitstamp offers.
 
-use crate::exchange::{ExchangeApi};
+use crate::exchange::{ExchangeApi, Exchange};
 use crate::bitstamp::api::BitstampApi;
 use crate::bitstamp::utils;
 
@@ -10,6 +10,8 @@ use crate::error::*;
 use crate::types::*;
 use crate::helpers;
 use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde_json::Value;
 
 #[async_trait]
 impl ExchangeApi for BitstampApi {
@@ -133,4 +135,121 @@ impl ExchangeApi for BitstampApi {
 
         Ok(balances)
     }
+
+    /// Only plain limit and market orders are supported, see `OrderRequest::basic_order_type`
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        let order_type = order.basic_order_type()?;
+        self.add_order(order_type, order.pair, order.quantity, order.price).await
+    }
+
+    async fn cancel_order(&mut self, _pair: Pair, _order: &OrderInfo) -> Result<OrderInfo> {
+        Err(ErrorKind::Unsupported("cancel_order".to_string()).into())
+    }
+
+    async fn order_status(&mut self, _pair: Pair, _order: &OrderInfo) -> Result<Order> {
+        Err(ErrorKind::Unsupported("order_status".to_string()).into())
+    }
+
+    async fn open_orders(&mut self, _pair: Pair) -> Result<Vec<Order>> {
+        Err(ErrorKind::Unsupported("open_orders".to_string()).into())
+    }
+
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>> {
+        let raw_response = self.return_trade_history(pair).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let trade_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut trades = Vec::new();
+        // Most recent trades come first
+        for trade in trade_array.iter().rev() {
+            let date = trade["date"]
+                .as_str()
+                .and_then(|d| d.parse::<i64>().ok())
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat("date".to_string()))?;
+            let timestamp = date * 1000;
+            if since.map_or(false, |since| timestamp < since) {
+                continue;
+            }
+            let tt = match trade["type"].as_str() {
+                Some("0") => TradeType::Buy,
+                Some("1") => TradeType::Sell,
+                _ => TradeType::None,
+            };
+
+            trades.push(Trade {
+                id: trade["tid"].as_str().map(|id| id.to_string()),
+                timestamp,
+                pair,
+                price: helpers::from_json_bigdecimal(&trade["price"], "price")?,
+                volume: helpers::from_json_bigdecimal(&trade["amount"], "amount")?,
+                tt,
+            });
+        }
+        Ok(trades)
+    }
+
+    async fn candles(&mut self,
+                     _pair: Pair,
+                     _interval: Interval,
+                     _start: Option<i64>,
+                     _end: Option<i64>)
+                     -> Result<Vec<Candle>> {
+        Err(ErrorKind::Unsupported("candles".to_string()).into())
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        let raw_response = self.return_trading_pairs_info().await?;
+        let result = utils::parse_result(&raw_response)?;
+        let pair_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut symbols = SymbolMap::new();
+        for pair_obj in pair_array {
+            let (symbol, name) = parse_trading_pair(pair_obj)?;
+            symbols.insert(symbol, name);
+        }
+        symbols.register(Exchange::Bitstamp);
+        Ok(symbols)
+    }
+
+    async fn markets(&mut self) -> Result<Markets> {
+        let raw_response = self.return_trading_pairs_info().await?;
+        let result = utils::parse_result(&raw_response)?;
+        let pair_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut markets = Markets::new();
+        for pair_obj in pair_array {
+            let (symbol, name) = parse_trading_pair(pair_obj)?;
+            let mut market = MarketInfo::new(symbol.clone(), name);
+            if let Some(decimals) = pair_obj["counter_decimals"].as_u64() {
+                market.price_tick = Some(helpers::decimals_to_increment(decimals as u32));
+            }
+            if let Some(decimals) = pair_obj["base_decimals"].as_u64() {
+                market.quantity_step = Some(helpers::decimals_to_increment(decimals as u32));
+            }
+            // minimum_order is written "5.0 USD"
+            if let Some(minimum) = pair_obj["minimum_order"].as_str().and_then(|m| m.split_whitespace().next()) {
+                market.min_notional = Some(minimum.parse::<BigDecimal>()?);
+            }
+            markets.insert(symbol, market);
+        }
+        Ok(markets)
+    }
+}
+
+/// Return the symbol and the Bitstamp name of a `trading-pairs-info` entry
+fn parse_trading_pair(pair: &Value) -> Result<(Symbol, &str)> {
+    let name = pair["url_symbol"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("url_symbol".to_string()))?;
+    let symbol = pair["name"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("name".to_string()))?
+        .parse::<Symbol>()?;
+    Ok((symbol, name))
 }
@@ -6,5 +6,6 @@ pub mod credentials;
 pub mod utils;
 pub mod models;
 pub mod streaming_api;
+pub mod user_streaming_api;
 pub use self::credentials::BitstampCreds;
 pub use self::api::BitstampApi;
@@ -1,10 +1,11 @@
 use serde::{Serialize, Deserialize};
 use serde_json;
 use std::collections::HashSet;
-use crate::types::{LiveEvent, Pair, Channel};
+use crate::types::{LiveEvent, Pair, Channel, OrderSide, OrderStatus, BestBidOffer, Price, Volume};
 use crate::types;
 use bigdecimal::BigDecimal;
 use super::utils;
+use crate::exchange::Exchange;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LiveTrade {
@@ -21,6 +22,18 @@ pub struct LiveTrade {
     id: i64,
 }
 
+impl LiveTrade {
+    /// UNIX timestamp in ms
+    pub fn event_ms(&self) -> i64 {
+        self.microtimestamp.parse::<i64>().map(|us| us / 1000).unwrap_or_default()
+    }
+
+    /// Exact price and amount, from their string representations
+    pub fn price_volume(&self) -> Option<(Price, Volume)> {
+        Some((self.price_str.parse().ok()?, self.amount_str.parse().ok()?))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LiveOrderBook {
     microtimestamp: String,
@@ -29,6 +42,36 @@ pub struct LiveOrderBook {
     asks: Vec<(String, String)>,
 }
 
+impl LiveOrderBook {
+    /// Levels of an `order_book` snapshot, levels that do not parse are skipped
+    pub fn asks(&self) -> Vec<(BigDecimal, BigDecimal)> {
+        parse_levels(&self.asks)
+    }
+
+    pub fn bids(&self) -> Vec<(BigDecimal, BigDecimal)> {
+        parse_levels(&self.bids)
+    }
+
+    /// Top of an `order_book` snapshot
+    pub fn best_bid_offer(&self, pair: Pair) -> Option<BestBidOffer> {
+        let level = |l: &(String, String)| -> Option<(BigDecimal, BigDecimal)> { Some((l.0.parse().ok()?, l.1.parse().ok()?)) };
+        let (bid, bid_volume) = level(self.bids.first()?)?;
+        let (ask, ask_volume) = level(self.asks.first()?)?;
+        Some(BestBidOffer {
+            event_ms: self.microtimestamp.parse::<i64>().map(|us| us / 1000).ok()?,
+            pair,
+            bid,
+            ask,
+            bid_volume: Some(bid_volume),
+            ask_volume: Some(ask_volume),
+        })
+    }
+}
+
+fn parse_levels(levels: &[(String, String)]) -> Vec<(BigDecimal, BigDecimal)> {
+    levels.iter().filter_map(|(p, v)| Some((p.parse().ok()?, v.parse().ok()?))).collect()
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LiveOrder {
     id: i64,
@@ -44,7 +87,7 @@ pub struct LiveOrder {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Enveloppe<T> {
-    data: T,
+    pub data: T,
     pub channel: String,
 }
 
@@ -56,6 +99,8 @@ pub type PlainEvent = Enveloppe<serde_json::Value>;
 pub enum Event {
     #[serde(alias = "bts:subscription_succeeded")]
     SubSucceeded(PlainEvent),
+    #[serde(alias = "bts:unsubscription_succeeded")]
+    UnsubSucceeded(PlainEvent),
     #[serde(alias = "bts:request_reconnect")]
     ReconnectRequest(PlainEvent),
     #[serde(alias = "trade")]
@@ -76,13 +121,16 @@ impl Into<LiveEvent> for Event {
                 tt: e.data.order_type.into(),
                 pair: utils::get_pair_string(&Pair::BTC_USD).unwrap().to_string(),
             }),
-            Event::LiveTrade(e) => LiveEvent::LiveTrade(types::LiveTrade{
-                amount: e.data.amount,
-                event_ms: e.data.microtimestamp.parse::<i64>().unwrap(),
-                price: e.data.price.into(),
-                tt: e.data.ty.into(),
-                pair: utils::get_pair_string(&Pair::BTC_USD).unwrap().to_string(),
-            }),
+            Event::LiveTrade(e) => match channel_pair(&e.channel) {
+                Some(pair) => LiveEvent::LiveTrade(types::LiveTrade{
+                    amount: e.data.amount,
+                    event_ms: e.data.microtimestamp.parse::<i64>().unwrap(),
+                    price: e.data.price.into(),
+                    tt: e.data.ty.into(),
+                    pair,
+                }),
+                None => LiveEvent::Noop,
+            },
             Event::LiveFullOrderBook(e) => {
                 let asks = e.data.asks;
                 let bids = e.data.bids;
@@ -106,7 +154,10 @@ impl Into<LiveEvent> for Event {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Data {
-    channel: String
+    channel: String,
+    /// Websocket token, for private channels only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Message)]
@@ -116,22 +167,137 @@ pub struct Subscription {
     data: Data,
 }
 
-pub fn subscription(c: Channel, currency_pair: &str) -> Subscription {
-    let channel_str = match c {
-        Channel::LiveTrades => "live_trades",
-        Channel::LiveOrders => "live_orders",
-        Channel::LiveOrderBook => "order_book",
-        Channel::LiveDetailOrderBook => "detail_order_book",
-        Channel::LiveFullOrderBook => "diff_order_book",
-    };
-    Subscription {
+/// Return the name of the exchange channel of a channel, None if it is not available.
+/// Several channels can share the same exchange channel.
+pub fn channel_name(c: &Channel) -> Option<&'static str> {
+    match c {
+        // Candles are built from the trades
+        Channel::LiveTrades | Channel::LiveCandles(_) => Some("live_trades"),
+        Channel::LiveOrders => Some("live_orders"),
+        // Private channels also need the user id and a token, see `private_subscriptions`
+        Channel::UserData => Some("private-my_orders"),
+        // The top of the `order_book` snapshots
+        Channel::LiveOrderBook | Channel::LiveBestBidOffer => Some("order_book"),
+        Channel::LiveDetailOrderBook => Some("detail_order_book"),
+        Channel::LiveFullOrderBook => Some("diff_order_book"),
+        Channel::LiveTicker => None,
+    }
+}
+
+/// Return the subscription message for a channel, None if the channel is not available
+pub fn subscription(c: Channel, currency_pair: &str) -> Option<Subscription> {
+    Some(Subscription {
         event: String::from("bts:subscribe"),
         data: Data {
-            channel: format!("{}_{}", channel_str, currency_pair)
+            channel: format!("{}_{}", channel_name(&c)?, currency_pair),
+            auth: None,
         },
+    })
+}
+
+pub fn unsubscription(c: Channel, currency_pair: &str) -> Option<Subscription> {
+    subscription(c, currency_pair).map(|sub| Subscription { event: String::from("bts:unsubscribe"), ..sub })
+}
+
+/// Return the pair of a public channel name such as `order_book_btcusd`
+pub fn channel_pair(channel: &str) -> Option<Pair> {
+    crate::utils::pair_enum(Exchange::Bitstamp, channel.rsplitn(2, '_').next()?)
+}
+
+/// Order of the account, from a `private-my_orders` channel
+#[derive(Deserialize, Debug)]
+pub struct MyOrder {
+    pub id: i64,
+    /// 0 for buy orders, 1 for sell orders
+    pub order_type: i64,
+    pub microtimestamp: String,
+    /// Remaining amount
+    pub amount_str: String,
+    pub price_str: String,
+    pub amount_traded: Option<String>,
+    pub amount_at_create: Option<String>,
+}
+
+impl MyOrder {
+    pub fn side(&self) -> OrderSide {
+        if self.order_type == 0 { OrderSide::Buy } else { OrderSide::Sell }
     }
 }
 
+/// Trade of the account, from a `private-my_trades` channel
+#[derive(Deserialize, Debug)]
+pub struct MyTrade {
+    pub id: i64,
+    pub order_id: i64,
+    pub amount: String,
+    pub price: String,
+    pub fee: String,
+    pub side: String,
+    pub microtimestamp: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event")]
+pub enum PrivateEvent {
+    #[serde(rename = "order_created")]
+    OrderCreated(Enveloppe<MyOrder>),
+    #[serde(rename = "order_changed")]
+    OrderChanged(Enveloppe<MyOrder>),
+    #[serde(rename = "order_deleted")]
+    OrderDeleted(Enveloppe<MyOrder>),
+    #[serde(rename = "trade")]
+    Trade(Enveloppe<MyTrade>),
+    #[serde(rename = "bts:request_reconnect")]
+    ReconnectRequest,
+    /// Subscription acknowledgements and errors
+    #[serde(other)]
+    Other,
+}
+
+impl PrivateEvent {
+    /// Status of the order after this event, deleted orders with a remaining amount were canceled
+    pub fn order_status(&self) -> Option<OrderStatus> {
+        match self {
+            PrivateEvent::OrderCreated(_) => Some(OrderStatus::Open),
+            PrivateEvent::OrderChanged(_) => Some(OrderStatus::PartiallyFilled),
+            PrivateEvent::OrderDeleted(e) => match e.data.amount_str.parse::<BigDecimal>() {
+                Ok(remaining) if remaining == BigDecimal::from(0) => Some(OrderStatus::Filled),
+                _ => Some(OrderStatus::Canceled),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Return the pair of a private channel name such as `private-my_orders_btcusd-123`
+pub fn private_channel_pair(channel: &str) -> Option<Pair> {
+    let name = channel.rsplitn(2, '-').last()?;
+    let currency_pair = name.rsplitn(2, '_').next()?;
+    crate::utils::pair_enum(Exchange::Bitstamp, currency_pair)
+}
+
+/// Subscriptions to the orders and trades of the account on a pair
+pub fn private_subscriptions(currency_pair: &str, user_id: i64, token: &str) -> Vec<Subscription> {
+    ["private-my_orders", "private-my_trades"].iter()
+        .map(|channel| Subscription {
+            event: String::from("bts:subscribe"),
+            data: Data {
+                channel: format!("{}_{}-{}", channel, currency_pair, user_id),
+                auth: Some(token.to_string()),
+            },
+        })
+        .collect()
+}
+
+pub fn private_unsubscriptions(currency_pair: &str, user_id: i64) -> Vec<Subscription> {
+    private_subscriptions(currency_pair, user_id, "").into_iter()
+        .map(|sub| Subscription {
+            event: String::from("bts:unsubscribe"),
+            data: Data { auth: None, ..sub.data },
+        })
+        .collect()
+}
+
 lazy_static! {
     static ref PAIRS : HashSet<&'static str> = vec!["btcusd", "btceur", "eurusd", "xrpusd", "xrpeur", "xrpbtc", "ltcusd", "ltceur", "ltcbtc", "ethusd", "etheur", "ethbtc", "bchusd", "bcheur", "bchbtc"].into_iter().collect();
 }
@@ -145,6 +311,34 @@ mod model_tests {
         let _v: Event = serde_json::from_slice(b"{\"data\": {\"microtimestamp\": \"1577146143220559\", \"amount\": 0.00434678, \"buy_order_id\": 4481152330, \"sell_order_id\": 4481152280, \"amount_str\": \"0.00434678\", \"price_str\": \"7312.91\", \"timestamp\": \"1577146143\", \"price\": 7312.91, \"type\": 0, \"id\": 102177815}, \"event\": \"trade\", \"channel\": \"live_trades_btcusd\"}").unwrap();
     }
 
+    #[test]
+    fn best_bid_offer_of_order_book() {
+        let v: Event = serde_json::from_slice(b"{\"data\": {\"timestamp\": \"1577146143\", \"microtimestamp\": \"1577146143220559\", \"bids\": [[\"7312.90\", \"0.5\"], [\"7312.00\", \"1.2\"]], \"asks\": [[\"7313.10\", \"0.2\"]]}, \"event\": \"data\", \"channel\": \"order_book_btcusd\"}").unwrap();
+        match v {
+            Event::LiveFullOrderBook(e) => {
+                assert_eq!(channel_pair(&e.channel), Some(Pair::BTC_USD));
+                let bbo = e.data.best_bid_offer(Pair::BTC_USD).unwrap();
+                assert_eq!(bbo.event_ms, 1577146143220);
+                assert_eq!(bbo.bid, "7312.90".parse::<BigDecimal>().unwrap());
+                assert_eq!(bbo.ask_volume, Some("0.2".parse::<BigDecimal>().unwrap()));
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_private_order() {
+        let v: PrivateEvent = serde_json::from_slice(b"{\"data\": {\"id\": 1440, \"id_str\": \"1440\", \"order_type\": 1, \"datetime\": \"1577146143\", \"microtimestamp\": \"1577146143220559\", \"amount\": 0.0, \"amount_str\": \"0.00000000\", \"amount_traded\": \"1.0\", \"amount_at_create\": \"1.0\", \"price\": 7312.91, \"price_str\": \"7312.91\"}, \"event\": \"order_deleted\", \"channel\": \"private-my_orders_btcusd-123\"}").unwrap();
+        assert_eq!(v.order_status(), Some(OrderStatus::Filled));
+        match v {
+            PrivateEvent::OrderDeleted(e) => {
+                assert_eq!(private_channel_pair(&e.channel), Some(Pair::BTC_USD));
+                assert_eq!(e.data.side(), OrderSide::Sell);
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
     #[test]
     fn deserialize_sub_succeeded() {
         let _v: Event = serde_json::from_slice(b"{\"data\": {\"microtimestamp\": \"1577146143220559\", \"amount\": 0.00434678, \"buy_order_id\": 4481152330, \"sell_order_id\": 4481152280, \"amount_str\": \"0.00434678\", \"price_str\": \"7312.91\", \"timestamp\": \"1577146143\", \"price\": 7312.91, \"type\": 0, \"id\": 102177815}, \"event\": \"trade\", \"channel\": \"live_trades_btcusd\"}").unwrap();
@@ -1,28 +1,41 @@
 use crate::coinnect::Credentials;
-use crate::exchange_bot::{DefaultWsActor, WsHandler, ExchangeBot};
+use crate::exchange_bot::{DefaultWsActor, WsHandler, WsWriter, ExchangeBot, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, add_channel_pairs, remove_channel_pairs, candle_intervals};
 use crate::error::*;
 use super::models::*;
 use bytes::Bytes;
 use bytes::Buf;
-use futures::stream::{SplitSink};
-use actix::{io::SinkWrite, Addr, Recipient, Context, Actor};
+use actix::{Addr, Recipient, Context, Actor};
 use awc::{
-    ws::{Codec, Message},  BoxedSocket
+    ws::Message
 };
-use actix_codec::{Framed};
-use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair};
+use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair, LiveCandles, LiveBooks, BookSettings, Orderbook};
+use crate::utils;
 use crate::exchange::Exchange;
 use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 use async_trait::async_trait;
 
 pub struct BitstampBot {
-    addr: Addr<DefaultWsActor>
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+    books: LiveBooks,
 }
 
 impl ExchangeBot for BitstampBot {
-    fn is_connected(&self) -> bool {
-        unimplemented!()
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.full_order_book(pair)
     }
 }
 
@@ -33,55 +46,179 @@ pub struct BitstampStreamingApi {
     customer_id: String,
     pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
     channels: HashMap<Channel, HashSet<Pair>>,
+    books: LiveBooks,
+    candles: LiveCandles,
 }
 
 impl BitstampStreamingApi {
-    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BitstampBot> {
-        let api = BitstampStreamingApi {
-            api_key: creds.get("api_key").unwrap_or_default(),
-            api_secret: creds.get("api_secret").unwrap_or_default(),
-            customer_id: creds.get("customer_id").unwrap_or_default(),
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, book_settings: BookSettings, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BitstampBot> {
+        let books = LiveBooks::new(book_settings);
+        let monitor = FeedMonitor::new(Exchange::Bitstamp, channels.clone(), recipients.clone());
+        let mut api = BitstampStreamingApi::new_handler(channels, books.clone(), recipients);
+        api.api_key = creds.get("api_key").unwrap_or_default();
+        api.api_secret = creds.get("api_secret").unwrap_or_default();
+        api.customer_id = creds.get("customer_id").unwrap_or_default();
+        let addr = DefaultWsActor::new("BitstampStream", "wss://ws.bitstamp.net", Some(Duration::from_secs(5)), connection, monitor.clone(), Box::new(api)).await?;
+        Ok(BitstampBot { addr, books, monitor })
+    }
+
+    /// Create the handler of the feed without connecting it, see `replay::FeedReplay`
+    pub fn new_handler(channels: HashMap<Channel, HashSet<Pair>>, books: LiveBooks, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> BitstampStreamingApi {
+        books.flush_to(Exchange::Bitstamp, recipients.clone());
+        BitstampStreamingApi {
+            api_key: String::new(),
+            api_secret: String::new(),
+            customer_id: String::new(),
             recipients,
             channels,
-        };
-        let addr = DefaultWsActor::new("BitstampStream", "wss://ws.bitstamp.net", Some(Duration::from_secs(5)), Box::new(api)).await?;
-        Ok(BitstampBot { addr })
+            books,
+            candles: LiveCandles::default(),
+        }
+    }
+
+    fn is_subscribed(&self, channel: Channel, pair: &Pair) -> bool {
+        self.channels.get(&channel).map_or(false, |pairs| pairs.contains(pair))
+    }
+
+    fn broadcast(&self, v: LiveEvent) {
+        let vec = self.recipients.clone();
+        if vec.len() == 0 as usize {
+            debug!("{:?}", v);
+        } else {
+            for r in &vec {
+                let le: LiveEvent = v.clone();
+                r.do_send(LiveEventEnveloppe(Exchange::Bitstamp, le));
+            }
+        }
     }
 }
 
 #[async_trait]
 impl WsHandler for BitstampStreamingApi {
     #[cfg_attr(feature = "flame_it", flame)]
-    fn handle_in(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes) {
-        let v : Event = serde_json::from_slice(msg.bytes()).unwrap();
+    fn handle_in(&mut self, w: &mut dyn WsWriter, msg: Bytes) {
+        let v: Event = match serde_json::from_slice(msg.bytes()) {
+            Ok(v) => v,
+            Err(e) => return trace!("Bitstamp : error {:?} deserializing {:?}", e, msg),
+        };
         match v {
-            Event::ReconnectRequest(_) =>  {
-                self.handle_started(w);
-            },
-            Event::SubSucceeded(_) => (),
-            o => {
-                let vec = self.recipients.clone();
-                if vec.len() == 0 as usize {
-                    debug!("{:?}", o);
-                } else {
-                    let le : LiveEvent = o.into();
-                    for r in &vec {
-                        let le : LiveEvent = le.clone();
-                        r.do_send(LiveEventEnveloppe(Exchange::Bitstamp, le));
+            // Subscriptions are sent again on the new connection
+            Event::ReconnectRequest(_) => w.reconnect(),
+            Event::SubSucceeded(_) | Event::UnsubSucceeded(_) => (),
+            Event::LiveTrade(e) => {
+                let pair = match channel_pair(&e.channel) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                if let Some((price, volume)) = e.data.price_volume() {
+                    let intervals = candle_intervals(&self.channels, &pair);
+                    for c in self.candles.add_trade(pair, &intervals, e.data.event_ms(), &price, &volume) {
+                        self.broadcast(LiveEvent::LiveCandle(c));
                     }
                 }
-            },
+                if self.is_subscribed(Channel::LiveTrades, &pair) {
+                    self.broadcast(Event::LiveTrade(e).into());
+                }
+            }
+            Event::LiveFullOrderBook(e) if e.channel.starts_with("order_book_") => {
+                let pair = match channel_pair(&e.channel) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                if self.is_subscribed(Channel::LiveBestBidOffer, &pair) {
+                    if let Some(bbo) = e.data.best_bid_offer(pair) {
+                        self.broadcast(LiveEvent::LiveBestBidOffer(bbo));
+                    }
+                }
+                if self.is_subscribed(Channel::LiveOrderBook, &pair) {
+                    // Each message is a snapshot of the top of the book
+                    let (asks, bids) = (e.data.asks(), e.data.bids());
+                    let (state, latest) = self.books.with_book(pair, |agg| {
+                        (agg.apply_snapshot(None, asks.into_iter(), bids.into_iter()).map(|_| agg.book_state()), agg.latest_order_book())
+                    });
+                    if let Some(state) = state {
+                        self.broadcast(LiveEvent::LiveBookState(state));
+                    }
+                    if let Some(ob) = latest {
+                        self.broadcast(LiveEvent::LiveOrderbook(ob));
+                    }
+                }
+            }
+            o => self.broadcast(o.into()),
         };
     }
 
+    fn handle_tick(&mut self, now_ms: i64) {
+        for c in self.candles.close_over(now_ms) {
+            self.broadcast(LiveEvent::LiveCandle(c));
+        }
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
-    fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
+    fn handle_started(&mut self, w: &mut dyn WsWriter) {
         for (k, v) in self.channels.clone() {
             for pair in v {
-                let result = serde_json::to_string(&subscription(k.clone(), *super::utils::get_pair_string(&pair).unwrap())).unwrap();
+                let currency_pair = match utils::pair_name(Exchange::Bitstamp, &pair) {
+                    Some(currency_pair) => currency_pair,
+                    None => {
+                        warn!("Bitstamp : pair {:?} is not supported", pair);
+                        continue;
+                    }
+                };
+                match subscription(k.clone(), &currency_pair) {
+                    Some(sub) => {
+                        let result = serde_json::to_string(&sub).unwrap();
+                        w.write(Message::Binary(result.into()));
+                    }
+                    None => warn!("Bitstamp : channel {:?} is not available", k),
+                }
+            }
+
+        }
+    }
+
+    fn handle_subscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel_name(&channel).is_none() {
+            warn!("Bitstamp : channel {:?} is not available", channel);
+            return HashSet::new();
+        }
+        let (pairs, unsupported): (HashSet<Pair>, HashSet<Pair>) = pairs.into_iter()
+            .partition(|pair| utils::pair_name(Exchange::Bitstamp, pair).is_some());
+        if !unsupported.is_empty() {
+            warn!("Bitstamp : pairs {:?} are not supported", unsupported);
+        }
+        for pair in add_channel_pairs(&mut self.channels, channel.clone(), pairs.clone()) {
+            let currency_pair = utils::pair_name(Exchange::Bitstamp, &pair).unwrap();
+            if let Some(sub) = subscription(channel.clone(), &currency_pair) {
+                let result = serde_json::to_string(&sub).unwrap();
                 w.write(Message::Binary(result.into()));
             }
+        }
+        pairs
+    }
 
+    fn handle_unsubscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let removed = remove_channel_pairs(&mut self.channels, channel.clone(), pairs);
+        match channel {
+            Channel::LiveCandles(interval) => for pair in &removed {
+                self.candles.remove(*pair, interval);
+            },
+            Channel::LiveOrderBook => for pair in &removed {
+                self.books.remove(*pair);
+            },
+            _ => (),
+        }
+        // Pairs still subscribed on a channel sharing the same exchange channel stay subscribed
+        let name = channel_name(&channel);
+        for pair in &removed {
+            if self.channels.iter().any(|(c, pairs)| channel_name(c) == name && pairs.contains(pair)) {
+                continue;
+            }
+            if let Some(sub) = utils::pair_name(Exchange::Bitstamp, pair).and_then(|currency_pair| unsubscription(channel.clone(), &currency_pair)) {
+                let result = serde_json::to_string(&sub).unwrap();
+                w.write(Message::Binary(result.into()));
+            }
         }
+        removed.into_iter().collect()
     }
 }
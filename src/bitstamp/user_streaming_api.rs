@@ -0,0 +1,211 @@
+use crate::coinnect::Credentials;
+use crate::exchange_bot::{DefaultWsActor, WsHandler, WsWriter, ExchangeBot, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, ClientCommand};
+use crate::error::*;
+use super::models::*;
+use super::BitstampApi;
+use bytes::Bytes;
+use bytes::Buf;
+use actix::{Addr, Recipient};
+use awc::{
+    ws::Message
+};
+use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair, OrderUpdate, Fill, OrderSide, OrderStatus};
+use crate::exchange::Exchange;
+use crate::utils;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::Duration;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+
+pub struct BitstampUserBot {
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+}
+
+impl ExchangeBot for BitstampUserBot {
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+}
+
+/// Orders and trades of the account, private channels are subscribed with a token fetched
+/// from the REST api on every connection
+pub struct BitstampUserStreamingApi {
+    api: BitstampApi,
+    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    pairs: HashSet<Pair>,
+    /// Set once connected, subscriptions are sent when the token is received
+    addr: Rc<RefCell<Option<Addr<DefaultWsActor>>>>,
+    /// Account id, part of the private channel names
+    user_id: Rc<Cell<Option<i64>>>,
+}
+
+impl BitstampUserStreamingApi {
+    /// Create a new bitstamp user data bot, order updates and fills are only sent for `pairs`
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, pairs: HashSet<Pair>, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BitstampUserBot> {
+        let mut channels = HashMap::new();
+        channels.insert(Channel::UserData, pairs.clone());
+        let monitor = FeedMonitor::new(Exchange::Bitstamp, channels, recipients.clone());
+        let addr_cell = Rc::new(RefCell::new(None));
+        let handler = BitstampUserStreamingApi {
+            api: BitstampApi::new(*creds)?,
+            recipients,
+            pairs,
+            addr: addr_cell.clone(),
+            user_id: Rc::new(Cell::new(None)),
+        };
+        let addr = DefaultWsActor::new("BitstampUserStream", "wss://ws.bitstamp.net", Some(Duration::from_secs(5)), connection, monitor.clone(), Box::new(handler)).await?;
+        *addr_cell.borrow_mut() = Some(addr.clone());
+        Ok(BitstampUserBot { addr, monitor })
+    }
+
+    /// Fetch a token in the background and subscribe to the private channels of `pairs`
+    fn subscribe_pairs(&self, pairs: Vec<Pair>) {
+        if pairs.is_empty() {
+            return;
+        }
+        let mut api = self.api.clone();
+        let addr = self.addr.clone();
+        let user_id_cell = self.user_id.clone();
+        actix_rt::spawn(async move {
+            let (user_id, token) = match api.websockets_token().await {
+                Ok(r) => match (r.get("user_id").and_then(|v| v.as_i64()), r.get("token").and_then(|v| v.as_str())) {
+                    (Some(user_id), Some(token)) => (user_id, token.to_string()),
+                    _ => return warn!("Bitstamp : invalid websockets token {:?}", r),
+                },
+                Err(e) => return warn!("Bitstamp : error fetching a websockets token : {:?}", e),
+            };
+            user_id_cell.set(Some(user_id));
+            let addr = match addr.borrow().clone() {
+                Some(addr) => addr,
+                None => return,
+            };
+            for pair in pairs {
+                if let Some(currency_pair) = utils::pair_name(Exchange::Bitstamp, &pair) {
+                    for sub in private_subscriptions(&currency_pair, user_id, &token) {
+                        addr.do_send(ClientCommand(serde_json::to_string(&sub).unwrap()));
+                    }
+                }
+            }
+        });
+    }
+
+    fn broadcast(&self, v: LiveEvent) {
+        if self.recipients.is_empty() {
+            debug!("{:?}", v);
+        } else {
+            for r in &self.recipients {
+                r.do_send(LiveEventEnveloppe(Exchange::Bitstamp, v.clone()));
+            }
+        }
+    }
+
+    fn subscribed_pair(&self, channel: &str) -> Option<Pair> {
+        private_channel_pair(channel).filter(|pair| self.pairs.contains(pair))
+    }
+}
+
+fn event_ms(microtimestamp: &str) -> i64 {
+    microtimestamp.parse::<i64>().map(|us| us / 1000).unwrap_or_default()
+}
+
+#[async_trait]
+impl WsHandler for BitstampUserStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, w: &mut dyn WsWriter, msg: Bytes) {
+        let v: PrivateEvent = match serde_json::from_slice(msg.bytes()) {
+            Ok(v) => v,
+            Err(e) => return trace!("Bitstamp : error {:?} deserializing {:?}", e, msg),
+        };
+        let status = v.order_status();
+        match v {
+            PrivateEvent::OrderCreated(e) | PrivateEvent::OrderChanged(e) | PrivateEvent::OrderDeleted(e) => {
+                let pair = match self.subscribed_pair(&e.channel) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                let order = e.data;
+                self.broadcast(LiveEvent::LiveOrderUpdate(OrderUpdate {
+                    event_ms: event_ms(&order.microtimestamp),
+                    order_id: order.id.to_string(),
+                    pair,
+                    status: status.unwrap_or(OrderStatus::Unknown),
+                    side: Some(order.side()),
+                    price: order.price_str.parse::<BigDecimal>().ok(),
+                    quantity: order.amount_at_create.as_ref().and_then(|a| a.parse::<BigDecimal>().ok()),
+                    executed_quantity: order.amount_traded.as_ref().and_then(|a| a.parse::<BigDecimal>().ok()),
+                }));
+            }
+            PrivateEvent::Trade(e) => {
+                let pair = match self.subscribed_pair(&e.channel) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                let trade = e.data;
+                match (trade.price.parse::<BigDecimal>(), trade.amount.parse::<BigDecimal>()) {
+                    (Ok(price), Ok(quantity)) => self.broadcast(LiveEvent::LiveFill(Fill {
+                        event_ms: event_ms(&trade.microtimestamp),
+                        order_id: trade.order_id.to_string(),
+                        trade_id: trade.id.to_string(),
+                        pair,
+                        side: if trade.side == "buy" { OrderSide::Buy } else { OrderSide::Sell },
+                        price,
+                        quantity,
+                        fee: trade.fee.parse::<BigDecimal>().ok(),
+                        fee_currency: None,
+                    })),
+                    _ => trace!("Bitstamp : invalid trade {:?}", trade),
+                }
+            }
+            // Subscriptions are sent again on the new connection
+            PrivateEvent::ReconnectRequest => w.reconnect(),
+            PrivateEvent::Other => (),
+        }
+    }
+
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_started(&mut self, _w: &mut dyn WsWriter) {
+        info!("Bitstamp : connecting to the private channels of {:?}", &self.pairs);
+        self.subscribe_pairs(self.pairs.iter().cloned().collect());
+    }
+
+    fn handle_subscribe(&mut self, _w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel != Channel::UserData {
+            warn!("Bitstamp : channel {:?} is not available on the user data stream", channel);
+            return HashSet::new();
+        }
+        let (pairs, unsupported): (HashSet<Pair>, HashSet<Pair>) = pairs.into_iter()
+            .partition(|pair| utils::pair_name(Exchange::Bitstamp, pair).is_some());
+        if !unsupported.is_empty() {
+            warn!("Bitstamp : pairs {:?} are not supported", unsupported);
+        }
+        let new_pairs: Vec<Pair> = pairs.iter().filter(|pair| self.pairs.insert(**pair)).cloned().collect();
+        self.subscribe_pairs(new_pairs);
+        pairs
+    }
+
+    fn handle_unsubscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel != Channel::UserData {
+            return HashSet::new();
+        }
+        let removed: HashSet<Pair> = pairs.into_iter().filter(|pair| self.pairs.remove(pair)).collect();
+        for pair in &removed {
+            if let (Some(currency_pair), Some(user_id)) = (utils::pair_name(Exchange::Bitstamp, pair), self.user_id.get()) {
+                for sub in private_unsubscriptions(&currency_pair, user_id) {
+                    w.write(Message::Text(serde_json::to_string(&sub).unwrap()));
+                }
+            }
+        }
+        removed
+    }
+}
@@ -73,8 +73,18 @@ pub fn build_signature(nonce: &str,
     Ok(String::from_utf8(signature)?)
 }
 
+pub const BASE_URL: &str = "https://www.bitstamp.net";
+
 pub fn build_url(method: &str, pair: &str) -> String {
-    "https://www.bitstamp.net/api/v2/".to_string() + method + "/" + pair + "/"
+    build_url_from(BASE_URL, method, pair)
+}
+
+/// Url of a method on another host than Bitstamp, `base_url` has no trailing slash
+pub fn build_url_from(base_url: &str, method: &str, pair: &str) -> String {
+    if pair.is_empty() {
+        return base_url.to_string() + "/api/v2/" + method + "/";
+    }
+    base_url.to_string() + "/api/v2/" + method + "/" + pair + "/"
 }
 
 pub fn deserialize_json(json_string: &str) -> Result<Map<String, Value>> {
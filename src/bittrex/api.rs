@@ -6,8 +6,7 @@
 use hmac::{Hmac, Mac};
 use sha2::{Sha512};
 
-use hyper::{Client, Uri, Method, Request, Body};
-use hyper_tls::HttpsConnector;
+use hyper::{Method, Request, Body};
 
 use data_encoding::HEXLOWER;
 
@@ -18,14 +17,14 @@ use std::collections::HashMap;
 use std::thread;
 use std::time::Duration;
 use std::str;
+use std::sync::Arc;
 
 use crate::error::*;
 use crate::helpers;
 
 use crate::exchange::Exchange;
 use crate::coinnect::Credentials;
-use hyper::client::HttpConnector;
-use futures::{TryFutureExt};
+use crate::transport::{self, HttpClient, HttpTransport};
 use bytes::buf::BufExt as _;
 use crate::helpers::json;
 
@@ -36,7 +35,8 @@ pub struct BittrexApi {
     last_request: i64, // unix timestamp in ms, to avoid ban
     api_key: String,
     api_secret: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    transport: Arc<dyn HttpTransport>,
+    base_url: String,
     burst: bool,
 }
 
@@ -48,14 +48,12 @@ impl BittrexApi {
             return Err(ErrorKind::InvalidConfigType(Exchange::Bittrex, creds.exchange()).into());
         }
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
-
         Ok(BittrexApi {
                last_request: 0,
                api_key: creds.get("api_key").unwrap_or_default(),
                api_secret: creds.get("api_secret").unwrap_or_default(),
-               http_client: ssl,
+               transport: transport::default_transport(),
+               base_url: "https://bittrex.com".to_string(),
                burst: false,
            })
     }
@@ -86,14 +84,13 @@ impl BittrexApi {
                     -> Result<Map<String, Value>> {
 
         helpers::strip_empties(params);
-        let string = "https://bittrex.com/api/v1.1".to_string() + method + "?" +
+        let url = self.base_url.clone() + "/api/v1.1" + method + "?" +
             &helpers::url_encode_hashmap(params);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
 
         self.block_or_continue().await;
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.get(&url).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
+        let reader = resp.body.reader();
         json::deserialize_json_r(reader)
     }
 
@@ -107,7 +104,7 @@ impl BittrexApi {
         initial_params.insert("nonce", &nonce);
         initial_params.insert("apikey", &self.api_key);
 
-        let base_url = "https://bittrex.com/api/v1.1".to_string() + method + "?apikey=" +
+        let base_url = self.base_url.clone() + "/api/v1.1" + method + "?apikey=" +
         &self.api_key + "&nonce=" + &nonce;
 
         let url = if params.is_empty() {
@@ -129,9 +126,9 @@ impl BittrexApi {
             .body(Body::empty())
             .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
         let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.send(req2).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
+        let reader = resp.body.reader();
         json::deserialize_json_r(reader)
     }
 
@@ -771,3 +768,13 @@ impl BittrexApi {
         self.private_query("/account/getdeposithistory", &mut params).await
     }
 }
+
+impl HttpClient for BittrexApi {
+    fn set_transport(&mut self, transport: Arc<dyn HttpTransport>) {
+        self.transport = transport;
+    }
+
+    fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = transport::trim_base_url(base_url);
+    }
+}
@@ -5,22 +5,21 @@
 use bigdecimal::BigDecimal;
 use std::str::FromStr;
 
-use crate::exchange::{ExchangeApi};
+use crate::exchange::{ExchangeApi, Exchange};
 use crate::bittrex::api::BittrexApi;
 
 use crate::error::*;
 use crate::types::*;
 use crate::bittrex::utils;
 use crate::helpers;
+use crate::utils::pair_or;
 use async_trait::async_trait;
+use serde_json::Value;
 
 #[async_trait]
 impl ExchangeApi for BittrexApi {
     async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bittrex, &pair)?;
 
         let raw_response = self.get_market_summary(pair_name).await?;
 
@@ -52,10 +51,7 @@ impl ExchangeApi for BittrexApi {
     }
 
     async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Bittrex, &pair)?;
 
         let raw_response = self.get_order_book(pair_name, "both").await?;
 
@@ -111,10 +107,7 @@ impl ExchangeApi for BittrexApi {
                  price: Option<Price>)
                  -> Result<OrderInfo> {
 
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(pair_str) => pair_str,
-            None => return Err(ErrorKind::PairUnsupported.into())
-        };
+        let pair_name = &pair_or(Exchange::Bittrex, &pair)?;
 
         let raw_response = match order_type {
             OrderType::BuyLimit => {
@@ -149,6 +142,11 @@ impl ExchangeApi for BittrexApi {
         })
     }
 
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        let order_type = order.basic_order_type()?;
+        self.add_order(order_type, order.pair, order.quantity, order.price).await
+    }
+
     async fn balances(&mut self) -> Result<Balances> {
         let raw_response = self.get_balances().await?;
 
@@ -174,4 +172,185 @@ impl ExchangeApi for BittrexApi {
         }
         Ok(balances)
     }
+
+    async fn cancel_order(&mut self, _pair: Pair, order: &OrderInfo) -> Result<OrderInfo> {
+        let mut canceled = Vec::new();
+
+        for uuid in &order.identifier {
+            let raw_response = self.cancel(uuid).await?;
+            utils::parse_result(&raw_response)?;
+            canceled.push(uuid.clone());
+        }
+
+        Ok(OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: canceled,
+        })
+    }
+
+    async fn order_status(&mut self, pair: Pair, order: &OrderInfo) -> Result<Order> {
+        let uuid = order.identifier
+            .first()
+            .ok_or_else(|| ErrorKind::MissingField("uuid".to_string()))?;
+
+        let raw_response = self.get_order(uuid).await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        parse_order(pair, &result, "Type")
+    }
+
+    async fn open_orders(&mut self, pair: Pair) -> Result<Vec<Order>> {
+        let pair_name = &pair_or(Exchange::Bittrex, &pair)?;
+
+        let raw_response = self.get_open_orders(pair_name).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let open = result.as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result)))?;
+
+        let mut orders = Vec::new();
+        for order_obj in open {
+            orders.push(parse_order(pair, order_obj, "OrderType")?);
+        }
+        Ok(orders)
+    }
+
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>> {
+        let pair_name = &pair_or(Exchange::Bittrex, &pair)?;
+
+        let raw_response = self.get_market_history(pair_name).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let trade_array = result.as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result)))?;
+
+        let mut trades = Vec::new();
+        // Most recent trades come first
+        for trade in trade_array.iter().rev() {
+            let timestamp = utils::parse_date(trade["TimeStamp"].as_str().unwrap_or_default())?;
+            if since.map_or(false, |since| timestamp < since) {
+                continue;
+            }
+
+            trades.push(Trade {
+                id: trade.get("Id").map(|id| id.to_string()),
+                timestamp,
+                pair,
+                price: helpers::from_json_bigdecimal(&trade["Price"], "Price")?,
+                volume: helpers::from_json_bigdecimal(&trade["Quantity"], "Quantity")?,
+                tt: trade["OrderType"].as_str().unwrap_or_default().to_string().into(),
+            });
+        }
+        Ok(trades)
+    }
+
+    async fn candles(&mut self,
+                     _pair: Pair,
+                     _interval: Interval,
+                     _start: Option<i64>,
+                     _end: Option<i64>)
+                     -> Result<Vec<Candle>> {
+        Err(ErrorKind::Unsupported("candles".to_string()).into())
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        let raw_response = self.get_markets().await?;
+        let result = utils::parse_result(&raw_response)?;
+        let market_array = result
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result)))?;
+
+        let mut symbols = SymbolMap::new();
+        for market in market_array {
+            let (symbol, name) = parse_market_symbol(market)?;
+            symbols.insert(symbol, name);
+        }
+        symbols.register(Exchange::Bittrex);
+        Ok(symbols)
+    }
+
+    async fn markets(&mut self) -> Result<Markets> {
+        let raw_response = self.get_markets().await?;
+        let result = utils::parse_result(&raw_response)?;
+        let market_array = result
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result)))?;
+
+        // Bittrex only publishes the minimum trade size, prices and quantities use 8 decimals
+        let mut markets = Markets::new();
+        for market_obj in market_array {
+            let (symbol, name) = parse_market_symbol(market_obj)?;
+            let mut market = MarketInfo::new(symbol.clone(), name);
+            market.price_tick = Some(helpers::decimals_to_increment(8));
+            market.quantity_step = Some(helpers::decimals_to_increment(8));
+            market.min_quantity = Some(helpers::from_json_bigdecimal(&market_obj["MinTradeSize"], "MinTradeSize")?);
+            markets.insert(symbol, market);
+        }
+        Ok(markets)
+    }
+}
+
+/// Return the symbol and the Bittrex name of a `getmarkets` entry
+fn parse_market_symbol(market: &Value) -> Result<(Symbol, &str)> {
+    let name = market["MarketName"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("MarketName".to_string()))?;
+    // Bittrex BaseCurrency is the quote currency in the normalized notation
+    let base = market["MarketCurrency"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("MarketCurrency".to_string()))?;
+    let quote = market["BaseCurrency"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("BaseCurrency".to_string()))?;
+    Ok((Symbol::new(base, quote), name))
+}
+
+/// Build a normalized `Order` from a `getorder` result or a `getopenorders` entry, the two
+/// endpoints name the order type field differently
+fn parse_order(pair: Pair, order_obj: &Value, type_field: &str) -> Result<Order> {
+    let decimal_field = |field: &str| -> Result<BigDecimal> {
+        let value = order_obj[field]
+            .as_f64()
+            .ok_or_else(|| ErrorKind::MissingField(field.to_string()))?;
+        Ok(BigDecimal::from_str(&value.to_string())?)
+    };
+
+    let uuid = order_obj["OrderUuid"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("OrderUuid".to_string()))?;
+    let order_type = order_obj[type_field]
+        .as_str()
+        .and_then(utils::get_order_type)
+        .ok_or_else(|| ErrorKind::InvalidFieldFormat(type_field.to_string()))?;
+
+    let quantity = decimal_field("Quantity")?;
+    let remaining = decimal_field("QuantityRemaining")?;
+    let executed_quantity = quantity.clone() - remaining.clone();
+
+    // getopenorders does not return IsOpen, every entry is open
+    let is_open = order_obj["IsOpen"].as_bool().unwrap_or(true);
+    let cancel_initiated = order_obj["CancelInitiated"].as_bool().unwrap_or(false);
+    let status = if is_open && executed_quantity > BigDecimal::from(0) {
+        OrderStatus::PartiallyFilled
+    } else if is_open {
+        OrderStatus::Open
+    } else if remaining > BigDecimal::from(0) || cancel_initiated {
+        OrderStatus::Canceled
+    } else {
+        OrderStatus::Filled
+    };
+
+    let timestamp = match order_obj["Opened"].as_str() {
+        Some(date) => utils::parse_date(date)?,
+        None => 0,
+    };
+
+    Ok(Order {
+        identifier: vec![uuid.to_string()],
+        timestamp,
+        pair,
+        order_type,
+        status,
+        price: decimal_field("Limit").ok(),
+        quantity,
+        executed_quantity,
+    })
 }
@@ -1,7 +1,8 @@
 #[allow(non_snake_case)]
 
 use serde::{Serialize, Deserialize};
-use crate::types::LiveTrade;
+use crate::types::{LiveTrade, Pair, Ticker, BestBidOffer};
+use bigdecimal::BigDecimal;
 
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize)]
@@ -146,7 +147,7 @@ pub(crate) struct MarketDelta {
     #[serde(alias = "M")]
     pub MarketName: String,
     #[serde(alias = "N")]
-    Nonce: i32,
+    pub Nonce: i32,
     #[serde(alias = "Z")]
     pub Buys: Vec<OrderLog>,
     #[serde(alias = "S")]
@@ -159,7 +160,7 @@ pub(crate) struct MarketDelta {
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct SummaryDelta {
     #[serde(alias = "M")]
-    MarketName: String,
+    pub MarketName: String,
     #[serde(alias = "H")]
     High: f32,
     #[serde(alias = "L")]
@@ -167,15 +168,15 @@ pub(crate) struct SummaryDelta {
     #[serde(alias = "V")]
     Volume: f32,
     #[serde(alias = "l")]
-    Last: f32,
+    pub Last: f32,
     #[serde(alias = "m")]
-    BaseVolume: f32,
+    pub BaseVolume: f32,
     #[serde(alias = "T")]
-    TimeStamp: i64,
+    pub TimeStamp: i64,
     #[serde(alias = "B")]
-    Bid: f32,
+    pub Bid: f32,
     #[serde(alias = "A")]
-    Ask: f32,
+    pub Ask: f32,
     #[serde(alias = "G")]
     OpenBuyOrders: i32,
     #[serde(alias = "g")]
@@ -186,12 +187,38 @@ pub(crate) struct SummaryDelta {
     Created: i64,
 }
 
+impl SummaryDelta {
+    /// Bittrex volumes are in the base currency of the market name, the quote of the pair
+    pub fn ticker(&self, pair: Pair) -> Ticker {
+        Ticker {
+            timestamp: self.TimeStamp,
+            pair,
+            last_trade_price: BigDecimal::from(self.Last),
+            lowest_ask: BigDecimal::from(self.Ask),
+            highest_bid: BigDecimal::from(self.Bid),
+            volume: Some(BigDecimal::from(self.BaseVolume)),
+        }
+    }
+
+    /// Summaries carry no sizes
+    pub fn best_bid_offer(&self, pair: Pair) -> BestBidOffer {
+        BestBidOffer {
+            event_ms: self.TimeStamp,
+            pair,
+            bid: BigDecimal::from(self.Bid),
+            ask: BigDecimal::from(self.Ask),
+            bid_volume: None,
+            ask_volume: None,
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct SummaryDeltaResponse {
     #[serde(alias = "N")]
     Nonce: i32,
     #[serde(alias = "D")]
-    Deltas: Vec<SummaryDelta>,
+    pub Deltas: Vec<SummaryDelta>,
 }
 
@@ -1,11 +1,14 @@
 use crate::coinnect::Credentials;
-use crate::exchange_bot::{ExchangeBot};
+use crate::exchange_bot::{ExchangeBot, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, TICK_INTERVAL};
+use crate::recorder::FeedRecorder;
 use crate::error::*;
 use super::models::*;
 use serde_json::Value;
 use std::io::Read;
 use actix::{Addr, Recipient};
-use crate::types::{LiveEvent, Channel, Orderbook, Pair, LiveAggregatedOrderBook, LiveEventEnveloppe, LiveTrade};
+use crate::types::{LiveEvent, Channel, Pair, LiveEventEnveloppe, LiveTrade, BookState, BookUpdate, BookSettings, LiveBooks, LiveCandles, Orderbook, Interval};
+use crate::helpers;
+use crate::utils;
 use signalr_rs::hub::client::{HubClientError, HubClientHandler, HubClient, HubQuery, RestartPolicy, PendingQuery};
 use serde::de::DeserializeOwned;
 use libflate::deflate::Decoder;
@@ -14,6 +17,7 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use crate::exchange::Exchange;
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct BittrexStreamingApi {
@@ -21,60 +25,185 @@ pub struct BittrexStreamingApi {
     api_secret: String,
     customer_id: String,
     pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
-    books: Rc<RefCell<HashMap<Pair, LiveAggregatedOrderBook>>>,
-    order_book_pairs: HashSet<Pair>,
-    trade_pairs: HashSet<Pair>
+    books: LiveBooks,
+    /// Set once connected, used to query a new book snapshot when a delta is missed
+    hub: Rc<RefCell<Option<Addr<HubClient>>>>,
+    monitor: FeedMonitor,
+    order_book_pairs: Rc<RefCell<HashSet<Pair>>>,
+    trade_pairs: Rc<RefCell<HashSet<Pair>>>,
+    ticker_pairs: Rc<RefCell<HashSet<Pair>>>,
+    bbo_pairs: Rc<RefCell<HashSet<Pair>>>,
+    candle_pairs: Rc<RefCell<HashMap<Interval, HashSet<Pair>>>>,
+    /// Candles are built from the fills of the market deltas, shared with the timer closing them
+    candles: Rc<RefCell<LiveCandles>>,
+    /// Hub messages are recorded before being handled
+    recorder: RefCell<Option<FeedRecorder>>,
 }
 
 pub struct BittrexBot {
-    addr: Addr<HubClient>
+    addr: Addr<HubClient>,
+    monitor: FeedMonitor,
+    books: LiveBooks,
+    order_book_pairs: Rc<RefCell<HashSet<Pair>>>,
+    trade_pairs: Rc<RefCell<HashSet<Pair>>>,
+    ticker_pairs: Rc<RefCell<HashSet<Pair>>>,
+    bbo_pairs: Rc<RefCell<HashSet<Pair>>>,
+    candle_pairs: Rc<RefCell<HashMap<Interval, HashSet<Pair>>>>,
+}
+
+impl BittrexBot {
+    fn channel_pairs(&self, channel: &Channel) -> Option<&Rc<RefCell<HashSet<Pair>>>> {
+        match channel {
+            Channel::LiveFullOrderBook => Some(&self.order_book_pairs),
+            Channel::LiveTrades => Some(&self.trade_pairs),
+            Channel::LiveTicker => Some(&self.ticker_pairs),
+            Channel::LiveBestBidOffer => Some(&self.bbo_pairs),
+            _ => None,
+        }
+    }
+
+    fn is_subscribed_to_any(&self, pair: &Pair) -> bool {
+        self.order_book_pairs.borrow().contains(pair) || self.trade_pairs.borrow().contains(pair)
+            || self.candle_pairs.borrow().values().any(|pairs| pairs.contains(pair))
+    }
+
+    fn is_subscribed_to_summaries(&self) -> bool {
+        !self.ticker_pairs.borrow().is_empty() || !self.bbo_pairs.borrow().is_empty()
+    }
 }
 
 impl ExchangeBot for BittrexBot {
-    fn is_connected(&self) -> bool {
-        unimplemented!()
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, mut msg: Subscribe) {
+        let (pairs, unsupported): (HashSet<Pair>, HashSet<Pair>) = msg.pairs.into_iter()
+            .partition(|pair| utils::pair_name(Exchange::Bittrex, pair).is_some());
+        if !unsupported.is_empty() {
+            warn!("Bittrex : pairs {:?} are not supported", unsupported);
+        }
+        msg.pairs = pairs;
+        if let Channel::LiveCandles(interval) = msg.channel {
+            self.monitor.on_subscribe(msg.channel.clone(), msg.pairs.clone());
+            for pair in msg.pairs {
+                if !self.is_subscribed_to_any(&pair) {
+                    subscribe_to_exchange_deltas(&self.addr, pair);
+                }
+                self.candle_pairs.borrow_mut().entry(interval).or_default().insert(pair);
+            }
+            return;
+        }
+        let channel_pairs = match self.channel_pairs(&msg.channel) {
+            Some(pairs) => pairs,
+            None => return warn!("Bittrex : channel {:?} is not available", msg.channel),
+        };
+        self.monitor.on_subscribe(msg.channel.clone(), msg.pairs.clone());
+        if msg.channel == Channel::LiveTicker || msg.channel == Channel::LiveBestBidOffer {
+            // A single subscription sends the summaries of every market
+            if !self.is_subscribed_to_summaries() {
+                subscribe_to_summary_deltas(&self.addr);
+            }
+            channel_pairs.borrow_mut().extend(msg.pairs);
+            return;
+        }
+        for pair in msg.pairs {
+            // Deltas of a market carry both its book and its trades
+            if !self.is_subscribed_to_any(&pair) {
+                subscribe_to_exchange_deltas(&self.addr, pair);
+            }
+            if !channel_pairs.borrow_mut().insert(pair) {
+                continue;
+            }
+            if msg.channel == Channel::LiveFullOrderBook {
+                self.books.with_book(pair, |_| ());
+                query_exchange_state(&self.addr, pair);
+            }
+        }
+    }
+
+    /// Bittrex cannot unsubscribe from deltas and summaries, they are ignored until the connection
+    /// restarts
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        if let Channel::LiveCandles(interval) = msg.channel {
+            self.monitor.on_unsubscribe(msg.channel.clone(), msg.pairs.clone());
+            if let Some(pairs) = self.candle_pairs.borrow_mut().get_mut(&interval) {
+                pairs.retain(|pair| !msg.pairs.contains(pair));
+            }
+            return;
+        }
+        let channel_pairs = match self.channel_pairs(&msg.channel) {
+            Some(pairs) => pairs,
+            None => return,
+        };
+        self.monitor.on_unsubscribe(msg.channel.clone(), msg.pairs.clone());
+        for pair in msg.pairs {
+            if channel_pairs.borrow_mut().remove(&pair) && msg.channel == Channel::LiveFullOrderBook {
+                self.books.remove(pair);
+            }
+        }
+    }
+
+    fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.full_order_book(pair)
     }
 }
 
 const BITTREX_HUB: &'static str = "c2";
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
 
 impl BittrexStreamingApi {
     /// Create a new bittrex exchange bot, unavailable channels and currencies are ignored
-    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BittrexBot> {
-        // Live order book pairs
-        let mut map = channels.clone();
-        let order_book_pairs: &HashSet<Pair> = map.entry(Channel::LiveFullOrderBook).or_default();
-        // Live trade pairs
-        let mut map = channels.clone();
-        let trade_pairs : &HashSet<Pair> = map.entry(Channel::LiveTrades).or_default();
-
-        let api = Box::new(BittrexStreamingApi {
-            api_key: creds.get("api_key").unwrap_or_default(),
-            api_secret: creds.get("api_secret").unwrap_or_default(),
-            customer_id: creds.get("customer_id").unwrap_or_default(),
-            recipients,
-            books: Rc::new(RefCell::new(HashMap::new())),
-            order_book_pairs: order_book_pairs.clone(),
-            trade_pairs: trade_pairs.clone(),
-        });
-        let rc = api.books.clone();
-
-        let mut books = rc.borrow_mut();
-        for &pair in order_book_pairs {
-            books.insert(pair, LiveAggregatedOrderBook::default(pair));
-        }
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, book_settings: BookSettings, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BittrexBot> {
+        let order_book_pairs = channels.get(&Channel::LiveFullOrderBook).cloned().unwrap_or_default();
+        let books = LiveBooks::new(book_settings);
+        let monitor = FeedMonitor::new(Exchange::Bittrex, channels.clone(), recipients.clone());
+        let recorder = connection.record.map(|record| FeedRecorder::new("BittrexStream", Exchange::Bittrex, record));
+        let mut api = Box::new(BittrexStreamingApi::new_handler(channels, books.clone(), monitor.clone(), recorder, recipients));
+        api.api_key = creds.get("api_key").unwrap_or_default();
+        api.api_secret = creds.get("api_secret").unwrap_or_default();
+        api.customer_id = creds.get("customer_id").unwrap_or_default();
+        let hub = api.hub.clone();
+        let shared_book_pairs = api.order_book_pairs.clone();
+        let shared_trade_pairs = api.trade_pairs.clone();
+        let shared_ticker_pairs = api.ticker_pairs.clone();
+        let shared_bbo_pairs = api.bbo_pairs.clone();
+        let shared_candle_pairs = api.candle_pairs.clone();
+        let candles = Rc::downgrade(&api.candles);
+        let candle_recipients = api.recipients.clone();
 
         // SignalR Client
         let client = HubClient::new(BITTREX_HUB, "https://socket.bittrex.com/signalr/", 20, RestartPolicy::Always, api).await;
         match client {
             Ok(addr) => {
-                if !order_book_pairs.is_empty() {
-                    for &pair in order_book_pairs {
-                        let currency = *super::utils::get_pair_string(&pair).unwrap();
-                        addr.do_send(HubQuery::new(BITTREX_HUB.to_string(), "QueryExchangeState".to_string(), vec![currency.to_string()], "QE2".to_string()));
+                *hub.borrow_mut() = Some(addr.clone());
+                // The hub client does not report disconnections, only staleness can be checked
+                monitor.watch(HEALTH_CHECK_INTERVAL);
+                // The handler is only called on messages, candles are closed on a timer until it is dropped
+                let candle_pairs = shared_candle_pairs.clone();
+                actix_rt::spawn(async move {
+                    loop {
+                        tokio::time::delay_for(TICK_INTERVAL).await;
+                        let candles = match candles.upgrade() {
+                            Some(candles) => candles,
+                            None => return,
+                        };
+                        broadcast(&candle_recipients, closed_candles(&candles, &candle_pairs, helpers::get_unix_timestamp_ms()));
                     }
+                });
+                for &pair in &order_book_pairs {
+                    query_exchange_state(&addr, pair);
                 }
-                return Ok(BittrexBot { addr });
+                return Ok(BittrexBot {
+                    addr,
+                    monitor,
+                    books,
+                    order_book_pairs: shared_book_pairs,
+                    trade_pairs: shared_trade_pairs,
+                    ticker_pairs: shared_ticker_pairs,
+                    bbo_pairs: shared_bbo_pairs,
+                    candle_pairs: shared_candle_pairs,
+                });
             }
             Err(e) => {
                 return Err(ErrorKind::Hub(e).into());
@@ -82,6 +211,47 @@ impl BittrexStreamingApi {
         }
     }
 
+    /// Create the handler of the feed without connecting it, see `replay::FeedReplay`
+    pub fn new_handler(channels: HashMap<Channel, HashSet<Pair>>, books: LiveBooks, monitor: FeedMonitor, recorder: Option<FeedRecorder>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> BittrexStreamingApi {
+        let pairs = |channel: Channel| Rc::new(RefCell::new(channels.get(&channel).cloned().unwrap_or_default()));
+        let candle_pairs: HashMap<Interval, HashSet<Pair>> = channels.iter()
+            .filter_map(|(channel, pairs)| match channel {
+                Channel::LiveCandles(interval) => Some((*interval, pairs.clone())),
+                _ => None,
+            })
+            .collect();
+        let order_book_pairs = pairs(Channel::LiveFullOrderBook);
+        for &pair in order_book_pairs.borrow().iter() {
+            books.with_book(pair, |_| ());
+        }
+        books.flush_to(Exchange::Bittrex, recipients.clone());
+        BittrexStreamingApi {
+            api_key: String::new(),
+            api_secret: String::new(),
+            customer_id: String::new(),
+            recipients,
+            books,
+            hub: Rc::new(RefCell::new(None)),
+            monitor,
+            order_book_pairs,
+            trade_pairs: pairs(Channel::LiveTrades),
+            ticker_pairs: pairs(Channel::LiveTicker),
+            bbo_pairs: pairs(Channel::LiveBestBidOffer),
+            candle_pairs: Rc::new(RefCell::new(candle_pairs)),
+            candles: Rc::new(RefCell::new(LiveCandles::default())),
+            recorder: RefCell::new(recorder),
+        }
+    }
+
+    fn broadcast(&self, les: Vec<LiveEvent>) {
+        broadcast(&self.recipients, les);
+    }
+
+    /// Close the candles whose interval is over at `now_ms`, see `replay::FeedReplay`
+    pub fn close_candles(&self, now_ms: i64) {
+        self.broadcast(closed_candles(&self.candles, &self.candle_pairs, now_ms));
+    }
+
     fn deflate<T>(binary: &String) -> Result<T> where T: DeserializeOwned {
         let decoded = base64::decode(binary).map_err(|e| ErrorKind::Hub(HubClientError::Base64DecodeError(e)))?;
         let mut decoder = Decoder::new(&decoded[..]);
@@ -103,47 +273,119 @@ impl BittrexStreamingApi {
     }
 }
 
+fn broadcast(recipients: &[Recipient<LiveEventEnveloppe>], les: Vec<LiveEvent>) {
+    for le in les {
+        for r in recipients {
+            let le: LiveEvent = le.clone();
+            if let Err(e) = r.do_send(LiveEventEnveloppe(Exchange::Bittrex, le)) {
+                trace!("Bittrex : live event not delivered : {:?}", e);
+            }
+        }
+    }
+}
+
+/// Close the candles whose interval is over, candles of pairs unsubscribed since are closed
+/// without being sent
+fn closed_candles(candles: &RefCell<LiveCandles>, candle_pairs: &RefCell<HashMap<Interval, HashSet<Pair>>>, now_ms: i64) -> Vec<LiveEvent> {
+    let candle_pairs = candle_pairs.borrow();
+    candles.borrow_mut().close_over(now_ms).into_iter()
+        .filter(|c| candle_pairs.get(&c.candle.interval).map_or(false, |pairs| pairs.contains(&c.candle.pair)))
+        .map(LiveEvent::LiveCandle)
+        .collect()
+}
+
+fn subscribe_to_exchange_deltas(addr: &Addr<HubClient>, pair: Pair) {
+    if let Some(currency) = utils::pair_name(Exchange::Bittrex, &pair) {
+        addr.do_send(HubQuery::new(BITTREX_HUB.to_string(), "SubscribeToExchangeDeltas".to_string(), vec![currency], "1".to_string()));
+    }
+}
+
+/// Summaries of every market, handled as `uS` methods
+fn subscribe_to_summary_deltas(addr: &Addr<HubClient>) {
+    addr.do_send(HubQuery::new(BITTREX_HUB.to_string(), "SubscribeToSummaryDeltas".to_string(), vec![], "1".to_string()));
+}
+
+/// Request the book of a market, the answer is handled as a `QE` method
+fn query_exchange_state(addr: &Addr<HubClient>, pair: Pair) {
+    if let Some(currency) = utils::pair_name(Exchange::Bittrex, &pair) {
+        addr.do_send(HubQuery::new(BITTREX_HUB.to_string(), "QueryExchangeState".to_string(), vec![currency], "QE2".to_string()));
+    }
+}
+
 impl HubClientHandler for BittrexStreamingApi {
     fn on_connect(&self) -> Vec<Box<PendingQuery>> {
+        self.monitor.on_connected();
+        if let Some(recorder) = self.recorder.borrow_mut().as_mut() {
+            recorder.on_connected();
+        }
         let mut conn_queries : Vec<Box<PendingQuery>> = vec![];
-        if !self.trade_pairs.is_empty() || !self.order_book_pairs.is_empty() {
-            let all_pairs : HashSet<Pair> = self.trade_pairs.union(&self.order_book_pairs).map(|&p| p).collect();
-            let currencies : Vec<String> = all_pairs.iter().map(|p| (*super::utils::get_pair_string(p).unwrap()).to_string()).collect();
+        let trade_pairs = self.trade_pairs.borrow();
+        let order_book_pairs = self.order_book_pairs.borrow();
+        let candle_pairs: HashSet<Pair> = self.candle_pairs.borrow().values().flatten().cloned().collect();
+        if !trade_pairs.is_empty() || !order_book_pairs.is_empty() || !candle_pairs.is_empty() {
+            let all_pairs : HashSet<Pair> = trade_pairs.union(&order_book_pairs).chain(candle_pairs.iter()).map(|&p| p).collect();
+            let currencies : Vec<String> = all_pairs.iter().filter_map(|p| utils::pair_name(Exchange::Bittrex, p)).collect();
             info!("Bittrex : connecting to ExchangeDeltas for {:?}", &currencies);
             for currency in currencies {
                 conn_queries.push(Box::new(HubQuery::new(BITTREX_HUB.to_string(), "SubscribeToExchangeDeltas".to_string(), vec![currency], "1".to_string())));
             }
         }
+        if !self.ticker_pairs.borrow().is_empty() || !self.bbo_pairs.borrow().is_empty() {
+            info!("Bittrex : connecting to SummaryDeltas");
+            conn_queries.push(Box::new(HubQuery::new(BITTREX_HUB.to_string(), "SubscribeToSummaryDeltas".to_string(), vec![], "1".to_string())));
+        }
+        self.monitor.on_subscribed();
         conn_queries
     }
 
     fn error(&self, _: Option<&str>, _: &Value) {}
 
     fn handle(&mut self, method: &str, message: &Value) {
+        self.monitor.on_message();
+        if let Some(recorder) = self.recorder.get_mut() {
+            recorder.record_method(method, message);
+        }
         let live_events = match method {
             "uE" => {
                 let delta = BittrexStreamingApi::deflate_array::<MarketDelta>(message).unwrap();
-                let pair = super::utils::get_pair_enum(delta.MarketName.as_str());
-                if pair.is_none() {
-                    return;
-                }
+                let current_pair = match utils::pair_enum(Exchange::Bittrex, delta.MarketName.as_str()) {
+                    Some(pair) => pair,
+                    None => return,
+                };
                 let mut events = vec![];
-                let current_pair = *pair.unwrap();
-                if self.order_book_pairs.contains(&current_pair) {
-                    let mut books = self.books.borrow_mut();
-                    let default_book = LiveAggregatedOrderBook::default(current_pair);
-                    let mut agg = books.entry(current_pair).or_insert(default_book);
-                    let asks = delta.Sells.into_iter().map(|op| (BigDecimal::from(op.Rate), BigDecimal::from(op.Quantity)));
-                    agg.update_asks(asks);
-                    let buys = delta.Buys.into_iter().map(|op| (BigDecimal::from(op.Rate), BigDecimal::from(op.Quantity)));
-                    agg.update_bids(buys);
-                    agg.latest_order_book().map(|ob| events.push(LiveEvent::LiveOrderbook(ob)));
+                if self.order_book_pairs.borrow().contains(&current_pair) {
+                    let nonce = delta.Nonce as u64;
+                    let update = BookUpdate {
+                        update_ids: Some((nonce, nonce)),
+                        asks: delta.Sells.into_iter().map(|op| (BigDecimal::from(op.Rate), BigDecimal::from(op.Quantity))).collect(),
+                        bids: delta.Buys.into_iter().map(|op| (BigDecimal::from(op.Rate), BigDecimal::from(op.Quantity))).collect(),
+                    };
+                    let hub = &self.hub;
+                    self.books.with_book(current_pair, |agg| {
+                        if let Some(state) = agg.apply_update(update) {
+                            events.push(LiveEvent::LiveBookState(agg.book_state()));
+                            if state == BookState::Resyncing {
+                                if let Some(addr) = hub.borrow().as_ref() {
+                                    query_exchange_state(addr, current_pair);
+                                }
+                            }
+                        }
+                        agg.latest_order_book().map(|ob| events.push(LiveEvent::LiveOrderbook(ob)));
+                    });
+                }
+                let intervals: Vec<Interval> = self.candle_pairs.borrow().iter()
+                    .filter(|(_, pairs)| pairs.contains(&current_pair))
+                    .map(|(interval, _)| *interval)
+                    .collect();
+                for fill in &delta.Fills {
+                    let candles = self.candles.borrow_mut().add_trade(current_pair, &intervals, fill.TimeStamp as i64, &BigDecimal::from(fill.Rate), &BigDecimal::from(fill.Quantity));
+                    events.extend(candles.into_iter().map(LiveEvent::LiveCandle));
                 }
-                if self.trade_pairs.contains(&current_pair) {
+                if self.trade_pairs.borrow().contains(&current_pair) {
                     for fill in delta.Fills {
                         let lt = LiveTrade {
                             event_ms: fill.TimeStamp as i64,
-                            pair: format!("{:?}", current_pair),
+                            pair: current_pair,
                             amount: fill.Quantity,
                             price: BigDecimal::from(fill.Rate),
                             tt: fill.OrderType.into(),
@@ -158,25 +400,52 @@ impl HubClientHandler for BittrexStreamingApi {
                 }
             }
             "uS" => {
-                BittrexStreamingApi::deflate_array::<SummaryDeltaResponse>(message);
-                Err(())
+                let summaries = match BittrexStreamingApi::deflate_array::<SummaryDeltaResponse>(message) {
+                    Ok(summaries) => summaries,
+                    Err(e) => return trace!("Bittrex : error {:?} deflating summaries", e),
+                };
+                let mut events = vec![];
+                for delta in summaries.Deltas {
+                    let current_pair = match utils::pair_enum(Exchange::Bittrex, delta.MarketName.as_str()) {
+                        Some(pair) => pair,
+                        None => continue,
+                    };
+                    if self.ticker_pairs.borrow().contains(&current_pair) {
+                        events.push(LiveEvent::LiveTicker(delta.ticker(current_pair)));
+                    }
+                    if self.bbo_pairs.borrow().contains(&current_pair) {
+                        events.push(LiveEvent::LiveBestBidOffer(delta.best_bid_offer(current_pair)));
+                    }
+                }
+                Ok(events)
             }
             s if s.starts_with("QE") => {
                 let state = BittrexStreamingApi::deflate_string::<ExchangeState>(message).unwrap();
-                let pair = super::utils::get_pair_enum(state.MarketName.as_str());
-                if pair.is_none() {
+                let current_pair = match utils::pair_enum(Exchange::Bittrex, state.MarketName.as_str()) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                if !self.order_book_pairs.borrow().contains(&current_pair) {
                     return;
                 }
-                let mut books = self.books.borrow_mut();
-                let current_pair = *pair.unwrap();
-                let default_book = LiveAggregatedOrderBook::default(current_pair);
-                let mut agg = books.entry(current_pair).or_insert(default_book);
                 let asks = state.Sells.into_iter().map(|op| (BigDecimal::from(op.R), BigDecimal::from(op.Q)));
-                agg.reset_asks(asks);
                 let bids = state.Buys.into_iter().map(|op| (BigDecimal::from(op.R), BigDecimal::from(op.Q)));
-                agg.reset_bids(bids);
-                let latest_order_book: Orderbook = agg.order_book();
-                Ok(vec![LiveEvent::LiveOrderbook(latest_order_book.clone())])
+                let nonce = state.Nonce as u64;
+                let hub = &self.hub;
+                let mut events = vec![];
+                self.books.with_book(current_pair, |agg| {
+                    if agg.apply_snapshot(Some(nonce), asks, bids).is_some() {
+                        events.push(LiveEvent::LiveBookState(agg.book_state()));
+                    }
+                    if agg.state == BookState::Resyncing {
+                        // The buffered deltas do not follow the snapshot
+                        if let Some(addr) = hub.borrow().as_ref() {
+                            query_exchange_state(addr, current_pair);
+                        }
+                    }
+                    agg.latest_order_book().map(|ob| events.push(LiveEvent::LiveOrderbook(ob)));
+                });
+                Ok(events)
             }
             _ => {
                 trace!("Unknown message : method {:?} message {:?}", method, message);
@@ -184,14 +453,7 @@ impl HubClientHandler for BittrexStreamingApi {
             }
         };
         if let Ok(les) = live_events {
-            let recipients = self.recipients.clone();
-            for le in les {
-                for r in &recipients {
-                    let le: LiveEvent = le.clone();
-                    r.do_send(LiveEventEnveloppe(Exchange::Bittrex, le)).unwrap();
-                }
-            }
-
+            self.broadcast(les);
         }
     }
 }
@@ -2,9 +2,11 @@ use bidir_map::BidirMap;
 use serde_json;
 use serde_json::Value;
 use serde_json::value::Map;
+use chrono::NaiveDateTime;
 
 use crate::error::*;
 use crate::types::Currency;
+use crate::types::OrderType;
 use crate::types::Pair;
 use crate::types::Pair::*;
 
@@ -345,6 +347,24 @@ pub fn parse_result(response: &Map<String, Value>) -> Result<Value> {
 
 }
 
+/// Return the order type associated to the one used by Bittrex (`LIMIT_BUY`, `LIMIT_SELL`)
+pub fn get_order_type(order_type: &str) -> Option<OrderType> {
+    match order_type {
+        "LIMIT_BUY" => Some(OrderType::BuyLimit),
+        "LIMIT_SELL" => Some(OrderType::SellLimit),
+        "MARKET_BUY" => Some(OrderType::BuyMarket),
+        "MARKET_SELL" => Some(OrderType::SellMarket),
+        _ => None,
+    }
+}
+
+/// Convert a date used by Bittrex (`2014-07-09T03:55:48.77`, UTC) to a UNIX timestamp in ms
+pub fn parse_date(date: &str) -> Result<i64> {
+    let datetime = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f")
+        .chain_err(|| ErrorKind::InvalidFieldFormat("date".to_string()))?;
+    Ok(datetime.timestamp_millis())
+}
+
 /// Return the currency enum associated with the
 /// string used by Bittrex. If no currency is found,
 /// return None
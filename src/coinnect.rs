@@ -5,17 +5,22 @@
 
 use std::path::PathBuf;
 
-use crate::kraken::{KrakenApi, KrakenCreds};
-use crate::poloniex::{PoloniexApi, PoloniexCreds};
+use crate::kraken::{KrakenApi, KrakenCreds, streaming_api::KrakenStreamingApi, user_streaming_api::KrakenUserStreamingApi};
+use crate::poloniex::{PoloniexApi, PoloniexCreds, streaming_api::PoloniexStreamingApi};
 use crate::bittrex::{BittrexApi, BittrexCreds};
 use crate::bittrex::streaming_api::BittrexStreamingApi;
-use crate::gdax::{GdaxApi, GdaxCreds};
-use crate::binance::{BinanceApi, BinanceCreds, streaming_api::BinanceStreamingApi};
-use crate::error::{Result};
+use crate::gdax::{GdaxApi, GdaxCreds, streaming_api::GdaxStreamingApi};
+use crate::binance::{BinanceApi, BinanceCreds, streaming_api::BinanceStreamingApi, user_streaming_api::BinanceUserStreamingApi};
+use crate::error::{Result, ErrorKind};
 use crate::exchange::{Exchange, ExchangeApi, ExchangeSettings};
 use crate::bitstamp::{BitstampApi, BitstampCreds};
 use crate::bitstamp::streaming_api::BitstampStreamingApi;
+use crate::bitstamp::user_streaming_api::BitstampUserStreamingApi;
 use crate::exchange_bot::{ExchangeBot};
+use crate::event_stream::{LiveEventStream, OverflowPolicy};
+use crate::transport::{HttpClient, HttpSettings};
+use crate::paper::{PaperExchange, PaperSettings};
+use crate::validation::ValidatedApi;
 use actix::{Recipient};
 use crate::types::{Channel, Pair, LiveEventEnveloppe};
 use std::collections::{HashMap, HashSet};
@@ -33,21 +38,75 @@ pub trait Credentials {
 pub struct Coinnect;
 
 impl Coinnect {
-    /// Create a new CoinnectApi by providing an API key & API secret
+    /// Create a new CoinnectApi by providing an API key & API secret.
+    /// Orders are checked before being sent, see `validation::ValidatedApi`.
     pub fn new<C: Credentials>(exchange: Exchange, creds: C) -> Result<Box<dyn ExchangeApi>> {
+        Ok(Box::new(ValidatedApi::new(Coinnect::new_unvalidated(exchange, creds)?)))
+    }
+
+    /// Same as `new`, orders are sent without the pre-trade checks
+    pub fn new_unvalidated<C: Credentials>(exchange: Exchange, creds: C) -> Result<Box<dyn ExchangeApi + Send>> {
         match exchange {
             Exchange::Bitstamp => Ok(Box::new(BitstampApi::new(creds)?)),
             Exchange::Kraken => Ok(Box::new(KrakenApi::new(creds)?)),
             Exchange::Poloniex => Ok(Box::new(PoloniexApi::new(creds)?)),
             Exchange::Bittrex => Ok(Box::new(BittrexApi::new(creds)?)),
             Exchange::Gdax => Ok(Box::new(GdaxApi::new(creds)?)),
-            Exchange::Binance => unimplemented!(),
+            Exchange::Binance => Ok(Box::new(BinanceApi::new(creds)?)),
         }
     }
 
+    /// Create a new CoinnectApi sending its requests with the HTTP settings, to target a mock
+    /// server or a gateway. Binance requests are made by its own client and cannot be redirected.
+    pub fn new_with_http<C: Credentials>(exchange: Exchange, creds: C, http: HttpSettings) -> Result<Box<dyn ExchangeApi>> {
+        let api: Box<dyn ExchangeApi + Send> = match exchange {
+            Exchange::Bitstamp => {
+                let mut api = BitstampApi::new(creds)?;
+                api.apply_http(&http);
+                Box::new(api)
+            },
+            Exchange::Kraken => {
+                let mut api = KrakenApi::new(creds)?;
+                api.apply_http(&http);
+                Box::new(api)
+            },
+            Exchange::Poloniex => {
+                let mut api = PoloniexApi::new(creds)?;
+                api.apply_http(&http);
+                Box::new(api)
+            },
+            Exchange::Bittrex => {
+                let mut api = BittrexApi::new(creds)?;
+                api.apply_http(&http);
+                Box::new(api)
+            },
+            Exchange::Gdax => {
+                let mut api = GdaxApi::new(creds)?;
+                api.apply_http(&http);
+                Box::new(api)
+            },
+            Exchange::Binance if http.is_default() => Box::new(BinanceApi::new(creds)?),
+            Exchange::Binance => return Err(ErrorKind::Unsupported("HTTP settings of the Binance client".to_string()).into()),
+        };
+        Ok(Box::new(ValidatedApi::new(api)))
+    }
+
+    /// Create a new CoinnectApi trading the account when `paper` is None, or a `PaperExchange`
+    /// simulating it over the market data of the exchange otherwise
+    pub fn new_trading<C: Credentials>(exchange: Exchange, creds: C, paper: Option<PaperSettings>) -> Result<Box<dyn ExchangeApi>> {
+        let settings = match paper {
+            Some(settings) => settings,
+            None => return Coinnect::new(exchange, creds),
+        };
+        let api = Coinnect::new_unvalidated(exchange, creds)?;
+        Ok(Box::new(PaperExchange::new(exchange, &settings, Some(api))))
+    }
+
     pub async fn new_stream<C: Credentials>(exchange: Exchange, creds: Box<C>, s: ExchangeSettings, r: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Box<dyn ExchangeBot>> {
         let mut channels : HashMap<Channel, HashSet<Pair>> = HashMap::new();
-        let pair_fn = crate::utils::pair_fn(exchange);
+        let pair_fn = |pair: &Pair| crate::utils::pair_name(exchange, pair);
+        let book_settings = s.orderbook.as_ref().map(|fs| fs.book.clone()).unwrap_or_default();
+        let connection = s.connection.clone();
         if let Some(fs) = s.orderbook {
             // Live order book pairs
             let order_book_pairs: HashSet<Pair> = fs.symbols
@@ -61,12 +120,54 @@ impl Coinnect {
                 .iter().filter(|&currency_pair| pair_fn(&currency_pair).is_some()).map(|&p| p).collect();
             channels.insert(Channel::LiveTrades, trade_pairs);
         }
+        if let Some(fs) = s.ticker {
+            let ticker_pairs: HashSet<Pair> = fs.symbols
+                .iter().filter(|&currency_pair| pair_fn(&currency_pair).is_some()).map(|&p| p).collect();
+            channels.insert(Channel::LiveTicker, ticker_pairs);
+        }
+        if let Some(fs) = s.best_bid_offer {
+            let bbo_pairs: HashSet<Pair> = fs.symbols
+                .iter().filter(|&currency_pair| pair_fn(&currency_pair).is_some()).map(|&p| p).collect();
+            channels.insert(Channel::LiveBestBidOffer, bbo_pairs);
+        }
+        if let Some(cs) = s.candles {
+            let candle_pairs: HashSet<Pair> = cs.symbols
+                .iter().filter(|&currency_pair| pair_fn(&currency_pair).is_some()).map(|&p| p).collect();
+            for interval in cs.intervals {
+                channels.insert(Channel::LiveCandles(interval), candle_pairs.clone());
+            }
+        }
         debug!("{:?}", channels);
         match exchange {
-            Exchange::Bitstamp => Ok(Box::new(BitstampStreamingApi::new_bot(creds, channels, r).await?)),
-            Exchange::Bittrex => Ok(Box::new(BittrexStreamingApi::new_bot(creds, channels, r).await?)),
-            Exchange::Binance => Ok(Box::new(BinanceStreamingApi::new_bot(creds, channels, r).await?)),
-            _ => unimplemented!()
+            Exchange::Bitstamp => Ok(Box::new(BitstampStreamingApi::new_bot(creds, channels, book_settings, connection, r).await?)),
+            Exchange::Bittrex => Ok(Box::new(BittrexStreamingApi::new_bot(creds, channels, book_settings, connection, r).await?)),
+            Exchange::Binance => Ok(Box::new(BinanceStreamingApi::new_bot(creds, channels, book_settings, connection, r).await?)),
+            Exchange::Kraken => Ok(Box::new(KrakenStreamingApi::new_bot(creds, channels, book_settings, connection, r).await?)),
+            Exchange::Poloniex => Ok(Box::new(PoloniexStreamingApi::new_bot(creds, channels, book_settings, connection, r).await?)),
+            Exchange::Gdax => Ok(Box::new(GdaxStreamingApi::new_bot(creds, channels, book_settings, connection, r).await?)),
+        }
+    }
+
+    /// Same as `new_stream`, live events are returned as a stream buffering up to `capacity`
+    /// events instead of being sent to actix recipients
+    pub async fn new_event_stream<C: Credentials>(exchange: Exchange, creds: Box<C>, s: ExchangeSettings, capacity: usize, policy: OverflowPolicy) -> Result<(Box<dyn ExchangeBot>, LiveEventStream)> {
+        let (stream, recipient) = LiveEventStream::new(capacity, policy);
+        let bot = Coinnect::new_stream(exchange, creds, s, vec![recipient]).await?;
+        Ok((bot, stream))
+    }
+
+    /// Open the private stream of the account : order updates, fills and balance changes.
+    /// Order updates and fills are only sent for the `user_data` pairs of the settings.
+    pub async fn new_user_stream<C: Credentials>(exchange: Exchange, creds: Box<C>, s: ExchangeSettings, r: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Box<dyn ExchangeBot>> {
+        let pair_fn = |pair: &Pair| crate::utils::pair_name(exchange, pair);
+        let pairs: HashSet<Pair> = s.user_data.map_or(HashSet::new(), |fs| fs.symbols
+            .iter().filter(|&currency_pair| pair_fn(currency_pair).is_some()).map(|&p| p).collect());
+        let connection = s.connection;
+        match exchange {
+            Exchange::Binance => Ok(Box::new(BinanceUserStreamingApi::new_bot(creds, pairs, connection, r).await?)),
+            Exchange::Bitstamp => Ok(Box::new(BitstampUserStreamingApi::new_bot(creds, pairs, connection, r).await?)),
+            Exchange::Kraken => Ok(Box::new(KrakenUserStreamingApi::new_bot(creds, pairs, connection, r).await?)),
+            _ => Err(ErrorKind::Unsupported(format!("private stream on {:?}", exchange)).into()),
         }
     }
 
@@ -75,29 +176,19 @@ impl Coinnect {
     ///
     /// For this example, you could use load your Bitstamp account with
     /// `new_from_file(Exchange::Bitstamp, "account_bitstamp", Path::new("/keys.json"))`
+    ///
+    /// Orders are checked before being sent, as with `new`.
     pub fn new_from_file(exchange: Exchange,
                          name: &str,
                          path: PathBuf)
                          -> Result<Box<dyn ExchangeApi>> {
         match exchange {
-            Exchange::Bitstamp => {
-                Ok(Box::new(BitstampApi::new(BitstampCreds::new_from_file(name, path)?)?))
-            }
-            Exchange::Kraken => {
-                Ok(Box::new(KrakenApi::new(KrakenCreds::new_from_file(name, path)?)?))
-            }
-            Exchange::Poloniex => {
-                Ok(Box::new(PoloniexApi::new(PoloniexCreds::new_from_file(name, path)?)?))
-            }
-            Exchange::Bittrex => {
-                Ok(Box::new(BittrexApi::new(BittrexCreds::new_from_file(name, path)?)?))
-            }
-            Exchange::Gdax => {
-                Ok(Box::new(GdaxApi::new(GdaxCreds::new_from_file(name, path)?)?))
-            },
-            Exchange::Binance => {
-                Ok(Box::new(BinanceApi::new(BinanceCreds::new_from_file(name, path)?)?))
-            }
+            Exchange::Bitstamp => Coinnect::new(exchange, BitstampCreds::new_from_file(name, path)?),
+            Exchange::Kraken => Coinnect::new(exchange, KrakenCreds::new_from_file(name, path)?),
+            Exchange::Poloniex => Coinnect::new(exchange, PoloniexCreds::new_from_file(name, path)?),
+            Exchange::Bittrex => Coinnect::new(exchange, BittrexCreds::new_from_file(name, path)?),
+            Exchange::Gdax => Coinnect::new(exchange, GdaxCreds::new_from_file(name, path)?),
+            Exchange::Binance => Coinnect::new(exchange, BinanceCreds::new_from_file(name, path)?),
         }
     }
 }
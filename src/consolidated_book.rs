@@ -0,0 +1,270 @@
+//! Order books of several exchanges merged by price, fed by the live events of their bots.
+
+use actix::{Actor, Context, Handler, Recipient};
+use bigdecimal::{BigDecimal, Zero};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use crate::exchange::Exchange;
+use crate::types::{BookState, FeedStatus, LiveEvent, LiveEventEnveloppe, OrderSide, Orderbook, Pair, Price, Volume};
+
+/// A price level of a consolidated book with the volume of every exchange quoting it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsolidatedLevel {
+    pub price: Price,
+    pub volumes: Vec<(Exchange, Volume)>,
+}
+
+impl ConsolidatedLevel {
+    /// Volume of the level over every exchange
+    pub fn volume(&self) -> Volume {
+        self.volumes.iter().fold(BigDecimal::zero(), |total, (_, v)| total + v.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsolidatedOrderbook {
+    /// UNIX timestamp in ms of the most recent exchange book
+    pub timestamp: i64,
+    pub pair: Pair,
+    /// Ask levels by ascending price
+    pub asks: Vec<ConsolidatedLevel>,
+    /// Bid levels by descending price
+    pub bids: Vec<ConsolidatedLevel>,
+}
+
+impl ConsolidatedOrderbook {
+    pub fn best_bid(&self) -> Option<&ConsolidatedLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&ConsolidatedLevel> {
+        self.asks.first()
+    }
+
+    /// Return the average price of an order of `quantity` taking the levels of every exchange,
+    /// asks for a buy and bids for a sell. None if the book is not deep enough.
+    pub fn effective_price(&self, side: OrderSide, quantity: &Volume) -> Option<Price> {
+        let levels = match side {
+            OrderSide::Buy => &self.asks,
+            OrderSide::Sell => &self.bids,
+        };
+        effective_price(levels, quantity)
+    }
+}
+
+fn effective_price(levels: &[ConsolidatedLevel], quantity: &Volume) -> Option<Price> {
+    if *quantity <= BigDecimal::zero() {
+        return None;
+    }
+    let mut remaining = quantity.clone();
+    let mut cost = BigDecimal::zero();
+    for level in levels {
+        let taken = std::cmp::min(remaining.clone(), level.volume());
+        cost = cost + level.price.clone() * taken.clone();
+        remaining = remaining - taken;
+        if remaining.is_zero() {
+            return Some(cost / quantity.clone());
+        }
+    }
+    None
+}
+
+/// Latest book of every exchange by normalized pair
+#[derive(Debug, Default)]
+pub struct ConsolidatedBooks {
+    /// Pairs merged into the book of another pair, such as `BTC_USDT` into `BTC_USD`
+    aliases: HashMap<Pair, Pair>,
+    books: HashMap<Pair, HashMap<Exchange, Orderbook>>,
+}
+
+impl ConsolidatedBooks {
+    pub fn new(aliases: HashMap<Pair, Pair>) -> ConsolidatedBooks {
+        ConsolidatedBooks { aliases, books: HashMap::new() }
+    }
+
+    /// Return the pair under which the books of `pair` are merged
+    pub fn normalize(&self, pair: Pair) -> Pair {
+        self.aliases.get(&pair).cloned().unwrap_or(pair)
+    }
+
+    /// Replace the book of an exchange
+    pub fn update(&mut self, exchange: Exchange, book: Orderbook) {
+        let pair = self.normalize(book.pair);
+        self.books.entry(pair).or_default().insert(exchange, book);
+    }
+
+    pub fn remove(&mut self, exchange: Exchange, pair: Pair) {
+        let pair = self.normalize(pair);
+        if let Some(books) = self.books.get_mut(&pair) {
+            books.remove(&exchange);
+        }
+    }
+
+    /// Remove every book of an exchange, when its feed is down
+    pub fn remove_exchange(&mut self, exchange: Exchange) {
+        for books in self.books.values_mut() {
+            books.remove(&exchange);
+        }
+    }
+
+    /// Exchanges with a book for the pair
+    pub fn exchanges(&self, pair: Pair) -> Vec<Exchange> {
+        self.books.get(&self.normalize(pair)).map_or(vec![], |books| books.keys().cloned().collect())
+    }
+
+    /// Merge the asks (by ascending price) or the bids (by descending price) of every exchange
+    fn levels(&self, pair: Pair, bids: bool) -> Vec<ConsolidatedLevel> {
+        let mut merged: BTreeMap<Price, Vec<(Exchange, Volume)>> = BTreeMap::new();
+        if let Some(books) = self.books.get(&self.normalize(pair)) {
+            for (exchange, book) in books {
+                let levels = if bids { &book.bids } else { &book.asks };
+                for (price, volume) in levels {
+                    merged.entry(price.clone()).or_default().push((*exchange, volume.clone()));
+                }
+            }
+        }
+        let levels = merged.into_iter().map(|(price, volumes)| ConsolidatedLevel { price, volumes });
+        if bids {
+            levels.rev().collect()
+        } else {
+            levels.collect()
+        }
+    }
+
+    pub fn order_book(&self, pair: Pair) -> Option<ConsolidatedOrderbook> {
+        let pair = self.normalize(pair);
+        let timestamp = self.books.get(&pair)?.values().map(|book| book.timestamp).max()?;
+        Some(ConsolidatedOrderbook {
+            timestamp,
+            pair,
+            asks: self.levels(pair, false),
+            bids: self.levels(pair, true),
+        })
+    }
+
+    pub fn best_bid(&self, pair: Pair) -> Option<ConsolidatedLevel> {
+        self.levels(pair, true).into_iter().next()
+    }
+
+    pub fn best_ask(&self, pair: Pair) -> Option<ConsolidatedLevel> {
+        self.levels(pair, false).into_iter().next()
+    }
+
+    /// See `ConsolidatedOrderbook::effective_price`
+    pub fn effective_price(&self, pair: Pair, side: OrderSide, quantity: &Volume) -> Option<Price> {
+        effective_price(&self.levels(pair, side == OrderSide::Sell), quantity)
+    }
+
+    /// Follow the books of a live event, books that are out of sync or of a feed that is down
+    /// are left out
+    pub fn handle_event(&mut self, exchange: Exchange, event: &LiveEvent) {
+        match event {
+            LiveEvent::LiveOrderbook(book) => self.update(exchange, book.clone()),
+            LiveEvent::LiveBookState(state) if state.state != BookState::Synced => self.remove(exchange, state.pair),
+            LiveEvent::LiveFeedStatus(status) if status.status == FeedStatus::Disconnected || status.status == FeedStatus::Stale => {
+                self.remove_exchange(exchange)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Consolidated books shared with the actor following the bots
+#[derive(Debug, Clone)]
+pub struct ConsolidatedBook {
+    books: Rc<RefCell<ConsolidatedBooks>>,
+}
+
+impl ConsolidatedBook {
+    /// Return the consolidated books and the recipient to give to the bots of every exchange.
+    /// Books of the pairs in `aliases` are merged into the book of the pair they map to.
+    /// Must be called from a running actix system, queries must be made from the same thread.
+    pub fn new(aliases: HashMap<Pair, Pair>) -> (ConsolidatedBook, Recipient<LiveEventEnveloppe>) {
+        let books = Rc::new(RefCell::new(ConsolidatedBooks::new(aliases)));
+        let actor = ConsolidatedBookActor { books: books.clone() }.start();
+        (ConsolidatedBook { books }, actor.recipient())
+    }
+
+    pub fn order_book(&self, pair: Pair) -> Option<ConsolidatedOrderbook> {
+        self.books.borrow().order_book(pair)
+    }
+
+    pub fn best_bid(&self, pair: Pair) -> Option<ConsolidatedLevel> {
+        self.books.borrow().best_bid(pair)
+    }
+
+    pub fn best_ask(&self, pair: Pair) -> Option<ConsolidatedLevel> {
+        self.books.borrow().best_ask(pair)
+    }
+
+    /// See `ConsolidatedOrderbook::effective_price`
+    pub fn effective_price(&self, pair: Pair, side: OrderSide, quantity: &Volume) -> Option<Price> {
+        self.books.borrow().effective_price(pair, side, quantity)
+    }
+
+    pub fn exchanges(&self, pair: Pair) -> Vec<Exchange> {
+        self.books.borrow().exchanges(pair)
+    }
+}
+
+/// Applies the live events sent by bots to the consolidated books
+struct ConsolidatedBookActor {
+    books: Rc<RefCell<ConsolidatedBooks>>,
+}
+
+impl Actor for ConsolidatedBookActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for ConsolidatedBookActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        self.books.borrow_mut().handle_event(msg.0, &msg.1);
+    }
+}
+
+#[cfg(test)]
+mod consolidated_book_tests {
+    use super::*;
+    use crate::types::LiveBookState;
+
+    fn book(pair: Pair, timestamp: i64, asks: &[(i32, i32)], bids: &[(i32, i32)]) -> Orderbook {
+        let levels = |l: &[(i32, i32)]| l.iter().map(|&(p, v)| (BigDecimal::from(p), BigDecimal::from(v))).collect();
+        Orderbook { timestamp, pair, asks: levels(asks), bids: levels(bids) }
+    }
+
+    #[test]
+    fn should_merge_books_by_price() {
+        let mut aliases = HashMap::new();
+        aliases.insert(Pair::BTC_USDT, Pair::BTC_USD);
+        let mut books = ConsolidatedBooks::new(aliases);
+        books.update(Exchange::Kraken, book(Pair::BTC_USD, 1, &[(101, 1), (102, 2)], &[(99, 1)]));
+        books.update(Exchange::Binance, book(Pair::BTC_USDT, 2, &[(101, 3)], &[(100, 2), (99, 1)]));
+
+        let ob = books.order_book(Pair::BTC_USDT).unwrap();
+        assert_eq!(ob.pair, Pair::BTC_USD);
+        assert_eq!(ob.timestamp, 2);
+        assert_eq!(ob.asks.iter().map(|l| (l.price.clone(), l.volume())).collect::<Vec<_>>(),
+                   vec![(BigDecimal::from(101), BigDecimal::from(4)), (BigDecimal::from(102), BigDecimal::from(2))]);
+        let best_bid = books.best_bid(Pair::BTC_USD).unwrap();
+        assert_eq!(best_bid.volumes, vec![(Exchange::Binance, BigDecimal::from(2))]);
+        assert_eq!(ob.bids[1].volumes.len(), 2);
+
+        // 4 at 101 and 1 at 102
+        assert_eq!(books.effective_price(Pair::BTC_USD, OrderSide::Buy, &BigDecimal::from(5)), "101.2".parse::<BigDecimal>().ok());
+        assert_eq!(books.effective_price(Pair::BTC_USD, OrderSide::Sell, &BigDecimal::from(2)), Some(BigDecimal::from(100)));
+        assert!(books.effective_price(Pair::BTC_USD, OrderSide::Buy, &BigDecimal::from(7)).is_none());
+    }
+
+    #[test]
+    fn should_leave_out_unsynced_books() {
+        let mut books = ConsolidatedBooks::default();
+        books.update(Exchange::Kraken, book(Pair::BTC_EUR, 1, &[(101, 1)], &[(99, 1)]));
+        books.update(Exchange::Gdax, book(Pair::BTC_EUR, 1, &[(100, 1)], &[(98, 1)]));
+        books.handle_event(Exchange::Gdax, &LiveEvent::LiveBookState(LiveBookState { event_ms: 2, pair: Pair::BTC_EUR, state: BookState::Resyncing }));
+        assert_eq!(books.exchanges(Pair::BTC_EUR), vec![Exchange::Kraken]);
+        assert_eq!(books.best_ask(Pair::BTC_EUR).unwrap().price, BigDecimal::from(101));
+    }
+}
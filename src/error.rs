@@ -105,6 +105,11 @@ error_chain!{
                 display("No price specified.")
         }
 
+        Unsupported(operation: String) {
+            description("Unsupported")
+                display("This exchange does not support: {}.", operation)
+        }
+
         InvalidConfigType(expected: Exchange, find: Exchange){
             description("InvalidConfigType")
                 display("Invalid config: \nExpected: {:?}\nFind: {:?}", expected, find)
@@ -0,0 +1,166 @@
+//! Live events as a `futures::Stream`, for consumers that do not write actix actors.
+
+use actix::{Actor, Context, Handler, ActorContext, Recipient};
+use futures::channel::mpsc;
+use futures::stream::Stream;
+use futures::task::{Context as TaskContext, Poll};
+use serde::Deserialize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::types::LiveEventEnveloppe;
+
+/// What to do with a new event when the stream buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Drop the new event
+    DropNewest,
+    /// Drop the oldest buffered event to make room for the new one
+    DropOldest,
+    /// End the stream once the buffered events are consumed, the consumer is too slow
+    Close,
+}
+
+/// Sending side of a `LiveEventStream`, applies the overflow policy
+#[derive(Debug)]
+struct EventSink {
+    sender: mpsc::Sender<LiveEventEnveloppe>,
+    /// Shared with the stream to drop the oldest events
+    receiver: Arc<Mutex<mpsc::Receiver<LiveEventEnveloppe>>>,
+    policy: OverflowPolicy,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventSink {
+    /// Return false once the stream is closed or dropped
+    fn push(&mut self, event: LiveEventEnveloppe) -> bool {
+        let event = match self.sender.try_send(event) {
+            Ok(()) => return true,
+            Err(e) if e.is_disconnected() => return false,
+            Err(e) => e.into_inner(),
+        };
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        match self.policy {
+            OverflowPolicy::DropNewest => true,
+            OverflowPolicy::DropOldest => {
+                let _ = self.receiver.lock().unwrap().try_next();
+                self.sender.try_send(event).is_ok()
+            }
+            OverflowPolicy::Close => {
+                warn!("Live event stream full, closing it");
+                self.sender.close_channel();
+                false
+            }
+        }
+    }
+}
+
+/// Bounded stream of live events, fed by the `Recipient` returned with it.
+/// The stream ends when the recipient is dropped along with the bots holding it.
+#[derive(Debug)]
+pub struct LiveEventStream {
+    receiver: Arc<Mutex<mpsc::Receiver<LiveEventEnveloppe>>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LiveEventStream {
+    /// Return a stream buffering up to `capacity` events and the recipient to give to a bot.
+    /// The recipient is an actor, this must be called from the actix system running the bots.
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> (LiveEventStream, Recipient<LiveEventEnveloppe>) {
+        let (stream, sink) = event_channel(capacity, policy);
+        let forwarder = StreamForwarder { sink }.start();
+        (stream, forwarder.recipient())
+    }
+
+    /// Events dropped so far because the buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+fn event_channel(capacity: usize, policy: OverflowPolicy) -> (LiveEventStream, EventSink) {
+    // The channel holds one more message per sender
+    let (sender, receiver) = mpsc::channel(capacity.max(1) - 1);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let dropped = Arc::new(AtomicU64::new(0));
+    let sink = EventSink { sender, receiver: receiver.clone(), policy, dropped: dropped.clone() };
+    (LiveEventStream { receiver, dropped }, sink)
+}
+
+impl Stream for LiveEventStream {
+    type Item = LiveEventEnveloppe;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let mut receiver = self.receiver.lock().unwrap();
+        Pin::new(&mut *receiver).poll_next(cx)
+    }
+}
+
+impl Drop for LiveEventStream {
+    fn drop(&mut self) {
+        if let Ok(mut receiver) = self.receiver.lock() {
+            receiver.close();
+        }
+    }
+}
+
+/// Pushes the events sent by bots to a `LiveEventStream`
+struct StreamForwarder {
+    sink: EventSink,
+}
+
+impl Actor for StreamForwarder {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for StreamForwarder {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, ctx: &mut Context<Self>) {
+        if !self.sink.push(msg) {
+            ctx.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod event_stream_tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use crate::exchange::Exchange;
+    use crate::types::LiveEvent;
+
+    /// Push the events then drop the sink, return the events streamed and the events dropped
+    fn stream_all(capacity: usize, policy: OverflowPolicy, exchanges: &[Exchange]) -> (Vec<Exchange>, u64) {
+        let (stream, mut sink) = event_channel(capacity, policy);
+        for &exchange in exchanges {
+            if !sink.push(LiveEventEnveloppe(exchange, LiveEvent::Noop)) {
+                break;
+            }
+        }
+        drop(sink);
+        let dropped = stream.dropped();
+        (block_on(stream.map(|e| e.0).collect()), dropped)
+    }
+
+    #[test]
+    fn should_apply_overflow_policy() {
+        let newest = stream_all(2, OverflowPolicy::DropNewest, &[Exchange::Kraken, Exchange::Gdax, Exchange::Binance]);
+        assert_eq!(newest, (vec![Exchange::Kraken, Exchange::Gdax], 1));
+
+        let oldest = stream_all(2, OverflowPolicy::DropOldest, &[Exchange::Kraken, Exchange::Gdax, Exchange::Binance]);
+        assert_eq!(oldest, (vec![Exchange::Gdax, Exchange::Binance], 1));
+
+        let close = stream_all(2, OverflowPolicy::Close, &[Exchange::Kraken, Exchange::Gdax, Exchange::Binance, Exchange::Poloniex]);
+        assert_eq!(close, (vec![Exchange::Kraken, Exchange::Gdax], 1));
+    }
+
+    #[test]
+    fn should_end_when_the_senders_drop() {
+        let (stream, sink) = event_channel(2, OverflowPolicy::DropNewest);
+        drop(sink);
+        assert_eq!(block_on(stream.collect::<Vec<_>>()).len(), 0);
+    }
+}
@@ -6,6 +6,7 @@ use std::str::FromStr;
 
 use crate::error::*;
 use crate::types::*;
+use crate::exchange_bot::ConnectionSettings;
 use futures::{Future};
 use async_trait::async_trait;
 use serde::{Deserializer, Deserialize};
@@ -96,18 +97,81 @@ pub trait ExchangeApi: Debug {
                  price: Option<Price>)
                  -> Result<OrderInfo>;
 
+    /// Place an order with options beyond `add_order` (stop orders, time in force, post-only,
+    /// client order id). Options the exchange cannot honor return `ErrorKind::Unsupported`
+    /// before anything is sent.
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo>;
+
     /// Retrieve the current amounts of all the currencies that the account holds
     /// The amounts returned are available (not used to open an order)
     async fn balances(&mut self) -> Result<Balances>;
+
+    /// Cancel an order previously placed with `add_order`.
+    /// Every identifier of the `OrderInfo` is canceled, the returned `OrderInfo` lists the ones
+    /// the exchange acknowledged.
+    async fn cancel_order(&mut self, pair: Pair, order: &OrderInfo) -> Result<OrderInfo>;
+
+    /// Return the current state of an order previously placed with `add_order`.
+    async fn order_status(&mut self, pair: Pair, order: &OrderInfo) -> Result<Order>;
+
+    /// Return the orders still open on the exchange for the specified Pair.
+    async fn open_orders(&mut self, pair: Pair) -> Result<Vec<Order>>;
+
+    /// Return the latest trades for the specified Pair, by ascending time.
+    /// `since` is a UNIX timestamp in ms, when None the exchange default history is returned.
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>>;
+
+    /// Return OHLC candles for the specified Pair, by ascending time.
+    /// `start` and `end` are UNIX timestamps in ms, when None the exchange default range is
+    /// returned.
+    async fn candles(&mut self,
+                     pair: Pair,
+                     interval: Interval,
+                     start: Option<i64>,
+                     end: Option<i64>)
+                     -> Result<Vec<Candle>>;
+
+    /// Return the mapping between normalized symbols and the exchange names for every market
+    /// listed by the exchange, including the ones the `Pair` enum has no variant for.
+    /// The markets are registered, so that `Pair::from(symbol)` can then be used with the other
+    /// methods and the streaming bots.
+    async fn symbols(&mut self) -> Result<SymbolMap>;
+
+    /// Return the trading rules (price tick, quantity step, limits and fees) of every market
+    /// listed by the exchange. Use `MarketInfo::round_price` and `MarketInfo::round_quantity`
+    /// before placing an order.
+    async fn markets(&mut self) -> Result<Markets>;
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct FeedSettings {
-    pub symbols: Vec<Pair>
+    pub symbols: Vec<Pair>,
+    /// Order book options (depth, throttle, emission), ignored by other feeds
+    #[serde(flatten)]
+    pub book: BookSettings,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CandleSettings {
+    pub symbols: Vec<Pair>,
+    /// A `LiveCandles` channel is subscribed for each interval
+    pub intervals: Vec<Interval>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ExchangeSettings {
     pub orderbook: Option<FeedSettings>,
     pub trades: Option<FeedSettings>,
+    #[serde(default)]
+    pub ticker: Option<FeedSettings>,
+    #[serde(default)]
+    pub best_bid_offer: Option<FeedSettings>,
+    #[serde(default)]
+    pub candles: Option<CandleSettings>,
+    /// Pairs of the private order updates and fills, see `Coinnect::new_user_stream`
+    #[serde(default)]
+    pub user_data: Option<FeedSettings>,
+    /// Websocket ping and idle timeout options
+    #[serde(default)]
+    pub connection: ConnectionSettings,
 }
@@ -1,4 +1,4 @@
-use actix::{Context, io::SinkWrite, Actor, Handler, StreamHandler, AsyncContext, ActorContext, Addr, ActorFuture, WrapFuture, ContextFutureSpawner, Supervisor};
+use actix::{Context, io::SinkWrite, Actor, Handler, StreamHandler, AsyncContext, ActorContext, Addr, ActorFuture, WrapFuture, ContextFutureSpawner, Supervisor, Recipient};
 use awc::{error::WsProtocolError, ws::{Codec, Frame, Message}, BoxedSocket};
 use actix_codec::{Framed};
 use std::time::{Duration, Instant};
@@ -6,6 +6,9 @@ use bytes::Bytes;
 use futures::stream::{SplitSink, StreamExt};
 use crate::helpers;
 use crate::error::*;
+use crate::types::{Orderbook, Pair, Channel, LiveEvent, LiveEventEnveloppe, LiveFeedStatus, FeedStatus, Interval};
+use crate::exchange::Exchange;
+use crate::recorder::{FeedRecorder, RecorderSettings};
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
 use async_std::task;
@@ -13,27 +16,166 @@ use std::marker::PhantomData;
 use std::pin::Pin;
 use futures::task::Poll;
 use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::cell::RefCell;
+use chrono::Utc;
+use serde::Deserialize;
+
+/// Feeds with no message received for this long are flagged as stale
+pub const STALE_FEED_DELAY: Duration = Duration::from_secs(60);
+
+/// Interval of `WsHandler::handle_tick`, to close candles when no trade comes
+pub const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Options of the websocket connection of a feed
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConnectionSettings {
+    /// Delay between two pings sent to the server, in ms
+    #[serde(default = "default_ping_interval_ms")]
+    pub ping_interval_ms: u64,
+    /// The connection is opened again when nothing was received for this long, pongs included, in ms
+    #[serde(default = "default_idle_timeout_ms")]
+    pub idle_timeout_ms: u64,
+    /// Raw frames received are written to files when set
+    #[serde(default)]
+    pub record: Option<RecorderSettings>,
+    /// Replaces the websocket url of the feed, such as the one of a `mock_server::MockServer`
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+fn default_ping_interval_ms() -> u64 {
+    30_000
+}
+
+fn default_idle_timeout_ms() -> u64 {
+    90_000
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        ConnectionSettings {
+            ping_interval_ms: default_ping_interval_ms(),
+            idle_timeout_ms: default_idle_timeout_ms(),
+            record: None,
+            url: None,
+        }
+    }
+}
 
 pub struct DefaultWsActor {
     inner: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
     handler: Box<dyn WsHandler>,
+    /// Last time a frame was received
     hb: Instant,
     conn_backoff: ExponentialBackoff,
+    settings: ConnectionSettings,
+    monitor: FeedMonitor,
+    recorder: Option<FeedRecorder>,
     pub url: String,
     pub name: String
 }
 
+/// Frames sent to the server by a `WsHandler`
+pub trait WsWriter {
+    /// Queue a frame, it is returned if the connection is closed
+    fn write(&mut self, msg: Message) -> std::result::Result<(), Message>;
+    /// Drop the connection once the incoming frame is handled, the supervisor opens a new one
+    fn reconnect(&mut self) {}
+}
+
+impl WsWriter for SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>> {
+    fn write(&mut self, msg: Message) -> std::result::Result<(), Message> {
+        SinkWrite::write(self, msg)
+    }
+}
+
+/// Writer of the frames handled by `WsHandler::handle_in`, records the reconnection requests
+struct FrameWriter<'a> {
+    sink: &'a mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
+    reconnect: bool,
+}
+
+impl<'a> WsWriter for FrameWriter<'a> {
+    fn write(&mut self, msg: Message) -> std::result::Result<(), Message> {
+        self.sink.write(msg)
+    }
+
+    fn reconnect(&mut self) {
+        self.reconnect = true;
+    }
+}
+
 #[async_trait]
 pub trait WsHandler {
     /// Handle incoming messages
-    fn handle_in(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes);
-    fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>);
+    fn handle_in(&mut self, w: &mut dyn WsWriter, msg: Bytes);
+    fn handle_started(&mut self, w: &mut dyn WsWriter);
+    /// Subscribe to pairs of a channel, they are kept in the channel map for reconnections.
+    /// Return the pairs subscribed, unsupported pairs and channels are left out.
+    fn handle_subscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair>;
+    /// Unsubscribe from pairs of a channel and remove them from the channel map, return the pairs
+    /// that were subscribed
+    fn handle_unsubscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair>;
+    /// Handle a frame recorded by the handler itself rather than received, such as a book
+    /// snapshot fetched over REST, see `replay::FeedReplay`
+    fn handle_recorded(&mut self, _method: &str, _payload: &str) {}
+    /// Called every `TICK_INTERVAL` with the clock, and before each frame with its reception
+    /// time during a replay
+    fn handle_tick(&mut self, _now_ms: i64) {}
     async fn handle_async(&mut self) {}
 }
 
+/// Subscribe a running feed to pairs of a channel
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct Subscribe {
+    pub channel: Channel,
+    pub pairs: HashSet<Pair>,
+}
+
+/// Unsubscribe a running feed from pairs of a channel
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct Unsubscribe {
+    pub channel: Channel,
+    pub pairs: HashSet<Pair>,
+}
+
+/// Add pairs to a channel map, return the ones that were not subscribed yet
+pub fn add_channel_pairs(channels: &mut HashMap<Channel, HashSet<Pair>>, channel: Channel, pairs: HashSet<Pair>) -> Vec<Pair> {
+    let subscribed = channels.entry(channel).or_default();
+    pairs.into_iter().filter(|&pair| subscribed.insert(pair)).collect()
+}
+
+/// Remove pairs from a channel map, return the ones that were subscribed
+pub fn remove_channel_pairs(channels: &mut HashMap<Channel, HashSet<Pair>>, channel: Channel, pairs: HashSet<Pair>) -> Vec<Pair> {
+    match channels.get_mut(&channel) {
+        Some(subscribed) => pairs.into_iter().filter(|pair| subscribed.remove(pair)).collect(),
+        None => vec![],
+    }
+}
+
+/// Return the intervals of the `LiveCandles` channels subscribed for a pair
+pub fn candle_intervals(channels: &HashMap<Channel, HashSet<Pair>>, pair: &Pair) -> Vec<Interval> {
+    channels.iter()
+        .filter_map(|(channel, pairs)| match channel {
+            Channel::LiveCandles(interval) if pairs.contains(pair) => Some(*interval),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Send a text frame, for handlers that subscribe after an asynchronous call
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ClientCommand(pub String);
+
+/// Connect again to a new url, for feeds authenticated by their url
 #[derive(Message)]
 #[rtype(result = "()")]
-struct ClientCommand(String);
+pub struct Reconnect(pub String);
 
 impl Actor for DefaultWsActor
 {
@@ -41,11 +183,13 @@ impl Actor for DefaultWsActor
 
     fn started(&mut self, ctx: &mut Context<Self>) {
         // start heartbeats otherwise server will disconnect after 10 seconds
-        self.hb(ctx)
+        self.hb(ctx);
+        ctx.run_interval(TICK_INTERVAL, |act, _| act.handler.handle_tick(helpers::get_unix_timestamp_ms()));
     }
 
     fn stopped(&mut self, _: &mut Context<Self>) {
         info!("DefaultWsActor {} : disconnected", self.name);
+        self.monitor.on_disconnected();
     }
 }
 
@@ -60,6 +204,7 @@ impl actix::Supervised for DefaultWsActor {
                     let (sink, stream) = client.split();
                     DefaultWsActor::add_stream(stream, ctx);
                     act.conn_backoff.reset();
+                    act.hb = Instant::now();
                     act.inner = SinkWrite::new(sink, ctx);
                 }
                 Err(err) => {
@@ -76,8 +221,8 @@ impl actix::Supervised for DefaultWsActor {
 
 impl DefaultWsActor
 {
-    pub async fn new(name: &'static str, wss_url: &str, conn_timeout: Option<Duration>, handler: Box<dyn WsHandler>) -> Result<Addr<DefaultWsActor>> {
-        let url = wss_url.to_string();
+    pub async fn new(name: &'static str, wss_url: &str, conn_timeout: Option<Duration>, settings: ConnectionSettings, monitor: FeedMonitor, handler: Box<dyn WsHandler>) -> Result<Addr<DefaultWsActor>> {
+        let url = settings.url.clone().unwrap_or_else(|| wss_url.to_string());
         let name = name.to_string();
         let mut conn_backoff = ExponentialBackoff::default();
         conn_backoff.max_elapsed_time = conn_timeout;
@@ -102,28 +247,82 @@ impl DefaultWsActor
         conn_backoff.max_elapsed_time = None;
         conn_backoff.reset();
         let (sink, stream) = c.unwrap().split();
+        let recorder = settings.record.clone().map(|record| FeedRecorder::new(&name, monitor.exchange(), record));
         Ok(Supervisor::start(move |ctx| {
             DefaultWsActor::add_stream(stream, ctx);
-            DefaultWsActor { inner: SinkWrite::new(sink, ctx), handler, hb: Instant::now(), url: url.clone(), conn_backoff, name: name.clone() }
+            DefaultWsActor { inner: SinkWrite::new(sink, ctx), handler, hb: Instant::now(), url: url.clone(), conn_backoff, settings: settings.clone(), monitor: monitor.clone(), recorder, name: name.clone() }
         }))
     }
     fn hb(&self, ctx: &mut Context<Self>) {
-        ctx.run_later(Duration::new(30, 0), |act, ctx| {
-            act.inner.write(Message::Ping(Bytes::from_static(b""))).unwrap();
+        ctx.run_later(Duration::from_millis(self.settings.ping_interval_ms), |act, ctx| {
+            let idle_timeout = Duration::from_millis(act.settings.idle_timeout_ms);
+            if Instant::now().duration_since(act.hb) > idle_timeout {
+                // Half open connections never end the stream, the supervisor reconnects
+                warn!("DefaultWsActor {} : nothing received for {:?}, reconnecting", act.name, idle_timeout);
+                ctx.stop();
+                return;
+            }
+            if act.inner.write(Message::Ping(Bytes::from_static(b""))).is_err() {
+                warn!("DefaultWsActor {} : cannot send ping, reconnecting", act.name);
+                ctx.stop();
+                return;
+            }
+            if act.monitor.check_stale(STALE_FEED_DELAY) {
+                warn!("DefaultWsActor {} : no message received for {:?}", act.name, STALE_FEED_DELAY);
+            }
             act.hb(ctx);
-            // client should also check for a timeout here, similar to the
-            // server code
         });
     }
 }
 
-/// Handle stdin commands
+/// Send the text frame of a `ClientCommand` on the connection
 impl Handler<ClientCommand> for DefaultWsActor
 {
     type Result = ();
 
-    fn handle(&mut self, msg: ClientCommand, _ctx: &mut Context<Self>) {
-        self.inner.write(Message::Text(msg.0)).unwrap();
+    fn handle(&mut self, msg: ClientCommand, ctx: &mut Context<Self>) {
+        if self.inner.write(Message::Text(msg.0)).is_err() {
+            // The command is sent again by the handler once reconnected
+            warn!("DefaultWsActor {} : cannot send command, reconnecting", self.name);
+            ctx.stop();
+        }
+    }
+}
+
+/// Stop the actor so that the supervisor connects to the new url, the url of the connection
+/// settings is kept
+impl Handler<Reconnect> for DefaultWsActor
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: Reconnect, ctx: &mut Context<Self>) {
+        if self.settings.url.is_none() {
+            self.url = msg.0;
+        }
+        info!("DefaultWsActor {} : reconnecting to a new url", self.name);
+        ctx.stop();
+    }
+}
+
+impl Handler<Subscribe> for DefaultWsActor
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: Subscribe, _ctx: &mut Context<Self>) {
+        info!("DefaultWsActor {} : subscribing to {:?} for {:?}", self.name, msg.channel, msg.pairs);
+        let subscribed = self.handler.handle_subscribe(&mut self.inner, msg.channel.clone(), msg.pairs);
+        self.monitor.on_subscribe(msg.channel, subscribed);
+    }
+}
+
+impl Handler<Unsubscribe> for DefaultWsActor
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _ctx: &mut Context<Self>) {
+        info!("DefaultWsActor {} : unsubscribing from {:?} for {:?}", self.name, msg.channel, msg.pairs);
+        let unsubscribed = self.handler.handle_unsubscribe(&mut self.inner, msg.channel.clone(), msg.pairs);
+        self.monitor.on_unsubscribe(msg.channel, unsubscribed);
     }
 }
 
@@ -131,13 +330,24 @@ impl Handler<ClientCommand> for DefaultWsActor
 impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for DefaultWsActor
 {
     fn handle(&mut self, msg: std::result::Result<Frame, WsProtocolError>, ctx: &mut Context<Self>) {
+        if msg.is_ok() {
+            self.hb = Instant::now();
+            self.monitor.on_message();
+        }
         match msg {
             Ok(Frame::Ping(msg)) => {
-                self.hb = Instant::now();
                 self.inner.write(Message::Pong(Bytes::copy_from_slice(&msg)));
             }
             Ok(Frame::Text(txt)) => {
-                self.handler.handle_in(&mut self.inner, txt);
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.record(&txt);
+                }
+                let mut writer = FrameWriter { sink: &mut self.inner, reconnect: false };
+                self.handler.handle_in(&mut writer, txt);
+                if writer.reconnect {
+                    info!("DefaultWsActor {} : reconnection requested", self.name);
+                    ctx.stop();
+                }
             }
             _ => {
                 ();
@@ -147,7 +357,12 @@ impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for DefaultWsAct
 
     fn started(&mut self, ctx: &mut Context<Self>) {
         info!("DefaultWsActor {} : connected", self.name);
+        self.monitor.on_connected();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.on_connected();
+        }
         self.handler.handle_started(&mut self.inner);
+        self.monitor.on_subscribed();
     }
 
     fn finished(&mut self, ctx: &mut Context<Self>) {
@@ -160,7 +375,199 @@ impl actix::io::WriteHandler<WsProtocolError> for DefaultWsActor
 {}
 
 pub trait ExchangeBot {
-    /// Returns the address of the exchange actor
-    fn is_connected(&self) -> bool;
+    /// Returns true if the feed is connected and not stale
+    fn is_connected(&self) -> bool {
+        self.health().state == ConnectionState::Connected
+    }
+
+    /// Connection state, last message time, reconnections and channels of the feed
+    fn health(&self) -> FeedHealth;
+
+    /// Subscribe the feed to pairs of a channel, the subscription is kept on reconnections
+    fn subscribe(&self, msg: Subscribe);
+
+    /// Unsubscribe the feed from pairs of a channel
+    fn unsubscribe(&self, msg: Unsubscribe);
+
+    /// Return every level of the local order book of the pair, None if the book is not synced or
+    /// if the stream does not keep local books
+    fn full_order_book(&self, _pair: Pair) -> Option<Orderbook> {
+        None
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Not connected yet
+    Connecting,
+    Connected,
+    Disconnected,
+    /// Connected but nothing was received for `STALE_FEED_DELAY`
+    Stale,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedHealth {
+    pub state: ConnectionState,
+    /// UNIX timestamp in ms of the last message received
+    pub last_message_ms: Option<i64>,
+    /// Connections made after the first one
+    pub reconnects: u32,
+    pub channels: HashMap<Channel, HashSet<Pair>>,
+}
+
+/// Health of a live feed, shared between the connection and its bot. Status changes are sent
+/// to the recipients as `LiveEvent::LiveFeedStatus`.
+#[derive(Debug, Clone)]
+pub struct FeedMonitor {
+    exchange: Exchange,
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    health: Rc<RefCell<FeedHealth>>,
+}
+
+impl FeedMonitor {
+    pub fn new(exchange: Exchange, channels: HashMap<Channel, HashSet<Pair>>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> FeedMonitor {
+        FeedMonitor {
+            exchange,
+            recipients,
+            health: Rc::new(RefCell::new(FeedHealth {
+                state: ConnectionState::Connecting,
+                last_message_ms: None,
+                reconnects: 0,
+                channels,
+            })),
+        }
+    }
+
+    pub fn health(&self) -> FeedHealth {
+        self.health.borrow().clone()
+    }
+
+    pub fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    pub fn on_connected(&self) {
+        {
+            let mut health = self.health.borrow_mut();
+            if health.state != ConnectionState::Connecting {
+                health.reconnects += 1;
+            }
+            health.state = ConnectionState::Connected;
+            health.last_message_ms = Some(Utc::now().timestamp_millis());
+        }
+        self.notify(FeedStatus::Connected);
+    }
+
+    /// Call once the channels are subscribed, only reconnections are notified
+    pub fn on_subscribed(&self) {
+        if self.health.borrow().reconnects > 0 {
+            self.notify(FeedStatus::Resubscribed);
+        }
+    }
+
+    pub fn on_message(&self) {
+        let revived = {
+            let mut health = self.health.borrow_mut();
+            health.last_message_ms = Some(Utc::now().timestamp_millis());
+            if health.state == ConnectionState::Stale {
+                health.state = ConnectionState::Connected;
+                true
+            } else {
+                false
+            }
+        };
+        if revived {
+            self.notify(FeedStatus::Connected);
+        }
+    }
+
+    pub fn on_subscribe(&self, channel: Channel, pairs: HashSet<Pair>) {
+        add_channel_pairs(&mut self.health.borrow_mut().channels, channel, pairs);
+    }
+
+    pub fn on_unsubscribe(&self, channel: Channel, pairs: HashSet<Pair>) {
+        remove_channel_pairs(&mut self.health.borrow_mut().channels, channel, pairs);
+    }
+
+    pub fn on_disconnected(&self) {
+        let previous = std::mem::replace(&mut self.health.borrow_mut().state, ConnectionState::Disconnected);
+        if previous != ConnectionState::Disconnected {
+            self.notify(FeedStatus::Disconnected);
+        }
+    }
+
+    /// Flag a connected feed as stale if nothing was received for `delay`, return true if it is stale
+    pub fn check_stale(&self, delay: Duration) -> bool {
+        let stale = {
+            let mut health = self.health.borrow_mut();
+            let silent = health.last_message_ms.map_or(false, |ms| Utc::now().timestamp_millis() - ms > delay.as_millis() as i64);
+            match health.state {
+                ConnectionState::Connected if silent => {
+                    health.state = ConnectionState::Stale;
+                    Some(true)
+                }
+                ConnectionState::Stale => Some(false),
+                _ => None,
+            }
+        };
+        if stale == Some(true) {
+            self.notify(FeedStatus::Stale);
+        }
+        stale.is_some()
+    }
+
+    /// Check the feed for staleness every `interval` until the monitor is dropped, for feeds
+    /// that are not run by a `DefaultWsActor`
+    pub fn watch(&self, interval: Duration) {
+        let health = Rc::downgrade(&self.health);
+        let exchange = self.exchange;
+        let recipients = self.recipients.clone();
+        actix_rt::spawn(async move {
+            loop {
+                tokio::time::delay_for(interval).await;
+                match health.upgrade() {
+                    Some(health) => {
+                        FeedMonitor { exchange, recipients: recipients.clone(), health }.check_stale(STALE_FEED_DELAY);
+                    }
+                    None => return,
+                }
+            }
+        });
+    }
+
+    fn notify(&self, status: FeedStatus) {
+        let le = LiveEvent::LiveFeedStatus(LiveFeedStatus { event_ms: Utc::now().timestamp_millis(), status });
+        if self.recipients.is_empty() {
+            debug!("{:?} : {:?}", self.exchange, le);
+        }
+        for r in &self.recipients {
+            r.do_send(LiveEventEnveloppe(self.exchange, le.clone()));
+        }
+    }
 }
 
+#[cfg(test)]
+mod monitor_tests {
+    use super::*;
+
+    #[test]
+    fn should_track_feed_health() {
+        let monitor = FeedMonitor::new(Exchange::Kraken, HashMap::new(), vec![]);
+        assert_eq!(monitor.health().state, ConnectionState::Connecting);
+        monitor.on_connected();
+        assert!(!monitor.check_stale(Duration::from_secs(60)));
+        monitor.health.borrow_mut().last_message_ms = Some(0);
+        assert!(monitor.check_stale(Duration::from_secs(60)));
+        assert_eq!(monitor.health().state, ConnectionState::Stale);
+        monitor.on_message();
+        assert_eq!(monitor.health().state, ConnectionState::Connected);
+
+        monitor.on_disconnected();
+        monitor.on_connected();
+        let health = monitor.health();
+        assert_eq!(health.state, ConnectionState::Connected);
+        assert_eq!(health.reconnects, 1);
+    }
+}
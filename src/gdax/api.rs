@@ -2,17 +2,16 @@
 //! Please see examples for more informations.
 
 
-use hyper::{Client, Uri, Request, Body, Method};
+use hyper::{Request, Body, Method};
 use hyper::header::{CONTENT_TYPE,USER_AGENT};
 
-use hyper_tls::HttpsConnector;
-
 use serde_json::Value;
 use serde_json::value::Map;
 
 use std::collections::HashMap;
 use std::thread;
 use std::time::Duration;
+use std::sync::Arc;
 
 use crate::coinnect::Credentials;
 use crate::exchange::Exchange;
@@ -21,9 +20,9 @@ use crate::error::*;
 use crate::helpers;
 use crate::types::Pair;
 use crate::gdax::utils;
+use crate::utils::pair_or;
 use crate::types::*;
-use hyper::client::HttpConnector;
-use futures::{TryFutureExt};
+use crate::transport::{self, HttpClient, HttpTransport};
 use bytes::buf::BufExt as _;
 use crate::helpers::json;
 
@@ -33,7 +32,8 @@ pub struct GdaxApi {
     api_key: String,
     api_secret: String,
     customer_id: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    transport: Arc<dyn HttpTransport>,
+    base_url: String,
     burst: bool,
 }
 
@@ -45,15 +45,13 @@ impl GdaxApi {
             return Err(ErrorKind::InvalidConfigType(Exchange::Gdax, creds.exchange()).into());
         }
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
-
         Ok(GdaxApi {
                last_request: 0,
                api_key: creds.get("api_key").unwrap_or_default(),
                api_secret: creds.get("api_secret").unwrap_or_default(),
                customer_id: creds.get("customer_id").unwrap_or_default(),
-               http_client: ssl,
+               transport: transport::default_transport(),
+               base_url: utils::BASE_URL.to_string(),
                burst: false, // No burst by default
            })
     }
@@ -84,24 +82,23 @@ impl GdaxApi {
             .get("method")
             .ok_or_else(|| "Missing \"method\" field.")?;
         let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let string = utils::build_url(method, pair);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
+        let url = utils::build_url_from(&self.base_url, method, pair);
 
         self.block_or_continue();
         let req: Result<Request<Body>> = Request::builder()
             .method(Method::GET)
-            .uri(url)
+            .uri(url.as_str())
             .header(USER_AGENT, "coinnect")
             .body(Body::empty())
             .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
 
         let req2 = req.unwrap();
-        let resp = self.http_client.request(req2).await?;
-        let buf = hyper::body::aggregate(resp.into_body()).await?;
+        let resp = self.transport.send(req2).await?;
 
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
-        json::deserialize_json_r(reader)
+        let reader = resp.body.reader();
+        // products returns an array
+        json::deserialize_json_or_array_r(reader)
     }
 
     ///
@@ -121,8 +118,7 @@ impl GdaxApi {
             .get("method")
             .ok_or_else(|| "Missing \"method\" field.")?;
         let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let string = utils::build_url(method, pair);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
+        let url = utils::build_url_from(&self.base_url, method, pair);
 
         let nonce = utils::generate_nonce(None);
         let signature =
@@ -143,14 +139,14 @@ impl GdaxApi {
         let post_data = helpers::url_encode_hashmap(post_params);
         let req: Result<Request<Body>> = Request::builder()
             .method("POST")
-            .uri(url)
+            .uri(url.as_str())
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded".to_owned())
             .body(Body::from(post_data))
             .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
         let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.send(req2).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
+        let reader = resp.body.reader();
         json::deserialize_json_r(reader)
     }
 
@@ -167,10 +163,7 @@ impl GdaxApi {
     /// ... }
     /// ```
     pub async fn return_ticker(&mut self, pair: Pair) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Gdax, &pair)?;
 
         let mut params: HashMap<&str, &str> = HashMap::new();
         params.insert("pair", pair_name);
@@ -185,11 +178,7 @@ impl GdaxApi {
     /// [0.00006900,408], ... ], "timestamp": "1234567890"}
     /// ```
     pub async fn return_order_book(&mut self, pair: Pair) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-
-        };
+        let pair_name = &pair_or(Exchange::Gdax, &pair)?;
 
         let mut params: HashMap<&str, &str> = HashMap::new();
         params.insert("method", "order_book");
@@ -206,10 +195,7 @@ impl GdaxApi {
     /// "total":"0.04978"}, ... ]
     /// ```
     pub async fn return_trade_history(&mut self, pair: Pair) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Gdax, &pair)?;
 
         let mut params: HashMap<&str, &str> = HashMap::new();
         params.insert("pair", pair_name);
@@ -217,6 +203,19 @@ impl GdaxApi {
         self.public_query(&params).await
     }
 
+    /// Sample output :
+    ///
+    /// ```json
+    /// [{"id": "BTC-USD", "base_currency": "BTC", "quote_currency": "USD",
+    /// "base_min_size": "0.001", "base_max_size": "280", "quote_increment": "0.01"}, ...]
+    /// ```
+    pub async fn return_products(&mut self) -> Result<Map<String, Value>> {
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("pair", "");
+        params.insert("method", "products");
+        self.public_query(&params).await
+    }
+
 
     /// Returns all of your available balances.
     ///
@@ -244,10 +243,7 @@ impl GdaxApi {
                      price_limit: Option<Price>,
                      daily_order: Option<bool>)
                      -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Gdax, &pair)?;
 
         let amount_string = amount.to_string();
         let price_string = price.to_string();
@@ -283,10 +279,7 @@ impl GdaxApi {
                       price_limit: Option<Price>,
                       daily_order: Option<bool>)
                       -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Gdax, &pair)?;
 
         let amount_string = amount.to_string();
         let price_string = price.to_string();
@@ -315,10 +308,7 @@ impl GdaxApi {
     /// on the market conditions and that these conditions may be subject to sudden changes
     /// that cannot be foreseen.
     pub async fn buy_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Gdax, &pair)?;
 
         let amount_string = amount.to_string();
 
@@ -336,10 +326,7 @@ impl GdaxApi {
     /// on the market conditions and that these conditions may be subject to sudden changes
     /// that cannot be foreseen.
     pub async fn sell_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Gdax, &pair)?;
 
         let amount_string = amount.to_string();
 
@@ -353,6 +340,16 @@ impl GdaxApi {
     }
 }
 
+impl HttpClient for GdaxApi {
+    fn set_transport(&mut self, transport: Arc<dyn HttpTransport>) {
+        self.transport = transport;
+    }
+
+    fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = transport::trim_base_url(base_url);
+    }
+}
+
 
 #[cfg(test)]
 mod gdax_api_tests {
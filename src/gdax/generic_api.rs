@@ -2,7 +2,7 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Gdax offers.
 
-use crate::exchange::{ExchangeApi};
+use crate::exchange::{ExchangeApi, Exchange};
 use crate::gdax::api::GdaxApi;
 use crate::gdax::utils;
 
@@ -10,6 +10,8 @@ use crate::error::*;
 use crate::types::*;
 use crate::helpers;
 use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde_json::Value;
 
 #[async_trait]
 impl ExchangeApi for GdaxApi {
@@ -137,4 +139,92 @@ impl ExchangeApi for GdaxApi {
 
         Ok(balances)
     }
+
+    /// Only plain limit and market orders are supported, see `OrderRequest::basic_order_type`
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        let order_type = order.basic_order_type()?;
+        self.add_order(order_type, order.pair, order.quantity, order.price).await
+    }
+
+    async fn cancel_order(&mut self, _pair: Pair, _order: &OrderInfo) -> Result<OrderInfo> {
+        Err(ErrorKind::Unsupported("cancel_order".to_string()).into())
+    }
+
+    async fn order_status(&mut self, _pair: Pair, _order: &OrderInfo) -> Result<Order> {
+        Err(ErrorKind::Unsupported("order_status".to_string()).into())
+    }
+
+    async fn open_orders(&mut self, _pair: Pair) -> Result<Vec<Order>> {
+        Err(ErrorKind::Unsupported("open_orders".to_string()).into())
+    }
+
+    async fn recent_trades(&mut self, _pair: Pair, _since: Option<i64>) -> Result<Vec<Trade>> {
+        Err(ErrorKind::Unsupported("recent_trades".to_string()).into())
+    }
+
+    async fn candles(&mut self,
+                     _pair: Pair,
+                     _interval: Interval,
+                     _start: Option<i64>,
+                     _end: Option<i64>)
+                     -> Result<Vec<Candle>> {
+        Err(ErrorKind::Unsupported("candles".to_string()).into())
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        let result = self.return_products().await?;
+        let product_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut symbols = SymbolMap::new();
+        for product in product_array {
+            let (symbol, name) = parse_product(product)?;
+            // Product ids are written lower case everywhere else in this module
+            symbols.insert(symbol, &name.to_lowercase());
+        }
+        symbols.register(Exchange::Gdax);
+        Ok(symbols)
+    }
+
+    async fn markets(&mut self) -> Result<Markets> {
+        let result = self.return_products().await?;
+        let product_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut markets = Markets::new();
+        for product in product_array {
+            let (symbol, name) = parse_product(product)?;
+            let mut market = MarketInfo::new(symbol.clone(), name);
+            market.price_tick = optional_bigdecimal(product, "quote_increment")?;
+            market.quantity_step = optional_bigdecimal(product, "base_increment")?;
+            market.min_quantity = optional_bigdecimal(product, "base_min_size")?;
+            market.max_quantity = optional_bigdecimal(product, "base_max_size")?;
+            market.min_notional = optional_bigdecimal(product, "min_market_funds")?;
+            markets.insert(symbol, market);
+        }
+        Ok(markets)
+    }
+}
+
+/// Return the symbol and the Gdax name of a `products` entry
+fn parse_product(product: &Value) -> Result<(Symbol, &str)> {
+    let name = product["id"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("id".to_string()))?;
+    let base = product["base_currency"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("base_currency".to_string()))?;
+    let quote = product["quote_currency"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("quote_currency".to_string()))?;
+    Ok((Symbol::new(base, quote), name))
+}
+
+fn optional_bigdecimal(product: &Value, key: &str) -> Result<Option<BigDecimal>> {
+    if product[key].is_null() {
+        return Ok(None);
+    }
+    Ok(Some(helpers::from_json_bigdecimal(&product[key], key)?))
 }
@@ -0,0 +1,199 @@
+use serde::{Serialize, Deserialize};
+use chrono::DateTime;
+use crate::types::{Channel, TradeType, Pair, Ticker, BestBidOffer};
+use bigdecimal::BigDecimal;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    pub product_id: String,
+    pub bids: Vec<(String, String)>,
+    pub asks: Vec<(String, String)>,
+}
+
+/// Changes are side ("buy" or "sell"), price and the new size, a zero size removes the level
+#[derive(Serialize, Deserialize, Debug)]
+pub struct L2Update {
+    pub product_id: String,
+    pub time: String,
+    pub changes: Vec<(String, String, String)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Match {
+    pub trade_id: i64,
+    pub sequence: i64,
+    pub time: String,
+    pub product_id: String,
+    pub size: String,
+    pub price: String,
+    /// Side of the maker order
+    pub side: String,
+}
+
+impl Match {
+    pub fn event_ms(&self) -> i64 {
+        parse_time(&self.time)
+    }
+
+    /// The taker side, opposite to the maker order side sent by the exchange
+    pub fn trade_type(&self) -> TradeType {
+        match self.side.as_str() {
+            "buy" => TradeType::Sell,
+            "sell" => TradeType::Buy,
+            _ => TradeType::None,
+        }
+    }
+}
+
+/// Sent after every match, with the top of book
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TickerMessage {
+    pub product_id: String,
+    pub price: String,
+    pub best_bid: String,
+    pub best_ask: String,
+    pub best_bid_size: Option<String>,
+    pub best_ask_size: Option<String>,
+    pub volume_24h: Option<String>,
+    pub time: Option<String>,
+}
+
+impl TickerMessage {
+    pub fn event_ms(&self) -> Option<i64> {
+        self.time.as_ref().map(|t| parse_time(t))
+    }
+
+    pub fn ticker(&self, pair: Pair, timestamp: i64) -> Option<Ticker> {
+        Some(Ticker {
+            timestamp,
+            pair,
+            last_trade_price: self.price.parse::<BigDecimal>().ok()?,
+            lowest_ask: self.best_ask.parse::<BigDecimal>().ok()?,
+            highest_bid: self.best_bid.parse::<BigDecimal>().ok()?,
+            volume: self.volume_24h.as_ref().and_then(|v| v.parse::<BigDecimal>().ok()),
+        })
+    }
+
+    pub fn best_bid_offer(&self, pair: Pair, event_ms: i64) -> Option<BestBidOffer> {
+        Some(BestBidOffer {
+            event_ms,
+            pair,
+            bid: self.best_bid.parse::<BigDecimal>().ok()?,
+            ask: self.best_ask.parse::<BigDecimal>().ok()?,
+            bid_volume: self.best_bid_size.as_ref().and_then(|v| v.parse::<BigDecimal>().ok()),
+            ask_volume: self.best_ask_size.as_ref().and_then(|v| v.parse::<BigDecimal>().ok()),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorMessage {
+    pub message: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum Event {
+    #[serde(rename = "snapshot")]
+    Snapshot(Snapshot),
+    #[serde(rename = "l2update")]
+    L2Update(L2Update),
+    #[serde(rename = "match", alias = "last_match")]
+    Match(Match),
+    #[serde(rename = "ticker")]
+    Ticker(TickerMessage),
+    #[serde(rename = "error")]
+    Error(ErrorMessage),
+    /// Subscriptions, heartbeats...
+    #[serde(other)]
+    Other,
+}
+
+/// Return the UNIX timestamp in ms of an ISO 8601 time, 0 if it is invalid
+pub fn parse_time(time: &str) -> i64 {
+    DateTime::parse_from_rfc3339(time).map(|t| t.timestamp_millis()).unwrap_or_default()
+}
+
+#[derive(Serialize, Debug)]
+pub struct Subscription {
+    #[serde(rename = "type")]
+    ty: String,
+    product_ids: Vec<String>,
+    channels: Vec<String>,
+}
+
+/// Return the name of the exchange channel of a public channel, None if the channel is private.
+/// Several channels can share the same exchange channel.
+pub fn channel_name(c: &Channel) -> Option<&'static str> {
+    match c {
+        // Candles are built from the trades
+        Channel::LiveTrades | Channel::LiveCandles(_) => Some("matches"),
+        Channel::LiveOrderBook | Channel::LiveDetailOrderBook | Channel::LiveFullOrderBook => Some("level2"),
+        Channel::LiveTicker | Channel::LiveBestBidOffer => Some("ticker"),
+        Channel::LiveOrders | Channel::UserData => None,
+    }
+}
+
+/// Return the subscription message for a public channel, None if the channel is private
+pub fn subscription(c: Channel, product_ids: Vec<String>) -> Option<Subscription> {
+    let channel_str = channel_name(&c)?;
+    Some(Subscription {
+        ty: "subscribe".to_string(),
+        product_ids,
+        channels: vec![channel_str.to_string()],
+    })
+}
+
+pub fn unsubscription(c: Channel, product_ids: Vec<String>) -> Option<Subscription> {
+    subscription(c, product_ids).map(|sub| Subscription { ty: "unsubscribe".to_string(), ..sub })
+}
+
+#[cfg(test)]
+mod model_tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_match() {
+        let v: Event = serde_json::from_slice(b"{\"type\":\"match\",\"trade_id\":10,\"sequence\":50,\"maker_order_id\":\"ac928c66-ca53-498f-9c13-a110027a60e8\",\"taker_order_id\":\"132fb6ae-456b-4654-b4e0-d681ac05cea1\",\"time\":\"2014-11-07T08:19:27.028459Z\",\"product_id\":\"BTC-USD\",\"size\":\"5.23512\",\"price\":\"400.23\",\"side\":\"sell\"}").unwrap();
+        match v {
+            Event::Match(m) => {
+                assert_eq!(m.event_ms(), 1415348367028);
+                assert_eq!(m.trade_type(), TradeType::Buy);
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_l2update() {
+        let v: Event = serde_json::from_slice(b"{\"type\":\"l2update\",\"product_id\":\"BTC-USD\",\"time\":\"2019-08-14T20:42:27.265Z\",\"changes\":[[\"buy\",\"10101.80000000\",\"0.162567\"]]}").unwrap();
+        match v {
+            Event::L2Update(u) => assert_eq!(u.changes.len(), 1),
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_ticker() {
+        let v: Event = serde_json::from_slice(b"{\"type\":\"ticker\",\"trade_id\":20153558,\"sequence\":3262786978,\"time\":\"2017-09-02T17:05:49.250000Z\",\"product_id\":\"BTC-USD\",\"price\":\"4388.01000000\",\"side\":\"buy\",\"last_size\":\"0.03000000\",\"best_bid\":\"4388\",\"best_ask\":\"4388.01\"}").unwrap();
+        match v {
+            Event::Ticker(t) => {
+                assert_eq!(t.event_ms(), Some(1504371949250));
+                let bbo = t.best_bid_offer(Pair::BTC_USD, 0).unwrap();
+                assert_eq!(bbo.ask, "4388.01".parse::<BigDecimal>().unwrap());
+                assert_eq!(bbo.bid_volume, None);
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_subscriptions() {
+        let v: Event = serde_json::from_slice(b"{\"type\":\"subscriptions\",\"channels\":[{\"name\":\"level2\",\"product_ids\":[\"BTC-USD\"]}]}").unwrap();
+        match v {
+            Event::Other => (),
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::coinnect::Credentials;
+use crate::exchange_bot::{DefaultWsActor, WsHandler, WsWriter, ExchangeBot, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, add_channel_pairs, remove_channel_pairs, candle_intervals};
+use crate::error::*;
+use super::models::*;
+use bytes::Bytes;
+use bytes::Buf;
+use actix::{Addr, Recipient};
+use awc::{
+    ws::Message
+};
+use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair, LiveTrade, Price, Volume, BookUpdate, BookSettings, LiveBooks, LiveCandles, Orderbook};
+use crate::exchange::Exchange;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use crate::utils;
+
+static WEBSOCKET_URL: &'static str = "wss://ws-feed.pro.coinbase.com";
+
+pub struct GdaxBot {
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+    books: LiveBooks,
+}
+
+impl ExchangeBot for GdaxBot {
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.full_order_book(pair)
+    }
+}
+
+#[derive(Debug)]
+pub struct GdaxStreamingApi {
+    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    channels: HashMap<Channel, HashSet<Pair>>,
+    books: LiveBooks,
+    candles: LiveCandles,
+}
+
+impl GdaxStreamingApi {
+    /// Create a new gdax exchange bot, unavailable channels and currencies are ignored
+    pub async fn new_bot<C: Credentials>(_creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, book_settings: BookSettings, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<GdaxBot> {
+        let books = LiveBooks::new(book_settings);
+        let monitor = FeedMonitor::new(Exchange::Gdax, channels.clone(), recipients.clone());
+        let api = GdaxStreamingApi::new_handler(channels, books.clone(), recipients);
+        let addr = DefaultWsActor::new("GdaxStream", WEBSOCKET_URL, Some(Duration::from_secs(10)), connection, monitor.clone(), Box::new(api)).await?;
+        Ok(GdaxBot { addr, books, monitor })
+    }
+
+    /// Create the handler of the feed without connecting it, see `replay::FeedReplay`
+    pub fn new_handler(channels: HashMap<Channel, HashSet<Pair>>, books: LiveBooks, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> GdaxStreamingApi {
+        books.flush_to(Exchange::Gdax, recipients.clone());
+        GdaxStreamingApi {
+            recipients,
+            channels,
+            books,
+            candles: LiveCandles::default(),
+        }
+    }
+
+    /// Return the pair of a product id if it is subscribed on the channel
+    fn subscribed_pair(&self, channel: Channel, product_id: &str) -> Option<Pair> {
+        let pair = utils::pair_enum(Exchange::Gdax, &product_id.to_lowercase())?;
+        if self.channels.get(&channel).map_or(false, |pairs| pairs.contains(&pair)) {
+            Some(pair)
+        } else {
+            None
+        }
+    }
+
+    fn broadcast(&self, v: LiveEvent) {
+        let vec = self.recipients.clone();
+        if vec.len() == 0 as usize {
+            debug!("{:?}", v);
+        } else {
+            for r in &vec {
+                let le: LiveEvent = v.clone();
+                r.do_send(LiveEventEnveloppe(Exchange::Gdax, le));
+            }
+        }
+    }
+}
+
+fn parse_level(price: &str, size: &str) -> Option<(Price, Volume)> {
+    match (price.parse::<BigDecimal>(), size.parse::<BigDecimal>()) {
+        (Ok(price), Ok(size)) => Some((price, size)),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl WsHandler for GdaxStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, _w: &mut dyn WsWriter, msg: Bytes) {
+        let v: Result<Event> = serde_json::from_slice(msg.bytes()).map_err(|e| ErrorKind::Json(e).into());
+        match v {
+            Ok(Event::Snapshot(snapshot)) => {
+                let current_pair = match self.subscribed_pair(Channel::LiveFullOrderBook, &snapshot.product_id) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                let asks = snapshot.asks.iter().filter_map(|(p, s)| parse_level(p, s));
+                let bids = snapshot.bids.iter().filter_map(|(p, s)| parse_level(p, s));
+                // level2 updates are not sequenced, they follow the snapshot on the same channel
+                let (state, latest) = self.books.with_book(current_pair, |agg| {
+                    (agg.apply_snapshot(None, asks, bids).map(|_| agg.book_state()), agg.latest_order_book())
+                });
+                if let Some(state) = state {
+                    self.broadcast(LiveEvent::LiveBookState(state));
+                }
+                if let Some(ob) = latest {
+                    self.broadcast(LiveEvent::LiveOrderbook(ob));
+                }
+            }
+            Ok(Event::L2Update(update)) => {
+                let current_pair = match self.subscribed_pair(Channel::LiveFullOrderBook, &update.product_id) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                let mut book_update = BookUpdate { update_ids: None, asks: vec![], bids: vec![] };
+                for (side, price, size) in &update.changes {
+                    match (side.as_str(), parse_level(price, size)) {
+                        ("buy", Some(level)) => book_update.bids.push(level),
+                        ("sell", Some(level)) => book_update.asks.push(level),
+                        _ => trace!("Gdax : invalid change {:?} {:?} {:?}", side, price, size),
+                    }
+                }
+                let latest = self.books.with_book(current_pair, |agg| {
+                    agg.apply_update(book_update);
+                    agg.latest_order_book()
+                });
+                if let Some(ob) = latest {
+                    self.broadcast(LiveEvent::LiveOrderbook(ob));
+                }
+            }
+            Ok(Event::Match(m)) => {
+                let current_pair = match utils::pair_enum(Exchange::Gdax, &m.product_id.to_lowercase()) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                let (price, volume) = match (m.price.parse::<BigDecimal>(), m.size.parse::<BigDecimal>()) {
+                    (Ok(price), Ok(volume)) => (price, volume),
+                    _ => return trace!("Gdax : invalid match {:?}", m),
+                };
+                let intervals = candle_intervals(&self.channels, &current_pair);
+                for c in self.candles.add_trade(current_pair, &intervals, m.event_ms(), &price, &volume) {
+                    self.broadcast(LiveEvent::LiveCandle(c));
+                }
+                if self.subscribed_pair(Channel::LiveTrades, &m.product_id).is_some() {
+                    self.broadcast(LiveEvent::LiveTrade(LiveTrade {
+                        event_ms: m.event_ms(),
+                        pair: current_pair,
+                        amount: m.size.parse::<f32>().unwrap_or_default(),
+                        price,
+                        tt: m.trade_type(),
+                    }));
+                }
+            }
+            Ok(Event::Ticker(t)) => {
+                // Both channels are fed by the ticker channel
+                let event_ms = t.event_ms().unwrap_or_else(|| self.books.clock().now_ms());
+                if let Some(ticker) = self.subscribed_pair(Channel::LiveTicker, &t.product_id).and_then(|pair| t.ticker(pair, event_ms)) {
+                    self.broadcast(LiveEvent::LiveTicker(ticker));
+                }
+                if let Some(bbo) = self.subscribed_pair(Channel::LiveBestBidOffer, &t.product_id).and_then(|pair| t.best_bid_offer(pair, event_ms)) {
+                    self.broadcast(LiveEvent::LiveBestBidOffer(bbo));
+                }
+            }
+            Ok(Event::Error(e)) => warn!("Gdax : {} {:?}", e.message, e.reason),
+            Ok(Event::Other) => (),
+            Err(e) => trace!("Gdax : error {:?} deserializing {:?}", e, msg),
+        }
+    }
+
+    fn handle_tick(&mut self, now_ms: i64) {
+        for c in self.candles.close_over(now_ms) {
+            self.broadcast(LiveEvent::LiveCandle(c));
+        }
+    }
+
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_started(&mut self, w: &mut dyn WsWriter) {
+        // A snapshot is sent again for every level2 subscription
+        for state in self.books.start_resync() {
+            self.broadcast(LiveEvent::LiveBookState(state));
+        }
+        for (k, v) in &self.channels {
+            let product_ids: Vec<String> = v.iter()
+                .filter_map(|pair| utils::pair_name(Exchange::Gdax, pair).map(|s| s.to_uppercase()))
+                .collect();
+            if product_ids.is_empty() {
+                continue;
+            }
+            info!("Gdax : connecting to {:?} for {:?}", k, &product_ids);
+            match subscription(k.clone(), product_ids) {
+                Some(sub) => {
+                    let result = serde_json::to_string(&sub).unwrap();
+                    w.write(Message::Text(result));
+                }
+                None => warn!("Gdax : channel {:?} is not available", k),
+            }
+        }
+    }
+
+    fn handle_subscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel_name(&channel).is_none() {
+            warn!("Gdax : channel {:?} is not available", channel);
+            return HashSet::new();
+        }
+        let (pairs, unsupported): (HashSet<Pair>, HashSet<Pair>) = pairs.into_iter()
+            .partition(|pair| utils::pair_name(Exchange::Gdax, pair).is_some());
+        if !unsupported.is_empty() {
+            warn!("Gdax : pairs {:?} are not supported", unsupported);
+        }
+        let product_ids: Vec<String> = add_channel_pairs(&mut self.channels, channel.clone(), pairs.clone()).iter()
+            .filter_map(|pair| utils::pair_name(Exchange::Gdax, pair).map(|s| s.to_uppercase()))
+            .collect();
+        if !product_ids.is_empty() {
+            if let Some(sub) = subscription(channel, product_ids) {
+                w.write(Message::Text(serde_json::to_string(&sub).unwrap()));
+            }
+        }
+        pairs
+    }
+
+    fn handle_unsubscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let removed = remove_channel_pairs(&mut self.channels, channel.clone(), pairs);
+        if channel == Channel::LiveFullOrderBook {
+            for pair in &removed {
+                self.books.remove(*pair);
+            }
+        }
+        if let Channel::LiveCandles(interval) = channel {
+            for pair in &removed {
+                self.candles.remove(*pair, interval);
+            }
+        }
+        // Pairs still subscribed on a channel sharing the same exchange channel stay subscribed
+        let name = channel_name(&channel);
+        let shared: HashSet<Pair> = self.channels.iter()
+            .filter(|(c, _)| channel_name(c) == name)
+            .flat_map(|(_, pairs)| pairs.iter().cloned())
+            .collect();
+        let product_ids: Vec<String> = removed.iter()
+            .filter(|pair| !shared.contains(pair))
+            .filter_map(|pair| utils::pair_name(Exchange::Gdax, pair).map(|s| s.to_uppercase()))
+            .collect();
+        if !product_ids.is_empty() {
+            if let Some(sub) = unsubscription(channel, product_ids) {
+                w.write(Message::Text(serde_json::to_string(&sub).unwrap()));
+            }
+        }
+        removed.into_iter().collect()
+    }
+}
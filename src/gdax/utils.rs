@@ -66,11 +66,19 @@ pub fn build_signature(nonce: &str,
     Ok(String::from_utf8(signature)?)
 }
 
+pub const BASE_URL: &str = "https://api.gdax.com";
+
 pub fn build_url(method: &str, pair: &str) -> String {
+    build_url_from(BASE_URL, method, pair)
+}
+
+/// Url of a method on another host than Gdax, `base_url` has no trailing slash
+pub fn build_url_from(base_url: &str, method: &str, pair: &str) -> String {
     match method {
-        "ticker" => "https://api.gdax.com/products/".to_string() + pair + "/ticker",
-        "order_book" => "https://api.gdax.com/products/".to_string() + pair + "/book",
-        "transactions" => "https://api.gdax.com/accounts/".to_string() + pair + "/ledger",
+        "ticker" => base_url.to_string() + "/products/" + pair + "/ticker",
+        "order_book" => base_url.to_string() + "/products/" + pair + "/book",
+        "transactions" => base_url.to_string() + "/accounts/" + pair + "/ledger",
+        "products" => base_url.to_string() + "/products",
         _ => "not implemented yet".to_string(),
     }
 }
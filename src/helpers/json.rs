@@ -61,3 +61,22 @@ pub fn deserialize_json_array_r<B>(reader: Reader<B>) -> Result<Map<String, Valu
         Err(ErrorKind::BadParse.into())
     }
 }
+
+/// Convert a JSON object into a map, or a JSON array into a map containing a Vec for the "data"
+/// key, for endpoints whose response type depends on the parameters
+pub fn deserialize_json_or_array_r<B>(reader: Reader<B>) -> Result<Map<String, Value>> where B: Buf {
+    let data: Value = match serde_json::from_reader(reader) {
+        Ok(data) => data,
+        Err(_) => return Err(ErrorKind::BadParse.into()),
+    };
+
+    match data {
+        Value::Object(value) => Ok(value),
+        Value::Array(_) => {
+            let mut map = Map::new();
+            map.insert("data".to_string(), data);
+            Ok(map)
+        }
+        _ => Err(ErrorKind::BadParse.into()),
+    }
+}
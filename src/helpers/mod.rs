@@ -4,7 +4,7 @@
 pub mod json;
 
 use serde_json::Value;
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, Zero};
 use std::str::FromStr;
 
 use std::collections::HashMap;
@@ -60,12 +60,47 @@ pub fn get_json_string<'a>(json_obj: &'a Value, key: &str) -> Result<&'a str> {
            .ok_or_else(|| ErrorKind::InvalidFieldFormat(key.to_string()))?)
 }
 
+/// Parse a decimal sent either as a JSON string or as a JSON number
 pub fn from_json_bigdecimal(json_obj: &Value, key: &str) -> Result<BigDecimal> {
-    let num = json_obj
-        .as_str()
-        .ok_or_else(|| ErrorKind::MissingField(key.to_string()))?;
+    let num = match json_obj {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return Err(ErrorKind::MissingField(key.to_string()).into()),
+    };
+
+    Ok(BigDecimal::from_str(&num).chain_err(|| ErrorKind::InvalidFieldFormat(key.to_string()))?)
+}
+
+/// Return the increment matching a number of decimals (2 -> 0.01)
+pub fn decimals_to_increment(decimals: u32) -> BigDecimal {
+    let increment = if decimals == 0 {
+        "1".to_string()
+    } else {
+        format!("0.{}1", "0".repeat(decimals as usize - 1))
+    };
+    BigDecimal::from_str(&increment).unwrap()
+}
+
+/// Round a decimal down to a multiple of the increment, a zero increment leaves it unchanged
+pub fn floor_to_increment(value: &BigDecimal, increment: &BigDecimal) -> BigDecimal {
+    if increment.is_zero() {
+        return value.clone();
+    }
+    let steps = (value / increment).with_scale(0);
+    let steps = if &steps * increment > *value { steps - BigDecimal::from(1) } else { steps };
+    steps * increment
+}
+
+/// Round a decimal up to a multiple of the increment, a zero increment leaves it unchanged
+pub fn ceil_to_increment(value: &BigDecimal, increment: &BigDecimal) -> BigDecimal {
+    let floor = floor_to_increment(value, increment);
+    if &floor < value { floor + increment } else { floor }
+}
 
-    Ok(BigDecimal::from_str(num).chain_err(|| ErrorKind::InvalidFieldFormat(key.to_string()))?)
+/// Round a decimal to the nearest multiple of the increment, a zero increment leaves it unchanged
+pub fn round_to_increment(value: &BigDecimal, increment: &BigDecimal) -> BigDecimal {
+    let half = increment / BigDecimal::from(2);
+    floor_to_increment(&(value + &half), increment)
 }
 
 pub async fn new_ws_client(url: String) -> Result<Framed<BoxedSocket, Codec>> {
@@ -6,8 +6,7 @@
 use hmac::{Hmac, Mac};
 use sha2::{Sha256, Sha512, Digest};
 
-use hyper::{Client, Uri, Method, Request, Body};
-use hyper_tls::HttpsConnector;
+use hyper::{Method, Request, Body};
 
 use data_encoding::BASE64;
 
@@ -24,21 +23,22 @@ use crate::helpers;
 
 use crate::exchange::Exchange;
 use crate::coinnect::Credentials;
-use hyper::client::HttpConnector;
-use futures::{TryFutureExt};
+use std::sync::Arc;
+use crate::transport::{self, HttpClient, HttpTransport};
 use bytes::buf::BufExt as _;
 use crate::helpers::json;
 
 const KEY_HEADER: &str = "API-Key";
 const SIGN_HEADER: &str = "API-Sign";
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct KrakenApi {
     last_request: i64, // unix timestamp in ms, to avoid ban
     api_key: String,
     api_secret: String,
     otp: Option<String>, // two-factor password (if two-factor enabled, otherwise not required)
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    transport: Arc<dyn HttpTransport>,
+    base_url: String,
     burst: bool,
 }
 
@@ -50,15 +50,13 @@ impl KrakenApi {
             return Err(ErrorKind::InvalidConfigType(Exchange::Kraken, creds.exchange()).into());
         }
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
-
         Ok(KrakenApi {
                last_request: 0,
                api_key: creds.get("api_key").unwrap_or_default(),
                api_secret: creds.get("api_secret").unwrap_or_default(),
                otp: None,
-               http_client: ssl,
+               transport: transport::default_transport(),
+               base_url: "https://api.kraken.com".to_string(),
                burst: false,
            })
     }
@@ -94,14 +92,13 @@ impl KrakenApi {
                     params: &mut HashMap<&str, &str>)
                     -> Result<Map<String, Value>> {
         helpers::strip_empties(params);
-        let string = "https://api.kraken.com/0/public/".to_string() + method + "?" +
+        let url = self.base_url.clone() + "/0/public/" + method + "?" +
             &helpers::url_encode_hashmap(params);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
 
         self.block_or_continue().await;
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.get(&url).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
+        let reader = resp.body.reader();
         json::deserialize_json_r(reader)
     }
 
@@ -109,7 +106,7 @@ impl KrakenApi {
                      method: &str,
                      mut params: &mut HashMap<&str, &str>)
                      -> Result<Map<String, Value>> {
-        let url = "https://api.kraken.com/0/private/".to_string() + method;
+        let url = self.base_url.clone() + "/0/private/" + method;
 
         let urlpath = "/0/private/".to_string() + method;
 
@@ -135,9 +132,9 @@ impl KrakenApi {
             .body(post_data.into())
             .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
         let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.send(req2).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
+        let reader = resp.body.reader();
         json::deserialize_json_r(reader)
     }
 
@@ -870,6 +867,17 @@ impl KrakenApi {
         self.private_query("AddOrder", &mut params).await
     }
 
+    /// Result:
+    ///
+    /// ```json
+    /// token = token used to subscribe to the private websocket feeds
+    /// expires = seconds before the token must be used to connect
+    /// ```
+    pub async fn get_websockets_token(&mut self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.private_query("GetWebSocketsToken", &mut params).await
+    }
+
     /// Input:
     ///
     /// ```json
@@ -1106,6 +1114,16 @@ impl KrakenApi {
     }
 }
 
+impl HttpClient for KrakenApi {
+    fn set_transport(&mut self, transport: Arc<dyn HttpTransport>) {
+        self.transport = transport;
+    }
+
+    fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = transport::trim_base_url(base_url);
+    }
+}
+
 #[cfg(test)]
 mod kraken_api_tests {
     use super::*;
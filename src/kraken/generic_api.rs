@@ -2,31 +2,31 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Kraken offers.
 
-use crate::exchange::{ExchangeApi};
+use crate::exchange::{ExchangeApi, Exchange};
 use crate::kraken::api::KrakenApi;
 
 use crate::error::*;
 use crate::types::*;
 use crate::kraken::utils;
 use crate::helpers;
+use crate::utils::pair_or;
 use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use serde_json::Value;
 
 #[async_trait]
 impl ExchangeApi for KrakenApi {
     async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Kraken, &pair)?;
 
         let raw_response = self.get_ticker_information(pair_name).await?;
 
         let result = utils::parse_result(&raw_response)?;
 
-        let price = helpers::from_json_bigdecimal(&result[*pair_name]["c"][0], "c")?;
-        let ask = helpers::from_json_bigdecimal(&result[*pair_name]["a"][0], "a")?;
-        let bid = helpers::from_json_bigdecimal(&result[*pair_name]["b"][0], "b")?;
-        let vol = helpers::from_json_bigdecimal(&result[*pair_name]["v"][0], "v")?;
+        let price = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["c"][0], "c")?;
+        let ask = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["a"][0], "a")?;
+        let bid = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["b"][0], "b")?;
+        let vol = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["v"][0], "v")?;
 
         Ok(Ticker {
                timestamp: helpers::get_unix_timestamp_ms(),
@@ -40,10 +40,7 @@ impl ExchangeApi for KrakenApi {
     }
 
     async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Kraken, &pair)?;
 
         let raw_response = self.get_order_book(pair_name, "1000").await?; // 1000 entries max
 
@@ -53,18 +50,18 @@ impl ExchangeApi for KrakenApi {
         let mut bid_offers = Vec::new();
 
         let ask_array =
-            result[*pair_name]["asks"]
+            result[pair_name.as_str()]["asks"]
                 .as_array()
                 .ok_or_else(|| {
                                 ErrorKind::InvalidFieldFormat(format!("{}.asks",
-                                                                      result[*pair_name]))
+                                                                      result[pair_name.as_str()]))
                             })?;
         let bid_array =
-            result[*pair_name]["bids"]
+            result[pair_name.as_str()]["bids"]
                 .as_array()
                 .ok_or_else(|| {
                                 ErrorKind::InvalidFieldFormat(format!("{}.bids",
-                                                                      result[*pair_name]))
+                                                                      result[pair_name.as_str()]))
                             })?;
 
         for ask in ask_array {
@@ -95,10 +92,7 @@ impl ExchangeApi for KrakenApi {
                  quantity: Volume,
                  price: Option<Price>)
                  -> Result<OrderInfo> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Kraken, &pair)?;
 
         let direction = match order_type {
             OrderType::BuyLimit | OrderType::BuyMarket => "buy",
@@ -110,6 +104,10 @@ impl ExchangeApi for KrakenApi {
             OrderType::BuyMarket | OrderType::SellMarket => "market",
         };
 
+        if order_type_str == "limit" && price.is_none() {
+            return Err(ErrorKind::MissingPrice.into());
+        }
+
         let mut price_str = "".to_string();
         if price.is_some() {
             price_str = price.unwrap().to_string()
@@ -149,6 +147,89 @@ impl ExchangeApi for KrakenApi {
            })
     }
 
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        order.check_prices()?;
+        let pair_name = &pair_or(Exchange::Kraken, &order.pair)?;
+        if order.reduce_only {
+            return Err(ErrorKind::Unsupported("reduce-only orders".to_string()).into());
+        }
+
+        let direction = match order.side {
+            OrderSide::Buy => "buy",
+            OrderSide::Sell => "sell",
+        };
+
+        let price = order.price.as_ref().map(|p| p.to_string()).unwrap_or_default();
+        let stop_price = order.stop_price.as_ref().map(|p| p.to_string()).unwrap_or_default();
+        // Trigger orders take the trigger in price and the limit in price2
+        let (order_type_str, price, price2) = match (order.kind, order.price.is_some()) {
+            (OrderKind::Limit, _) => ("limit", price, "".to_string()),
+            (OrderKind::Market, _) => ("market", "".to_string(), "".to_string()),
+            (OrderKind::Stop, _) => ("stop-loss", stop_price, "".to_string()),
+            (OrderKind::StopLimit, _) => ("stop-loss-limit", stop_price, price),
+            (OrderKind::TakeProfit, false) => ("take-profit", stop_price, "".to_string()),
+            (OrderKind::TakeProfit, true) => ("take-profit-limit", stop_price, price),
+        };
+
+        let expiretm = match order.time_in_force {
+            TimeInForce::GoodTillCancel => "".to_string(),
+            TimeInForce::GoodTillDate(timestamp) => (timestamp / 1000).to_string(),
+            tif => return Err(ErrorKind::Unsupported(format!("time in force {:?}", tif)).into()),
+        };
+
+        let oflags = if order.post_only {
+            if order.kind != OrderKind::Limit {
+                return Err(ErrorKind::Unsupported("post-only orders other than limit".to_string()).into());
+            }
+            "post"
+        } else {
+            ""
+        };
+
+        // Kraken user references are 32-bit signed numbers
+        let userref = match order.client_order_id {
+            Some(ref id) => {
+                id.parse::<i32>()
+                    .map_err(|_| ErrorKind::Unsupported("non numeric client order ids".to_string()))?
+                    .to_string()
+            }
+            None => "".to_string(),
+        };
+
+        let raw_response = self.add_standard_order(pair_name,
+                                                   direction,
+                                                   order_type_str,
+                                                   &price,
+                                                   &price2,
+                                                   &order.quantity.to_string(),
+                                                   "",
+                                                   oflags,
+                                                   "",
+                                                   &expiretm,
+                                                   &userref,
+                                                   "").await?;
+
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut txids = Vec::new();
+
+        let list_id =
+            result["txid"]
+                .as_array()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["txid"])))?;
+
+        for id in list_id {
+            txids.push(id.as_str()
+                           .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", id)))?
+                           .to_string());
+        }
+
+        Ok(OrderInfo {
+               timestamp: helpers::get_unix_timestamp_ms(),
+               identifier: txids,
+           })
+    }
+
     async fn balances(&mut self) -> Result<Balances> {
         let raw_response = self.get_account_balance().await?;
         let result = utils::parse_result(&raw_response)?;
@@ -170,4 +251,245 @@ impl ExchangeApi for KrakenApi {
 
         Ok(balances)
     }
+
+    async fn cancel_order(&mut self, _pair: Pair, order: &OrderInfo) -> Result<OrderInfo> {
+        let mut canceled = Vec::new();
+
+        for txid in &order.identifier {
+            let raw_response = self.cancel_open_order(txid).await?;
+            utils::parse_result(&raw_response)?;
+            canceled.push(txid.clone());
+        }
+
+        Ok(OrderInfo {
+               timestamp: helpers::get_unix_timestamp_ms(),
+               identifier: canceled,
+           })
+    }
+
+    async fn order_status(&mut self, pair: Pair, order: &OrderInfo) -> Result<Order> {
+        let txid = order.identifier
+            .first()
+            .ok_or_else(|| ErrorKind::MissingField("txid".to_string()))?;
+
+        let raw_response = self.query_orders_info("", "", txid).await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let order_obj = result.get(txid.as_str())
+            .ok_or_else(|| ErrorKind::MissingField(txid.clone()))?;
+
+        let mut status = parse_order(pair, txid, order_obj)?;
+        status.identifier = order.identifier.clone();
+        Ok(status)
+    }
+
+    async fn open_orders(&mut self, pair: Pair) -> Result<Vec<Order>> {
+        let pair_name = &pair_or(Exchange::Kraken, &pair)?;
+
+        let raw_response = self.get_open_orders("", "").await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let open = result["open"]
+            .as_object()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["open"])))?;
+
+        let mut orders = Vec::new();
+        for (txid, order_obj) in open {
+            let descr_pair = order_obj["descr"]["pair"].as_str().unwrap_or_default();
+            if !utils::is_same_pair(pair_name, descr_pair) {
+                continue;
+            }
+            orders.push(parse_order(pair, txid, order_obj)?);
+        }
+        Ok(orders)
+    }
+
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>> {
+        let pair_name = &pair_or(Exchange::Kraken, &pair)?;
+
+        // Kraken uses a timestamp in ns as trade id
+        let since_id = since.map(|ms| (ms * 1_000_000).to_string()).unwrap_or_default();
+        let raw_response = self.get_recent_trades(pair_name, &since_id).await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let trade_array =
+            result[pair_name.as_str()]
+                .as_array()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result[pair_name.as_str()])))?;
+
+        let mut trades = Vec::new();
+        for trade in trade_array {
+            let time = trade[2]
+                .as_f64()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat("trade time".to_string()))?;
+            let tt = match trade[3].as_str() {
+                Some("b") => TradeType::Buy,
+                Some("s") => TradeType::Sell,
+                _ => TradeType::None,
+            };
+
+            trades.push(Trade {
+                id: None,
+                timestamp: (time * 1000.0) as i64,
+                pair,
+                price: helpers::from_json_bigdecimal(&trade[0], "trade price")?,
+                volume: helpers::from_json_bigdecimal(&trade[1], "trade volume")?,
+                tt,
+            });
+        }
+        Ok(trades)
+    }
+
+    async fn candles(&mut self,
+                     pair: Pair,
+                     interval: Interval,
+                     start: Option<i64>,
+                     end: Option<i64>)
+                     -> Result<Vec<Candle>> {
+        let pair_name = &pair_or(Exchange::Kraken, &pair)?;
+        let interval_str = match utils::get_interval_string(interval) {
+            Some(i) => i,
+            None => return Err(ErrorKind::Unsupported(format!("{:?} candles", interval)).into()),
+        };
+
+        // OHLC only accepts a start (in seconds), the end is applied on the result
+        let since = start.map(|ms| (ms / 1000).to_string()).unwrap_or_default();
+        let raw_response = self.get_ohlc_data(pair_name, interval_str, &since).await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let ohlc_array =
+            result[pair_name.as_str()]
+                .as_array()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result[pair_name.as_str()])))?;
+
+        let mut candles = Vec::new();
+        for ohlc in ohlc_array {
+            let time = ohlc[0]
+                .as_i64()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat("ohlc time".to_string()))?;
+            let timestamp = time * 1000;
+            if end.map_or(false, |end| timestamp > end) {
+                break;
+            }
+
+            candles.push(Candle {
+                timestamp,
+                pair,
+                interval,
+                open: helpers::from_json_bigdecimal(&ohlc[1], "open")?,
+                high: helpers::from_json_bigdecimal(&ohlc[2], "high")?,
+                low: helpers::from_json_bigdecimal(&ohlc[3], "low")?,
+                close: helpers::from_json_bigdecimal(&ohlc[4], "close")?,
+                volume: helpers::from_json_bigdecimal(&ohlc[6], "volume")?,
+            });
+        }
+        Ok(candles)
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        let raw_response = self.get_tradable_asset_pairs("", "").await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut symbols = SymbolMap::new();
+        for (name, info) in result.iter() {
+            // Dark pool pairs share the symbol of the regular pair
+            if name.ends_with(".d") {
+                continue;
+            }
+            symbols.insert(parse_asset_pair_symbol(info)?, name);
+        }
+        symbols.register(Exchange::Kraken);
+        Ok(symbols)
+    }
+
+    async fn markets(&mut self) -> Result<Markets> {
+        let raw_response = self.get_tradable_asset_pairs("", "").await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut markets = Markets::new();
+        for (name, info) in result.iter() {
+            if name.ends_with(".d") {
+                continue;
+            }
+            let symbol = parse_asset_pair_symbol(info)?;
+            let mut market = MarketInfo::new(symbol.clone(), name);
+
+            if let Some(decimals) = info["pair_decimals"].as_u64() {
+                market.price_tick = Some(helpers::decimals_to_increment(decimals as u32));
+            }
+            if let Some(decimals) = info["lot_decimals"].as_u64() {
+                market.quantity_step = Some(helpers::decimals_to_increment(decimals as u32));
+            }
+            if !info["ordermin"].is_null() {
+                market.min_quantity = Some(helpers::from_json_bigdecimal(&info["ordermin"], "ordermin")?);
+            }
+            // Fee schedules are [volume, percent fee] tuples, the first one applies without volume
+            if !info["fees"][0].is_null() {
+                let taker = helpers::from_json_bigdecimal(&info["fees"][0][1], "fees")?;
+                let maker = if info["fees_maker"][0].is_null() {
+                    taker.clone()
+                } else {
+                    helpers::from_json_bigdecimal(&info["fees_maker"][0][1], "fees_maker")?
+                };
+                market.fee_tier = Some(FeeTier {
+                    maker: maker / BigDecimal::from(100),
+                    taker: taker / BigDecimal::from(100),
+                });
+            }
+            markets.insert(symbol, market);
+        }
+        Ok(markets)
+    }
+}
+
+/// Build the normalized symbol of an entry of the `AssetPairs` result
+fn parse_asset_pair_symbol(info: &Value) -> Result<Symbol> {
+    let base = info["base"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("base".to_string()))?;
+    let quote = info["quote"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("quote".to_string()))?;
+    Ok(Symbol::new(utils::normalize_asset(base), utils::normalize_asset(quote)))
+}
+
+/// Build a normalized `Order` from an entry of the `OpenOrders` or `QueryOrders` results
+fn parse_order(pair: Pair, txid: &str, order_obj: &Value) -> Result<Order> {
+    let descr = &order_obj["descr"];
+
+    let order_type = match (descr["type"].as_str(), descr["ordertype"].as_str()) {
+        (Some("buy"), Some("market")) => OrderType::BuyMarket,
+        (Some("sell"), Some("market")) => OrderType::SellMarket,
+        (Some("buy"), _) => OrderType::BuyLimit,
+        (Some("sell"), _) => OrderType::SellLimit,
+        _ => return Err(ErrorKind::InvalidFieldFormat("descr.type".to_string()).into()),
+    };
+
+    let price = match order_type {
+        OrderType::BuyMarket | OrderType::SellMarket => None,
+        _ => Some(helpers::from_json_bigdecimal(&descr["price"], "descr.price")?),
+    };
+    let quantity = helpers::from_json_bigdecimal(&order_obj["vol"], "vol")?;
+    let executed_quantity = helpers::from_json_bigdecimal(&order_obj["vol_exec"], "vol_exec")?;
+
+    let status_str = order_obj["status"]
+        .as_str()
+        .ok_or_else(|| ErrorKind::MissingField("status".to_string()))?;
+    let mut status = utils::get_order_status(status_str);
+    if status == OrderStatus::Open && !executed_quantity.is_zero() {
+        status = OrderStatus::PartiallyFilled;
+    }
+
+    let opentm = order_obj["opentm"].as_f64().unwrap_or_default();
+
+    Ok(Order {
+           identifier: vec![txid.to_string()],
+           timestamp: (opentm * 1000.0) as i64,
+           pair,
+           order_type,
+           status,
+           price,
+           quantity,
+           executed_quantity,
+       })
 }
@@ -5,6 +5,9 @@ pub mod api;
 pub mod generic_api;
 pub mod credentials;
 pub mod utils;
+pub mod models;
+pub mod streaming_api;
+pub mod user_streaming_api;
 
 pub use self::credentials::KrakenCreds;
 pub use self::api::KrakenApi;
@@ -0,0 +1,420 @@
+use serde::{Serialize, Deserialize};
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use bigdecimal::BigDecimal;
+use crate::error::*;
+use crate::types::{Channel, TradeType, Price, Volume, Pair, Ticker, BestBidOffer, Candle, Interval};
+
+/// Depths of the book subscription accepted by Kraken
+pub const BOOK_DEPTHS: [u32; 5] = [10, 25, 100, 500, 1000];
+
+/// Events sent as json objects (everything but market data)
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event")]
+pub enum StatusEvent {
+    #[serde(rename = "heartbeat")]
+    Heartbeat,
+    #[serde(rename = "systemStatus")]
+    SystemStatus { status: String },
+    #[serde(rename = "subscriptionStatus")]
+    SubscriptionStatus {
+        pair: Option<String>,
+        status: String,
+        #[serde(rename = "errorMessage")]
+        error_message: Option<String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// A trade : price, volume, time (in seconds), side, order type and misc
+#[derive(Debug, Deserialize)]
+pub struct LiveTrade(pub String, pub String, pub String, pub String, pub String, pub String);
+
+impl LiveTrade {
+    pub fn event_ms(&self) -> i64 {
+        self.2.parse::<f64>().map(|t| (t * 1000.0) as i64).unwrap_or_default()
+    }
+
+    pub fn trade_type(&self) -> TradeType {
+        match self.3.as_str() {
+            "b" => TradeType::Buy,
+            "s" => TradeType::Sell,
+            _ => TradeType::None,
+        }
+    }
+}
+
+/// Book levels are arrays of price, volume, timestamp and an optional update type
+#[derive(Debug, Default, Deserialize)]
+pub struct BookPayload {
+    #[serde(rename = "as", default)]
+    snapshot_asks: Vec<Vec<String>>,
+    #[serde(rename = "bs", default)]
+    snapshot_bids: Vec<Vec<String>>,
+    #[serde(rename = "a", default)]
+    asks: Vec<Vec<String>>,
+    #[serde(rename = "b", default)]
+    bids: Vec<Vec<String>>,
+}
+
+/// Ticker payload, only the fields used are read
+#[derive(Debug, Deserialize)]
+pub struct TickerPayload {
+    /// Best ask price, whole lot volume and lot volume
+    pub a: Vec<Value>,
+    /// Best bid price, whole lot volume and lot volume
+    pub b: Vec<Value>,
+    /// Last trade price and volume
+    pub c: Vec<String>,
+    /// Volume today and over the last 24 hours
+    pub v: Vec<String>,
+}
+
+impl TickerPayload {
+    pub fn ticker(&self, pair: Pair, timestamp: i64) -> Option<Ticker> {
+        let parse = |p: &str| p.parse::<BigDecimal>().ok();
+        Some(Ticker {
+            timestamp,
+            pair,
+            last_trade_price: self.c.get(0).and_then(|p| parse(p))?,
+            lowest_ask: self.a.get(0).and_then(|p| p.as_str()).and_then(parse)?,
+            highest_bid: self.b.get(0).and_then(|p| p.as_str()).and_then(parse)?,
+            volume: self.v.get(0).and_then(|v| v.parse::<BigDecimal>().ok()),
+        })
+    }
+}
+
+/// Spread payload : bid, ask, time (in seconds), bid volume and ask volume
+#[derive(Debug, Deserialize)]
+pub struct Spread(pub String, pub String, pub String, pub String, pub String);
+
+impl Spread {
+    pub fn best_bid_offer(&self, pair: Pair) -> Option<BestBidOffer> {
+        Some(BestBidOffer {
+            event_ms: self.2.parse::<f64>().map(|t| (t * 1000.0) as i64).ok()?,
+            pair,
+            bid: self.0.parse::<BigDecimal>().ok()?,
+            ask: self.1.parse::<BigDecimal>().ok()?,
+            bid_volume: self.3.parse::<BigDecimal>().ok(),
+            ask_volume: self.4.parse::<BigDecimal>().ok(),
+        })
+    }
+}
+
+/// OHLC payload : time, end time (in seconds), open, high, low, close, vwap, volume and trade count
+#[derive(Debug, Deserialize)]
+pub struct Ohlc(pub String, pub String, pub String, pub String, pub String, pub String, pub String, pub String, pub i64);
+
+impl Ohlc {
+    /// The candle opens one interval before its end time
+    pub fn candle(&self, pair: Pair, interval: Interval) -> Option<Candle> {
+        let end_ms = self.1.parse::<f64>().map(|t| (t * 1000.0) as i64).ok()?;
+        Some(Candle {
+            timestamp: interval.open_time(end_ms - 1),
+            pair,
+            interval,
+            open: self.2.parse::<BigDecimal>().ok()?,
+            high: self.3.parse::<BigDecimal>().ok()?,
+            low: self.4.parse::<BigDecimal>().ok()?,
+            close: self.5.parse::<BigDecimal>().ok()?,
+            volume: self.7.parse::<BigDecimal>().ok()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Status(StatusEvent),
+    Trades { pair: String, trades: Vec<LiveTrade> },
+    Ticker { pair: String, ticker: TickerPayload },
+    Spread { pair: String, spread: Spread },
+    /// Candle in progress, `minutes` is the interval length read from the channel name (`ohlc-5`)
+    Ohlc { pair: String, minutes: String, ohlc: Ohlc },
+    /// A snapshot replaces the whole book, otherwise levels with a zero volume are removed
+    Book { pair: String, snapshot: bool, asks: Vec<(Price, Volume)>, bids: Vec<(Price, Volume)> },
+}
+
+fn parse_levels(levels: Vec<Vec<String>>) -> Result<Vec<(Price, Volume)>> {
+    levels.into_iter().map(|level| -> Result<(Price, Volume)> {
+        match (level.get(0), level.get(1)) {
+            (Some(price), Some(volume)) => Ok((price.parse::<BigDecimal>()?, volume.parse::<BigDecimal>()?)),
+            _ => Err(ErrorKind::InvalidFieldFormat("book level".to_string()).into()),
+        }
+    }).collect()
+}
+
+/// Market data is sent as arrays : channel id, one or two payloads, channel name and pair
+pub fn parse_event(msg: &[u8]) -> Result<Event> {
+    let v: Value = serde_json::from_slice(msg)?;
+    let array = match v {
+        Value::Array(array) => array,
+        o => return Ok(Event::Status(serde_json::from_value(o)?)),
+    };
+    if array.len() < 4 {
+        return Err(ErrorKind::BadParse.into());
+    }
+    let len = array.len();
+    let pair = array[len - 1].as_str().ok_or_else(|| ErrorKind::MissingField("pair".to_string()))?.to_string();
+    let channel_name = array[len - 2].as_str().ok_or_else(|| ErrorKind::MissingField("channelName".to_string()))?;
+    let payloads = &array[1..len - 2];
+    if channel_name == "trade" {
+        let trades: Vec<LiveTrade> = serde_json::from_value(payloads[0].clone())?;
+        Ok(Event::Trades { pair, trades })
+    } else if channel_name.starts_with("book") {
+        let mut asks = vec![];
+        let mut bids = vec![];
+        let mut snapshot = false;
+        for payload in payloads {
+            let payload: BookPayload = serde_json::from_value(payload.clone())?;
+            snapshot |= !payload.snapshot_asks.is_empty() || !payload.snapshot_bids.is_empty();
+            asks.extend(parse_levels(payload.snapshot_asks)?);
+            asks.extend(parse_levels(payload.asks)?);
+            bids.extend(parse_levels(payload.snapshot_bids)?);
+            bids.extend(parse_levels(payload.bids)?);
+        }
+        Ok(Event::Book { pair, snapshot, asks, bids })
+    } else if channel_name == "ticker" {
+        Ok(Event::Ticker { pair, ticker: serde_json::from_value(payloads[0].clone())? })
+    } else if channel_name == "spread" {
+        Ok(Event::Spread { pair, spread: serde_json::from_value(payloads[0].clone())? })
+    } else if channel_name.starts_with("ohlc-") {
+        let minutes = channel_name["ohlc-".len()..].to_string();
+        Ok(Event::Ohlc { pair, minutes, ohlc: serde_json::from_value(payloads[0].clone())? })
+    } else {
+        Err(ErrorKind::InvalidFieldValue("channelName".to_string()).into())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SubscriptionName {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<u32>,
+    /// Candle length in minutes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Subscription {
+    event: String,
+    pair: Vec<String>,
+    subscription: SubscriptionName,
+}
+
+/// Return the subscription message for a public channel, None if the channel is private or
+/// the candle interval unavailable. Books are subscribed with the smallest depth covering
+/// `book_depth` levels.
+pub fn subscription(c: Channel, pairs: Vec<String>, book_depth: usize) -> Option<Subscription> {
+    let name = match c {
+        Channel::LiveTrades => SubscriptionName { name: "trade".to_string(), depth: None, interval: None },
+        Channel::LiveOrderBook | Channel::LiveDetailOrderBook | Channel::LiveFullOrderBook => {
+            let depth = BOOK_DEPTHS.iter().find(|&&d| d as usize >= book_depth).unwrap_or(&BOOK_DEPTHS[BOOK_DEPTHS.len() - 1]);
+            SubscriptionName { name: "book".to_string(), depth: Some(*depth), interval: None }
+        }
+        Channel::LiveTicker => SubscriptionName { name: "ticker".to_string(), depth: None, interval: None },
+        Channel::LiveBestBidOffer => SubscriptionName { name: "spread".to_string(), depth: None, interval: None },
+        Channel::LiveCandles(interval) => {
+            let minutes = super::utils::get_interval_string(interval)?.parse::<u32>().ok()?;
+            SubscriptionName { name: "ohlc".to_string(), depth: None, interval: Some(minutes) }
+        }
+        Channel::LiveOrders | Channel::UserData => return None,
+    };
+    Some(Subscription {
+        event: "subscribe".to_string(),
+        pair: pairs,
+        subscription: name,
+    })
+}
+
+pub fn unsubscription(c: Channel, pairs: Vec<String>, book_depth: usize) -> Option<Subscription> {
+    subscription(c, pairs, book_depth).map(|sub| Subscription { event: "unsubscribe".to_string(), ..sub })
+}
+
+/// Trade of the account, from the `ownTrades` feed
+#[derive(Debug, Deserialize)]
+pub struct OwnTrade {
+    pub ordertxid: String,
+    /// Websocket pair name, such as `XBT/EUR`
+    pub pair: String,
+    /// Seconds with a decimal part
+    pub time: String,
+    #[serde(rename = "type")]
+    pub side: String,
+    pub price: String,
+    pub vol: String,
+    pub fee: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderDescription {
+    pub pair: String,
+    #[serde(rename = "type")]
+    pub side: String,
+    pub price: Option<String>,
+}
+
+/// Order of the account, from the `openOrders` feed. Only the first message of an order carries
+/// its description, updates only carry the changed fields.
+#[derive(Debug, Deserialize)]
+pub struct OpenOrder {
+    pub status: Option<String>,
+    pub descr: Option<OrderDescription>,
+    pub vol: Option<String>,
+    pub vol_exec: Option<String>,
+    /// Seconds with a decimal part
+    pub lastupdated: Option<String>,
+    pub opentm: Option<String>,
+}
+
+impl OpenOrder {
+    pub fn event_ms(&self) -> Option<i64> {
+        self.lastupdated.as_ref().or(self.opentm.as_ref())
+            .and_then(|t| t.parse::<f64>().ok())
+            .map(|t| (t * 1000.0) as i64)
+    }
+}
+
+#[derive(Debug)]
+pub enum PrivateEvent {
+    Status(StatusEvent),
+    /// Trades by trade id
+    OwnTrades(Vec<(String, OwnTrade)>),
+    /// Orders by order id
+    OpenOrders(Vec<(String, OpenOrder)>),
+}
+
+/// Private feeds are arrays : a list of objects by id, channel name and sequence number
+pub fn parse_private_event(msg: &[u8]) -> Result<PrivateEvent> {
+    let v: Value = serde_json::from_slice(msg)?;
+    let mut array = match v {
+        Value::Array(array) => array,
+        o => return Ok(PrivateEvent::Status(serde_json::from_value(o)?)),
+    };
+    if array.len() < 2 {
+        return Err(ErrorKind::BadParse.into());
+    }
+    let channel_name = array[1].as_str().ok_or_else(|| ErrorKind::MissingField("channelName".to_string()))?.to_string();
+    let payload = array.swap_remove(0);
+    match channel_name.as_str() {
+        "ownTrades" => {
+            let trades: Vec<HashMap<String, OwnTrade>> = serde_json::from_value(payload)?;
+            Ok(PrivateEvent::OwnTrades(trades.into_iter().flatten().collect()))
+        }
+        "openOrders" => {
+            let orders: Vec<HashMap<String, OpenOrder>> = serde_json::from_value(payload)?;
+            Ok(PrivateEvent::OpenOrders(orders.into_iter().flatten().collect()))
+        }
+        _ => Err(ErrorKind::InvalidFieldValue("channelName".to_string()).into()),
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PrivateSubscriptionName {
+    name: String,
+    token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PrivateSubscription {
+    event: String,
+    subscription: PrivateSubscriptionName,
+}
+
+/// Subscriptions to the orders and trades of the account, past trades are not sent again
+/// when connecting
+pub fn private_subscriptions(token: &str) -> Vec<PrivateSubscription> {
+    vec![("openOrders", None), ("ownTrades", Some(false))].into_iter()
+        .map(|(name, snapshot)| PrivateSubscription {
+            event: "subscribe".to_string(),
+            subscription: PrivateSubscriptionName { name: name.to_string(), token: token.to_string(), snapshot },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod model_tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_live_trade() {
+        let v = parse_event(b"[0,[[\"5541.20000\",\"0.15850568\",\"1534614057.321597\",\"s\",\"l\",\"\"]],\"trade\",\"XBT/USD\"]").unwrap();
+        match v {
+            Event::Trades { pair, trades } => {
+                assert_eq!(pair, "XBT/USD");
+                assert_eq!(trades[0].event_ms(), 1534614057321);
+                assert_eq!(trades[0].trade_type(), TradeType::Sell);
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_book_update() {
+        let v = parse_event(b"[1234,{\"a\":[[\"5541.30000\",\"0.00000000\",\"1534614335.345903\"]]},{\"b\":[[\"5541.20000\",\"1.52900000\",\"1534614335.345903\",\"r\"]],\"c\":\"974942666\"},\"book-10\",\"XBT/USD\"]").unwrap();
+        match v {
+            Event::Book { pair, snapshot, asks, bids } => {
+                assert_eq!(pair, "XBT/USD");
+                assert!(!snapshot);
+                assert_eq!(asks.len(), 1);
+                assert_eq!(bids.len(), 1);
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_spread() {
+        let v = parse_event(b"[0,[\"5698.40000\",\"5700.00000\",\"1542057299.545897\",\"1.01234567\",\"0.98765432\"],\"spread\",\"XBT/USD\"]").unwrap();
+        match v {
+            Event::Spread { pair, spread } => {
+                assert_eq!(pair, "XBT/USD");
+                let bbo = spread.best_bid_offer(Pair::BTC_USD).unwrap();
+                assert_eq!(bbo.event_ms, 1542057299545);
+                assert_eq!(bbo.bid, "5698.4".parse::<BigDecimal>().unwrap());
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_ohlc() {
+        let v = parse_event(b"[42,[\"1542057314.748456\",\"1542057360.000000\",\"3586.70000\",\"3586.70000\",\"3586.60000\",\"3586.60000\",\"3586.68894\",\"0.03373000\",2],\"ohlc-1\",\"XBT/USD\"]").unwrap();
+        match v {
+            Event::Ohlc { pair, minutes, ohlc } => {
+                assert_eq!(pair, "XBT/USD");
+                assert_eq!(minutes, "1");
+                let candle = ohlc.candle(Pair::BTC_USD, Interval::OneMinute).unwrap();
+                assert_eq!(candle.timestamp, 1542057300000);
+                assert_eq!(candle.volume, "0.03373".parse::<BigDecimal>().unwrap());
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_own_trades() {
+        let v = parse_private_event(b"[[{\"TDLH43-DVQXD-2KHVYY\":{\"cost\":\"1000000.00000\",\"fee\":\"1600.00000\",\"margin\":\"0.00000\",\"ordertxid\":\"TDLH43-DVQXD-2KHVYY\",\"ordertype\":\"limit\",\"pair\":\"XBT/EUR\",\"postxid\":\"OGTT3Y-C6I3P-XRI6HX\",\"price\":\"100000.00000\",\"time\":\"1560516023.070651\",\"type\":\"sell\",\"vol\":\"1000000000.00000000\"}}],\"ownTrades\",{\"sequence\":2}]").unwrap();
+        match v {
+            PrivateEvent::OwnTrades(trades) => {
+                let (trade_id, trade) = &trades[0];
+                assert_eq!(trade_id, "TDLH43-DVQXD-2KHVYY");
+                assert_eq!(trade.pair, "XBT/EUR");
+                assert_eq!(trade.side, "sell");
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_heartbeat() {
+        match parse_event(b"{\"event\":\"heartbeat\"}").unwrap() {
+            Event::Status(StatusEvent::Heartbeat) => (),
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::coinnect::Credentials;
+use crate::exchange_bot::{DefaultWsActor, WsHandler, WsWriter, ExchangeBot, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, add_channel_pairs, remove_channel_pairs};
+use crate::error::*;
+use super::models::*;
+use bytes::Bytes;
+use bytes::Buf;
+use actix::{Addr, Recipient};
+use awc::{
+    ws::Message
+};
+use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair, LiveTrade, BookUpdate, BookSettings, LiveBooks, LiveCandles, Orderbook};
+use crate::exchange::Exchange;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+
+static WEBSOCKET_URL: &'static str = "wss://ws.kraken.com";
+
+pub struct KrakenBot {
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+    books: LiveBooks,
+}
+
+impl ExchangeBot for KrakenBot {
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.full_order_book(pair)
+    }
+}
+
+#[derive(Debug)]
+pub struct KrakenStreamingApi {
+    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    channels: HashMap<Channel, HashSet<Pair>>,
+    /// Pairs by websocket name (`XBT/EUR`)
+    pairs: HashMap<String, Pair>,
+    books: LiveBooks,
+    candles: LiveCandles,
+}
+
+impl KrakenStreamingApi {
+    /// Create a new kraken exchange bot, unavailable channels and currencies are ignored
+    pub async fn new_bot<C: Credentials>(_creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, book_settings: BookSettings, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<KrakenBot> {
+        let books = LiveBooks::new(book_settings);
+        let monitor = FeedMonitor::new(Exchange::Kraken, channels.clone(), recipients.clone());
+        let api = KrakenStreamingApi::new_handler(channels, books.clone(), recipients);
+        let addr = DefaultWsActor::new("KrakenStream", WEBSOCKET_URL, Some(Duration::from_secs(10)), connection, monitor.clone(), Box::new(api)).await?;
+        Ok(KrakenBot { addr, books, monitor })
+    }
+
+    /// Create the handler of the feed without connecting it, see `replay::FeedReplay`
+    pub fn new_handler(channels: HashMap<Channel, HashSet<Pair>>, books: LiveBooks, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> KrakenStreamingApi {
+        books.flush_to(Exchange::Kraken, recipients.clone());
+        let pairs = channels.values().flatten()
+            .filter_map(|pair| super::utils::get_ws_pair_string(pair).map(|name| (name, *pair)))
+            .collect();
+        KrakenStreamingApi {
+            recipients,
+            channels,
+            pairs,
+            books,
+            candles: LiveCandles::default(),
+        }
+    }
+
+    fn is_subscribed(&self, channel: Channel, pair: &Pair) -> bool {
+        self.channels.get(&channel).map_or(false, |pairs| pairs.contains(pair))
+    }
+
+    fn broadcast(&self, v: LiveEvent) {
+        let vec = self.recipients.clone();
+        if vec.len() == 0 as usize {
+            debug!("{:?}", v);
+        } else {
+            for r in &vec {
+                let le: LiveEvent = v.clone();
+                r.do_send(LiveEventEnveloppe(Exchange::Kraken, le));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl WsHandler for KrakenStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, _w: &mut dyn WsWriter, msg: Bytes) {
+        let event = match parse_event(msg.bytes()) {
+            Ok(event) => event,
+            Err(e) => return trace!("Kraken : error {:?} deserializing {:?}", e, msg),
+        };
+        match event {
+            Event::Status(StatusEvent::SubscriptionStatus { pair, status, error_message }) => {
+                if status == "error" {
+                    warn!("Kraken : subscription to {:?} failed : {:?}", pair, error_message);
+                }
+            }
+            Event::Status(_) => (),
+            Event::Trades { pair, trades } => {
+                let current_pair = match self.pairs.get(&pair) {
+                    Some(&p) if self.is_subscribed(Channel::LiveTrades, &p) => p,
+                    _ => return,
+                };
+                for trade in trades {
+                    match (trade.0.parse::<BigDecimal>(), trade.1.parse::<f32>()) {
+                        (Ok(price), Ok(amount)) => self.broadcast(LiveEvent::LiveTrade(LiveTrade {
+                            event_ms: trade.event_ms(),
+                            pair: current_pair,
+                            amount,
+                            price,
+                            tt: trade.trade_type(),
+                        })),
+                        _ => trace!("Kraken : invalid trade {:?}", trade),
+                    }
+                }
+            }
+            Event::Ticker { pair, ticker } => {
+                let current_pair = match self.pairs.get(&pair) {
+                    Some(&p) if self.is_subscribed(Channel::LiveTicker, &p) => p,
+                    _ => return,
+                };
+                if let Some(ticker) = ticker.ticker(current_pair, self.books.clock().now_ms()) {
+                    self.broadcast(LiveEvent::LiveTicker(ticker));
+                }
+            }
+            Event::Spread { pair, spread } => {
+                let current_pair = match self.pairs.get(&pair) {
+                    Some(&p) if self.is_subscribed(Channel::LiveBestBidOffer, &p) => p,
+                    _ => return,
+                };
+                if let Some(bbo) = spread.best_bid_offer(current_pair) {
+                    self.broadcast(LiveEvent::LiveBestBidOffer(bbo));
+                }
+            }
+            Event::Ohlc { pair, minutes, ohlc } => {
+                let interval = self.channels.keys().find_map(|c| match c {
+                    Channel::LiveCandles(i) if super::utils::get_interval_string(*i) == Some(minutes.as_str()) => Some(*i),
+                    _ => None,
+                });
+                let candle = match (interval, self.pairs.get(&pair)) {
+                    (Some(interval), Some(&p)) if self.is_subscribed(Channel::LiveCandles(interval), &p) => ohlc.candle(p, interval),
+                    _ => return,
+                };
+                // Kraken only sends the candle in progress, it is closed by the next one
+                if let Some(candle) = candle {
+                    for c in self.candles.update_candle(candle, false) {
+                        self.broadcast(LiveEvent::LiveCandle(c));
+                    }
+                }
+            }
+            Event::Book { pair, snapshot, asks, bids } => {
+                let current_pair = match self.pairs.get(&pair) {
+                    Some(&p) if self.is_subscribed(Channel::LiveFullOrderBook, &p) => p,
+                    _ => return,
+                };
+                // Kraken does not sequence book updates, they follow the snapshot on the same channel
+                let (state, latest) = self.books.with_book(current_pair, |agg| {
+                    let state_change = if snapshot {
+                        agg.apply_snapshot(None, asks.into_iter(), bids.into_iter())
+                    } else {
+                        agg.apply_update(BookUpdate { update_ids: None, asks, bids })
+                    };
+                    (state_change.map(|_| agg.book_state()), agg.latest_order_book())
+                });
+                if let Some(state) = state {
+                    self.broadcast(LiveEvent::LiveBookState(state));
+                }
+                if let Some(ob) = latest {
+                    self.broadcast(LiveEvent::LiveOrderbook(ob));
+                }
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_started(&mut self, w: &mut dyn WsWriter) {
+        // A snapshot is sent again for every book subscription
+        for state in self.books.start_resync() {
+            self.broadcast(LiveEvent::LiveBookState(state));
+        }
+        for (k, v) in &self.channels {
+            let pairs: Vec<String> = v.iter().filter_map(super::utils::get_ws_pair_string).collect();
+            if pairs.is_empty() {
+                continue;
+            }
+            info!("Kraken : connecting to {:?} for {:?}", k, &pairs);
+            match subscription(k.clone(), pairs, self.books.settings().depth) {
+                Some(sub) => {
+                    let result = serde_json::to_string(&sub).unwrap();
+                    w.write(Message::Text(result));
+                }
+                None => warn!("Kraken : channel {:?} is not available", k),
+            }
+        }
+    }
+
+    fn handle_subscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let depth = self.books.settings().depth;
+        if subscription(channel.clone(), vec![], depth).is_none() {
+            warn!("Kraken : channel {:?} is not available", channel);
+            return HashSet::new();
+        }
+        let (pairs, unsupported): (HashSet<Pair>, HashSet<Pair>) = pairs.into_iter()
+            .partition(|pair| super::utils::get_ws_pair_string(pair).is_some());
+        if !unsupported.is_empty() {
+            warn!("Kraken : pairs {:?} are not supported", unsupported);
+        }
+        let mut ws_pairs = vec![];
+        for pair in add_channel_pairs(&mut self.channels, channel.clone(), pairs.clone()) {
+            if let Some(name) = super::utils::get_ws_pair_string(&pair) {
+                self.pairs.insert(name.clone(), pair);
+                ws_pairs.push(name);
+            }
+        }
+        if !ws_pairs.is_empty() {
+            if let Some(sub) = subscription(channel, ws_pairs, depth) {
+                w.write(Message::Text(serde_json::to_string(&sub).unwrap()));
+            }
+        }
+        pairs
+    }
+
+    fn handle_unsubscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let removed = remove_channel_pairs(&mut self.channels, channel.clone(), pairs);
+        if channel == Channel::LiveFullOrderBook {
+            for pair in &removed {
+                self.books.remove(*pair);
+            }
+        }
+        if let Channel::LiveCandles(interval) = channel {
+            for pair in &removed {
+                self.candles.remove(*pair, interval);
+            }
+        }
+        let ws_pairs: Vec<String> = removed.iter().filter_map(super::utils::get_ws_pair_string).collect();
+        if !ws_pairs.is_empty() {
+            if let Some(sub) = unsubscription(channel, ws_pairs, self.books.settings().depth) {
+                w.write(Message::Text(serde_json::to_string(&sub).unwrap()));
+            }
+        }
+        removed.into_iter().collect()
+    }
+}
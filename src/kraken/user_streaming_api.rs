@@ -0,0 +1,206 @@
+use crate::coinnect::Credentials;
+use crate::exchange_bot::{DefaultWsActor, WsHandler, WsWriter, ExchangeBot, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, ClientCommand};
+use crate::error::*;
+use super::models::*;
+use super::KrakenApi;
+use bytes::Bytes;
+use bytes::Buf;
+use actix::{Addr, Recipient};
+use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair, OrderUpdate, Fill, OrderSide, OrderStatus};
+use crate::exchange::Exchange;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::Duration;
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+
+static WEBSOCKET_URL: &'static str = "wss://ws-auth.kraken.com";
+
+pub struct KrakenUserBot {
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+}
+
+impl ExchangeBot for KrakenUserBot {
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+}
+
+/// Orders and trades of the account, the private feeds are subscribed with a token fetched
+/// from the REST api on every connection. Kraken has no balance feed.
+pub struct KrakenUserStreamingApi {
+    api: KrakenApi,
+    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    pairs: HashSet<Pair>,
+    /// Pair and side of the open orders, updates do not repeat them
+    orders: HashMap<String, (Pair, OrderSide)>,
+    /// Set once connected, subscriptions are sent when the token is received
+    addr: Rc<RefCell<Option<Addr<DefaultWsActor>>>>,
+}
+
+impl KrakenUserStreamingApi {
+    /// Create a new kraken user data bot, order updates and fills are only sent for `pairs`
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, pairs: HashSet<Pair>, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<KrakenUserBot> {
+        let mut channels = HashMap::new();
+        channels.insert(Channel::UserData, pairs.clone());
+        let monitor = FeedMonitor::new(Exchange::Kraken, channels, recipients.clone());
+        let addr_cell = Rc::new(RefCell::new(None));
+        let handler = KrakenUserStreamingApi {
+            api: KrakenApi::new(*creds)?,
+            recipients,
+            pairs,
+            orders: HashMap::new(),
+            addr: addr_cell.clone(),
+        };
+        let addr = DefaultWsActor::new("KrakenUserStream", WEBSOCKET_URL, Some(Duration::from_secs(10)), connection, monitor.clone(), Box::new(handler)).await?;
+        *addr_cell.borrow_mut() = Some(addr.clone());
+        Ok(KrakenUserBot { addr, monitor })
+    }
+
+    /// Return the pair of a websocket pair name (`XBT/EUR`) if it is subscribed
+    fn subscribed_pair(&self, name: &str) -> Option<Pair> {
+        self.pairs.iter().find(|pair| super::utils::get_ws_pair_string(pair).map_or(false, |n| n == name)).cloned()
+    }
+
+    fn broadcast(&self, v: LiveEvent) {
+        if self.recipients.is_empty() {
+            debug!("{:?}", v);
+        } else {
+            for r in &self.recipients {
+                r.do_send(LiveEventEnveloppe(Exchange::Kraken, v.clone()));
+            }
+        }
+    }
+
+    fn order_update(&mut self, order_id: String, order: OpenOrder) -> Option<OrderUpdate> {
+        if let Some(descr) = &order.descr {
+            if let Some(pair) = self.subscribed_pair(&descr.pair) {
+                let side = if descr.side == "buy" { OrderSide::Buy } else { OrderSide::Sell };
+                self.orders.insert(order_id.clone(), (pair, side));
+            }
+        }
+        let (pair, side) = *self.orders.get(&order_id)?;
+        let executed_quantity = order.vol_exec.as_ref().and_then(|v| v.parse::<BigDecimal>().ok());
+        // Executions only send the executed volume
+        let mut status = order.status.as_ref().map_or(OrderStatus::PartiallyFilled, |s| super::utils::get_order_status(s));
+        if status == OrderStatus::Open && executed_quantity.as_ref().map_or(false, |q| !q.is_zero()) {
+            status = OrderStatus::PartiallyFilled;
+        }
+        if !status.is_open() {
+            self.orders.remove(&order_id);
+        }
+        Some(OrderUpdate {
+            event_ms: order.event_ms().unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+            order_id,
+            pair,
+            status,
+            side: Some(side),
+            price: order.descr.as_ref().and_then(|d| d.price.as_ref()).and_then(|p| p.parse::<BigDecimal>().ok()).filter(|p| !p.is_zero()),
+            quantity: order.vol.as_ref().and_then(|v| v.parse::<BigDecimal>().ok()),
+            executed_quantity,
+        })
+    }
+
+    fn fill(&self, trade_id: String, trade: OwnTrade) -> Option<Fill> {
+        let pair = self.subscribed_pair(&trade.pair)?;
+        Some(Fill {
+            event_ms: trade.time.parse::<f64>().map(|t| (t * 1000.0) as i64).ok()?,
+            order_id: trade.ordertxid,
+            trade_id,
+            pair,
+            side: if trade.side == "buy" { OrderSide::Buy } else { OrderSide::Sell },
+            price: trade.price.parse::<BigDecimal>().ok()?,
+            quantity: trade.vol.parse::<BigDecimal>().ok()?,
+            fee: trade.fee.and_then(|f| f.parse::<BigDecimal>().ok()),
+            fee_currency: None,
+        })
+    }
+}
+
+#[async_trait]
+impl WsHandler for KrakenUserStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, _w: &mut dyn WsWriter, msg: Bytes) {
+        let event = match parse_private_event(msg.bytes()) {
+            Ok(event) => event,
+            Err(e) => return trace!("Kraken : error {:?} deserializing {:?}", e, msg),
+        };
+        match event {
+            PrivateEvent::Status(StatusEvent::SubscriptionStatus { status, error_message, .. }) => {
+                if status == "error" {
+                    warn!("Kraken : private subscription failed : {:?}", error_message);
+                }
+            }
+            PrivateEvent::Status(_) => (),
+            PrivateEvent::OpenOrders(orders) => {
+                for (order_id, order) in orders {
+                    if let Some(update) = self.order_update(order_id, order) {
+                        self.broadcast(LiveEvent::LiveOrderUpdate(update));
+                    }
+                }
+            }
+            PrivateEvent::OwnTrades(trades) => {
+                for (trade_id, trade) in trades {
+                    if let Some(fill) = self.fill(trade_id, trade) {
+                        self.broadcast(LiveEvent::LiveFill(fill));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tokens must be used within 15 minutes, a new one is fetched for every connection
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_started(&mut self, _w: &mut dyn WsWriter) {
+        // The open orders snapshot sent after subscribing describes the orders again
+        self.orders.clear();
+        info!("Kraken : connecting to the private feeds for {:?}", &self.pairs);
+        let mut api = self.api.clone();
+        let addr = self.addr.clone();
+        actix_rt::spawn(async move {
+            let token = match api.get_websockets_token().await.and_then(|raw| super::utils::parse_result(&raw)) {
+                Ok(r) => match r.get("token").and_then(|t| t.as_str()) {
+                    Some(token) => token.to_string(),
+                    None => return warn!("Kraken : invalid websockets token {:?}", r),
+                },
+                Err(e) => return warn!("Kraken : error fetching a websockets token : {:?}", e),
+            };
+            let addr = match addr.borrow().clone() {
+                Some(addr) => addr,
+                None => return,
+            };
+            for sub in private_subscriptions(&token) {
+                addr.do_send(ClientCommand(serde_json::to_string(&sub).unwrap()));
+            }
+        });
+    }
+
+    /// Private feeds cover every pair of the account, subscribing only changes the pairs sent
+    fn handle_subscribe(&mut self, _w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel != Channel::UserData {
+            warn!("Kraken : channel {:?} is not available on the user data stream", channel);
+            return HashSet::new();
+        }
+        self.pairs.extend(pairs.iter().cloned());
+        pairs
+    }
+
+    fn handle_unsubscribe(&mut self, _w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        if channel != Channel::UserData {
+            return HashSet::new();
+        }
+        let removed: HashSet<Pair> = pairs.into_iter().filter(|pair| self.pairs.remove(pair)).collect();
+        self.orders.retain(|_, (pair, _)| !removed.contains(pair));
+        removed
+    }
+}
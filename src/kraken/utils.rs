@@ -5,7 +5,9 @@ use serde_json::value::Map;
 
 use crate::error::*;
 use crate::types::Currency;
-use crate::types::Pair;
+use crate::types::{Interval, OrderStatus};
+use crate::types::{Pair, Symbol};
+use crate::exchange::Exchange;
 use crate::types::Pair::*;
 
 lazy_static! {
@@ -85,6 +87,76 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
+/// Return the name of the pair used by the Kraken websocket feed (`XBT/EUR`)
+/// If the Pair is not supported, None is returned.
+pub fn get_ws_pair_string(pair: &Pair) -> Option<String> {
+    crate::utils::pair_name(Exchange::Kraken, pair)?;
+    let symbol = Symbol::from(*pair);
+    let ws_asset = |asset: &str| if asset == "BTC" { "XBT".to_string() } else { asset.to_string() };
+    Some(format!("{}/{}", ws_asset(symbol.base.as_str()), ws_asset(symbol.quote.as_str())))
+}
+
+/// Return true if the pair name found in an order description (`descr.pair`) designates the
+/// Kraken pair `pair_name`. Order descriptions use the pair alternate name, where the `X`/`Z`
+/// asset class prefixes are dropped (`XXBTZEUR` becomes `XBTEUR`).
+pub fn is_same_pair(pair_name: &str, descr_pair: &str) -> bool {
+    if pair_name == descr_pair {
+        return true;
+    }
+    let (name, dark_pool) = match pair_name.find('.') {
+        Some(idx) => pair_name.split_at(idx),
+        None => (pair_name, ""),
+    };
+    let prefixed = |c: Option<char>| c == Some('X') || c == Some('Z');
+    if name.len() == 8 && prefixed(name.chars().nth(0)) && prefixed(name.chars().nth(4)) {
+        let altname = format!("{}{}{}", &name[1..4], &name[5..8], dark_pool);
+        return altname == descr_pair;
+    }
+    false
+}
+
+/// Return the normalized code of an asset used by Kraken (XXBT and XBT become BTC, ZEUR becomes
+/// EUR)
+pub fn normalize_asset(asset: &str) -> &str {
+    let asset = if asset.len() == 4 && (asset.starts_with('X') || asset.starts_with('Z')) {
+        &asset[1..]
+    } else {
+        asset
+    };
+    match asset {
+        "XBT" => "BTC",
+        other => other,
+    }
+}
+
+/// Return the normalized order status associated to the status used by Kraken
+pub fn get_order_status(status: &str) -> OrderStatus {
+    match status {
+        "pending" => OrderStatus::Pending,
+        "open" => OrderStatus::Open,
+        "closed" => OrderStatus::Filled,
+        "canceled" => OrderStatus::Canceled,
+        "expired" => OrderStatus::Expired,
+        _ => OrderStatus::Unknown,
+    }
+}
+
+/// Return the OHLC interval (in minutes) used by Kraken
+/// If the interval is not supported, None is returned.
+pub fn get_interval_string(interval: Interval) -> Option<&'static str> {
+    match interval {
+        Interval::OneMinute => Some("1"),
+        Interval::FiveMinutes => Some("5"),
+        Interval::FifteenMinutes => Some("15"),
+        Interval::ThirtyMinutes => Some("30"),
+        Interval::OneHour => Some("60"),
+        Interval::FourHours => Some("240"),
+        Interval::OneDay => Some("1440"),
+        Interval::OneWeek => Some("10080"),
+        Interval::TwoHours => None,
+    }
+}
+
 /// If error array is null, return the result (encoded in a json object)
 /// else return the error string found in array
 pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
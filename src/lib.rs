@@ -62,10 +62,23 @@ extern crate libflate;
 pub mod coinnect;
 pub mod exchange;
 pub mod exchange_bot;
+pub mod event_stream;
+pub mod consolidated_book;
+pub mod router;
+pub mod arbitrage;
+pub mod recorder;
+pub mod replay;
+pub mod transport;
+pub mod paper;
+#[cfg(feature = "mock_server")]
+pub mod mock_server;
 #[allow(deprecated)]
 pub mod error;
 pub mod types;
 pub mod helpers;
+pub mod validation;
+#[cfg(test)]
+mod test_util;
 
 pub mod bitstamp;
 pub mod poloniex;
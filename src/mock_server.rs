@@ -0,0 +1,407 @@
+//! Local HTTP and websocket server emulating the endpoints of an exchange, for tests that must
+//! run offline. Enable it with the `mock_server` feature.
+//!
+//! Point a REST client at `MockServer::url` with `transport::HttpClient::set_base_url` and a
+//! feed at `MockServer::ws_url` with `ConnectionSettings::url`. There are no built-in responses,
+//! each test registers the ones it needs by method and path. Private calls are rejected the way
+//! the exchange would unless they are signed with the credentials of the server.
+//!
+//! The Binance client cannot be pointed at another host (see `Coinnect::new_with_http`), only
+//! its feeds can be served by the mock server.
+
+use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix_web::dev::Server;
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web_actors::ws;
+use data_encoding::{BASE64, HEXLOWER, HEXUPPER};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use crate::error::*;
+use crate::exchange::Exchange;
+
+/// A request received by the server
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: Method,
+    pub path: String,
+    /// Query string without the leading `?`
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl MockRequest {
+    /// Parameters of the query string and of a form body
+    pub fn params(&self) -> HashMap<String, String> {
+        url::form_urlencoded::parse(self.query.as_bytes())
+            .chain(url::form_urlencoded::parse(self.body.as_bytes()))
+            .into_owned()
+            .collect()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|h| h.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn json(body: serde_json::Value) -> MockResponse {
+        MockResponse { status: 200, body: body.to_string() }
+    }
+
+    pub fn with_status(status: u16, body: &str) -> MockResponse {
+        MockResponse { status, body: body.to_string() }
+    }
+}
+
+type ResponseFn = Arc<dyn Fn(&MockRequest) -> MockResponse + Send + Sync>;
+
+enum Route {
+    /// Responses in turn, the last one is repeated
+    Sequence(VecDeque<MockResponse>),
+    Script(ResponseFn),
+}
+
+/// What to answer to a request, scripts are called once the state is unlocked
+enum Answer {
+    Ready(MockResponse),
+    Script(ResponseFn),
+}
+
+impl Route {
+    fn answer(&mut self) -> Answer {
+        match self {
+            Route::Sequence(responses) if responses.len() > 1 => Answer::Ready(responses.pop_front().unwrap()),
+            Route::Sequence(responses) => Answer::Ready(responses.front().cloned().unwrap_or_else(|| MockResponse::with_status(404, ""))),
+            Route::Script(f) => Answer::Script(f.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct MockCredentials {
+    api_key: String,
+    api_secret: String,
+    customer_id: String,
+}
+
+#[derive(Default)]
+struct MockState {
+    base_url: String,
+    credentials: Option<MockCredentials>,
+    routes: HashMap<(Method, String), Route>,
+    requests: Vec<MockRequest>,
+    sessions: Vec<Addr<MockWsSession>>,
+    /// Frames sent to every new websocket connection
+    ws_welcome: Vec<String>,
+    /// Frames sent back when a message containing the pattern is received
+    ws_replies: Vec<(String, Vec<String>)>,
+    ws_received: Vec<String>,
+}
+
+/// Server of one exchange on a random local port
+pub struct MockServer {
+    exchange: Exchange,
+    url: String,
+    state: Arc<Mutex<MockState>>,
+    server: Server,
+}
+
+impl MockServer {
+    /// Start the server, must be called from a running actix system
+    pub fn start(exchange: Exchange) -> Result<MockServer> {
+        let state: Arc<Mutex<MockState>> = Arc::new(Mutex::new(MockState::default()));
+        let app_state = state.clone();
+        let http = HttpServer::new(move || {
+            App::new()
+                .data(ServerData { exchange, state: app_state.clone() })
+                .route("/ws", web::get().to(ws_route))
+                .route("/ws/{tail:.*}", web::get().to(ws_route))
+                .default_service(web::route().to(http_route))
+        })
+            .workers(1)
+            .disable_signals()
+            .bind("127.0.0.1:0")?;
+        let addr = http.addrs().into_iter().next().ok_or_else(|| ErrorKind::ServiceUnavailable("no local address".to_string()))?;
+        let url = format!("http://{}", addr);
+        state.lock().unwrap().base_url = url.clone();
+        Ok(MockServer { exchange, url, state, server: http.run() })
+    }
+
+    pub fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    /// Base url of the REST endpoints, such as `http://127.0.0.1:43127`
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Url of the websocket feed, every path under it is accepted
+    pub fn ws_url(&self) -> String {
+        format!("{}/ws", self.url.replacen("http", "ws", 1))
+    }
+
+    /// Private calls must be signed with these credentials, they are rejected until they are set
+    pub fn set_credentials(&self, api_key: &str, api_secret: &str, customer_id: &str) {
+        self.state.lock().unwrap().credentials = Some(MockCredentials {
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            customer_id: customer_id.to_string(),
+        });
+    }
+
+    /// Answer every request on the path with the response
+    pub fn on(&self, method: Method, path: &str, response: MockResponse) {
+        self.on_sequence(method, path, vec![response]);
+    }
+
+    /// Answer the requests on the path with the responses in turn, then with the last one
+    pub fn on_sequence(&self, method: Method, path: &str, responses: Vec<MockResponse>) {
+        self.state.lock().unwrap().routes.insert((method, path.to_string()), Route::Sequence(responses.into()));
+    }
+
+    /// Answer the requests on the path with the response built by `f`, which may call the
+    /// server back
+    pub fn on_request<F>(&self, method: Method, path: &str, f: F) where F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static {
+        self.state.lock().unwrap().routes.insert((method, path.to_string()), Route::Script(Arc::new(f)));
+    }
+
+    /// Requests received so far, rejected ones included
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Send the frames on every new websocket connection
+    pub fn on_ws_connect(&self, frames: Vec<String>) {
+        self.state.lock().unwrap().ws_welcome = frames;
+    }
+
+    /// Send the frames back when a message containing `pattern` is received, such as a
+    /// subscription
+    pub fn on_ws_message(&self, pattern: &str, frames: Vec<String>) {
+        self.state.lock().unwrap().ws_replies.push((pattern.to_string(), frames));
+    }
+
+    /// Send a frame on every open websocket connection
+    pub fn send_ws(&self, frame: &str) {
+        for session in &self.state.lock().unwrap().sessions {
+            session.do_send(WsFrame(frame.to_string()));
+        }
+    }
+
+    /// Close every open websocket connection, to test reconnections
+    pub fn drop_ws_connections(&self) {
+        for session in self.state.lock().unwrap().sessions.drain(..) {
+            session.do_send(WsClose);
+        }
+    }
+
+    /// Text messages received on the websocket connections
+    pub fn ws_messages(&self) -> Vec<String> {
+        self.state.lock().unwrap().ws_received.clone()
+    }
+
+    pub async fn stop(self) {
+        self.server.stop(true).await;
+    }
+}
+
+#[derive(Clone)]
+struct ServerData {
+    exchange: Exchange,
+    state: Arc<Mutex<MockState>>,
+}
+
+async fn http_route(req: HttpRequest, body: web::Bytes, data: web::Data<ServerData>) -> HttpResponse {
+    let request = MockRequest {
+        method: req.method().clone(),
+        path: req.path().to_string(),
+        query: req.query_string().to_string(),
+        headers: req.headers().iter()
+            .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.as_str().to_lowercase(), v.to_string())))
+            .collect(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    };
+    let answer = {
+        let mut state = data.state.lock().unwrap();
+        state.requests.push(request.clone());
+        match verify(data.exchange, &state.base_url, state.credentials.as_ref(), &request) {
+            Err(rejection) => Answer::Ready(rejection),
+            Ok(()) => match state.routes.get_mut(&(request.method.clone(), request.path.clone())) {
+                Some(route) => route.answer(),
+                None => Answer::Ready(MockResponse::with_status(404, &format!("no mock response for {} {}", request.method, request.path))),
+            },
+        }
+    };
+    let response = match answer {
+        Answer::Ready(response) => response,
+        Answer::Script(f) => f(&request),
+    };
+    HttpResponse::build(StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+        .content_type("application/json")
+        .body(response.body)
+}
+
+async fn ws_route(req: HttpRequest, stream: web::Payload, data: web::Data<ServerData>) -> std::result::Result<HttpResponse, actix_web::Error> {
+    ws::start(MockWsSession { state: data.state.clone() }, &req, stream)
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new(key);
+    mac.input(message);
+    mac.result().code().to_vec()
+}
+
+fn hmac_sha512(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha512>::new(key);
+    mac.input(message);
+    mac.result().code().to_vec()
+}
+
+/// The error the exchange answers to a private call with wrong credentials
+fn rejection(exchange: Exchange) -> MockResponse {
+    match exchange {
+        Exchange::Kraken => MockResponse::with_status(200, r#"{"error":["EAPI:Invalid signature"]}"#),
+        Exchange::Poloniex => MockResponse::with_status(403, r#"{"error":"Invalid API key/secret pair."}"#),
+        Exchange::Bittrex => MockResponse::with_status(200, r#"{"success":false,"message":"INVALID_SIGNATURE","result":null}"#),
+        Exchange::Bitstamp => MockResponse::with_status(403, r#"{"status":"error","reason":"Invalid signature","code":"API0005"}"#),
+        Exchange::Binance => MockResponse::with_status(400, r#"{"code":-1022,"msg":"Signature for this request is not valid."}"#),
+        Exchange::Gdax => MockResponse::with_status(401, r#"{"message":"invalid signature"}"#),
+    }
+}
+
+/// Check the signature of private calls as the exchange does, public calls always pass
+fn verify(exchange: Exchange, base_url: &str, creds: Option<&MockCredentials>, req: &MockRequest) -> std::result::Result<(), MockResponse> {
+    let params = req.params();
+    let private = match exchange {
+        Exchange::Kraken => req.path.starts_with("/0/private/"),
+        Exchange::Poloniex => req.path == "/tradingApi",
+        Exchange::Bittrex => req.path.starts_with("/api/v1.1/") && !req.path.starts_with("/api/v1.1/public/"),
+        Exchange::Bitstamp => req.method == Method::POST,
+        Exchange::Binance => params.contains_key("signature"),
+        Exchange::Gdax => false,
+    };
+    if !private {
+        return Ok(());
+    }
+    let creds = creds.ok_or_else(|| rejection(exchange))?;
+    let secret = creds.api_secret.as_bytes();
+    let valid = match exchange {
+        Exchange::Kraken => {
+            let nonce = params.get("nonce").cloned().unwrap_or_default();
+            let mut sha256 = Sha256::default();
+            sha256.input((nonce + &req.body).as_bytes());
+            let mut message = req.path.as_bytes().to_vec();
+            message.extend(sha256.result());
+            let key = BASE64.decode(secret).unwrap_or_default();
+            req.header("API-Key") == Some(creds.api_key.as_str())
+                && req.header("API-Sign") == Some(BASE64.encode(&hmac_sha512(&key, &message)).as_str())
+        }
+        Exchange::Poloniex => {
+            req.header("Key") == Some(creds.api_key.as_str())
+                && req.header("Sign") == Some(HEXLOWER.encode(&hmac_sha512(secret, req.body.as_bytes())).as_str())
+        }
+        Exchange::Bittrex => {
+            // The whole url is signed
+            let url = format!("{}{}?{}", base_url, req.path, req.query);
+            params.get("apikey") == Some(&creds.api_key)
+                && req.header("apisign") == Some(HEXLOWER.encode(&hmac_sha512(secret, url.as_bytes())).as_str())
+        }
+        Exchange::Bitstamp => {
+            let nonce = params.get("nonce").cloned().unwrap_or_default();
+            let message = nonce + &creds.customer_id + &creds.api_key;
+            params.get("key") == Some(&creds.api_key)
+                && params.get("signature") == Some(&HEXUPPER.encode(&hmac_sha256(secret, message.as_bytes())))
+        }
+        Exchange::Binance => {
+            // The query string up to the signature, followed by the body
+            let query = req.query.rsplitn(2, "&signature=").nth(1).unwrap_or("");
+            let message = query.to_string() + &req.body;
+            req.header("X-MBX-APIKEY") == Some(creds.api_key.as_str())
+                && params.get("signature") == Some(&HEXLOWER.encode(&hmac_sha256(secret, message.as_bytes())))
+        }
+        Exchange::Gdax => true,
+    };
+    if valid { Ok(()) } else { Err(rejection(exchange)) }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct WsFrame(String);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct WsClose;
+
+struct MockWsSession {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Actor for MockWsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let mut state = self.state.lock().unwrap();
+        state.sessions.push(ctx.address());
+        for frame in &state.ws_welcome {
+            ctx.text(frame.clone());
+        }
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        let addr = ctx.address();
+        self.state.lock().unwrap().sessions.retain(|s| *s != addr);
+    }
+}
+
+impl StreamHandler<std::result::Result<ws::Message, ws::ProtocolError>> for MockWsSession {
+    fn handle(&mut self, msg: std::result::Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Text(text)) => {
+                let mut state = self.state.lock().unwrap();
+                for (pattern, frames) in &state.ws_replies {
+                    if text.contains(pattern.as_str()) {
+                        for frame in frames {
+                            ctx.text(frame.clone());
+                        }
+                    }
+                }
+                state.ws_received.push(text);
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(_) => ctx.stop(),
+            _ => (),
+        }
+    }
+}
+
+impl Handler<WsFrame> for MockWsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: WsFrame, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+impl Handler<WsClose> for MockWsSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: WsClose, ctx: &mut Self::Context) {
+        ctx.close(None);
+        ctx.stop();
+    }
+}
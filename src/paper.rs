@@ -0,0 +1,697 @@
+//! Paper trading : orders are matched against the books of the exchange instead of being sent,
+//! with simulated balances, fees and fills. See `Coinnect::new_trading` to switch between paper
+//! and live trading with configuration only.
+
+use actix::{Actor, Context, Handler, Recipient};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::helpers;
+use crate::types::*;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PaperSettings {
+    /// Funds of the account when it is created
+    #[serde(default)]
+    pub balances: HashMap<Currency, f64>,
+    /// Fee rate of the orders filled while resting in the book (0.001 for 0.1%)
+    #[serde(default)]
+    pub maker_fee: f64,
+    /// Fee rate of the orders taking liquidity
+    #[serde(default)]
+    pub taker_fee: f64,
+}
+
+fn decimal(value: f64) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).unwrap_or_else(|_| BigDecimal::zero())
+}
+
+fn currencies(pair: Pair) -> Result<(Currency, Currency)> {
+    let symbol = Symbol::from(pair);
+    match (symbol.base.currency(), symbol.quote.currency()) {
+        (Some(base), Some(quote)) => Ok((base, quote)),
+        _ => Err(ErrorKind::PairUnsupported.into()),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PaperOrder {
+    request: OrderRequest,
+    order: Order,
+    /// Funds locked for the rest of the order : quote currency for a buy, base currency for a
+    /// sell. Market buys lock nothing, they are limited by the free funds when executed.
+    reserved: Amount,
+    /// Stop and take profit orders wait for their stop price
+    triggered: bool,
+    fills: u64,
+}
+
+impl PaperOrder {
+    fn id(&self) -> &str {
+        &self.order.identifier[0]
+    }
+
+    fn remaining(&self) -> Volume {
+        self.order.quantity.clone() - self.order.executed_quantity.clone()
+    }
+
+    fn reserve_currency(&self) -> Currency {
+        let (base, quote) = currencies(self.request.pair).unwrap();
+        match self.request.side {
+            OrderSide::Buy => quote,
+            OrderSide::Sell => base,
+        }
+    }
+}
+
+/// Orders, balances and books of a simulated account.
+/// Orders take the levels of the last book of their pair, resting limit orders are filled at
+/// their price by the levels of later books that cross it, in the order they were placed.
+/// Fees are paid in quote currency.
+#[derive(Debug)]
+pub struct PaperAccount {
+    exchange: Exchange,
+    maker_fee: BigDecimal,
+    taker_fee: BigDecimal,
+    /// Free funds
+    balances: Balances,
+    books: HashMap<Pair, Orderbook>,
+    orders: HashMap<String, PaperOrder>,
+    /// Order ids in the order they were placed
+    order_ids: Vec<String>,
+    fills: Vec<Fill>,
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+}
+
+impl PaperAccount {
+    pub fn new(exchange: Exchange, settings: &PaperSettings) -> PaperAccount {
+        PaperAccount {
+            exchange,
+            maker_fee: decimal(settings.maker_fee),
+            taker_fee: decimal(settings.taker_fee),
+            balances: settings.balances.iter().map(|(c, amount)| (*c, decimal(*amount))).collect(),
+            books: HashMap::new(),
+            orders: HashMap::new(),
+            order_ids: vec![],
+            fills: vec![],
+            recipients: vec![],
+        }
+    }
+
+    /// Order updates, fills and balance updates are sent to the recipient, as by
+    /// `Coinnect::new_user_stream`
+    pub fn add_recipient(&mut self, recipient: Recipient<LiveEventEnveloppe>) {
+        self.recipients.push(recipient);
+    }
+
+    /// Funds available for new orders
+    pub fn balances(&self) -> Balances {
+        self.balances.clone()
+    }
+
+    /// Funds locked in open orders
+    pub fn locked(&self, currency: Currency) -> Amount {
+        self.orders.values()
+            .filter(|o| o.order.status.is_open() && o.reserve_currency() == currency)
+            .fold(BigDecimal::zero(), |total, o| total + o.reserved.clone())
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    /// Last book of the pair, without the levels taken by paper orders since it was received
+    pub fn order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.get(&pair).cloned()
+    }
+
+    pub fn order(&self, id: &str) -> Option<Order> {
+        self.orders.get(id).map(|o| o.order.clone())
+    }
+
+    pub fn open_orders(&self, pair: Pair) -> Vec<Order> {
+        self.order_ids.iter()
+            .map(|id| &self.orders[id].order)
+            .filter(|o| o.pair == pair && o.status.is_open())
+            .cloned()
+            .collect()
+    }
+
+    /// Follow the books of the live events of the exchange, a book that is out of sync is not
+    /// traded against until it is synced again
+    pub fn handle_event(&mut self, event: &LiveEvent) {
+        match event {
+            LiveEvent::LiveOrderbook(book) => self.handle_book(book.clone()),
+            LiveEvent::LiveBookState(state) if state.state != BookState::Synced => {
+                self.books.remove(&state.pair);
+            }
+            _ => (),
+        }
+    }
+
+    /// Replace the book of the pair and execute the open orders it reaches
+    pub fn handle_book(&mut self, mut book: Orderbook) {
+        let (pair, now) = (book.pair, book.timestamp);
+        // Orders take the best levels first whatever the order of the exchange
+        book.asks.sort_by(|a, b| a.0.cmp(&b.0));
+        book.bids.sort_by(|a, b| b.0.cmp(&a.0));
+        self.books.insert(pair, book);
+        let ids: Vec<String> = self.order_ids.iter()
+            .filter(|id| self.orders[*id].order.pair == pair)
+            .cloned()
+            .collect();
+        for id in ids {
+            let (open, triggered, time_in_force) = {
+                let o = &self.orders[&id];
+                (o.order.status.is_open(), o.triggered, o.request.time_in_force)
+            };
+            if !open {
+                continue;
+            }
+            match time_in_force {
+                TimeInForce::GoodTillDate(expiry) if now >= expiry => self.close(&id, OrderStatus::Expired, now),
+                _ if !triggered => self.trigger(&id, now),
+                _ => self.take(&id, true, now),
+            }
+        }
+    }
+
+    /// Place an order, it is executed right away against the last book of its pair
+    pub fn place(&mut self, request: OrderRequest, now: i64) -> Result<Order> {
+        request.check_prices()?;
+        if request.reduce_only {
+            return Err(ErrorKind::Unsupported("reduce-only orders".to_string()).into());
+        }
+        if request.post_only && request.price.is_none() {
+            return Err(ErrorKind::Unsupported("post-only market orders".to_string()).into());
+        }
+        if request.quantity <= BigDecimal::zero() {
+            return Err(ErrorKind::InsufficientOrderSize.into());
+        }
+        let (base, quote) = currencies(request.pair)?;
+        let max_fee = std::cmp::max(self.maker_fee.clone(), self.taker_fee.clone());
+        let (currency, reserved) = match (request.side, &request.price) {
+            (OrderSide::Sell, _) => (base, request.quantity.clone()),
+            (OrderSide::Buy, Some(price)) => (quote, request.quantity.clone() * price.clone() * (BigDecimal::from(1) + max_fee)),
+            (OrderSide::Buy, None) => (quote, BigDecimal::zero()),
+        };
+        let free = self.free(currency);
+        if reserved > free || free.is_zero() {
+            return Err(ErrorKind::InsufficientFunds.into());
+        }
+        let is_stop = match request.kind {
+            OrderKind::Stop | OrderKind::StopLimit | OrderKind::TakeProfit => true,
+            OrderKind::Limit | OrderKind::Market => false,
+        };
+        if request.post_only && !is_stop && self.crosses(&request) {
+            return Err(ErrorKind::ExchangeSpecificError("post-only order would take liquidity".to_string()).into());
+        }
+
+        let id = format!("paper-{}", self.order_ids.len() + 1);
+        let order_type = match (request.side, request.price.is_some()) {
+            (OrderSide::Buy, true) => OrderType::BuyLimit,
+            (OrderSide::Buy, false) => OrderType::BuyMarket,
+            (OrderSide::Sell, true) => OrderType::SellLimit,
+            (OrderSide::Sell, false) => OrderType::SellMarket,
+        };
+        let order = Order {
+            identifier: vec![id.clone()],
+            timestamp: now,
+            pair: request.pair,
+            order_type,
+            status: if is_stop { OrderStatus::Pending } else { OrderStatus::Open },
+            price: request.price.clone(),
+            quantity: request.quantity.clone(),
+            executed_quantity: BigDecimal::zero(),
+        };
+        self.debit(currency, reserved.clone());
+        self.orders.insert(id.clone(), PaperOrder { request, order, reserved, triggered: !is_stop, fills: 0 });
+        self.order_ids.push(id.clone());
+        self.order_update(&id, now);
+        self.balance_update(currency, now);
+        if is_stop {
+            self.trigger(&id, now);
+        } else {
+            self.execute(&id, now);
+        }
+        Ok(self.orders[&id].order.clone())
+    }
+
+    /// Cancel an open order, return false if it is unknown or closed
+    pub fn cancel(&mut self, id: &str, now: i64) -> bool {
+        match self.orders.get(id) {
+            Some(o) if o.order.status.is_open() => {
+                self.close(id, OrderStatus::Canceled, now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn free(&self, currency: Currency) -> Amount {
+        self.balances.get(&currency).cloned().unwrap_or_else(BigDecimal::zero)
+    }
+
+    fn credit(&mut self, currency: Currency, amount: Amount) {
+        let balance = self.free(currency);
+        self.balances.insert(currency, balance + amount);
+    }
+
+    fn debit(&mut self, currency: Currency, amount: Amount) {
+        let balance = self.free(currency);
+        self.balances.insert(currency, balance - amount);
+    }
+
+    /// Levels of the book an order takes from, the best one first
+    fn levels(&self, pair: Pair, side: OrderSide) -> &[(Price, Volume)] {
+        match (self.books.get(&pair), side) {
+            (Some(book), OrderSide::Buy) => &book.asks,
+            (Some(book), OrderSide::Sell) => &book.bids,
+            (None, _) => &[],
+        }
+    }
+
+    /// Return true if the level can be taken by an order limited to `limit`
+    fn reaches(side: OrderSide, limit: Option<&Price>, price: &Price) -> bool {
+        match (side, limit) {
+            (_, None) => true,
+            (OrderSide::Buy, Some(limit)) => price <= limit,
+            (OrderSide::Sell, Some(limit)) => price >= limit,
+        }
+    }
+
+    fn crosses(&self, request: &OrderRequest) -> bool {
+        self.levels(request.pair, request.side).first()
+            .map_or(false, |(price, _)| PaperAccount::reaches(request.side, request.price.as_ref(), price))
+    }
+
+    /// Volume of the levels an order limited to `limit` can take
+    fn liquidity(&self, pair: Pair, side: OrderSide, limit: Option<&Price>) -> Volume {
+        self.levels(pair, side).iter()
+            .take_while(|(price, _)| PaperAccount::reaches(side, limit, price))
+            .fold(BigDecimal::zero(), |total, (_, volume)| total + volume.clone())
+    }
+
+    /// Execute a stop or take profit order once the book reaches its stop price
+    fn trigger(&mut self, id: &str, now: i64) {
+        let (pair, side, kind, stop_price) = {
+            let o = &self.orders[id].request;
+            (o.pair, o.side, o.kind, o.stop_price.clone().unwrap_or_else(BigDecimal::zero))
+        };
+        let best_bid = self.levels(pair, OrderSide::Sell).first().map(|l| l.0.clone());
+        let best_ask = self.levels(pair, OrderSide::Buy).first().map(|l| l.0.clone());
+        let triggered = match (kind, side) {
+            (OrderKind::TakeProfit, OrderSide::Buy) => best_ask.map_or(false, |ask| ask <= stop_price),
+            (OrderKind::TakeProfit, OrderSide::Sell) => best_bid.map_or(false, |bid| bid >= stop_price),
+            (_, OrderSide::Buy) => best_ask.map_or(false, |ask| ask >= stop_price),
+            (_, OrderSide::Sell) => best_bid.map_or(false, |bid| bid <= stop_price),
+        };
+        if triggered {
+            let o = self.orders.get_mut(id).unwrap();
+            o.triggered = true;
+            o.order.status = OrderStatus::Open;
+            self.order_update(id, now);
+            self.execute(id, now);
+        }
+    }
+
+    /// Execute an order that just became active, what a market, IOC or FOK order cannot take
+    /// is expired
+    fn execute(&mut self, id: &str, now: i64) {
+        let (request, remaining) = {
+            let o = &self.orders[id];
+            (o.request.clone(), o.remaining())
+        };
+        match request.time_in_force {
+            TimeInForce::GoodTillDate(expiry) if now >= expiry => return self.close(id, OrderStatus::Expired, now),
+            TimeInForce::FillOrKill if self.liquidity(request.pair, request.side, request.price.as_ref()) < remaining => {
+                return self.close(id, OrderStatus::Expired, now);
+            }
+            _ => (),
+        }
+        if request.post_only && self.crosses(&request) {
+            return self.close(id, OrderStatus::Rejected, now);
+        }
+        self.take(id, false, now);
+        let keeps_resting = request.price.is_some() && match request.time_in_force {
+            TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill => false,
+            _ => true,
+        };
+        if self.orders[id].order.status.is_open() && !keeps_resting {
+            self.close(id, OrderStatus::Expired, now);
+        }
+    }
+
+    /// Take the levels of the book reached by an order. A taker pays the price of the levels, a
+    /// resting order (`maker`) is filled at its own price.
+    fn take(&mut self, id: &str, maker: bool, now: i64) {
+        let (pair, side, limit, mut remaining) = {
+            let o = &self.orders[id];
+            (o.request.pair, o.request.side, o.request.price.clone(), o.remaining())
+        };
+        let fee_rate = if maker { self.maker_fee.clone() } else { self.taker_fee.clone() };
+        while remaining > BigDecimal::zero() {
+            let (level_price, volume) = match self.levels(pair, side).first() {
+                Some(level) if PaperAccount::reaches(side, limit.as_ref(), &level.0) => level.clone(),
+                _ => break,
+            };
+            let price = if maker { limit.clone().unwrap_or(level_price) } else { level_price };
+            let mut quantity = std::cmp::min(remaining.clone(), volume);
+            if side == OrderSide::Buy && limit.is_none() {
+                // Market buys are limited by the free funds
+                let (_, quote) = currencies(pair).unwrap();
+                let affordable = self.free(quote) / (price.clone() * (BigDecimal::from(1) + fee_rate.clone()));
+                quantity = std::cmp::min(quantity, helpers::floor_to_increment(&affordable, &helpers::decimals_to_increment(8)));
+                if quantity <= BigDecimal::zero() {
+                    break;
+                }
+            }
+            self.consume(pair, side, &quantity);
+            self.fill(id, price, quantity.clone(), &fee_rate, now);
+            remaining = remaining - quantity;
+        }
+    }
+
+    /// Remove the volume taken from the best level of the book
+    fn consume(&mut self, pair: Pair, side: OrderSide, quantity: &Volume) {
+        if let Some(book) = self.books.get_mut(&pair) {
+            let levels = match side {
+                OrderSide::Buy => &mut book.asks,
+                OrderSide::Sell => &mut book.bids,
+            };
+            if let Some(level) = levels.first_mut() {
+                level.1 = level.1.clone() - quantity.clone();
+                if level.1 <= BigDecimal::zero() {
+                    levels.remove(0);
+                }
+            }
+        }
+    }
+
+    fn fill(&mut self, id: &str, price: Price, quantity: Volume, fee_rate: &BigDecimal, now: i64) {
+        let value = price.clone() * quantity.clone();
+        let fee = value.clone() * fee_rate.clone();
+        let o = self.orders.get_mut(id).unwrap();
+        let (pair, side) = (o.request.pair, o.request.side);
+        let (base, quote) = currencies(pair).unwrap();
+        o.order.executed_quantity = o.order.executed_quantity.clone() + quantity.clone();
+        o.fills += 1;
+        let trade_id = format!("{}-{}", id, o.fills);
+        match side {
+            OrderSide::Buy => {
+                let cost = value + fee.clone();
+                let from_reserved = std::cmp::min(o.reserved.clone(), cost.clone());
+                o.reserved = o.reserved.clone() - from_reserved.clone();
+                self.debit(quote, cost - from_reserved);
+                self.credit(base, quantity.clone());
+            }
+            OrderSide::Sell => {
+                o.reserved = o.reserved.clone() - quantity.clone();
+                self.credit(quote, value - fee.clone());
+            }
+        }
+        let fill = Fill { event_ms: now, order_id: id.to_string(), trade_id, pair, side, price, quantity, fee: Some(fee), fee_currency: Some(quote) };
+        self.fills.push(fill.clone());
+        self.emit(LiveEvent::LiveFill(fill));
+
+        let filled = self.orders[id].remaining() <= BigDecimal::zero();
+        if filled {
+            self.close(id, OrderStatus::Filled, now);
+        } else {
+            self.orders.get_mut(id).unwrap().order.status = OrderStatus::PartiallyFilled;
+            self.order_update(id, now);
+        }
+        self.balance_update(base, now);
+        self.balance_update(quote, now);
+    }
+
+    /// Close an order and release its locked funds
+    fn close(&mut self, id: &str, status: OrderStatus, now: i64) {
+        let o = self.orders.get_mut(id).unwrap();
+        o.order.status = status;
+        let released = std::mem::replace(&mut o.reserved, BigDecimal::zero());
+        let currency = o.reserve_currency();
+        self.credit(currency, released);
+        self.order_update(id, now);
+        self.balance_update(currency, now);
+    }
+
+    fn order_update(&self, id: &str, now: i64) {
+        let o = &self.orders[id];
+        self.emit(LiveEvent::LiveOrderUpdate(OrderUpdate {
+            event_ms: now,
+            order_id: o.id().to_string(),
+            pair: o.order.pair,
+            status: o.order.status,
+            side: Some(o.request.side),
+            price: o.order.price.clone(),
+            quantity: Some(o.order.quantity.clone()),
+            executed_quantity: Some(o.order.executed_quantity.clone()),
+        }));
+    }
+
+    fn balance_update(&self, currency: Currency, now: i64) {
+        self.emit(LiveEvent::LiveBalanceUpdate(BalanceUpdate { event_ms: now, currency, free: self.free(currency), locked: self.locked(currency) }));
+    }
+
+    fn emit(&self, event: LiveEvent) {
+        for r in &self.recipients {
+            if let Err(e) = r.do_send(LiveEventEnveloppe(self.exchange, event.clone())) {
+                trace!("PaperAccount : cannot send {:?} : {}", event, e);
+            }
+        }
+    }
+}
+
+/// An `ExchangeApi` trading a `PaperAccount`. Books are polled from the api of the exchange when
+/// orders are placed or queried, until live events are received through `live_recipient`.
+/// Trades, candles, symbols and markets are those of the exchange api.
+#[derive(Debug)]
+pub struct PaperExchange {
+    exchange: Exchange,
+    account: Arc<Mutex<PaperAccount>>,
+    api: Option<Box<dyn ExchangeApi + Send>>,
+    live: bool,
+}
+
+impl PaperExchange {
+    /// Without an exchange api, books must come from live events or a recorded feed
+    pub fn new(exchange: Exchange, settings: &PaperSettings, api: Option<Box<dyn ExchangeApi + Send>>) -> PaperExchange {
+        PaperExchange { exchange, account: Arc::new(Mutex::new(PaperAccount::new(exchange, settings))), api, live: false }
+    }
+
+    /// Return the recipient to give to the bot of the exchange (`Coinnect::new_stream`) or to a
+    /// `FeedReplay`, books are no longer polled once it is called.
+    /// Must be called from a running actix system.
+    pub fn live_recipient(&mut self) -> Recipient<LiveEventEnveloppe> {
+        self.live = true;
+        PaperActor { exchange: self.exchange, account: self.account.clone() }.start().recipient()
+    }
+
+    /// See `PaperAccount::add_recipient`
+    pub fn add_recipient(&self, recipient: Recipient<LiveEventEnveloppe>) {
+        self.account.lock().unwrap().add_recipient(recipient);
+    }
+
+    /// The simulated account, shared with the live events
+    pub fn account(&self) -> Arc<Mutex<PaperAccount>> {
+        self.account.clone()
+    }
+
+    async fn refresh(&mut self, pair: Pair) -> Result<()> {
+        if self.live {
+            return Ok(());
+        }
+        if let Some(api) = self.api.as_mut() {
+            let book = api.orderbook(pair).await?;
+            self.account.lock().unwrap().handle_book(book);
+        }
+        Ok(())
+    }
+
+    fn api(&mut self) -> Result<&mut Box<dyn ExchangeApi + Send>> {
+        self.api.as_mut().ok_or_else(|| ErrorKind::Unsupported("market data without an exchange api".to_string()).into())
+    }
+
+    fn book(&self, pair: Pair) -> Result<Orderbook> {
+        self.account.lock().unwrap().order_book(pair)
+            .ok_or_else(|| ErrorKind::ServiceUnavailable(format!("no order book for {:?}", pair)).into())
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for PaperExchange {
+    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
+        if !self.live && self.api.is_some() {
+            return self.api()?.ticker(pair).await;
+        }
+        let book = self.book(pair)?;
+        let last_trade_price = self.account.lock().unwrap().fills().iter().rev()
+            .find(|f| f.pair == pair)
+            .map(|f| f.price.clone());
+        match (book.asks.first(), book.bids.first()) {
+            (Some(ask), Some(bid)) => Ok(Ticker {
+                timestamp: book.timestamp,
+                pair,
+                last_trade_price: last_trade_price.or_else(|| book.avg_price()).unwrap(),
+                lowest_ask: ask.0.clone(),
+                highest_bid: bid.0.clone(),
+                volume: None,
+            }),
+            _ => Err(ErrorKind::ServiceUnavailable(format!("empty order book for {:?}", pair)).into()),
+        }
+    }
+
+    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
+        self.refresh(pair).await?;
+        self.book(pair)
+    }
+
+    async fn add_order(&mut self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+        self.place_order(OrderRequest::from((order_type, pair, quantity, price))).await
+    }
+
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        self.refresh(order.pair).await?;
+        let now = helpers::get_unix_timestamp_ms();
+        let placed = self.account.lock().unwrap().place(order, now)?;
+        Ok(OrderInfo { timestamp: now, identifier: placed.identifier })
+    }
+
+    async fn balances(&mut self) -> Result<Balances> {
+        Ok(self.account.lock().unwrap().balances())
+    }
+
+    async fn cancel_order(&mut self, _pair: Pair, order: &OrderInfo) -> Result<OrderInfo> {
+        let now = helpers::get_unix_timestamp_ms();
+        let mut account = self.account.lock().unwrap();
+        let identifier = order.identifier.iter().filter(|id| account.cancel(id, now)).cloned().collect();
+        Ok(OrderInfo { timestamp: now, identifier })
+    }
+
+    async fn order_status(&mut self, pair: Pair, order: &OrderInfo) -> Result<Order> {
+        self.refresh(pair).await?;
+        let id = order.identifier.first().ok_or_else(|| ErrorKind::MissingField("identifier".to_string()))?;
+        self.account.lock().unwrap().order(id)
+            .ok_or_else(|| ErrorKind::ExchangeSpecificError(format!("unknown order {}", id)).into())
+    }
+
+    async fn open_orders(&mut self, pair: Pair) -> Result<Vec<Order>> {
+        self.refresh(pair).await?;
+        Ok(self.account.lock().unwrap().open_orders(pair))
+    }
+
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>> {
+        self.api()?.recent_trades(pair, since).await
+    }
+
+    async fn candles(&mut self, pair: Pair, interval: Interval, start: Option<i64>, end: Option<i64>) -> Result<Vec<Candle>> {
+        self.api()?.candles(pair, interval, start, end).await
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        self.api()?.symbols().await
+    }
+
+    /// Markets of the exchange with the fees of the paper account
+    async fn markets(&mut self) -> Result<Markets> {
+        let mut markets = self.api()?.markets().await?;
+        let account = self.account.lock().unwrap();
+        for market in markets.values_mut() {
+            market.fee_tier = Some(FeeTier { maker: account.maker_fee.clone(), taker: account.taker_fee.clone() });
+        }
+        Ok(markets)
+    }
+}
+
+/// Applies the live events of the exchange to a paper account
+struct PaperActor {
+    exchange: Exchange,
+    account: Arc<Mutex<PaperAccount>>,
+}
+
+impl Actor for PaperActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for PaperActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        if msg.0 == self.exchange {
+            self.account.lock().unwrap().handle_event(&msg.1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod paper_tests {
+    use super::*;
+    use crate::test_util::{book, decimal};
+
+    fn account(balances: &[(Currency, f64)]) -> PaperAccount {
+        let settings = PaperSettings { balances: balances.iter().cloned().collect(), maker_fee: 0.001, taker_fee: 0.002 };
+        PaperAccount::new(Exchange::Kraken, &settings)
+    }
+
+    #[test]
+    fn should_fill_resting_limit_orders_as_maker() {
+        let mut account = account(&[(Currency::EUR, 1000.0)]);
+        account.handle_book(book(Pair::BTC_EUR, &[("101", "1")], &[("99", "1")]));
+        let order = account.place(OrderRequest::limit(OrderSide::Buy, Pair::BTC_EUR, decimal("2"), decimal("100")), 1).unwrap();
+        assert_eq!(order.status, OrderStatus::Open);
+        // 2 * 100 with the highest fee
+        assert_eq!(account.locked(Currency::EUR), decimal("200.4"));
+
+        account.handle_book(book(Pair::BTC_EUR, &[("100", "1.5"), ("102", "1")], &[("99", "1")]));
+        let order = account.order("paper-1").unwrap();
+        assert_eq!(order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.executed_quantity, decimal("1.5"));
+
+        account.handle_book(book(Pair::BTC_EUR, &[("99", "3")], &[("98", "1")]));
+        assert_eq!(account.order("paper-1").unwrap().status, OrderStatus::Filled);
+        let balances = account.balances();
+        assert_eq!(balances[&Currency::BTC], decimal("2"));
+        // 200 and 0.2 of maker fees
+        assert_eq!(balances[&Currency::EUR], decimal("799.8"));
+        assert_eq!(account.locked(Currency::EUR), BigDecimal::zero());
+        assert_eq!(account.fills().iter().map(|f| f.price.clone()).collect::<Vec<_>>(), vec![decimal("100"), decimal("100")]);
+        assert!(account.open_orders(Pair::BTC_EUR).is_empty());
+    }
+
+    #[test]
+    fn should_take_the_best_levels_of_live_books() {
+        let mut account = account(&[(Currency::EUR, 1000.0)]);
+        account.handle_book(book(Pair::BTC_EUR, &[("103", "1"), ("101", "1"), ("102", "1")], &[("99", "1")]));
+
+        // Between the best and the worst ask
+        let order = account.place(OrderRequest::limit(OrderSide::Buy, Pair::BTC_EUR, decimal("2"), decimal("102")), 1).unwrap();
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(account.fills().iter().map(|f| f.price.clone()).collect::<Vec<_>>(), vec![decimal("101"), decimal("102")]);
+    }
+
+    #[test]
+    fn should_take_levels_with_market_orders() {
+        let mut account = account(&[(Currency::BTC, 1.0)]);
+        account.handle_book(book(Pair::BTC_EUR, &[("101", "1")], &[("100", "0.5"), ("99", "1")]));
+        let order = account.place(OrderRequest::market(OrderSide::Sell, Pair::BTC_EUR, decimal("1")), 1).unwrap();
+        assert_eq!(order.status, OrderStatus::Filled);
+        let balances = account.balances();
+        assert_eq!(balances[&Currency::BTC], BigDecimal::zero());
+        // 50 + 49.5 less 0.2% of taker fees
+        assert_eq!(balances[&Currency::EUR], decimal("99.301"));
+        // Both levels were taken until the next book
+        assert_eq!(account.order_book(Pair::BTC_EUR).unwrap().bids, vec![(decimal("99"), decimal("0.5"))]);
+
+        let rejected = account.place(OrderRequest::market(OrderSide::Sell, Pair::BTC_EUR, decimal("1")), 2);
+        match rejected {
+            Err(Error(ErrorKind::InsufficientFunds, _)) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -4,9 +4,8 @@
 use hmac::{Hmac, Mac};
 use sha2::Sha512;
 
-use hyper::{Client, Uri, Request, Body, Method};
+use hyper::{Request, Body, Method};
 use hyper::header::{CONTENT_TYPE};
-use hyper_tls::HttpsConnector;
 
 use data_encoding::HEXLOWER;
 
@@ -16,16 +15,16 @@ use serde_json::value::Map;
 use std::collections::HashMap;
 use std::thread;
 use std::time::Duration;
-
-use futures::{TryFutureExt};
+use std::sync::Arc;
 
 use crate::error::*;
 use crate::helpers::{self, json};
+use crate::types::{OrderType, Price, Volume};
 
 use crate::exchange::Exchange;
 use crate::coinnect::Credentials;
 
-use hyper::client::HttpConnector;
+use crate::transport::{self, HttpClient, HttpTransport};
 use bytes::buf::BufExt as _;
 
 const KEY_HEADER: &str = "Key";
@@ -66,8 +65,13 @@ pub struct PoloniexApi {
     last_request: i64, // unix timestamp in ms, to avoid ban
     api_key: String,
     api_secret: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    transport: Arc<dyn HttpTransport>,
+    base_url: String,
     burst: bool,
+    /// Type, quantity and price of the orders placed by this client, by order number. Orders
+    /// closed without any trade are only found there. Entries are removed once `order_status`
+    /// returns the order closed.
+    placed: HashMap<String, (OrderType, Volume, Option<Price>)>,
 }
 
 impl PoloniexApi {
@@ -77,16 +81,14 @@ impl PoloniexApi {
             return Err(ErrorKind::InvalidConfigType(Exchange::Poloniex, creds.exchange()).into());
         }
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
-
-
         Ok(PoloniexApi {
             last_request: 0,
             api_key: creds.get("api_key").unwrap_or_default(),
             api_secret: creds.get("api_secret").unwrap_or_default(),
-            http_client: ssl,
+            transport: transport::default_transport(),
+            base_url: "https://poloniex.com".to_string(),
             burst: false,
+            placed: HashMap::new(),
         })
     }
 
@@ -99,6 +101,18 @@ impl PoloniexApi {
         self.burst = burst
     }
 
+    pub(crate) fn remember_order(&mut self, order_number: &str, order: (OrderType, Volume, Option<Price>)) {
+        self.placed.insert(order_number.to_string(), order);
+    }
+
+    pub(crate) fn placed_order(&self, order_number: &str) -> Option<(OrderType, Volume, Option<Price>)> {
+        self.placed.get(order_number).cloned()
+    }
+
+    pub(crate) fn forget_order(&mut self, order_number: &str) {
+        self.placed.remove(order_number);
+    }
+
     fn block_or_continue(&self) {
         if !self.burst {
             let threshold: u64 = 167; // 6 requests/sec = 1/6*1000
@@ -113,18 +127,13 @@ impl PoloniexApi {
     async fn public_query(&mut self, method: &str, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
         let mut params = params.clone();
         helpers::strip_empties(&mut params);
-        let string = "https://poloniex.com/public?command=".to_string() + method + "&" + &helpers::url_encode_hashmap(&params);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
+        let url = self.base_url.clone() + "/public?command=" + method + "&" + &helpers::url_encode_hashmap(&params);
 
         self.block_or_continue();
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.get(&url).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
-
-        if method == "returnChartData" {
-            return json::deserialize_json_array_r(reader);
-        }
-        json::deserialize_json_r(reader)
+        let reader = resp.body.reader();
+        json::deserialize_json_or_array_r(reader)
     }
 
     async fn private_query(&mut self, method: &str, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
@@ -144,20 +153,17 @@ impl PoloniexApi {
 
         let req: Result<Request<Body>> = Request::builder()
             .method(Method::POST)
-            .uri("https://poloniex.com/tradingApi")
+            .uri(format!("{}/tradingApi", self.base_url).as_str())
             .header(KEY_HEADER, self.api_key.as_str())
             .header(SIGN_HEADER, sign)
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
             .body(post_data.into())
             .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
         let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
+        let resp = self.transport.send(req2).await?;
         self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
-        if method == "returnChartData" {
-            return json::deserialize_json_array_r(reader);
-        }
-        json::deserialize_json_r(reader)
+        let reader = resp.body.reader();
+        json::deserialize_json_or_array_r(reader)
     }
 
     /// Sample output :
@@ -760,6 +766,16 @@ impl PoloniexApi {
     }
 }
 
+impl HttpClient for PoloniexApi {
+    fn set_transport(&mut self, transport: Arc<dyn HttpTransport>) {
+        self.transport = transport;
+    }
+
+    fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = transport::trim_base_url(base_url);
+    }
+}
+
 #[cfg(test)]
 mod poloniex_api_tests {
     use super::*;
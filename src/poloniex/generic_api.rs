@@ -2,8 +2,8 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Poloniex offers.
 
-use crate::exchange::{ExchangeApi};
-use crate::poloniex::api::PoloniexApi;
+use crate::exchange::{ExchangeApi, Exchange};
+use crate::poloniex::api::{PoloniexApi, PlaceOrderOption};
 
 use bigdecimal::BigDecimal;
 use std::str::FromStr;
@@ -12,23 +12,22 @@ use crate::error::*;
 use crate::types::*;
 use crate::poloniex::utils;
 use crate::helpers;
+use crate::utils::pair_or;
 use async_trait::async_trait;
+use serde_json::Value;
 
 #[async_trait]
 impl ExchangeApi for PoloniexApi {
     async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Poloniex, &pair)?;
         let raw_response = self.return_ticker().await?;
 
         let result = utils::parse_result(&raw_response)?;
 
-        let price = helpers::from_json_bigdecimal(&result[*pair_name]["last"], "last")?;
-        let ask = helpers::from_json_bigdecimal(&result[*pair_name]["lowestAsk"], "lowestAsk")?;
-        let bid = helpers::from_json_bigdecimal(&result[*pair_name]["highestBid"], "highestBid")?;
-        let vol = helpers::from_json_bigdecimal(&result[*pair_name]["quoteVolume"], "quoteVolume")?;
+        let price = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["last"], "last")?;
+        let ask = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["lowestAsk"], "lowestAsk")?;
+        let bid = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["highestBid"], "highestBid")?;
+        let vol = helpers::from_json_bigdecimal(&result[pair_name.as_str()]["quoteVolume"], "quoteVolume")?;
 
         Ok(Ticker {
             timestamp: helpers::get_unix_timestamp_ms(),
@@ -41,10 +40,7 @@ impl ExchangeApi for PoloniexApi {
     }
 
     async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Poloniex, &pair)?;
         let raw_response = self.return_order_book(pair_name, "1000").await?; // 1000 entries max
 
         let result = utils::parse_result(&raw_response)?;
@@ -84,10 +80,7 @@ impl ExchangeApi for PoloniexApi {
     }
 
     async fn add_order(&mut self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+        let pair_name = &pair_or(Exchange::Poloniex, &pair)?;
 
         // The trick is to use minimal (0.0) and "maximum" (999..) price to simulate market order
         let raw_response = match order_type {
@@ -99,7 +92,7 @@ impl ExchangeApi for PoloniexApi {
 
                 self.buy(
                     pair_name,
-                    &price.unwrap().to_string(),
+                    &price.as_ref().unwrap().to_string(),
                     &quantity.to_string(),
                     None,
                 ).await
@@ -117,7 +110,7 @@ impl ExchangeApi for PoloniexApi {
 
                 self.sell(
                     pair_name,
-                    &price.unwrap().to_string(),
+                    &price.as_ref().unwrap().to_string(),
                     &quantity.to_string(),
                     None,
                 ).await
@@ -126,15 +119,71 @@ impl ExchangeApi for PoloniexApi {
         }?;
 
         let result = utils::parse_result(&raw_response)?;
+        let order_number = result["orderNumber"]
+            .as_f64()
+            .ok_or_else(|| ErrorKind::MissingField("orderNumber".to_string()))?
+            .to_string();
+        self.remember_order(&order_number, (order_type, quantity, price));
+
+        Ok(OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![order_number],
+        })
+    }
+
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        order.check_prices()?;
+        if order.reduce_only {
+            return Err(ErrorKind::Unsupported("reduce-only orders".to_string()).into());
+        }
+        if order.client_order_id.is_some() {
+            return Err(ErrorKind::Unsupported("client order ids".to_string()).into());
+        }
+
+        // Poloniex accepts a single option per order
+        let option = match (order.time_in_force, order.post_only) {
+            (TimeInForce::GoodTillCancel, false) => None,
+            (TimeInForce::GoodTillCancel, true) => Some(PlaceOrderOption::PostOnly),
+            (TimeInForce::ImmediateOrCancel, false) => Some(PlaceOrderOption::ImmediateOrCancel),
+            (TimeInForce::FillOrKill, false) => Some(PlaceOrderOption::FillOrKill),
+            (TimeInForce::GoodTillDate(_), _) => {
+                return Err(ErrorKind::Unsupported("time in force GoodTillDate".to_string()).into())
+            }
+            (_, true) => {
+                return Err(ErrorKind::Unsupported("post-only with a time in force".to_string()).into())
+            }
+        };
+
+        let pair_name = &pair_or(Exchange::Poloniex, &order.pair)?;
+        // Market orders use the same extreme prices as add_order
+        let rate = match (order.kind, order.side) {
+            (OrderKind::Limit, _) => order.price.as_ref().map(|p| p.to_string()).unwrap_or_default(),
+            (OrderKind::Market, OrderSide::Buy) => "9999999999999999999".to_string(),
+            (OrderKind::Market, OrderSide::Sell) => "0.0".to_string(),
+            (kind, _) => return Err(ErrorKind::Unsupported(format!("{:?} orders", kind)).into()),
+        };
+
+        let raw_response = match order.side {
+            OrderSide::Buy => self.buy(pair_name, &rate, &order.quantity.to_string(), option).await,
+            OrderSide::Sell => self.sell(pair_name, &rate, &order.quantity.to_string(), option).await,
+        }?;
+
+        let result = utils::parse_result(&raw_response)?;
+        let order_number = result["orderNumber"]
+            .as_f64()
+            .ok_or_else(|| ErrorKind::MissingField("orderNumber".to_string()))?
+            .to_string();
+        let order_type = match (order.kind, order.side) {
+            (OrderKind::Limit, OrderSide::Buy) => OrderType::BuyLimit,
+            (OrderKind::Limit, OrderSide::Sell) => OrderType::SellLimit,
+            (_, OrderSide::Buy) => OrderType::BuyMarket,
+            (_, OrderSide::Sell) => OrderType::SellMarket,
+        };
+        self.remember_order(&order_number, (order_type, order.quantity, order.price));
 
         Ok(OrderInfo {
             timestamp: helpers::get_unix_timestamp_ms(),
-            identifier: vec![
-                result["orderNumber"]
-                    .as_f64()
-                    .ok_or_else(|| ErrorKind::MissingField("orderNumber".to_string()))?
-                    .to_string(),
-            ],
+            identifier: vec![order_number],
         })
     }
 
@@ -154,4 +203,254 @@ impl ExchangeApi for PoloniexApi {
         }
         Ok(balances)
     }
+
+    async fn cancel_order(&mut self, _pair: Pair, order: &OrderInfo) -> Result<OrderInfo> {
+        let mut canceled = Vec::new();
+
+        for order_number in &order.identifier {
+            let raw_response = self.cancel_order(order_number).await?;
+            utils::parse_result(&raw_response)?;
+            canceled.push(order_number.clone());
+        }
+
+        Ok(OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: canceled,
+        })
+    }
+
+    /// Poloniex only knows about orders that are still open or that have been (partially) executed.
+    /// Closed orders are completed with what this client remembers of the orders it placed, until
+    /// they are returned closed once : other closed orders have an `Unknown` status and their
+    /// executed quantity, and the ones canceled before any execution return an error.
+    async fn order_status(&mut self, pair: Pair, order: &OrderInfo) -> Result<Order> {
+        let order_number = order.identifier
+            .first()
+            .ok_or_else(|| ErrorKind::MissingField("orderNumber".to_string()))?;
+
+        let open_orders = ExchangeApi::open_orders(self, pair).await?;
+        if let Some(open) = open_orders.into_iter().find(|o| o.identifier.contains(order_number)) {
+            return Ok(open);
+        }
+
+        let placed = self.placed_order(order_number);
+        let raw_response = self.return_order_trades(order_number).await?;
+        let trades = match utils::parse_result(&raw_response) {
+            Ok(result) => result["data"]
+                .as_array()
+                .cloned()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?,
+            // Orders closed without any trade are not found
+            Err(_) if placed.is_some() => vec![],
+            Err(e) => return Err(e),
+        };
+
+        let mut executed_quantity = Volume::from(0);
+        for trade in &trades {
+            executed_quantity += helpers::from_json_bigdecimal(&trade["amount"], "amount")?;
+        }
+        let (order_type, quantity, placed_price, status) = match (placed, trades.first()) {
+            // Closed orders are either filled or canceled
+            (Some((order_type, quantity, price)), _) => {
+                let status = if executed_quantity < quantity { OrderStatus::Canceled } else { OrderStatus::Filled };
+                self.forget_order(order_number);
+                (order_type, quantity, price, status)
+            }
+            (None, Some(first_trade)) => {
+                let order_type = match first_trade["type"].as_str() {
+                    Some("buy") => OrderType::BuyLimit,
+                    Some("sell") => OrderType::SellLimit,
+                    _ => return Err(ErrorKind::InvalidFieldFormat("type".to_string()).into()),
+                };
+                // The quantity of orders placed by another client is unknown, filled or not
+                (order_type, executed_quantity.clone(), None, OrderStatus::Unknown)
+            }
+            (None, None) => return Err(ErrorKind::MissingField("data".to_string()).into()),
+        };
+        let (timestamp, price) = match trades.first() {
+            Some(first_trade) => (utils::parse_date(first_trade["date"].as_str().unwrap_or_default())?,
+                                  Some(helpers::from_json_bigdecimal(&first_trade["rate"], "rate")?)),
+            None => (order.timestamp, placed_price),
+        };
+
+        Ok(Order {
+            identifier: order.identifier.clone(),
+            timestamp,
+            pair,
+            order_type,
+            status,
+            price,
+            quantity,
+            executed_quantity,
+        })
+    }
+
+    async fn open_orders(&mut self, pair: Pair) -> Result<Vec<Order>> {
+        let pair_name = &pair_or(Exchange::Poloniex, &pair)?;
+
+        let raw_response = self.return_open_orders(pair_name).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let open = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut orders = Vec::new();
+        for order_obj in open {
+            orders.push(parse_open_order(pair, order_obj)?);
+        }
+        Ok(orders)
+    }
+
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>> {
+        let pair_name = &pair_or(Exchange::Poloniex, &pair)?;
+
+        // Without a range, Poloniex returns the last 200 trades
+        let (start, end) = match since {
+            Some(ms) => ((ms / 1000).to_string(), (helpers::get_unix_timestamp_ms() / 1000).to_string()),
+            None => ("".to_string(), "".to_string()),
+        };
+        let raw_response = self.return_trade_history(pair_name, &start, &end).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let trade_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut trades = Vec::new();
+        // Most recent trades come first
+        for trade in trade_array.iter().rev() {
+            trades.push(Trade {
+                id: trade.get("tradeID").map(|id| id.to_string()),
+                timestamp: utils::parse_date(trade["date"].as_str().unwrap_or_default())?,
+                pair,
+                price: helpers::from_json_bigdecimal(&trade["rate"], "rate")?,
+                volume: helpers::from_json_bigdecimal(&trade["amount"], "amount")?,
+                tt: trade["type"].as_str().unwrap_or_default().to_string().into(),
+            });
+        }
+        Ok(trades)
+    }
+
+    async fn candles(&mut self,
+                     pair: Pair,
+                     interval: Interval,
+                     start: Option<i64>,
+                     end: Option<i64>)
+                     -> Result<Vec<Candle>> {
+        let pair_name = &pair_or(Exchange::Poloniex, &pair)?;
+        let period = match utils::get_interval_string(interval) {
+            Some(p) => p,
+            None => return Err(ErrorKind::Unsupported(format!("{:?} candles", interval)).into()),
+        };
+
+        // returnChartData requires a range, default to the last 500 candles
+        let end_secs = end.unwrap_or_else(helpers::get_unix_timestamp_ms) / 1000;
+        let start_secs = match start {
+            Some(ms) => ms / 1000,
+            None => end_secs - 500 * interval.as_secs(),
+        };
+        let raw_response = self.return_chart_data(pair_name,
+                                                  &start_secs.to_string(),
+                                                  &end_secs.to_string(),
+                                                  period).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let chart_array = result["data"]
+            .as_array()
+            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["data"])))?;
+
+        let mut candles = Vec::new();
+        for chart in chart_array {
+            let date = chart["date"]
+                .as_i64()
+                .ok_or_else(|| ErrorKind::InvalidFieldFormat("date".to_string()))?;
+
+            // Poloniex volume is expressed in the pair's counter currency, quoteVolume is the
+            // volume of the traded currency
+            candles.push(Candle {
+                timestamp: date * 1000,
+                pair,
+                interval,
+                open: helpers::from_json_bigdecimal(&chart["open"], "open")?,
+                high: helpers::from_json_bigdecimal(&chart["high"], "high")?,
+                low: helpers::from_json_bigdecimal(&chart["low"], "low")?,
+                close: helpers::from_json_bigdecimal(&chart["close"], "close")?,
+                volume: helpers::from_json_bigdecimal(&chart["quoteVolume"], "quoteVolume")?,
+            });
+        }
+        Ok(candles)
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        let raw_response = self.return_ticker().await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut symbols = SymbolMap::new();
+        for name in result.keys() {
+            // Poloniex writes QUOTE_BASE, e.g. BTC_ZEC for the ZEC_BTC symbol
+            let mut parts = name.splitn(2, '_');
+            match (parts.next(), parts.next()) {
+                (Some(quote), Some(base)) => symbols.insert(Symbol::new(base, quote), name),
+                _ => return Err(ErrorKind::InvalidFieldFormat(name.to_string()).into()),
+            }
+        }
+        symbols.register(Exchange::Poloniex);
+        Ok(symbols)
+    }
+
+    async fn markets(&mut self) -> Result<Markets> {
+        let symbols = self.symbols().await?;
+
+        // Poloniex does not publish trading rules, all markets use 8 decimals
+        let mut markets = Markets::new();
+        for symbol in symbols.symbols() {
+            let name = symbols.name(symbol).unwrap_or_default();
+            let mut market = MarketInfo::new(symbol.clone(), name);
+            market.price_tick = Some(helpers::decimals_to_increment(8));
+            market.quantity_step = Some(helpers::decimals_to_increment(8));
+            markets.insert(symbol.clone(), market);
+        }
+        Ok(markets)
+    }
+}
+
+/// Build a normalized `Order` from an entry of `returnOpenOrders`
+fn parse_open_order(pair: Pair, order_obj: &Value) -> Result<Order> {
+    let order_number = match &order_obj["orderNumber"] {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return Err(ErrorKind::MissingField("orderNumber".to_string()).into()),
+    };
+    let order_type = match order_obj["type"].as_str() {
+        Some("buy") => OrderType::BuyLimit,
+        Some("sell") => OrderType::SellLimit,
+        _ => return Err(ErrorKind::InvalidFieldFormat("type".to_string()).into()),
+    };
+
+    let remaining = helpers::from_json_bigdecimal(&order_obj["amount"], "amount")?;
+    // startingAmount is not returned by older versions of the API
+    let quantity = match order_obj.get("startingAmount") {
+        Some(starting) => helpers::from_json_bigdecimal(starting, "startingAmount")?,
+        None => remaining.clone(),
+    };
+    let executed_quantity = quantity.clone() - remaining;
+    let status = if executed_quantity > Volume::from(0) {
+        OrderStatus::PartiallyFilled
+    } else {
+        OrderStatus::Open
+    };
+
+    let timestamp = match order_obj["date"].as_str() {
+        Some(date) => utils::parse_date(date)?,
+        None => 0,
+    };
+
+    Ok(Order {
+        identifier: vec![order_number],
+        timestamp,
+        pair,
+        order_type,
+        status,
+        price: Some(helpers::from_json_bigdecimal(&order_obj["rate"], "rate")?),
+        quantity,
+        executed_quantity,
+    })
 }
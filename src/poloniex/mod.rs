@@ -4,6 +4,8 @@ pub mod api;
 pub mod generic_api;
 pub mod credentials;
 pub mod utils;
+pub mod models;
+pub mod streaming_api;
 
 pub use self::credentials::PoloniexCreds;
 pub use self::api::PoloniexApi;
@@ -0,0 +1,181 @@
+use serde::{Serialize, Deserialize};
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use bigdecimal::BigDecimal;
+use crate::error::*;
+use crate::types::{TradeType, Price, Volume};
+
+/// Channel id of the heartbeats sent when nothing else happens for a second
+pub const HEARTBEAT_CHANNEL: i64 = 1010;
+
+#[derive(Debug, Deserialize)]
+pub struct BookSnapshot {
+    #[serde(rename = "currencyPair")]
+    pub currency_pair: String,
+    /// Asks then bids, by price
+    #[serde(rename = "orderBook")]
+    pub order_book: Vec<HashMap<String, String>>,
+}
+
+#[derive(Debug)]
+pub enum Update {
+    /// The book of the channel pair, sent first after subscribing
+    Snapshot { pair: String, asks: Vec<(Price, Volume)>, bids: Vec<(Price, Volume)> },
+    /// A book level update, a zero volume removes the level
+    Book { bid: bool, level: (Price, Volume) },
+    Trade { event_ms: i64, tt: TradeType, price: Price, amount: f32 },
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Heartbeat,
+    /// Subscription acknowledgement
+    Ack(i64),
+    Updates { channel: i64, sequence: i64, updates: Vec<Update> },
+}
+
+fn field<'a>(array: &'a [Value], idx: usize, name: &str) -> Result<&'a Value> {
+    array.get(idx).ok_or_else(|| ErrorKind::MissingField(name.to_string()).into())
+}
+
+fn str_field<'a>(array: &'a [Value], idx: usize, name: &str) -> Result<&'a str> {
+    field(array, idx, name)?.as_str().ok_or_else(|| ErrorKind::InvalidFieldFormat(name.to_string()).into())
+}
+
+fn int_field(array: &[Value], idx: usize, name: &str) -> Result<i64> {
+    field(array, idx, name)?.as_i64().ok_or_else(|| ErrorKind::InvalidFieldFormat(name.to_string()).into())
+}
+
+fn parse_levels(levels: Option<&HashMap<String, String>>) -> Result<Vec<(Price, Volume)>> {
+    match levels {
+        Some(levels) => levels.iter()
+            .map(|(price, volume)| -> Result<(Price, Volume)> { Ok((price.parse::<BigDecimal>()?, volume.parse::<BigDecimal>()?)) })
+            .collect(),
+        None => Ok(vec![]),
+    }
+}
+
+fn parse_update(update: &[Value]) -> Result<Update> {
+    match str_field(update, 0, "type")? {
+        "i" => {
+            let snapshot: BookSnapshot = serde_json::from_value(field(update, 1, "orderBook")?.clone())?;
+            Ok(Update::Snapshot {
+                asks: parse_levels(snapshot.order_book.get(0))?,
+                bids: parse_levels(snapshot.order_book.get(1))?,
+                pair: snapshot.currency_pair,
+            })
+        }
+        "o" => Ok(Update::Book {
+            bid: int_field(update, 1, "side")? == 1,
+            level: (str_field(update, 2, "rate")?.parse::<BigDecimal>()?,
+                    str_field(update, 3, "amount")?.parse::<BigDecimal>()?),
+        }),
+        // Trades carry the time in seconds, newer messages add it in ms
+        "t" => Ok(Update::Trade {
+            event_ms: match update.get(6).and_then(|v| v.as_str()) {
+                Some(ms) => ms.parse::<i64>().map_err(|_| ErrorKind::InvalidFieldFormat("epoch_ms".to_string()))?,
+                None => int_field(update, 5, "timestamp")? * 1000,
+            },
+            tt: if int_field(update, 2, "side")? == 1 { TradeType::Buy } else { TradeType::Sell },
+            price: str_field(update, 3, "rate")?.parse::<BigDecimal>()?,
+            amount: str_field(update, 4, "amount")?.parse::<f32>()?,
+        }),
+        other => Err(ErrorKind::InvalidFieldValue(other.to_string()).into()),
+    }
+}
+
+/// Messages are arrays : channel id, sequence number and a list of updates
+pub fn parse_event(msg: &[u8]) -> Result<Event> {
+    let v: Value = serde_json::from_slice(msg)?;
+    if let Some(error) = v.get("error").and_then(|e| e.as_str()) {
+        return Err(ErrorKind::ExchangeSpecificError(error.to_string()).into());
+    }
+    let array = v.as_array().ok_or(ErrorKind::BadParse)?;
+    let channel = int_field(array, 0, "channel")?;
+    if channel == HEARTBEAT_CHANNEL {
+        return Ok(Event::Heartbeat);
+    }
+    let updates = match array.get(2).and_then(|u| u.as_array()) {
+        Some(updates) => updates,
+        None => return Ok(Event::Ack(channel)),
+    };
+    let updates = updates.iter()
+        .map(|u| u.as_array().ok_or_else(|| Error::from(ErrorKind::BadParse)).and_then(|u| parse_update(u)))
+        .collect::<Result<Vec<Update>>>()?;
+    Ok(Event::Updates { channel, sequence: int_field(array, 1, "sequence")?, updates })
+}
+
+#[derive(Serialize, Debug)]
+pub struct Subscription {
+    command: String,
+    channel: String,
+}
+
+/// Trades and book updates of a pair share the same channel
+pub fn subscription(currency_pair: &str) -> Subscription {
+    Subscription {
+        command: "subscribe".to_string(),
+        channel: currency_pair.to_string(),
+    }
+}
+
+pub fn unsubscription(currency_pair: &str) -> Subscription {
+    Subscription {
+        command: "unsubscribe".to_string(),
+        channel: currency_pair.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod model_tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_order_book_snapshot() {
+        let v = parse_event(b"[148,573963482,[[\"i\",{\"currencyPair\":\"BTC_ETH\",\"orderBook\":[{\"0.08964203\":\"0.00225904\",\"0.04411103\":\"28.56406048\"},{\"0.03292800\":\"6.22200000\"}]}]]]").unwrap();
+        match v {
+            Event::Updates { channel, updates, .. } => {
+                assert_eq!(channel, 148);
+                match &updates[0] {
+                    Update::Snapshot { pair, asks, bids } => {
+                        assert_eq!(pair, "BTC_ETH");
+                        assert_eq!(asks.len(), 2);
+                        assert_eq!(bids.len(), 1);
+                    }
+                    o => panic!("Unexpected update {:?}", o),
+                }
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_updates() {
+        let v = parse_event(b"[148,573963483,[[\"o\",1,\"0.03292800\",\"0.00000000\"],[\"t\",\"42706057\",1,\"0.05567134\",\"0.00181421\",1522877119]]]").unwrap();
+        match v {
+            Event::Updates { updates, .. } => {
+                match &updates[0] {
+                    Update::Book { bid, .. } => assert!(*bid),
+                    o => panic!("Unexpected update {:?}", o),
+                }
+                match &updates[1] {
+                    Update::Trade { event_ms, tt, .. } => {
+                        assert_eq!(*event_ms, 1522877119000);
+                        assert_eq!(*tt, TradeType::Buy);
+                    }
+                    o => panic!("Unexpected update {:?}", o),
+                }
+            }
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+
+    #[test]
+    fn deserialize_heartbeat() {
+        match parse_event(b"[1010]").unwrap() {
+            Event::Heartbeat => (),
+            o => panic!("Unexpected event {:?}", o),
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use crate::coinnect::Credentials;
+use crate::exchange_bot::{DefaultWsActor, WsHandler, WsWriter, ExchangeBot, FeedMonitor, FeedHealth, ConnectionSettings, Subscribe, Unsubscribe, add_channel_pairs, remove_channel_pairs, candle_intervals};
+use crate::error::*;
+use super::models::*;
+use bytes::Bytes;
+use bytes::Buf;
+use actix::{Addr, Recipient};
+use awc::{
+    ws::Message
+};
+use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair, LiveTrade, BookState, BookUpdate, BookSettings, LiveBooks, LiveCandles, Orderbook, Price, Volume, Ticker, BestBidOffer};
+use crate::utils;
+use crate::exchange::Exchange;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+
+static WEBSOCKET_URL: &'static str = "wss://api2.poloniex.com";
+
+pub struct PoloniexBot {
+    addr: Addr<DefaultWsActor>,
+    monitor: FeedMonitor,
+    books: LiveBooks,
+}
+
+impl ExchangeBot for PoloniexBot {
+    fn health(&self) -> FeedHealth {
+        self.monitor.health()
+    }
+
+    fn subscribe(&self, msg: Subscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn unsubscribe(&self, msg: Unsubscribe) {
+        self.addr.do_send(msg);
+    }
+
+    fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.full_order_book(pair)
+    }
+}
+
+#[derive(Debug)]
+pub struct PoloniexStreamingApi {
+    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    channels: HashMap<Channel, HashSet<Pair>>,
+    /// Pairs by channel id, learned from the book snapshot sent after subscribing
+    channel_pairs: HashMap<i64, Pair>,
+    books: LiveBooks,
+    /// Top of book and last trade price by pair, tickers are derived from the pair channels
+    tops: HashMap<Pair, ((Price, Volume), (Price, Volume))>,
+    last_prices: HashMap<Pair, Price>,
+    candles: LiveCandles,
+}
+
+impl PoloniexStreamingApi {
+    /// Create a new poloniex exchange bot, unavailable channels and currencies are ignored
+    pub async fn new_bot<C: Credentials>(_creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, book_settings: BookSettings, connection: ConnectionSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<PoloniexBot> {
+        let books = LiveBooks::new(book_settings);
+        let monitor = FeedMonitor::new(Exchange::Poloniex, channels.clone(), recipients.clone());
+        let api = PoloniexStreamingApi::new_handler(channels, books.clone(), recipients);
+        let addr = DefaultWsActor::new("PoloniexStream", WEBSOCKET_URL, Some(Duration::from_secs(10)), connection, monitor.clone(), Box::new(api)).await?;
+        Ok(PoloniexBot { addr, books, monitor })
+    }
+
+    /// Create the handler of the feed without connecting it, see `replay::FeedReplay`
+    pub fn new_handler(channels: HashMap<Channel, HashSet<Pair>>, books: LiveBooks, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> PoloniexStreamingApi {
+        books.flush_to(Exchange::Poloniex, recipients.clone());
+        PoloniexStreamingApi {
+            recipients,
+            channels,
+            channel_pairs: HashMap::new(),
+            books,
+            tops: HashMap::new(),
+            last_prices: HashMap::new(),
+            candles: LiveCandles::default(),
+        }
+    }
+
+    fn is_subscribed(&self, channel: Channel, pair: &Pair) -> bool {
+        self.channels.get(&channel).map_or(false, |pairs| pairs.contains(pair))
+    }
+
+    fn is_subscribed_to_any(&self, pair: &Pair) -> bool {
+        self.channels.values().any(|pairs| pairs.contains(pair))
+    }
+
+    /// Send the best bid and offer when the top of book changed, and the ticker when a trade
+    /// happened as well
+    fn broadcast_top(&mut self, pair: Pair, top: Option<((Price, Volume), (Price, Volume))>, traded: bool) {
+        let top = match top {
+            Some(top) => top,
+            None => return,
+        };
+        let top_changed = self.tops.get(&pair) != Some(&top);
+        self.tops.insert(pair, top.clone());
+        let ((bid, bid_volume), (ask, ask_volume)) = top;
+        let now = self.books.clock().now_ms();
+        if (top_changed || traded) && self.is_subscribed(Channel::LiveTicker, &pair) {
+            if let Some(last_price) = self.last_prices.get(&pair) {
+                self.broadcast(LiveEvent::LiveTicker(Ticker {
+                    timestamp: now,
+                    pair,
+                    last_trade_price: last_price.clone(),
+                    lowest_ask: ask.clone(),
+                    highest_bid: bid.clone(),
+                    volume: None,
+                }));
+            }
+        }
+        if top_changed && self.is_subscribed(Channel::LiveBestBidOffer, &pair) {
+            self.broadcast(LiveEvent::LiveBestBidOffer(BestBidOffer {
+                event_ms: now,
+                pair,
+                bid,
+                ask,
+                bid_volume: Some(bid_volume),
+                ask_volume: Some(ask_volume),
+            }));
+        }
+    }
+
+    fn broadcast(&self, v: LiveEvent) {
+        let vec = self.recipients.clone();
+        if vec.len() == 0 as usize {
+            debug!("{:?}", v);
+        } else {
+            for r in &vec {
+                let le: LiveEvent = v.clone();
+                r.do_send(LiveEventEnveloppe(Exchange::Poloniex, le));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl WsHandler for PoloniexStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, w: &mut dyn WsWriter, msg: Bytes) {
+        let (channel, sequence, updates) = match parse_event(msg.bytes()) {
+            Ok(Event::Updates { channel, sequence, updates }) => (channel, sequence, updates),
+            Ok(_) => return,
+            Err(e) => return trace!("Poloniex : error {:?} deserializing {:?}", e, msg),
+        };
+        // Every update of a message belongs to the channel pair and shares its sequence number
+        let mut snapshot = None;
+        let mut book_update = BookUpdate { update_ids: Some((sequence as u64, sequence as u64)), asks: vec![], bids: vec![] };
+        let mut trades = vec![];
+        for update in updates {
+            match update {
+                Update::Snapshot { pair, asks, bids } => {
+                    match utils::pair_enum(Exchange::Poloniex, &pair) {
+                        Some(p) => self.channel_pairs.insert(channel, p),
+                        None => return,
+                    };
+                    snapshot = Some((asks, bids));
+                }
+                Update::Book { bid: true, level } => book_update.bids.push(level),
+                Update::Book { bid: false, level } => book_update.asks.push(level),
+                Update::Trade { event_ms, tt, price, amount } => trades.push((event_ms, tt, price, amount)),
+            }
+        }
+        let current_pair = match self.channel_pairs.get(&channel) {
+            Some(&p) => p,
+            None => return,
+        };
+
+        if let Some((_, _, price, _)) = trades.last() {
+            self.last_prices.insert(current_pair, price.clone());
+        }
+        let traded = !trades.is_empty();
+        let intervals = candle_intervals(&self.channels, &current_pair);
+        for (event_ms, _, price, amount) in &trades {
+            for c in self.candles.add_trade(current_pair, &intervals, *event_ms, price, &BigDecimal::from(*amount)) {
+                self.broadcast(LiveEvent::LiveCandle(c));
+            }
+        }
+        if self.is_subscribed(Channel::LiveTrades, &current_pair) {
+            for (event_ms, tt, price, amount) in trades {
+                self.broadcast(LiveEvent::LiveTrade(LiveTrade {
+                    event_ms,
+                    pair: current_pair,
+                    amount,
+                    price,
+                    tt,
+                }));
+            }
+        }
+
+        // Books are only sent, and flushed after the throttle, to the order book channel
+        let full_book = self.is_subscribed(Channel::LiveFullOrderBook, &current_pair);
+        let (state, latest, top) = self.books.with_book(current_pair, |agg| {
+            // Messages with trades only still advance the sequence
+            let state_change = match snapshot {
+                Some((asks, bids)) => {
+                    // Updates buffered before belong to the previous subscription
+                    agg.start_resync();
+                    agg.apply_snapshot(Some(sequence as u64), asks.into_iter(), bids.into_iter())
+                }
+                None => agg.apply_update(book_update),
+            };
+            let latest = if full_book { agg.latest_order_book() } else { None };
+            (state_change.map(|_| agg.book_state()), latest, agg.top_of_book())
+        });
+        self.broadcast_top(current_pair, top, traded);
+        // The book also feeds the ticker and best bid/offer channels, it is resynced for them too
+        if let Some(state) = &state {
+            if state.state == BookState::Resyncing {
+                // Subscribing again sends a new snapshot
+                if let Some(currency_pair) = utils::pair_name(Exchange::Poloniex, &current_pair) {
+                    info!("Poloniex : sequence gap on {:?}, subscribing again", current_pair);
+                    w.write(Message::Text(serde_json::to_string(&unsubscription(&currency_pair)).unwrap()));
+                    w.write(Message::Text(serde_json::to_string(&subscription(&currency_pair)).unwrap()));
+                }
+            }
+        }
+        if !full_book {
+            return;
+        }
+        if let Some(state) = state {
+            self.broadcast(LiveEvent::LiveBookState(state));
+        }
+        if let Some(ob) = latest {
+            self.broadcast(LiveEvent::LiveOrderbook(ob));
+        }
+    }
+
+    fn handle_tick(&mut self, now_ms: i64) {
+        for c in self.candles.close_over(now_ms) {
+            self.broadcast(LiveEvent::LiveCandle(c));
+        }
+    }
+
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_started(&mut self, w: &mut dyn WsWriter) {
+        // Channel ids are learned again from the snapshots
+        self.channel_pairs.clear();
+        for state in self.books.start_resync() {
+            self.broadcast(LiveEvent::LiveBookState(state));
+        }
+        let pairs: HashSet<Pair> = self.channels.values().flatten().cloned().collect();
+        info!("Poloniex : connecting to {:?}", &pairs);
+        for pair in pairs {
+            if let Some(currency_pair) = utils::pair_name(Exchange::Poloniex, &pair) {
+                let result = serde_json::to_string(&subscription(&currency_pair)).unwrap();
+                w.write(Message::Text(result));
+            }
+        }
+    }
+
+    fn handle_subscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let (pairs, unsupported): (HashSet<Pair>, HashSet<Pair>) = pairs.into_iter()
+            .partition(|pair| utils::pair_name(Exchange::Poloniex, pair).is_some());
+        if !unsupported.is_empty() {
+            warn!("Poloniex : pairs {:?} are not supported", unsupported);
+        }
+        // Pairs already subscribed on another channel share its data
+        let new_pairs: Vec<Pair> = pairs.iter().filter(|pair| !self.is_subscribed_to_any(pair)).cloned().collect();
+        add_channel_pairs(&mut self.channels, channel, pairs.clone());
+        for pair in new_pairs {
+            if let Some(currency_pair) = utils::pair_name(Exchange::Poloniex, &pair) {
+                w.write(Message::Text(serde_json::to_string(&subscription(&currency_pair)).unwrap()));
+            }
+        }
+        pairs
+    }
+
+    fn handle_unsubscribe(&mut self, w: &mut dyn WsWriter, channel: Channel, pairs: HashSet<Pair>) -> HashSet<Pair> {
+        let removed = remove_channel_pairs(&mut self.channels, channel.clone(), pairs);
+        for &pair in &removed {
+            if let Channel::LiveCandles(interval) = channel {
+                self.candles.remove(pair, interval);
+            }
+            if self.is_subscribed_to_any(&pair) {
+                continue;
+            }
+            self.channel_pairs.retain(|_, p| *p != pair);
+            self.books.remove(pair);
+            self.tops.remove(&pair);
+            self.last_prices.remove(&pair);
+            if let Some(currency_pair) = utils::pair_name(Exchange::Poloniex, &pair) {
+                w.write(Message::Text(serde_json::to_string(&unsubscription(&currency_pair)).unwrap()));
+            }
+        }
+        removed.into_iter().collect()
+    }
+}
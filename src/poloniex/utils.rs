@@ -2,9 +2,11 @@ use bidir_map::BidirMap;
 use serde_json;
 use serde_json::Value;
 use serde_json::value::Map;
+use chrono::NaiveDateTime;
 
 use crate::error::*;
 use crate::types::Currency;
+use crate::types::Interval;
 use crate::types::Pair;
 use crate::types::Pair::*;
 
@@ -129,6 +131,27 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
+/// Return the chart data period (in seconds) used by Poloniex
+/// If the interval is not supported, None is returned.
+pub fn get_interval_string(interval: Interval) -> Option<&'static str> {
+    match interval {
+        Interval::FiveMinutes => Some("300"),
+        Interval::FifteenMinutes => Some("900"),
+        Interval::ThirtyMinutes => Some("1800"),
+        Interval::TwoHours => Some("7200"),
+        Interval::FourHours => Some("14400"),
+        Interval::OneDay => Some("86400"),
+        Interval::OneMinute | Interval::OneHour | Interval::OneWeek => None,
+    }
+}
+
+/// Convert a date used by Poloniex (`2016-05-03 01:29:55`, UTC) to a UNIX timestamp in ms
+pub fn parse_date(date: &str) -> Result<i64> {
+    let datetime = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .chain_err(|| ErrorKind::InvalidFieldFormat("date".to_string()))?;
+    Ok(datetime.timestamp_millis())
+}
+
 /// If error array is null, return the result (encoded in a json object)
 /// else return the error string found in array
 pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
@@ -0,0 +1,371 @@
+//! Raw frames of the live feeds written to rotating gzip files, one JSON frame by line.
+//! Enable it with `ConnectionSettings::record`.
+
+use serde::{Deserialize, Serialize};
+use libflate::gzip::{Decoder, Encoder};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind as IoErrorKind, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::helpers;
+
+/// Connections of every feed of the process, so that connection ids are unique within records
+static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+
+const RECORD_EXTENSION: &'static str = "jsonl.gz";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecorderSettings {
+    /// Directory of the record files, created if missing
+    pub dir: PathBuf,
+    /// A new file is started once this many bytes of frames were written, before compression
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// A new file is started after this long, in ms
+    #[serde(default = "default_max_file_age_ms")]
+    pub max_file_age_ms: u64,
+}
+
+fn default_max_file_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_max_file_age_ms() -> u64 {
+    3_600_000
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// UNIX timestamp in ms of the reception of the frame
+    pub received_ms: i64,
+    pub exchange: Exchange,
+    /// Changes on every connection, reconnections included
+    pub connection_id: u64,
+    /// Hub method of SignalR feeds (Bittrex), None for websocket text frames
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// The text frame as received, or the JSON of the hub message
+    pub payload: String,
+}
+
+struct RecordFile {
+    encoder: Encoder<BufWriter<File>>,
+    path: PathBuf,
+    opened_ms: i64,
+    written: u64,
+}
+
+enum WriterCommand {
+    Frame(RecordedFrame),
+    Close,
+}
+
+/// Writes the frames of a feed, files are never overwritten. A file can be read entirely once
+/// the recorder moved on to the next one or was dropped.
+pub struct FeedRecorder {
+    exchange: Exchange,
+    /// Prefix of the file names, such as `BinanceStream`
+    name: String,
+    connection_id: u64,
+    /// Frames are compressed and written by a dedicated thread, not to hold the feed back
+    sender: Option<Sender<WriterCommand>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl FeedRecorder {
+    pub fn new(name: &str, exchange: Exchange, settings: RecorderSettings) -> FeedRecorder {
+        let (sender, receiver) = mpsc::channel();
+        let mut writer = RecordWriter { settings, name: name.to_string(), file: None };
+        let (sender, writer) = match thread::Builder::new().name(format!("{}Recorder", name)).spawn(move || writer.run(receiver)) {
+            Ok(handle) => (Some(sender), Some(handle)),
+            Err(e) => {
+                warn!("FeedRecorder {} : cannot start the writer thread, nothing is recorded : {}", name, e);
+                (None, None)
+            }
+        };
+        FeedRecorder { exchange, name: name.to_string(), connection_id: CONNECTIONS.fetch_add(1, Ordering::SeqCst), sender, writer }
+    }
+
+    /// Call on every new connection of the feed
+    pub fn on_connected(&mut self) {
+        self.connection_id = CONNECTIONS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    /// Record a websocket text frame
+    pub fn record(&mut self, payload: &[u8]) {
+        self.record_frame(None, String::from_utf8_lossy(payload).into_owned());
+    }
+
+    /// Record a hub message
+    pub fn record_method(&mut self, method: &str, message: &serde_json::Value) {
+        self.record_frame(Some(method.to_string()), message.to_string());
+    }
+
+    fn record_frame(&mut self, method: Option<String>, payload: String) {
+        let frame = RecordedFrame { received_ms: helpers::get_unix_timestamp_ms(), exchange: self.exchange, connection_id: self.connection_id, method, payload };
+        self.send(WriterCommand::Frame(frame));
+    }
+
+    /// Complete the current file, the next frame starts a new one
+    pub fn close(&mut self) {
+        self.send(WriterCommand::Close);
+    }
+
+    fn send(&mut self, command: WriterCommand) {
+        let sent = self.sender.as_ref().map_or(true, |sender| sender.send(command).is_ok());
+        if !sent {
+            warn!("FeedRecorder {} : the writer thread stopped, nothing is recorded", self.name);
+            self.sender = None;
+        }
+    }
+}
+
+impl std::fmt::Debug for FeedRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeedRecorder")
+            .field("name", &self.name)
+            .field("connection_id", &self.connection_id)
+            .finish()
+    }
+}
+
+impl Drop for FeedRecorder {
+    /// Wait for the frames sent to be written, so that the files are complete
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                warn!("FeedRecorder {} : the writer thread panicked", self.name);
+            }
+        }
+    }
+}
+
+/// Writes the frames of a `FeedRecorder` from its thread, until the recorder is dropped
+struct RecordWriter {
+    settings: RecorderSettings,
+    name: String,
+    file: Option<RecordFile>,
+}
+
+impl RecordWriter {
+    fn run(&mut self, commands: Receiver<WriterCommand>) {
+        for command in commands {
+            match command {
+                WriterCommand::Frame(frame) => {
+                    // Recording must not stop the feed, frames are lost until a file can be opened again
+                    if let Err(e) = self.write(&frame) {
+                        warn!("FeedRecorder {} : cannot record a frame : {}", self.name, e);
+                        self.file = None;
+                    }
+                }
+                WriterCommand::Close => self.close(),
+            }
+        }
+        self.close();
+    }
+
+    fn write(&mut self, frame: &RecordedFrame) -> Result<()> {
+        let mut line = serde_json::to_vec(frame)?;
+        line.push(b'\n');
+        let rotate = self.file.as_ref().map_or(false, |f| {
+            f.written >= self.settings.max_file_bytes || frame.received_ms - f.opened_ms >= self.settings.max_file_age_ms as i64
+        });
+        if rotate {
+            self.close();
+        }
+        if self.file.is_none() {
+            self.file = Some(self.open(frame.received_ms)?);
+        }
+        let file = self.file.as_mut().unwrap();
+        file.encoder.write_all(&line)?;
+        file.written += line.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self, now: i64) -> Result<RecordFile> {
+        fs::create_dir_all(&self.settings.dir)?;
+        let mut index = 0;
+        loop {
+            let path = self.settings.dir.join(format!("{}-{}-{}.{}", self.name, now, index, RECORD_EXTENSION));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    debug!("FeedRecorder {} : recording to {:?}", self.name, path);
+                    let encoder = Encoder::new(BufWriter::new(file))?;
+                    return Ok(RecordFile { encoder, path, opened_ms: now, written: 0 });
+                }
+                Err(ref e) if e.kind() == IoErrorKind::AlreadyExists => index += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(file) = self.file.take() {
+            let result = file.encoder.finish().into_result().and_then(|mut w| w.flush());
+            if let Err(e) = result {
+                warn!("FeedRecorder {} : cannot complete {:?} : {}", self.name, file.path, e);
+            }
+        }
+    }
+}
+
+/// Feed name, opening time and index of a record file named `{name}-{opened_ms}-{index}`
+fn record_name(path: &Path) -> Option<(String, i64, u64)> {
+    let name = path.file_name()?.to_str()?;
+    if !name.ends_with(RECORD_EXTENSION) {
+        return None;
+    }
+    // Names may contain dashes
+    let mut parts = name.trim_end_matches(RECORD_EXTENSION).trim_end_matches('.').rsplitn(3, '-');
+    let index = parts.next()?.parse().ok()?;
+    let opened_ms = parts.next()?.parse().ok()?;
+    Some((parts.next()?.to_string(), opened_ms, index))
+}
+
+/// Record files of a directory in the order they were written, only the ones of the feeds
+/// `names` (such as `BinanceStream`) unless it is empty
+pub fn record_files(dir: &Path, names: &[&str]) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<(String, i64, u64, PathBuf)> = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some((prefix, opened_ms, index)) = record_name(&path) {
+            if !names.is_empty() && !names.contains(&prefix.as_str()) {
+                continue;
+            }
+            files.push((prefix, opened_ms, index, path));
+        }
+    }
+    files.sort_by(|a, b| (a.1, &a.0, a.2).cmp(&(b.1, &b.0, b.2)));
+    Ok(files.into_iter().map(|f| f.3).collect())
+}
+
+type FrameLines = Lines<BufReader<Decoder<BufReader<File>>>>;
+
+/// Frames of the record files of a feed, one file after the other
+struct FeedFrames {
+    name: Option<String>,
+    files: VecDeque<PathBuf>,
+    lines: Option<FrameLines>,
+    /// Next frame of the feed, read ahead to order the feeds
+    next: Option<RecordedFrame>,
+}
+
+impl FeedFrames {
+    /// Read the next frame, None once every file is read. A file that was not completed is read
+    /// up to its last complete frame.
+    fn read(&mut self) -> Result<Option<RecordedFrame>> {
+        loop {
+            if self.lines.is_none() {
+                let path = match self.files.pop_front() {
+                    Some(path) => path,
+                    None => return Ok(None),
+                };
+                let decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+                self.lines = Some(BufReader::new(decoder).lines());
+            }
+            let line = match self.lines.as_mut().and_then(|lines| lines.next()) {
+                Some(Ok(line)) => line,
+                Some(Err(e)) if e.kind() != IoErrorKind::UnexpectedEof => return Err(e.into()),
+                _ => {
+                    self.lines = None;
+                    continue;
+                }
+            };
+            match serde_json::from_str(&line) {
+                Ok(frame) => return Ok(Some(frame)),
+                Err(e) if e.is_eof() => self.lines = None,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Frames of record files by reception time, read from the files as they are consumed, see
+/// `read_records`
+pub struct RecordReader {
+    feeds: Vec<FeedFrames>,
+}
+
+impl Iterator for RecordReader {
+    type Item = Result<RecordedFrame>;
+
+    /// A feed that cannot be read returns its error then ends
+    fn next(&mut self) -> Option<Result<RecordedFrame>> {
+        for feed in self.feeds.iter_mut().filter(|feed| feed.next.is_none()) {
+            match feed.read() {
+                Ok(frame) => feed.next = frame,
+                Err(e) => {
+                    feed.files.clear();
+                    feed.lines = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+        // The first feed goes first at equal times
+        self.feeds.iter_mut()
+            .filter(|feed| feed.next.is_some())
+            .min_by_key(|feed| feed.next.as_ref().map(|frame| frame.received_ms))?
+            .next.take().map(Ok)
+    }
+}
+
+/// Read the frames of a record file
+pub fn read_record(path: &Path) -> Result<Vec<RecordedFrame>> {
+    read_records(&[path.to_path_buf()]).collect()
+}
+
+/// Frames of several record files by reception time, the files are read as the frames are
+/// consumed. Frames of the files of a feed keep the order they were recorded in, feeds are
+/// ordered by their first file at equal times.
+pub fn read_records(paths: &[PathBuf]) -> RecordReader {
+    let mut feeds: Vec<FeedFrames> = vec![];
+    for path in paths {
+        let name = record_name(path).map(|(name, _, _)| name);
+        match feeds.iter_mut().find(|feed| name.is_some() && feed.name == name) {
+            Some(feed) => feed.files.push_back(path.clone()),
+            None => feeds.push(FeedFrames { name, files: vec![path.clone()].into_iter().collect(), lines: None, next: None }),
+        }
+    }
+    RecordReader { feeds }
+}
+
+#[cfg(test)]
+mod recorder_tests {
+    use super::*;
+
+    #[test]
+    fn should_rotate_and_read_records() {
+        let dir = std::env::temp_dir().join(format!("coinnect-recorder-{}", helpers::get_unix_timestamp_ms()));
+        let settings = RecorderSettings { dir: dir.clone(), max_file_bytes: 100, max_file_age_ms: default_max_file_age_ms() };
+        let mut recorder = FeedRecorder::new("BinanceStream", Exchange::Binance, settings);
+        let first_connection = recorder.connection_id();
+        for i in 0..3 {
+            recorder.record(format!("{{\"u\":{},\"padding\":\"{}\"}}", i, "x".repeat(80)).as_bytes());
+        }
+        recorder.on_connected();
+        recorder.record_method("uE", &serde_json::json!(["abc"]));
+        drop(recorder);
+
+        let files = record_files(&dir, &[]).unwrap();
+        assert_eq!(files.len(), 4);
+        assert!(record_files(&dir, &["KrakenStream"]).unwrap().is_empty());
+        let frames = read_records(&files).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].payload, format!("{{\"u\":0,\"padding\":\"{}\"}}", "x".repeat(80)));
+        assert_eq!(frames[0].exchange, Exchange::Binance);
+        assert_eq!(frames[2].connection_id, first_connection);
+        assert_ne!(frames[3].connection_id, first_connection);
+        assert_eq!(frames[3].method.as_ref().map(|m| m.as_str()), Some("uE"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+//! Replays recorded feeds (see `recorder`) through the handlers of the live bots, so that books
+//! and live events are built by the same code as live.
+//! The clock of the handlers follows the reception time of the frames : times not sent by the
+//! exchange (book timestamps, derived tickers, candles closing) and the throttle windows of the
+//! books are those of the recorded feed.
+
+use actix::Recipient;
+use awc::ws::Message;
+use bytes::Bytes;
+use serde_json::Value;
+use signalr_rs::hub::client::HubClientHandler;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+use crate::binance::streaming_api::BinanceStreamingApi;
+use crate::bitstamp::streaming_api::BitstampStreamingApi;
+use crate::bittrex::streaming_api::BittrexStreamingApi;
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::exchange_bot::{FeedMonitor, WsHandler, WsWriter};
+use crate::gdax::streaming_api::GdaxStreamingApi;
+use crate::kraken::streaming_api::KrakenStreamingApi;
+use crate::poloniex::streaming_api::PoloniexStreamingApi;
+use crate::recorder::{self, RecordReader, RecordedFrame};
+use crate::types::{BookSettings, Channel, LiveBooks, LiveEventEnveloppe, Orderbook, Pair};
+
+/// Frames written by the handlers are dropped, nothing is sent during a replay
+struct ReplayWriter;
+
+impl WsWriter for ReplayWriter {
+    fn write(&mut self, _msg: Message) -> std::result::Result<(), Message> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Frames are handled one after the other without waiting
+    Max,
+    /// Delays between frames are divided by the factor, 1.0 for the original speed
+    Factor(f64),
+}
+
+enum FeedHandler {
+    Ws(Box<dyn WsHandler>),
+    Hub(Box<BittrexStreamingApi>),
+}
+
+/// Names of the record files of the market data feed of an exchange
+pub fn feed_names(exchange: Exchange) -> &'static [&'static str] {
+    match exchange {
+        // Depth snapshots are fetched over REST and recorded apart
+        Exchange::Binance => &["BinanceStream", "BinanceSnapshots"],
+        Exchange::Bitstamp => &["BitstampStream"],
+        Exchange::Bittrex => &["BittrexStream"],
+        Exchange::Gdax => &["GdaxStream"],
+        Exchange::Kraken => &["KrakenStream"],
+        Exchange::Poloniex => &["PoloniexStream"],
+    }
+}
+
+/// Frames of the market data feed of an exchange recorded in a directory, by reception time,
+/// read as they are replayed
+pub fn read_feed(dir: &Path, exchange: Exchange) -> Result<RecordReader> {
+    Ok(recorder::read_records(&recorder::record_files(dir, feed_names(exchange))?))
+}
+
+/// Market data feed of an exchange driven by recorded frames instead of a connection
+pub struct FeedReplay {
+    exchange: Exchange,
+    handler: FeedHandler,
+    books: LiveBooks,
+    /// Connection of the last frame received, a new one starts the handler again
+    connection_id: Option<u64>,
+}
+
+impl FeedReplay {
+    /// Replay the feed of an exchange subscribed to `channels`, as created by
+    /// `Coinnect::new_stream`. Live events are sent to the recipients.
+    pub fn new(exchange: Exchange, channels: HashMap<Channel, HashSet<Pair>>, book_settings: BookSettings, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> FeedReplay {
+        let books = LiveBooks::new(book_settings);
+        let handler = match exchange {
+            Exchange::Binance => FeedHandler::Ws(Box::new(BinanceStreamingApi::new_handler(channels, books.clone(), None, None, recipients))),
+            Exchange::Bitstamp => FeedHandler::Ws(Box::new(BitstampStreamingApi::new_handler(channels, books.clone(), recipients))),
+            Exchange::Gdax => FeedHandler::Ws(Box::new(GdaxStreamingApi::new_handler(channels, books.clone(), recipients))),
+            Exchange::Kraken => FeedHandler::Ws(Box::new(KrakenStreamingApi::new_handler(channels, books.clone(), recipients))),
+            Exchange::Poloniex => FeedHandler::Ws(Box::new(PoloniexStreamingApi::new_handler(channels, books.clone(), recipients))),
+            Exchange::Bittrex => {
+                // Feed status events are not replayed
+                let monitor = FeedMonitor::new(Exchange::Bittrex, channels.clone(), vec![]);
+                FeedHandler::Hub(Box::new(BittrexStreamingApi::new_handler(channels, books.clone(), monitor, None, recipients)))
+            }
+        };
+        FeedReplay { exchange, handler, books, connection_id: None }
+    }
+
+    /// Every level of the local order book of the pair, None if the book is not synced
+    pub fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.full_order_book(pair)
+    }
+
+    /// Handle a frame as the live feed would, frames of other exchanges are ignored
+    pub fn handle_frame(&mut self, frame: &RecordedFrame) {
+        if frame.exchange != self.exchange {
+            return;
+        }
+        // Books, derived events and candles follow the time of the frames rather than of the replay
+        self.books.clock().set(frame.received_ms);
+        match &mut self.handler {
+            FeedHandler::Ws(handler) => handler.handle_tick(frame.received_ms),
+            FeedHandler::Hub(handler) => handler.close_candles(frame.received_ms),
+        }
+        match &mut self.handler {
+            FeedHandler::Ws(handler) => match &frame.method {
+                // Recorded by the handler itself, not received on the connection
+                Some(method) => handler.handle_recorded(method, &frame.payload),
+                None => {
+                    if self.connection_id != Some(frame.connection_id) {
+                        self.connection_id = Some(frame.connection_id);
+                        handler.handle_started(&mut ReplayWriter);
+                    }
+                    handler.handle_in(&mut ReplayWriter, Bytes::from(frame.payload.clone()));
+                }
+            },
+            FeedHandler::Hub(handler) => {
+                if self.connection_id != Some(frame.connection_id) {
+                    self.connection_id = Some(frame.connection_id);
+                    // The queries sent on connection were recorded with their answers
+                    handler.on_connect();
+                }
+                let method = frame.method.as_ref().map_or("", |m| m.as_str());
+                match serde_json::from_str::<Value>(&frame.payload) {
+                    Ok(message) => handler.handle(method, &message),
+                    Err(e) => trace!("Replay : error {:?} deserializing {:?}", e, frame.payload),
+                }
+            }
+        }
+    }
+
+    /// Handle the frames in order, waiting between two frames for as long as between their
+    /// receptions divided by the speed factor. Return the number of frames of the exchange, or
+    /// the first error reading the frames.
+    pub async fn run<I: IntoIterator<Item = Result<RecordedFrame>>>(&mut self, frames: I, speed: ReplaySpeed) -> Result<usize> {
+        let mut previous_ms = None;
+        let mut handled = 0;
+        for frame in frames {
+            let frame = frame?;
+            if frame.exchange != self.exchange {
+                continue;
+            }
+            if let (ReplaySpeed::Factor(factor), Some(previous)) = (speed, previous_ms) {
+                let delay_ms = (frame.received_ms - previous) as f64 / factor;
+                if delay_ms > 0.0 {
+                    tokio::time::delay_for(Duration::from_micros((delay_ms * 1000.0) as u64)).await;
+                }
+            }
+            previous_ms = Some(frame.received_ms);
+            self.handle_frame(&frame);
+            handled += 1;
+        }
+        Ok(handled)
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    fn frame(connection_id: u64, payload: &str) -> RecordedFrame {
+        RecordedFrame { received_ms: 0, exchange: Exchange::Kraken, connection_id, method: None, payload: payload.to_string() }
+    }
+
+    #[test]
+    fn should_build_books_from_recorded_frames() {
+        let mut channels = HashMap::new();
+        channels.insert(Channel::LiveFullOrderBook, vec![Pair::BTC_USD].into_iter().collect());
+        let mut replay = FeedReplay::new(Exchange::Kraken, channels, BookSettings::default(), vec![]);
+        replay.handle_frame(&frame(1, "[0,{\"as\":[[\"5541.30000\",\"2.50000000\",\"1534614248.123678\"]],\"bs\":[[\"5541.20000\",\"1.50000000\",\"1534614248.765567\"]]},\"book-10\",\"XBT/USD\"]"));
+        replay.handle_frame(&frame(1, "[0,{\"a\":[[\"5541.30000\",\"0.00000000\",\"1534614335.345903\"],[\"5542.00000\",\"1.00000000\",\"1534614335.345903\"]]},\"book-10\",\"XBT/USD\"]"));
+
+        let book = replay.full_order_book(Pair::BTC_USD).unwrap();
+        assert_eq!(book.asks, vec![(BigDecimal::from_str("5542").unwrap(), BigDecimal::from(1))]);
+        assert_eq!(book.bids, vec![(BigDecimal::from_str("5541.2").unwrap(), BigDecimal::from_str("1.5").unwrap())]);
+
+        // A new connection resyncs the book until its snapshot is in
+        replay.handle_frame(&frame(2, "{\"event\":\"heartbeat\"}"));
+        assert!(replay.full_order_book(Pair::BTC_USD).is_none());
+    }
+}
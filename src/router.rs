@@ -0,0 +1,349 @@
+//! Smart order routing : a parent order is split into child orders over several accounts,
+//! taking the cheapest levels of their books once fees are paid.
+
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::HashMap;
+
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::helpers;
+use crate::types::*;
+use crate::validation::check_order;
+
+/// The order to route
+#[derive(Debug, Clone)]
+pub struct ParentOrder {
+    pub pair: Pair,
+    pub side: OrderSide,
+    pub quantity: Volume,
+    /// Levels above (buy) or below (sell) this price are not taken
+    pub limit_price: Option<Price>,
+}
+
+/// An account the router can trade on
+#[derive(Debug)]
+pub struct RouterAccount {
+    pub exchange: Exchange,
+    pub api: Box<dyn ExchangeApi>,
+    /// Taker fee rate (0.001 for 0.1%), read from `ExchangeApi::markets` when None,
+    /// the account is left out of the routes when neither is known
+    pub taker_fee: Option<BigDecimal>,
+}
+
+impl RouterAccount {
+    pub fn new(exchange: Exchange, api: Box<dyn ExchangeApi>) -> RouterAccount {
+        RouterAccount { exchange, api, taker_fee: None }
+    }
+}
+
+/// What the router knows of an account when routing
+#[derive(Debug, Clone)]
+pub struct VenueQuote {
+    pub exchange: Exchange,
+    pub book: Orderbook,
+    /// Funds available for the order : quote currency for a buy, base currency for a sell.
+    /// None when the currency is unknown, the order is then not limited by the balance.
+    pub available: Option<Amount>,
+    pub taker_fee: BigDecimal,
+    pub market: Option<MarketInfo>,
+}
+
+/// A child order of a route
+#[derive(Debug, Clone)]
+pub struct ChildOrder {
+    /// Index of the venue in the quotes given to `plan_route`
+    pub venue: usize,
+    pub exchange: Exchange,
+    pub quantity: Volume,
+    /// Limit price, the worst level taken on the venue
+    pub price: Price,
+    /// Expected cost of a buy or proceeds of a sell, fees included, in quote currency
+    pub expected_value: Amount,
+    pub expected_fee: Amount,
+}
+
+/// Split the order over the venues, taking levels by ascending cost (buy) or descending proceeds
+/// (sell) once the taker fee is paid, within the available funds of every venue.
+/// Quantities are rounded down to the market rules and prices away from the book within the
+/// limit price, child orders that break the rules are dropped
+/// and their quantity is left unrouted.
+pub fn plan_route(order: &ParentOrder, venues: &[VenueQuote]) -> Vec<ChildOrder> {
+    let one = BigDecimal::from(1);
+    let mut levels: Vec<(usize, &Price, &Volume, Price)> = vec![];
+    for (i, venue) in venues.iter().enumerate() {
+        let (book_levels, fee_factor) = match order.side {
+            OrderSide::Buy => (&venue.book.asks, one.clone() + venue.taker_fee.clone()),
+            OrderSide::Sell => (&venue.book.bids, one.clone() - venue.taker_fee.clone()),
+        };
+        for (price, volume) in book_levels {
+            let within_limit = match (&order.limit_price, order.side) {
+                (Some(limit), OrderSide::Buy) => price <= limit,
+                (Some(limit), OrderSide::Sell) => price >= limit,
+                (None, _) => true,
+            };
+            if within_limit {
+                levels.push((i, price, volume, price.clone() * fee_factor.clone()));
+            }
+        }
+    }
+    match order.side {
+        OrderSide::Buy => levels.sort_by(|a, b| a.3.cmp(&b.3)),
+        OrderSide::Sell => levels.sort_by(|a, b| b.3.cmp(&a.3)),
+    }
+
+    let mut budgets: Vec<Option<Amount>> = venues.iter().map(|v| v.available.clone()).collect();
+    // Quantity, worst price and value by venue
+    let mut taken: HashMap<usize, (Volume, Price, Amount)> = HashMap::new();
+    let mut remaining = order.quantity.clone();
+    for (i, price, volume, unit_value) in levels {
+        if remaining <= BigDecimal::zero() {
+            break;
+        }
+        let mut quantity = std::cmp::min(remaining.clone(), volume.clone());
+        if let Some(budget) = &budgets[i] {
+            let affordable = match order.side {
+                OrderSide::Buy => budget.clone() / unit_value.clone(),
+                OrderSide::Sell => budget.clone(),
+            };
+            quantity = std::cmp::min(quantity, affordable);
+        }
+        if quantity <= BigDecimal::zero() {
+            continue;
+        }
+        let value = unit_value * quantity.clone();
+        if let Some(budget) = budgets[i].as_mut() {
+            *budget = match order.side {
+                OrderSide::Buy => budget.clone() - value.clone(),
+                OrderSide::Sell => budget.clone() - quantity.clone(),
+            };
+        }
+        remaining = remaining - quantity.clone();
+        let entry = taken.entry(i).or_insert_with(|| (BigDecimal::zero(), price.clone(), BigDecimal::zero()));
+        entry.0 = entry.0.clone() + quantity;
+        entry.1 = price.clone();
+        entry.2 = entry.2.clone() + value;
+    }
+
+    let mut children = vec![];
+    let mut venue_ids: Vec<usize> = taken.keys().cloned().collect();
+    venue_ids.sort();
+    for i in venue_ids {
+        let (quantity, price, value) = taken.remove(&i).unwrap();
+        let venue = &venues[i];
+        let (rounded, price) = match &venue.market {
+            Some(market) => (market.round_quantity(&quantity), child_price(order, market, &price)),
+            None => (quantity.clone(), price),
+        };
+        let order_type = match order.side {
+            OrderSide::Buy => OrderType::BuyLimit,
+            OrderSide::Sell => OrderType::SellLimit,
+        };
+        if let Err(e) = check_order(order_type, order.pair, &rounded, Some(&price), venue.market.as_ref(), None) {
+            debug!("Router : {:?} child order of {} dropped : {}", venue.exchange, rounded, e);
+            continue;
+        }
+        let expected_value = value * rounded.clone() / quantity;
+        let fee_rate = venue.taker_fee.clone();
+        let fee_factor = match order.side {
+            OrderSide::Buy => one.clone() + fee_rate.clone(),
+            OrderSide::Sell => one.clone() - fee_rate.clone(),
+        };
+        let expected_fee = expected_value.clone() * fee_rate / fee_factor;
+        children.push(ChildOrder { venue: i, exchange: venue.exchange, quantity: rounded, price, expected_value, expected_fee });
+    }
+    children
+}
+
+/// Round the price of a child order to the price tick so that it still takes its levels, up for
+/// buys and down for sells, without going past the limit of the parent order
+fn child_price(order: &ParentOrder, market: &MarketInfo, price: &Price) -> Price {
+    let tick = match &market.price_tick {
+        Some(tick) => tick,
+        None => return price.clone(),
+    };
+    // Limits off the tick are rounded to the passive side, to stay within them
+    match (order.side, &order.limit_price) {
+        (OrderSide::Buy, Some(limit)) => std::cmp::min(helpers::ceil_to_increment(price, tick), helpers::floor_to_increment(limit, tick)),
+        (OrderSide::Buy, None) => helpers::ceil_to_increment(price, tick),
+        (OrderSide::Sell, Some(limit)) => std::cmp::max(helpers::floor_to_increment(price, tick), helpers::ceil_to_increment(limit, tick)),
+        (OrderSide::Sell, None) => helpers::floor_to_increment(price, tick),
+    }
+}
+
+/// A child order sent to its account, with the answer of the exchange
+#[derive(Debug)]
+pub struct ChildExecution {
+    pub order: ChildOrder,
+    pub result: Result<OrderInfo>,
+}
+
+/// Aggregated result of a routed order. Child orders are limit orders, their fills are followed
+/// with `ExchangeApi::order_status` or the user data streams.
+#[derive(Debug)]
+pub struct RouteExecution {
+    pub pair: Pair,
+    pub side: OrderSide,
+    pub requested: Volume,
+    /// Quantity of the child orders the exchanges accepted
+    pub routed: Volume,
+    /// Expected average price of the accepted child orders, fees included
+    pub average_price: Option<Price>,
+    pub expected_fees: Amount,
+    pub children: Vec<ChildExecution>,
+}
+
+impl RouteExecution {
+    /// Quantity left unrouted : not enough liquidity within the limit, funds or rejected orders
+    pub fn unrouted(&self) -> Volume {
+        self.requested.clone() - self.routed.clone()
+    }
+}
+
+/// Routes orders over several accounts, see `plan_route`
+#[derive(Debug)]
+pub struct SmartOrderRouter {
+    accounts: Vec<RouterAccount>,
+    /// Market rules by account, loaded on the first route
+    markets: Vec<Option<Markets>>,
+}
+
+impl SmartOrderRouter {
+    pub fn new(accounts: Vec<RouterAccount>) -> SmartOrderRouter {
+        let markets = accounts.iter().map(|_| None).collect();
+        SmartOrderRouter { accounts, markets }
+    }
+
+    pub fn accounts(&self) -> &[RouterAccount] {
+        &self.accounts
+    }
+
+    /// Read the book, balances and fees of an account
+    async fn quote(&mut self, i: usize, order: &ParentOrder) -> Result<VenueQuote> {
+        let account = &mut self.accounts[i];
+        if self.markets[i].is_none() {
+            self.markets[i] = match account.api.markets().await {
+                Ok(markets) => Some(markets),
+                Err(Error(ErrorKind::Unsupported(_), _)) => Some(Markets::new()),
+                Err(e) => return Err(e),
+            };
+        }
+        let market = self.markets[i].as_ref().and_then(|markets| markets.get(&Symbol::from(order.pair))).cloned();
+        let book = account.api.orderbook(order.pair).await?;
+        let balances = account.api.balances().await?;
+        let symbol = Symbol::from(order.pair);
+        let asset = match order.side {
+            OrderSide::Buy => symbol.quote,
+            OrderSide::Sell => symbol.base,
+        };
+        let available = asset.currency().map(|c| balances.get(&c).cloned().unwrap_or_else(BigDecimal::zero));
+        let taker_fee = account.taker_fee.clone()
+            .or_else(|| market.as_ref().and_then(|m| m.fee_tier.as_ref()).map(|f| f.taker.clone()))
+            .ok_or_else(|| Error::from(ErrorKind::MissingField("taker_fee".to_string())))?;
+        Ok(VenueQuote { exchange: account.exchange, book, available, taker_fee, market })
+    }
+
+    /// Read every account, plan the route and send the child orders with `ExchangeApi::add_order`.
+    /// Accounts that cannot be read or without a known taker fee are left out of the route.
+    pub async fn route(&mut self, order: ParentOrder) -> Result<RouteExecution> {
+        if order.quantity <= BigDecimal::zero() {
+            return Err(ErrorKind::InsufficientOrderSize.into());
+        }
+        let mut quotes = vec![];
+        let mut quoted_accounts = vec![];
+        for i in 0..self.accounts.len() {
+            match self.quote(i, &order).await {
+                Ok(quote) => {
+                    quotes.push(quote);
+                    quoted_accounts.push(i);
+                }
+                Err(e) => warn!("Router : {:?} left out of the route : {}", self.accounts[i].exchange, e),
+            }
+        }
+
+        let order_type = match order.side {
+            OrderSide::Buy => OrderType::BuyLimit,
+            OrderSide::Sell => OrderType::SellLimit,
+        };
+        let mut routed = BigDecimal::zero();
+        let mut value = BigDecimal::zero();
+        let mut expected_fees = BigDecimal::zero();
+        let mut children = vec![];
+        for child in plan_route(&order, &quotes) {
+            let api = &mut self.accounts[quoted_accounts[child.venue]].api;
+            let result = api.add_order(order_type, order.pair, child.quantity.clone(), Some(child.price.clone())).await;
+            if result.is_ok() {
+                routed = routed + child.quantity.clone();
+                value = value + child.expected_value.clone();
+                expected_fees = expected_fees + child.expected_fee.clone();
+            }
+            children.push(ChildExecution { order: child, result });
+        }
+        let average_price = if routed.is_zero() { None } else { Some(value / routed.clone()) };
+        Ok(RouteExecution {
+            pair: order.pair,
+            side: order.side,
+            requested: order.quantity,
+            routed,
+            average_price,
+            expected_fees,
+            children,
+        })
+    }
+}
+
+#[cfg(test)]
+mod router_tests {
+    use super::*;
+    use crate::test_util::{book, decimal};
+
+    fn venue(exchange: Exchange, asks: &[(&str, &str)], fee: &str, available: Option<&str>) -> VenueQuote {
+        VenueQuote {
+            exchange,
+            book: book(Pair::BTC_EUR, asks, &[]),
+            available: available.map(decimal),
+            taker_fee: decimal(fee),
+            market: None,
+        }
+    }
+
+    #[test]
+    fn should_route_to_cheapest_levels_with_fees() {
+        let venues = vec![
+            // Cheaper book but a 1% fee
+            venue(Exchange::Kraken, &[("100", "1"), ("101", "1")], "0.01", None),
+            venue(Exchange::Binance, &[("100.5", "3"), ("102", "5")], "0", Some("150.75")),
+        ];
+        let order = ParentOrder { pair: Pair::BTC_EUR, side: OrderSide::Buy, quantity: decimal("2.5"), limit_price: Some(decimal("101.5")) };
+        let children = plan_route(&order, &venues);
+        // Binance funds only pay for 1.5 at 100.5, then 100 (101 with fees) on Kraken
+        assert_eq!(children.len(), 2);
+        assert_eq!((children[0].exchange, children[0].quantity.clone(), children[0].price.clone()), (Exchange::Kraken, decimal("1"), decimal("100")));
+        assert_eq!(children[0].expected_value, decimal("101"));
+        assert_eq!(children[0].expected_fee, decimal("1"));
+        assert_eq!((children[1].exchange, children[1].quantity.clone(), children[1].price.clone()), (Exchange::Binance, decimal("1.5"), decimal("100.5")));
+
+        // Levels over the limit price are not taken
+        let order = ParentOrder { limit_price: Some(decimal("100")), ..order };
+        let children = plan_route(&order, &venues);
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].quantity, decimal("1"));
+    }
+
+    #[test]
+    fn should_round_child_prices_within_the_limit() {
+        let mut market = MarketInfo::new(Symbol::new("BTC", "EUR"), "BTCEUR");
+        market.price_tick = Some(decimal("1"));
+        let buy = ParentOrder { pair: Pair::BTC_EUR, side: OrderSide::Buy, quantity: decimal("1"), limit_price: None };
+        assert_eq!(child_price(&buy, &market, &decimal("100.2")), decimal("101"));
+        let buy = ParentOrder { limit_price: Some(decimal("101.5")), ..buy };
+        assert_eq!(child_price(&buy, &market, &decimal("100.2")), decimal("101"));
+        let buy = ParentOrder { limit_price: Some(decimal("100.5")), ..buy };
+        assert_eq!(child_price(&buy, &market, &decimal("100")), decimal("100"));
+
+        let sell = ParentOrder { side: OrderSide::Sell, ..buy };
+        assert_eq!(child_price(&sell, &market, &decimal("101")), decimal("101"));
+        assert_eq!(child_price(&sell, &market, &decimal("100.8")), decimal("101"));
+        let sell = ParentOrder { limit_price: None, ..sell };
+        assert_eq!(child_price(&sell, &market, &decimal("100.8")), decimal("100"));
+    }
+}
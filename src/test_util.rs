@@ -0,0 +1,17 @@
+//! Fixtures shared by the unit tests
+
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+use crate::types::{LiveAggregatedOrderBook, Orderbook, Pair};
+
+pub fn decimal(s: &str) -> BigDecimal {
+    BigDecimal::from_str(s).unwrap()
+}
+
+/// A book as sent by a live feed, best levels first whatever the order of the levels given
+pub fn book(pair: Pair, asks: &[(&str, &str)], bids: &[(&str, &str)]) -> Orderbook {
+    let levels = |l: &[(&str, &str)]| l.iter().map(|(p, v)| (decimal(p), decimal(v))).collect::<Vec<_>>();
+    let mut agg = LiveAggregatedOrderBook::default(pair);
+    agg.apply_snapshot(None, levels(asks).into_iter(), levels(bids).into_iter());
+    agg.latest_order_book().unwrap()
+}
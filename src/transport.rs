@@ -0,0 +1,127 @@
+//! HTTP transport of the REST clients, replaceable to target a mock server or to route requests
+//! through a gateway.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use hyper_tls::HttpsConnector;
+use std::fmt::Debug;
+use std::sync::Arc;
+use crate::error::*;
+
+/// Response of a request with its whole body
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub body: Bytes,
+}
+
+#[async_trait]
+pub trait HttpTransport: Debug + Send + Sync {
+    /// Send a request and read the whole response
+    async fn send(&self, req: Request<Body>) -> Result<HttpResponse>;
+
+    async fn get(&self, url: &str) -> Result<HttpResponse> {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(url)
+            .body(Body::empty())
+            .map_err(|_e| ErrorKind::BadParse)?;
+        self.send(req).await
+    }
+}
+
+/// Sends requests with a hyper client over HTTPS (plain HTTP urls are allowed)
+#[derive(Debug, Clone)]
+pub struct HyperTransport {
+    client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl Default for HyperTransport {
+    fn default() -> Self {
+        HyperTransport { client: Client::builder().build::<_, Body>(HttpsConnector::new()) }
+    }
+}
+
+#[async_trait]
+impl HttpTransport for HyperTransport {
+    async fn send(&self, req: Request<Body>) -> Result<HttpResponse> {
+        let resp = self.client.request(req).await?;
+        let status = resp.status();
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        Ok(HttpResponse { status, body })
+    }
+}
+
+pub fn default_transport() -> Arc<dyn HttpTransport> {
+    Arc::new(HyperTransport::default())
+}
+
+/// Overrides of the HTTP settings of a REST client, see `Coinnect::new_with_http`
+#[derive(Debug, Clone, Default)]
+pub struct HttpSettings {
+    /// Replaces the scheme and host of the exchange, such as `http://localhost:8080`
+    pub base_url: Option<String>,
+    pub transport: Option<Arc<dyn HttpTransport>>,
+}
+
+impl HttpSettings {
+    pub fn is_default(&self) -> bool {
+        self.base_url.is_none() && self.transport.is_none()
+    }
+}
+
+/// REST clients whose transport and host can be replaced
+pub trait HttpClient {
+    fn set_transport(&mut self, transport: Arc<dyn HttpTransport>);
+
+    /// Send the requests to another host, such as `http://localhost:8080`
+    fn set_base_url(&mut self, base_url: &str);
+
+    fn apply_http(&mut self, http: &HttpSettings) {
+        if let Some(base_url) = &http.base_url {
+            self.set_base_url(base_url);
+        }
+        if let Some(transport) = &http.transport {
+            self.set_transport(transport.clone());
+        }
+    }
+}
+
+/// Remove the trailing slashes of a base url
+pub(crate) fn trim_base_url(base_url: &str) -> String {
+    base_url.trim_end_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod transport_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::kraken::{KrakenApi, KrakenCreds};
+
+    /// Answers every request with the same body and keeps their urls
+    #[derive(Debug, Default)]
+    struct CannedTransport {
+        urls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl HttpTransport for CannedTransport {
+        async fn send(&self, req: Request<Body>) -> Result<HttpResponse> {
+            self.urls.lock().unwrap().push(req.uri().to_string());
+            Ok(HttpResponse { status: StatusCode::OK, body: Bytes::from_static(b"{\"error\":[],\"result\":{\"unixtime\":1534614248}}") })
+        }
+    }
+
+    #[test]
+    fn should_send_requests_to_the_base_url() {
+        let transport = Arc::new(CannedTransport::default());
+        let mut api = KrakenApi::new(KrakenCreds::new("", "", "")).unwrap();
+        api.apply_http(&HttpSettings { base_url: Some("http://localhost:1234/".to_string()), transport: Some(transport.clone()) });
+
+        let result = futures::executor::block_on(api.get_server_time()).unwrap();
+        assert_eq!(result["result"]["unixtime"], 1534614248);
+        assert_eq!(*transport.urls.lock().unwrap(), vec!["http://localhost:1234/0/public/Time?".to_string()]);
+    }
+}
@@ -1,9 +1,16 @@
 //! Types definition used for handling returned data when generic API is used.
 
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap, VecDeque};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use bigdecimal::{BigDecimal, ToPrimitive, Zero};
 use std::str::FromStr;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
 use crate::error::{ErrorKind, Result};
+use crate::helpers;
 
 pub type Amount = BigDecimal;
 pub type Price = BigDecimal;
@@ -37,9 +44,17 @@ pub enum Channel {
     LiveOrderBook,
     LiveDetailOrderBook,
     LiveFullOrderBook,
+    /// Private orders, fills and balances of the account
+    UserData,
+    /// Last price, top of book and volume, as a `Ticker`
+    LiveTicker,
+    /// Top of book only, as a `BestBidOffer`
+    LiveBestBidOffer,
+    /// Candles of the interval, built from trades where the exchange has no candle feed
+    LiveCandles(Interval),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ticker {
     /// UNIX timestamp in ms (when the response was received)
     pub timestamp: i64,
@@ -56,6 +71,20 @@ pub struct Ticker {
     pub volume: Option<Volume>,
 }
 
+/// Best bid and best offer of a pair, sent by `LiveBestBidOffer` feeds
+#[derive(Debug, Clone)]
+pub struct BestBidOffer {
+    /// UNIX timestamp in ms (when the top of book changed, or when it was received if the exchange
+    /// does not tell)
+    pub event_ms: i64,
+    pub pair: Pair,
+    pub bid: Price,
+    pub ask: Price,
+    /// None when the exchange does not send the sizes
+    pub bid_volume: Option<Volume>,
+    pub ask_volume: Option<Volume>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Orderbook {
     /// UNIX timestamp in ms (when the response was received)
@@ -84,67 +113,295 @@ impl Orderbook {
     }
 }
 
+/// Synchronization state of a local order book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookState {
+    /// A snapshot was received and every update since was applied in sequence
+    Synced,
+    /// A snapshot is expected (first connection or gap detected), updates are buffered
+    Resyncing,
+    /// The book could not be synchronized, it is kept as is until the next snapshot
+    Stale,
+}
+
+/// A diff of an order book, levels with a zero volume are removed
+#[derive(Debug, Clone)]
+pub struct BookUpdate {
+    /// First and last exchange update ids covered by the diff, None when the exchange does not
+    /// sequence its updates
+    pub update_ids: Option<(u64, u64)>,
+    pub asks: Vec<(Price, Volume)>,
+    pub bids: Vec<(Price, Volume)>,
+}
+
+/// When a live order book is sent to the recipients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookEmission {
+    /// When one of the `depth` best levels changed
+    TopOfBook,
+    /// After every update, even beyond the `depth` best levels
+    AnyChange,
+}
+
+impl Default for BookEmission {
+    fn default() -> Self {
+        BookEmission::TopOfBook
+    }
+}
+
+/// Options of the live order books of a stream
+#[derive(Debug, Clone, Deserialize)]
+pub struct BookSettings {
+    /// Levels sent on each side
+    #[serde(default = "default_book_depth")]
+    pub depth: usize,
+    /// Minimum delay between two books sent for a pair, in ms. Changes made during the delay are
+    /// sent at the end of it, see `LiveBooks::on_flush`.
+    #[serde(default)]
+    pub throttle_ms: i64,
+    #[serde(default)]
+    pub emission: BookEmission,
+}
+
+fn default_book_depth() -> usize {
+    DEFAULT_BOOK_DEPTH
+}
+
+impl Default for BookSettings {
+    fn default() -> Self {
+        BookSettings {
+            depth: DEFAULT_BOOK_DEPTH,
+            throttle_ms: 0,
+            emission: BookEmission::default(),
+        }
+    }
+}
+
+/// Time of a live feed, the system clock unless a replay sets it to the reception time of its
+/// frames, see `replay::FeedReplay`
+#[derive(Debug, Clone, Default)]
+pub struct FeedClock(Rc<Cell<Option<i64>>>);
+
+impl FeedClock {
+    /// UNIX timestamp in ms
+    pub fn now_ms(&self) -> i64 {
+        self.0.get().unwrap_or_else(|| Utc::now().timestamp_millis())
+    }
+
+    /// Stop following the system clock, the time stays at `now_ms` until set again
+    pub fn set(&self, now_ms: i64) {
+        self.0.set(Some(now_ms));
+    }
+}
+
 #[derive(Debug)]
 pub struct LiveAggregatedOrderBook {
-    pub depth: i8,
+    pub depth: usize,
+    pub throttle_ms: i64,
+    pub emission: BookEmission,
     pub pair: Pair,
     pub asks_by_price: BTreeMap<Price, (Price, Volume)>,
     pub bids_by_price: BTreeMap<Price, (Price, Volume)>,
     pub last_asks: Vec<(Price, Volume)>,
     pub last_bids: Vec<(Price, Volume)>,
+    /// UNIX timestamp in ms of the last book returned by `latest_order_book`
+    pub last_emit_ms: i64,
+    /// A book was held back by the throttle since the last one returned
+    pub throttled: bool,
+    pub state: BookState,
+    /// Update id of the last snapshot or update applied
+    pub last_update_id: Option<u64>,
+    /// Time of the books and states returned, and of the throttle
+    pub clock: FeedClock,
+    pending: VecDeque<BookUpdate>,
 }
 
-const DEFAULT_BOOK_DEPTH: i8 = 5;
+const DEFAULT_BOOK_DEPTH: usize = 5;
+/// Updates kept while waiting for a snapshot, the oldest are dropped first
+const MAX_PENDING_UPDATES: usize = 1000;
 
 impl LiveAggregatedOrderBook {
     pub fn default(pair: Pair) -> LiveAggregatedOrderBook {
+        LiveAggregatedOrderBook::new(pair, &BookSettings::default())
+    }
+
+    pub fn new(pair: Pair, settings: &BookSettings) -> LiveAggregatedOrderBook {
         LiveAggregatedOrderBook {
-            depth: DEFAULT_BOOK_DEPTH,
+            depth: settings.depth,
+            throttle_ms: settings.throttle_ms,
+            emission: settings.emission,
             pair,
             asks_by_price: BTreeMap::new(),
             bids_by_price: BTreeMap::new(),
             last_asks: vec![],
             last_bids: vec![],
+            last_emit_ms: 0,
+            throttled: false,
+            state: BookState::Resyncing,
+            last_update_id: None,
+            clock: FeedClock::default(),
+            pending: VecDeque::new(),
         }
     }
 
     pub fn order_book(&self) -> Orderbook {
-        let asks: Vec<(Price, Volume)> = self.asks_by_price.iter().map(|(_, v)| v.clone()).take(self.depth as usize).rev().collect();
-        let bids: Vec<(Price, Volume)> = self.bids_by_price.iter().rev().map(|(_, v)| v.clone()).take(self.depth as usize).rev().collect();
+        let asks: Vec<(Price, Volume)> = self.asks_by_price.iter().map(|(_, v)| v.clone()).take(self.depth).collect();
+        let bids: Vec<(Price, Volume)> = self.bids_by_price.iter().rev().map(|(_, v)| v.clone()).take(self.depth).collect();
         Orderbook {
-            timestamp: Utc::now().timestamp_millis(),
+            timestamp: self.clock.now_ms(),
             pair: self.pair,
             asks,
             bids,
         }
     }
 
+    /// Return every level of the book, asks by ascending price and bids by descending price
+    pub fn full_order_book(&self) -> Orderbook {
+        Orderbook {
+            timestamp: self.clock.now_ms(),
+            pair: self.pair,
+            asks: self.asks_by_price.values().cloned().collect(),
+            bids: self.bids_by_price.values().rev().cloned().collect(),
+        }
+    }
+
+    /// Return the `depth` best levels if the book is synced and should be sent according to the
+    /// emission and throttle settings
     pub fn latest_order_book(&mut self) -> Option<Orderbook> {
+        if self.state != BookState::Synced {
+            return None;
+        }
         let latest_order_book: Orderbook = self.order_book();
-        if latest_order_book.asks == self.last_asks && latest_order_book.bids == self.last_bids {
+        if self.throttle_ms > 0 && latest_order_book.timestamp - self.last_emit_ms < self.throttle_ms {
+            trace!("Order book throttled, not flushing");
+            self.throttled = true;
+            return None;
+        }
+        if self.emission == BookEmission::TopOfBook && latest_order_book.asks == self.last_asks && latest_order_book.bids == self.last_bids {
             trace!("Order book top unchanged, not flushing");
             return None;
+        }
+        self.last_asks = latest_order_book.asks.clone();
+        self.last_bids = latest_order_book.bids.clone();
+        self.last_emit_ms = latest_order_book.timestamp;
+        self.throttled = false;
+        Some(latest_order_book)
+    }
+
+    /// Return the best bid and best ask levels, None if the book is not synced or a side is empty
+    pub fn top_of_book(&self) -> Option<((Price, Volume), (Price, Volume))> {
+        if self.state != BookState::Synced {
+            return None;
+        }
+        let bid = self.bids_by_price.values().next_back()?.clone();
+        let ask = self.asks_by_price.values().next()?.clone();
+        Some((bid, ask))
+    }
+
+    /// Return the current state as a live event
+    pub fn book_state(&self) -> LiveBookState {
+        LiveBookState {
+            event_ms: self.clock.now_ms(),
+            pair: self.pair,
+            state: self.state,
+        }
+    }
+
+    /// Replace the whole book with a snapshot, then apply the buffered updates that follow it.
+    /// Return the new state if it changed.
+    pub fn apply_snapshot<A, B>(&mut self, last_update_id: Option<u64>, asks: A, bids: B) -> Option<BookState>
+        where
+            A: Iterator<Item=(BigDecimal, BigDecimal)>,
+            B: Iterator<Item=(BigDecimal, BigDecimal)> {
+        let previous = self.state;
+        self.reset_asks(asks);
+        self.reset_bids(bids);
+        self.last_update_id = last_update_id;
+        self.state = BookState::Synced;
+        let pending: Vec<BookUpdate> = self.pending.drain(..).collect();
+        for update in pending {
+            // Updates received before an unsequenced snapshot cannot be ordered against it
+            if last_update_id.is_some() && update.update_ids.is_some() {
+                self.apply_update(update);
+            }
+        }
+        self.state_change(previous)
+    }
+
+    /// Apply a diff if it follows the last update applied, buffer it if a snapshot is expected.
+    /// A gap in the update ids switches the book to `BookState::Resyncing`, the owner is
+    /// expected to request a new snapshot. Return the new state if it changed.
+    pub fn apply_update(&mut self, update: BookUpdate) -> Option<BookState> {
+        let previous = self.state;
+        if self.state != BookState::Synced {
+            self.buffer(update);
+            return None;
+        }
+        if let (Some(last_update_id), Some((first, last))) = (self.last_update_id, update.update_ids) {
+            if last <= last_update_id {
+                // Already part of the book
+                return None;
+            }
+            if first > last_update_id + 1 {
+                debug!("Order book {:?} : gap between update ids {} and {}", self.pair, last_update_id, first);
+                self.state = BookState::Resyncing;
+                self.buffer(update);
+                return self.state_change(previous);
+            }
+            self.last_update_id = Some(last);
+        }
+        for ask in update.asks {
+            self.update_ask(ask);
+        }
+        for bid in update.bids {
+            self.update_bid(bid);
+        }
+        None
+    }
+
+    /// Wait for a new snapshot, buffered updates are dropped. Return the new state if it changed.
+    pub fn start_resync(&mut self) -> Option<BookState> {
+        let previous = self.state;
+        self.pending.clear();
+        self.state = BookState::Resyncing;
+        self.state_change(previous)
+    }
+
+    /// Flag the book as out of sync with no snapshot expected. Return the new state if it changed.
+    pub fn mark_stale(&mut self) -> Option<BookState> {
+        let previous = self.state;
+        self.state = BookState::Stale;
+        self.state_change(previous)
+    }
+
+    fn state_change(&self, previous: BookState) -> Option<BookState> {
+        if self.state != previous {
+            Some(self.state)
         } else {
-            self.last_asks = latest_order_book.asks.clone();
-            self.last_bids = latest_order_book.bids.clone();
-            Some(latest_order_book)
+            None
         }
     }
 
+    fn buffer(&mut self, update: BookUpdate) {
+        if self.pending.len() >= MAX_PENDING_UPDATES {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(update);
+    }
+
     pub fn reset_asks<I>(&mut self, iter: I)
         where
             I: Iterator<Item=(BigDecimal, BigDecimal)> {
-        for kp in iter {
-            self.asks_by_price.entry(kp.0.clone()).or_insert(kp);
-        }
+        self.asks_by_price.clear();
+        self.update_asks(iter);
     }
 
     pub fn reset_bids<I>(&mut self, iter: I)
         where
             I: Iterator<Item=(BigDecimal, BigDecimal)> {
-        for kp in iter {
-            self.bids_by_price.entry(kp.0.clone()).or_insert(kp);
-        }
+        self.bids_by_price.clear();
+        self.update_bids(iter);
     }
 
     pub fn update_asks<I>(&mut self, iter: I)
@@ -156,11 +413,11 @@ impl LiveAggregatedOrderBook {
     }
 
     pub fn update_ask(&mut self, kp: (BigDecimal, BigDecimal)) {
-        let bids = &mut self.asks_by_price;
+        let asks = &mut self.asks_by_price;
         if kp.1.is_zero() {
-            bids.remove(&kp.0.clone());
+            asks.remove(&kp.0);
         } else {
-            bids.entry(kp.0.clone()).or_insert(kp);
+            asks.insert(kp.0.clone(), kp);
         }
     }
 
@@ -168,20 +425,155 @@ impl LiveAggregatedOrderBook {
         where
             I: Iterator<Item=(BigDecimal, BigDecimal)> {
         for kp in iter {
-            self.update_ask(kp)
+            self.update_bid(kp)
         }
     }
 
     pub fn update_bid(&mut self, kp: (BigDecimal, BigDecimal)) {
         let bids = &mut self.bids_by_price;
         if kp.1.is_zero() {
-            bids.remove(&kp.0.clone());
+            bids.remove(&kp.0);
         } else {
-            bids.entry(kp.0.clone()).or_insert(kp);
+            bids.insert(kp.0.clone(), kp);
         }
     }
 }
 
+/// Sends the books held back by the throttle, see `LiveBooks::on_flush`
+#[derive(Clone)]
+struct BookFlush(Rc<dyn Fn(Orderbook)>);
+
+impl fmt::Debug for BookFlush {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BookFlush")
+    }
+}
+
+/// Delay in ms until the end of the throttle window of a book held back by the throttle
+fn throttle_left_ms(book: &LiveAggregatedOrderBook) -> Option<i64> {
+    if book.throttled {
+        Some(book.last_emit_ms + book.throttle_ms - book.clock.now_ms())
+    } else {
+        None
+    }
+}
+
+/// Live order books of a stream, shared between its websocket handler and its bot
+#[derive(Debug, Clone)]
+pub struct LiveBooks {
+    settings: BookSettings,
+    clock: FeedClock,
+    books: Rc<RefCell<HashMap<Pair, LiveAggregatedOrderBook>>>,
+    flush: Rc<RefCell<Option<BookFlush>>>,
+    /// Pairs waiting for the end of their throttle window
+    flushing: Rc<RefCell<HashSet<Pair>>>,
+}
+
+impl LiveBooks {
+    pub fn new(settings: BookSettings) -> LiveBooks {
+        LiveBooks {
+            settings,
+            clock: FeedClock::default(),
+            books: Rc::new(RefCell::new(HashMap::new())),
+            flush: Rc::new(RefCell::new(None)),
+            flushing: Rc::new(RefCell::new(HashSet::new())),
+        }
+    }
+
+    pub fn settings(&self) -> &BookSettings {
+        &self.settings
+    }
+
+    /// Clock of the books and of the events derived by the handler of the stream
+    pub fn clock(&self) -> &FeedClock {
+        &self.clock
+    }
+
+    /// Send the books held back by the throttle with `f` at the end of the throttle window,
+    /// when no other change comes to flush them. Flushes are scheduled on the running actix system.
+    pub fn on_flush<F>(&self, f: F) where F: Fn(Orderbook) + 'static {
+        *self.flush.borrow_mut() = Some(BookFlush(Rc::new(f)));
+    }
+
+    /// Send the flushed books of the exchange to the recipients of the stream, see `on_flush`
+    pub fn flush_to(&self, exchange: Exchange, recipients: Vec<actix::Recipient<LiveEventEnveloppe>>) {
+        self.on_flush(move |ob| {
+            for r in &recipients {
+                if let Err(e) = r.do_send(LiveEventEnveloppe(exchange, LiveEvent::LiveOrderbook(ob.clone()))) {
+                    trace!("Flushed order book not delivered : {:?}", e);
+                }
+            }
+        });
+    }
+
+    /// Run `f` on the book of the pair, the book is created on first use
+    pub fn with_book<R, F>(&self, pair: Pair, f: F) -> R where F: FnOnce(&mut LiveAggregatedOrderBook) -> R {
+        let (r, throttled_ms) = {
+            let mut books = self.books.borrow_mut();
+            let (settings, clock) = (&self.settings, &self.clock);
+            let book = books.entry(pair).or_insert_with(|| LiveAggregatedOrderBook { clock: clock.clone(), ..LiveAggregatedOrderBook::new(pair, settings) });
+            let r = f(book);
+            (r, throttle_left_ms(book))
+        };
+        if let Some(delay_ms) = throttled_ms {
+            self.schedule_flush(pair, delay_ms);
+        }
+        r
+    }
+
+    fn schedule_flush(&self, pair: Pair, delay_ms: i64) {
+        let flush = match self.flush.borrow().clone() {
+            Some(flush) => flush,
+            None => return,
+        };
+        if !actix_rt::System::is_set() || !self.flushing.borrow_mut().insert(pair) {
+            return;
+        }
+        let books = self.clone();
+        actix_rt::spawn(async move {
+            tokio::time::delay_for(std::time::Duration::from_millis(delay_ms.max(0) as u64)).await;
+            books.flushing.borrow_mut().remove(&pair);
+            let (latest, throttled_ms) = match books.books.borrow_mut().get_mut(&pair) {
+                Some(book) if book.throttled => {
+                    book.throttled = false;
+                    (book.latest_order_book(), throttle_left_ms(book))
+                }
+                // Flushed by a later change, or removed
+                _ => return,
+            };
+            if let Some(ob) = latest {
+                (flush.0)(ob);
+            }
+            if let Some(delay_ms) = throttled_ms {
+                books.schedule_flush(pair, delay_ms);
+            }
+        });
+    }
+
+    /// Wait for a new snapshot on every book, return the states that changed
+    pub fn start_resync(&self) -> Vec<LiveBookState> {
+        self.books.borrow_mut().values_mut()
+            .filter_map(|book| book.start_resync().map(|_| book.book_state()))
+            .collect()
+    }
+
+    pub fn contains(&self, pair: Pair) -> bool {
+        self.books.borrow().contains_key(&pair)
+    }
+
+    /// Drop the book of an unsubscribed pair
+    pub fn remove(&self, pair: Pair) {
+        self.books.borrow_mut().remove(&pair);
+    }
+
+    /// Return every level of the book of the pair, None if there is no synced book for it
+    pub fn full_order_book(&self, pair: Pair) -> Option<Orderbook> {
+        self.books.borrow().get(&pair)
+            .filter(|book| book.state == BookState::Synced)
+            .map(|book| book.full_order_book())
+    }
+}
+
 #[derive(Debug)]
 pub struct OrderInfo {
     /// UNIX timestamp in ms (when the response was received)
@@ -191,7 +583,7 @@ pub struct OrderInfo {
     pub identifier: Vec<String>,
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
 pub enum OrderType {
     BuyLimit,
     SellLimit,
@@ -199,6 +591,231 @@ pub enum OrderType {
     SellMarket,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum OrderKind {
+    Limit,
+    Market,
+    /// Market order sent when the stop price is reached
+    Stop,
+    /// Limit order sent when the stop price is reached
+    StopLimit,
+    /// Order sent when the stop price is reached in the profitable direction, a limit order if
+    /// a price is set, a market order otherwise
+    TakeProfit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum TimeInForce {
+    GoodTillCancel,
+    ImmediateOrCancel,
+    FillOrKill,
+    /// UNIX timestamp in ms after which the order is canceled
+    GoodTillDate(i64),
+}
+
+/// Description of an order for `ExchangeApi::place_order`.
+/// Options an exchange cannot honor are rejected with `ErrorKind::Unsupported`, they are never
+/// silently dropped.
+///
+/// ```
+/// use coinnect_rt::types::{OrderRequest, OrderSide, Pair};
+/// use std::str::FromStr;
+/// use bigdecimal::BigDecimal;
+///
+/// let order = OrderRequest::limit(OrderSide::Buy, Pair::BTC_EUR, BigDecimal::from(1), BigDecimal::from_str("6500.5").unwrap())
+///     .post_only(true)
+///     .client_order_id("my-order-1");
+/// ```
+#[derive(Debug, Clone)]
+pub struct OrderRequest {
+    pub side: OrderSide,
+    pub kind: OrderKind,
+    pub pair: Pair,
+    /// Quantity in base currency
+    pub quantity: Volume,
+    /// Limit price
+    pub price: Option<Price>,
+    /// Trigger price of stop and take profit orders
+    pub stop_price: Option<Price>,
+    pub time_in_force: TimeInForce,
+    /// Reject the order instead of taking liquidity
+    pub post_only: bool,
+    /// Only reduce an open position
+    pub reduce_only: bool,
+    pub client_order_id: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn new(side: OrderSide, kind: OrderKind, pair: Pair, quantity: Volume) -> OrderRequest {
+        OrderRequest {
+            side,
+            kind,
+            pair,
+            quantity,
+            price: None,
+            stop_price: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            post_only: false,
+            reduce_only: false,
+            client_order_id: None,
+        }
+    }
+
+    pub fn limit(side: OrderSide, pair: Pair, quantity: Volume, price: Price) -> OrderRequest {
+        OrderRequest::new(side, OrderKind::Limit, pair, quantity).price(price)
+    }
+
+    pub fn market(side: OrderSide, pair: Pair, quantity: Volume) -> OrderRequest {
+        OrderRequest::new(side, OrderKind::Market, pair, quantity)
+    }
+
+    pub fn stop(side: OrderSide, pair: Pair, quantity: Volume, stop_price: Price) -> OrderRequest {
+        OrderRequest::new(side, OrderKind::Stop, pair, quantity).stop_price(stop_price)
+    }
+
+    pub fn stop_limit(side: OrderSide, pair: Pair, quantity: Volume, stop_price: Price, price: Price) -> OrderRequest {
+        OrderRequest::new(side, OrderKind::StopLimit, pair, quantity).stop_price(stop_price).price(price)
+    }
+
+    pub fn take_profit(side: OrderSide, pair: Pair, quantity: Volume, stop_price: Price) -> OrderRequest {
+        OrderRequest::new(side, OrderKind::TakeProfit, pair, quantity).stop_price(stop_price)
+    }
+
+    pub fn price(mut self, price: Price) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn stop_price(mut self, stop_price: Price) -> Self {
+        self.stop_price = Some(stop_price);
+        self
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn post_only(mut self, post_only: bool) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = reduce_only;
+        self
+    }
+
+    pub fn client_order_id(mut self, client_order_id: &str) -> Self {
+        self.client_order_id = Some(client_order_id.to_string());
+        self
+    }
+
+    /// Check that the prices required by the order kind are set
+    pub fn check_prices(&self) -> Result<()> {
+        match self.kind {
+            OrderKind::Limit | OrderKind::StopLimit if self.price.is_none() => {
+                Err(ErrorKind::MissingPrice.into())
+            }
+            OrderKind::Stop | OrderKind::StopLimit | OrderKind::TakeProfit if self.stop_price.is_none() => {
+                Err(ErrorKind::MissingField("stop_price".to_string()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Return the `OrderType` of a plain limit or market order, for exchanges that support
+    /// nothing more. Any other option is `Unsupported`.
+    pub fn basic_order_type(&self) -> Result<OrderType> {
+        self.check_prices()?;
+        match self.time_in_force {
+            TimeInForce::GoodTillCancel => (),
+            tif => return Err(ErrorKind::Unsupported(format!("time in force {:?}", tif)).into()),
+        }
+        if self.post_only {
+            return Err(ErrorKind::Unsupported("post-only orders".to_string()).into());
+        }
+        if self.reduce_only {
+            return Err(ErrorKind::Unsupported("reduce-only orders".to_string()).into());
+        }
+        if self.client_order_id.is_some() {
+            return Err(ErrorKind::Unsupported("client order ids".to_string()).into());
+        }
+        match (self.side, self.kind) {
+            (OrderSide::Buy, OrderKind::Limit) => Ok(OrderType::BuyLimit),
+            (OrderSide::Sell, OrderKind::Limit) => Ok(OrderType::SellLimit),
+            (OrderSide::Buy, OrderKind::Market) => Ok(OrderType::BuyMarket),
+            (OrderSide::Sell, OrderKind::Market) => Ok(OrderType::SellMarket),
+            (_, kind) => Err(ErrorKind::Unsupported(format!("{:?} orders", kind)).into()),
+        }
+    }
+}
+
+impl From<(OrderType, Pair, Volume, Option<Price>)> for OrderRequest {
+    /// Build the request equivalent to an `add_order` call
+    fn from((order_type, pair, quantity, price): (OrderType, Pair, Volume, Option<Price>)) -> Self {
+        let (side, kind) = match order_type {
+            OrderType::BuyLimit => (OrderSide::Buy, OrderKind::Limit),
+            OrderType::SellLimit => (OrderSide::Sell, OrderKind::Limit),
+            OrderType::BuyMarket => (OrderSide::Buy, OrderKind::Market),
+            OrderType::SellMarket => (OrderSide::Sell, OrderKind::Market),
+        };
+        OrderRequest {
+            price,
+            ..OrderRequest::new(side, kind, pair, quantity)
+        }
+    }
+}
+
+/// Lifecycle state of an order, normalized across exchanges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderStatus {
+    /// Accepted by the exchange but not yet active (Kraken `pending`)
+    Pending,
+    /// Resting in the book, nothing executed yet
+    Open,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Rejected,
+    Expired,
+    /// The exchange returned a state that could not be mapped
+    Unknown,
+}
+
+impl OrderStatus {
+    /// Return true if the order can still be executed or canceled
+    pub fn is_open(&self) -> bool {
+        match self {
+            OrderStatus::Pending | OrderStatus::Open | OrderStatus::PartiallyFilled => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Order {
+    /// Same identifiers as the ones returned in `OrderInfo` by `add_order`
+    pub identifier: Vec<String>,
+    /// UNIX timestamp in ms (when the order was opened)
+    pub timestamp: i64,
+    pub pair: Pair,
+    pub order_type: OrderType,
+    pub status: OrderStatus,
+    /// Limit price, None for market orders
+    pub price: Option<Price>,
+    /// Total quantity of the order
+    pub quantity: Volume,
+    /// Quantity already executed
+    pub executed_quantity: Volume,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TradeType {
     Sell,
@@ -234,12 +851,228 @@ impl Into<i32> for TradeType {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Trade {
+    /// Identifier of the trade on the exchange, if provided
+    pub id: Option<String>,
+    /// UNIX timestamp in ms (when the trade occured)
+    pub timestamp: i64,
+    pub pair: Pair,
+    /// Price of the trade
+    pub price: Price,
+    /// Amount of the trade, in base currency
+    pub volume: Volume,
+    /// Side of the taker, None if the exchange does not tell
+    pub tt: TradeType,
+}
+
+/// Candle durations supported by the generic API, not every exchange supports every interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Interval {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    ThirtyMinutes,
+    OneHour,
+    TwoHours,
+    FourHours,
+    OneDay,
+    OneWeek,
+}
+
+impl Interval {
+    /// Duration of the interval in seconds
+    pub fn as_secs(&self) -> i64 {
+        match self {
+            Interval::OneMinute => 60,
+            Interval::FiveMinutes => 5 * 60,
+            Interval::FifteenMinutes => 15 * 60,
+            Interval::ThirtyMinutes => 30 * 60,
+            Interval::OneHour => 60 * 60,
+            Interval::TwoHours => 2 * 60 * 60,
+            Interval::FourHours => 4 * 60 * 60,
+            Interval::OneDay => 24 * 60 * 60,
+            Interval::OneWeek => 7 * 24 * 60 * 60,
+        }
+    }
+
+    /// Return the UNIX timestamp in ms of the start of the interval holding `ts_ms`.
+    /// Intervals are aligned on the epoch, weeks start on mondays like exchange candles.
+    pub fn open_time(&self, ts_ms: i64) -> i64 {
+        let len = self.as_secs() * 1000;
+        // The epoch is a thursday
+        let offset = if *self == Interval::OneWeek { 4 * 24 * 60 * 60 * 1000 } else { 0 };
+        ts_ms - (ts_ms - offset).rem_euclid(len)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candle {
+    /// UNIX timestamp in ms (when the candle opens)
+    pub timestamp: i64,
+    pub pair: Pair,
+    pub interval: Interval,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    /// Traded volume, in base currency
+    pub volume: Volume,
+}
+
+/// Candle of a `LiveCandles` feed
+#[derive(Debug, Clone)]
+pub struct LiveCandle {
+    pub candle: Candle,
+    /// False while the interval of the candle is in progress
+    pub closed: bool,
+}
+
+/// Delay after the end of an interval before `close_if_over` closes its candle, for the trades of
+/// the interval that arrive late
+pub const CANDLE_CLOSE_GRACE_MS: i64 = 2_000;
+
+/// Candles of a pair for an interval, built from trades or following a native candle feed, so
+/// that every exchange sends in progress and closed candles the same way
+#[derive(Debug, Clone)]
+pub struct CandleAggregator {
+    pub pair: Pair,
+    pub interval: Interval,
+    current: Option<Candle>,
+    /// Open time of the last closed candle, later trades for it are ignored
+    closed_ms: Option<i64>,
+}
+
+impl CandleAggregator {
+    pub fn new(pair: Pair, interval: Interval) -> CandleAggregator {
+        CandleAggregator { pair, interval, current: None, closed_ms: None }
+    }
+
+    fn is_closed(&self, open_ms: i64) -> bool {
+        self.closed_ms.map_or(false, |t| open_ms <= t)
+    }
+
+    fn close(&mut self, candle: Candle) -> LiveCandle {
+        self.closed_ms = Some(candle.timestamp);
+        LiveCandle { candle, closed: true }
+    }
+
+    /// Add a trade, return the candle it closes if any then the candle in progress.
+    /// Trades older than the candle in progress are ignored.
+    pub fn add_trade(&mut self, event_ms: i64, price: Price, volume: Volume) -> Vec<LiveCandle> {
+        let open_ms = self.interval.open_time(event_ms);
+        let current_open_ms = self.current.as_ref().map(|c| c.timestamp);
+        if current_open_ms.map_or(false, |t| t > open_ms) || self.is_closed(open_ms) {
+            debug!("{:?} {:?} candles : trade at {} arrived after its candle closed, dropped", self.pair, self.interval, event_ms);
+            return vec![];
+        }
+        let mut candles = vec![];
+        if current_open_ms == Some(open_ms) {
+            let c = self.current.as_mut().unwrap();
+            if price > c.high {
+                c.high = price.clone();
+            }
+            if price < c.low {
+                c.low = price.clone();
+            }
+            c.close = price;
+            c.volume = &c.volume + &volume;
+        } else {
+            if let Some(closed) = self.current.take() {
+                candles.push(self.close(closed));
+            }
+            self.current = Some(Candle {
+                timestamp: open_ms,
+                pair: self.pair,
+                interval: self.interval,
+                open: price.clone(),
+                high: price.clone(),
+                low: price.clone(),
+                close: price,
+                volume,
+            });
+        }
+        candles.extend(self.current.clone().map(|candle| LiveCandle { candle, closed: false }));
+        candles
+    }
+
+    /// Follow a candle sent by the exchange, return the previous candle if this one closes it
+    /// then the candle itself. Candles older than the one in progress are ignored.
+    pub fn update_candle(&mut self, candle: Candle, closed: bool) -> Vec<LiveCandle> {
+        let mut candles = vec![];
+        if self.is_closed(candle.timestamp) {
+            return candles;
+        }
+        match self.current.take() {
+            Some(current) if current.timestamp > candle.timestamp => {
+                self.current = Some(current);
+                return candles;
+            }
+            Some(current) if current.timestamp < candle.timestamp => {
+                candles.push(self.close(current));
+            }
+            _ => (),
+        }
+        if closed {
+            candles.push(self.close(candle));
+        } else {
+            self.current = Some(candle.clone());
+            candles.push(LiveCandle { candle, closed });
+        }
+        candles
+    }
+
+    /// Close the candle in progress if its interval is over at `now_ms`, once
+    /// `CANDLE_CLOSE_GRACE_MS` have passed
+    pub fn close_if_over(&mut self, now_ms: i64) -> Option<LiveCandle> {
+        let len = self.interval.as_secs() * 1000;
+        if self.current.as_ref()?.timestamp + len + CANDLE_CLOSE_GRACE_MS > now_ms {
+            return None;
+        }
+        let candle = self.current.take()?;
+        Some(self.close(candle))
+    }
+}
+
+/// Candle aggregators of a stream, by pair and interval
+#[derive(Debug, Clone, Default)]
+pub struct LiveCandles {
+    aggregators: HashMap<(Pair, Interval), CandleAggregator>,
+}
+
+impl LiveCandles {
+    /// Add a trade to the candles of the `intervals` subscribed for the pair
+    pub fn add_trade(&mut self, pair: Pair, intervals: &[Interval], event_ms: i64, price: &Price, volume: &Volume) -> Vec<LiveCandle> {
+        let mut candles = vec![];
+        for &interval in intervals {
+            let agg = self.aggregators.entry((pair, interval)).or_insert_with(|| CandleAggregator::new(pair, interval));
+            candles.extend(agg.add_trade(event_ms, price.clone(), volume.clone()));
+        }
+        candles
+    }
+
+    pub fn update_candle(&mut self, candle: Candle, closed: bool) -> Vec<LiveCandle> {
+        let (pair, interval) = (candle.pair, candle.interval);
+        self.aggregators.entry((pair, interval))
+            .or_insert_with(|| CandleAggregator::new(pair, interval))
+            .update_candle(candle, closed)
+    }
+
+    /// Close the candles in progress whose interval is over at `now_ms`
+    pub fn close_over(&mut self, now_ms: i64) -> Vec<LiveCandle> {
+        self.aggregators.values_mut().filter_map(|agg| agg.close_if_over(now_ms)).collect()
+    }
+
+    pub fn remove(&mut self, pair: Pair, interval: Interval) {
+        self.aggregators.remove(&(pair, interval));
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LiveTrade {
     /// UNIX timestamp in ms (when the event occured)
     pub event_ms: i64,
-    /// The Pair corresponding to the Ticker returned (maybe useful later for asynchronous APIs)
-    pub pair: String,
+    pub pair: Pair,
     /// Amount of the trade
     pub amount: f32,
     /// Price of the trade
@@ -262,12 +1095,93 @@ pub struct LiveOrder {
     pub tt: TradeType,
 }
 
+/// Sent when the synchronization state of a local order book changes
+#[derive(Debug, Clone)]
+pub struct LiveBookState {
+    /// UNIX timestamp in ms (when the state changed)
+    pub event_ms: i64,
+    pub pair: Pair,
+    pub state: BookState,
+}
+
+/// Change of an order of the account, sent by private streams
+#[derive(Debug, Clone)]
+pub struct OrderUpdate {
+    /// UNIX timestamp in ms (when the order changed)
+    pub event_ms: i64,
+    /// Identifier of the order on the exchange
+    pub order_id: String,
+    pub pair: Pair,
+    pub status: OrderStatus,
+    /// None when the exchange only sends the changed fields
+    pub side: Option<OrderSide>,
+    /// Limit price, None for market orders or when not sent
+    pub price: Option<Price>,
+    pub quantity: Option<Volume>,
+    pub executed_quantity: Option<Volume>,
+}
+
+/// Execution of an order of the account, sent by private streams
+#[derive(Debug, Clone)]
+pub struct Fill {
+    /// UNIX timestamp in ms (when the trade occured)
+    pub event_ms: i64,
+    pub order_id: String,
+    pub trade_id: String,
+    pub pair: Pair,
+    pub side: OrderSide,
+    pub price: Price,
+    pub quantity: Volume,
+    /// Fee paid, in `fee_currency` when the exchange tells it
+    pub fee: Option<Amount>,
+    pub fee_currency: Option<Currency>,
+}
+
+/// New balance of a currency of the account, sent by private streams
+#[derive(Debug, Clone)]
+pub struct BalanceUpdate {
+    /// UNIX timestamp in ms (when the balance changed)
+    pub event_ms: i64,
+    pub currency: Currency,
+    /// Funds available for new orders
+    pub free: Amount,
+    /// Funds locked in open orders
+    pub locked: Amount,
+}
+
+/// Connection status changes of a live feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedStatus {
+    Connected,
+    Disconnected,
+    /// The channels were subscribed again after a reconnection
+    Resubscribed,
+    /// Nothing was received for a while, the data may be outdated
+    Stale,
+}
+
+/// Sent when the connection status of a live feed changes
+#[derive(Debug, Clone)]
+pub struct LiveFeedStatus {
+    /// UNIX timestamp in ms (when the status changed)
+    pub event_ms: i64,
+    pub status: FeedStatus,
+}
+
 #[derive(Message, Clone, Debug)]
 #[rtype(result = "()")]
 pub enum LiveEvent {
     LiveOrder(LiveOrder),
     LiveTrade(LiveTrade),
     LiveOrderbook(Orderbook),
+    LiveBookState(LiveBookState),
+    LiveFeedStatus(LiveFeedStatus),
+    LiveOrderUpdate(OrderUpdate),
+    LiveFill(Fill),
+    LiveBalanceUpdate(BalanceUpdate),
+    LiveTicker(Ticker),
+    LiveBestBidOffer(BestBidOffer),
+    LiveCandle(LiveCandle),
     Noop,
 }
 
@@ -275,8 +1189,335 @@ pub enum LiveEvent {
 #[rtype(result = "()")]
 pub struct LiveEventEnveloppe(pub Exchange, pub LiveEvent);
 
+lazy_static! {
+    static ref INTERNED_ASSETS: RwLock<HashSet<Arc<str>>> = RwLock::new(HashSet::new());
+    static ref LISTED_SYMBOLS: RwLock<ListedSymbols> = RwLock::new(ListedSymbols::default());
+    static ref EXCHANGE_NAMES: RwLock<ExchangeNames> = RwLock::new(ExchangeNames::default());
+}
+
+/// An asset (BTC, EUR, ...) known at runtime.
+/// The code is interned so that assets with the same code share it, an Asset is cheap to clone,
+/// hash and compare.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Asset(Arc<str>);
+
+impl Asset {
+    /// Return the interned asset for this code, codes are upper cased
+    pub fn new(code: &str) -> Asset {
+        let code = code.to_uppercase();
+        if let Some(interned) = INTERNED_ASSETS.read().unwrap().get(code.as_str()) {
+            return Asset(interned.clone());
+        }
+        let mut assets = INTERNED_ASSETS.write().unwrap();
+        // Another thread may have interned it since the read lock was released
+        if let Some(interned) = assets.get(code.as_str()) {
+            return Asset(interned.clone());
+        }
+        let interned: Arc<str> = Arc::from(code);
+        assets.insert(interned.clone());
+        Asset(interned)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Return the Currency enum associated to this asset, if there is one
+    pub fn currency(&self) -> Option<Currency> {
+        serde_json::from_str(&format!("\"{}\"", enum_variant_name(&self.0))).ok()
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for Asset {
+    fn from(code: &str) -> Self {
+        Asset::new(code)
+    }
+}
+
+impl From<Currency> for Asset {
+    fn from(currency: Currency) -> Self {
+        Asset::new(format!("{:?}", currency).trim_start_matches('_'))
+    }
+}
+
+impl Serialize for Asset {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Asset {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where D: Deserializer<'de> {
+        let code = String::deserialize(deserializer)?;
+        Ok(Asset::new(&code))
+    }
+}
+
+/// A tradable pair known at runtime, as opposed to the variants of the `Pair` enum.
+/// The normalized notation is BASE_QUOTE, so 1 BTC is worth BTC_EUR euros.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol {
+    pub base: Asset,
+    pub quote: Asset,
+}
+
+impl Symbol {
+    pub fn new(base: &str, quote: &str) -> Symbol {
+        Symbol {
+            base: Asset::new(base),
+            quote: Asset::new(quote),
+        }
+    }
+
+    /// Return the Pair enum variant associated to this symbol, if there is one.
+    /// Use `Pair::from` to get a `Pair` for any symbol.
+    pub fn pair(&self) -> Option<Pair> {
+        let name = format!("{}_{}", enum_variant_name(self.base.as_str()), self.quote);
+        serde_json::from_str(&format!("\"{}\"", name)).ok()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}_{}", self.base, self.quote)
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = crate::error::Error;
+
+    /// Parse a symbol written BASE_QUOTE, BASE/QUOTE or BASE-QUOTE
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(2, |c| c == '_' || c == '/' || c == '-');
+        match (parts.next(), parts.next()) {
+            (Some(base), Some(quote)) if !base.is_empty() && !quote.is_empty() => Ok(Symbol::new(base, quote)),
+            _ => Err(ErrorKind::InvalidFieldValue(s.to_string()).into()),
+        }
+    }
+}
+
+/// Dark pool pairs ("_d" suffix) map to the same symbol as their regular pair.
+/// `Pair::NONE` maps to a symbol with an empty quote.
+impl From<Pair> for Symbol {
+    fn from(pair: Pair) -> Self {
+        if let Pair::Listed(listed) = pair {
+            return listed.symbol();
+        }
+        let name = format!("{:?}", pair);
+        let name = name.trim_start_matches('_').trim_end_matches("_d");
+        let mut parts = name.splitn(2, '_');
+        let base = parts.next().unwrap_or_default();
+        let quote = parts.next().unwrap_or_default();
+        Symbol::new(base, quote)
+    }
+}
+
+/// Symbols without a `Pair` variant become `Pair::Listed`
+impl From<Symbol> for Pair {
+    fn from(symbol: Symbol) -> Self {
+        symbol.pair().unwrap_or_else(|| Pair::Listed(ListedSymbol::intern(symbol)))
+    }
+}
+
+#[derive(Debug, Default)]
+struct ListedSymbols {
+    symbols: Vec<Symbol>,
+    ids: HashMap<Symbol, u32>,
+}
+
+/// Handle of a symbol the `Pair` enum has no variant for, see `Pair::Listed`.
+/// Symbols are interned so that the handle, and the `Pair`, stay cheap to copy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ListedSymbol(u32);
+
+impl ListedSymbol {
+    fn intern(symbol: Symbol) -> ListedSymbol {
+        if let Some(id) = LISTED_SYMBOLS.read().unwrap().ids.get(&symbol) {
+            return ListedSymbol(*id);
+        }
+        let mut listed = LISTED_SYMBOLS.write().unwrap();
+        // Another thread may have interned it since the read lock was released
+        if let Some(id) = listed.ids.get(&symbol) {
+            return ListedSymbol(*id);
+        }
+        let id = listed.symbols.len() as u32;
+        listed.symbols.push(symbol.clone());
+        listed.ids.insert(symbol, id);
+        ListedSymbol(id)
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        LISTED_SYMBOLS.read().unwrap().symbols[self.0 as usize].clone()
+    }
+}
+
+impl fmt::Display for ListedSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+/// Exchange names of the markets loaded with `ExchangeApi::symbols`
+#[derive(Debug, Default)]
+struct ExchangeNames {
+    names: HashMap<(Exchange, Pair), String>,
+    pairs: HashMap<(Exchange, String), Pair>,
+}
+
+/// Return the name an exchange uses for a pair, among the markets loaded with
+/// `ExchangeApi::symbols`
+pub fn listed_pair_name(exchange: Exchange, pair: &Pair) -> Option<String> {
+    EXCHANGE_NAMES.read().unwrap().names.get(&(exchange, *pair)).cloned()
+}
+
+/// Return the pair associated to the name an exchange uses, among the markets loaded with
+/// `ExchangeApi::symbols`
+pub fn listed_pair(exchange: Exchange, name: &str) -> Option<Pair> {
+    EXCHANGE_NAMES.read().unwrap().pairs.get(&(exchange, name.to_string())).cloned()
+}
+
+impl Serialize for Symbol {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where D: Deserializer<'de> {
+        let s = String::deserialize(deserializer)?;
+        Symbol::from_str(&s).map_err(|e| de::Error::custom(format!("{}", e)))
+    }
+}
+
+/// Enum variants cannot start with a number, they are prefixed with "_" (1ST becomes _1ST)
+fn enum_variant_name(code: &str) -> String {
+    if code.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", code)
+    } else {
+        code.to_string()
+    }
+}
+
+/// Mapping between the normalized symbols and the names an exchange uses for them.
+/// It is loaded at runtime from the exchange info endpoints (see `ExchangeApi::symbols`) so that
+/// new listings can be traded without a new release.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolMap {
+    names: HashMap<Symbol, String>,
+    symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolMap {
+    pub fn new() -> SymbolMap {
+        SymbolMap::default()
+    }
+
+    pub fn insert(&mut self, symbol: Symbol, name: &str) {
+        self.names.insert(symbol.clone(), name.to_string());
+        self.symbols.insert(name.to_string(), symbol);
+    }
+
+    /// Make the markets of the exchange usable as a `Pair` by every API of the crate, including
+    /// the ones listed since the `Pair` enum was written
+    pub fn register(&self, exchange: Exchange) {
+        let mut registered = EXCHANGE_NAMES.write().unwrap();
+        for (symbol, name) in &self.names {
+            let pair = Pair::from(symbol.clone());
+            registered.names.insert((exchange, pair), name.clone());
+            registered.pairs.insert((exchange, name.clone()), pair);
+        }
+    }
+
+    /// Return the name used by the exchange for this symbol
+    pub fn name(&self, symbol: &Symbol) -> Option<&str> {
+        self.names.get(symbol).map(|name| name.as_str())
+    }
+
+    /// Return the symbol associated to the name used by the exchange
+    pub fn symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).cloned()
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.names.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Fees of the account on a market, as fractions of the traded amount (0.001 is 0.1%)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeTier {
+    pub maker: BigDecimal,
+    pub taker: BigDecimal,
+}
+
+/// Trading rules of a market, orders that do not follow them are rejected by the exchange.
+/// Rules the exchange does not publish are None.
+#[derive(Debug, Clone)]
+pub struct MarketInfo {
+    pub symbol: Symbol,
+    /// The name used by the exchange
+    pub name: String,
+    /// Smallest price increment
+    pub price_tick: Option<Price>,
+    /// Smallest quantity increment
+    pub quantity_step: Option<Volume>,
+    pub min_quantity: Option<Volume>,
+    pub max_quantity: Option<Volume>,
+    /// Minimum price * quantity of an order, in quote currency
+    pub min_notional: Option<Price>,
+    pub fee_tier: Option<FeeTier>,
+}
+
+impl MarketInfo {
+    pub fn new(symbol: Symbol, name: &str) -> MarketInfo {
+        MarketInfo {
+            symbol,
+            name: name.to_string(),
+            price_tick: None,
+            quantity_step: None,
+            min_quantity: None,
+            max_quantity: None,
+            min_notional: None,
+            fee_tier: None,
+        }
+    }
+
+    /// Round the price to the nearest price tick
+    pub fn round_price(&self, price: &Price) -> Price {
+        match self.price_tick {
+            Some(ref tick) => helpers::round_to_increment(price, tick),
+            None => price.clone(),
+        }
+    }
+
+    /// Round the quantity down to the quantity step, so that it never exceeds the requested one
+    pub fn round_quantity(&self, quantity: &Volume) -> Volume {
+        match self.quantity_step {
+            Some(ref step) => helpers::floor_to_increment(quantity, step),
+            None => quantity.clone(),
+        }
+    }
+}
+
+pub type Markets = HashMap<Symbol, MarketInfo>;
+
 /// Currency lists all currencies that can be traded on supported exchanges.
 /// Update date : 27/10/2017.
+/// Currencies listed since are only available as an `Asset`.
 /// Note : 1ST, 2GIVE, 8BIT have been renammed "_1ST", "_2GIVE" and "_8BIT" since variables name
 /// cannot start with a number.
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Deserialize, Serialize)]
@@ -594,6 +1835,7 @@ pub enum Currency {
 
 /// Pair lists all pairs that can be traded on supported exchanges.
 /// Update date : 27/10/2017.
+/// Pairs listed since are `Pair::Listed`, built from a `Symbol` (see `ExchangeApi::symbols`).
 ///
 /// Order of quote currency <-> base currency is important. For example, Kraken supports ZEC_BTC
 /// but Poloniex is doing the opposite inside their API: BTC_ZEC, which equal to 1/ZEC_BTC.
@@ -962,4 +2204,248 @@ pub enum Pair {
     ZEN_BTC,
     ZRX_BTC,
     ZRX_ETH,
+    /// A pair without a variant, such as a market listed after 27/10/2017
+    #[serde(skip)]
+    Listed(ListedSymbol),
+}
+
+#[cfg(test)]
+mod types_tests {
+    use super::*;
+
+    #[test]
+    fn should_intern_assets() {
+        let btc = Asset::new("btc");
+        assert_eq!(btc, Asset::new("BTC"));
+        let other = Asset::new("BTC");
+        assert!(std::ptr::eq(btc.as_str(), other.as_str()));
+    }
+
+    #[test]
+    fn should_convert_pairs_to_symbols() {
+        assert_eq!(Symbol::from(Pair::BTC_EUR), Symbol::new("BTC", "EUR"));
+        assert_eq!(Symbol::from(Pair::_1ST_BTC), Symbol::new("1ST", "BTC"));
+        assert_eq!(Symbol::from(Pair::BTC_EUR_d), Symbol::new("BTC", "EUR"));
+        assert_eq!(Pair::from(Symbol::new("1st", "btc")), Pair::_1ST_BTC);
+        let listed = Pair::from(Symbol::new("NEWCOIN", "BTC"));
+        assert_eq!(listed, Pair::from(Symbol::new("newcoin", "btc")));
+        assert_eq!(Symbol::from(listed), Symbol::new("NEWCOIN", "BTC"));
+        assert_eq!(listed.to_string(), "NEWCOIN_BTC");
+    }
+
+    #[test]
+    fn should_convert_currencies_to_assets() {
+        assert_eq!(Asset::from(Currency::_2GIVE).as_str(), "2GIVE");
+        assert_eq!(Asset::new("2give").currency(), Some(Currency::_2GIVE));
+        assert_eq!(Asset::new("NEWCOIN").currency(), None);
+    }
+
+    #[test]
+    fn should_round_to_market_rules() {
+        let mut market = MarketInfo::new(Symbol::new("BTC", "EUR"), "XXBTZEUR");
+        market.price_tick = Some(BigDecimal::from_str("0.5").unwrap());
+        market.quantity_step = Some(helpers::decimals_to_increment(3));
+
+        assert_eq!(market.round_price(&BigDecimal::from_str("7000.74").unwrap()), BigDecimal::from_str("7000.5").unwrap());
+        assert_eq!(market.round_price(&BigDecimal::from_str("7000.76").unwrap()), BigDecimal::from_str("7001").unwrap());
+        assert_eq!(market.round_quantity(&BigDecimal::from_str("0.12399").unwrap()), BigDecimal::from_str("0.123").unwrap());
+    }
+
+    #[test]
+    fn should_map_basic_order_requests() {
+        let limit = OrderRequest::limit(OrderSide::Sell, Pair::BTC_EUR, BigDecimal::from(1), BigDecimal::from(7000));
+        assert_eq!(limit.basic_order_type().unwrap(), OrderType::SellLimit);
+
+        match limit.clone().post_only(true).basic_order_type() {
+            Err(crate::error::Error(ErrorKind::Unsupported(_), _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match OrderRequest::new(OrderSide::Buy, OrderKind::Limit, Pair::BTC_EUR, BigDecimal::from(1)).check_prices() {
+            Err(crate::error::Error(ErrorKind::MissingPrice, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    fn levels(levels: &[(i32, i32)]) -> Vec<(Price, Volume)> {
+        levels.iter().map(|&(p, v)| (BigDecimal::from(p), BigDecimal::from(v))).collect()
+    }
+
+    fn sequenced_update(first: u64, last: u64, asks: &[(i32, i32)], bids: &[(i32, i32)]) -> BookUpdate {
+        BookUpdate { update_ids: Some((first, last)), asks: levels(asks), bids: levels(bids) }
+    }
+
+    #[test]
+    fn should_replace_book_levels() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_EUR);
+        assert_eq!(book.apply_snapshot(None, levels(&[(101, 1)]).into_iter(), levels(&[(99, 1)]).into_iter()), Some(BookState::Synced));
+        book.apply_update(BookUpdate { update_ids: None, asks: levels(&[(101, 3), (102, 1)]), bids: levels(&[(99, 0), (98, 2)]) });
+
+        let ob = book.order_book();
+        assert!(ob.asks.contains(&(BigDecimal::from(101), BigDecimal::from(3))));
+        assert_eq!(ob.bids, levels(&[(98, 2)]));
+    }
+
+    #[test]
+    fn should_align_intervals() {
+        // 2020-01-01T10:07:30Z, a wednesday
+        let ts = 1577873250000;
+        assert_eq!(Interval::FiveMinutes.open_time(ts), 1577873100000);
+        assert_eq!(Interval::OneDay.open_time(ts), 1577836800000);
+        // Monday 2019-12-30
+        assert_eq!(Interval::OneWeek.open_time(ts), 1577664000000);
+    }
+
+    #[test]
+    fn should_aggregate_candles_from_trades() {
+        let mut agg = CandleAggregator::new(Pair::BTC_EUR, Interval::OneMinute);
+        agg.add_trade(60_000, BigDecimal::from(10), BigDecimal::from(1));
+        agg.add_trade(61_000, BigDecimal::from(12), BigDecimal::from(1));
+        let candles = agg.add_trade(62_000, BigDecimal::from(9), BigDecimal::from(2));
+        assert_eq!(candles.len(), 1);
+        let c = &candles[0].candle;
+        assert!(!candles[0].closed);
+        assert_eq!((c.open.clone(), c.high.clone(), c.low.clone(), c.close.clone(), c.volume.clone()),
+                   (BigDecimal::from(10), BigDecimal::from(12), BigDecimal::from(9), BigDecimal::from(9), BigDecimal::from(4)));
+        // Late trades are ignored
+        assert!(agg.add_trade(59_000, BigDecimal::from(1), BigDecimal::from(1)).is_empty());
+
+        let candles = agg.add_trade(125_000, BigDecimal::from(11), BigDecimal::from(1));
+        assert_eq!(candles.len(), 2);
+        assert!(candles[0].closed);
+        assert_eq!(candles[0].candle.timestamp, 60_000);
+        assert_eq!(candles[1].candle.timestamp, 120_000);
+
+        // Trades of the interval still count during the grace period
+        assert!(agg.close_if_over(180_000).is_none());
+        assert_eq!(agg.add_trade(179_500, BigDecimal::from(13), BigDecimal::from(1))[0].candle.high, BigDecimal::from(13));
+        assert!(agg.close_if_over(180_000 + CANDLE_CLOSE_GRACE_MS - 1).is_none());
+        assert!(agg.close_if_over(180_000 + CANDLE_CLOSE_GRACE_MS).unwrap().closed);
+        // Trades of a closed candle arriving late do not open it again
+        assert!(agg.add_trade(179_000, BigDecimal::from(1), BigDecimal::from(1)).is_empty());
+    }
+
+    #[test]
+    fn should_close_native_candles() {
+        let candle = |timestamp: i64| Candle { timestamp, pair: Pair::BTC_EUR, interval: Interval::OneMinute, open: BigDecimal::from(1), high: BigDecimal::from(1), low: BigDecimal::from(1), close: BigDecimal::from(1), volume: BigDecimal::from(1) };
+        let mut agg = CandleAggregator::new(Pair::BTC_EUR, Interval::OneMinute);
+        assert_eq!(agg.update_candle(candle(60_000), false).len(), 1);
+        let candles = agg.update_candle(candle(120_000), false);
+        assert_eq!(candles.iter().map(|c| (c.candle.timestamp, c.closed)).collect::<Vec<_>>(), vec![(60_000, true), (120_000, false)]);
+        assert!(agg.update_candle(candle(60_000), true).is_empty());
+    }
+
+    #[test]
+    fn should_return_top_of_synced_book() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_EUR);
+        assert!(book.top_of_book().is_none());
+        book.apply_snapshot(None, levels(&[(102, 1), (101, 2)]).into_iter(), levels(&[(98, 1), (99, 3)]).into_iter());
+        assert_eq!(book.top_of_book(), Some(((BigDecimal::from(99), BigDecimal::from(3)), (BigDecimal::from(101), BigDecimal::from(2)))));
+    }
+
+    #[test]
+    fn should_buffer_updates_until_snapshot() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_EUR);
+        book.apply_update(sequenced_update(8, 10, &[(101, 5)], &[]));
+        book.apply_update(sequenced_update(11, 12, &[(102, 1)], &[]));
+        assert!(book.latest_order_book().is_none());
+
+        book.apply_snapshot(Some(10), levels(&[(101, 1)]).into_iter(), levels(&[(99, 1)]).into_iter());
+        assert_eq!(book.state, BookState::Synced);
+        assert_eq!(book.last_update_id, Some(12));
+        // The first update is already part of the snapshot
+        assert_eq!(book.order_book().asks, levels(&[(101, 1), (102, 1)]));
+    }
+
+    #[test]
+    fn should_resync_on_gaps() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_EUR);
+        book.apply_snapshot(Some(10), levels(&[(101, 1)]).into_iter(), levels(&[(99, 1)]).into_iter());
+        assert_eq!(book.apply_update(sequenced_update(11, 11, &[(101, 2)], &[])), None);
+        assert_eq!(book.apply_update(sequenced_update(13, 14, &[(101, 3)], &[])), Some(BookState::Resyncing));
+        assert!(book.latest_order_book().is_none());
+
+        book.apply_snapshot(Some(13), levels(&[(101, 4)]).into_iter(), levels(&[(99, 1)]).into_iter());
+        assert_eq!(book.state, BookState::Synced);
+        assert_eq!(book.order_book().asks, levels(&[(101, 3)]));
+    }
+
+    #[test]
+    fn should_list_the_best_levels_first() {
+        let settings = BookSettings { depth: 2, throttle_ms: 0, emission: BookEmission::AnyChange };
+        let mut book = LiveAggregatedOrderBook::new(Pair::BTC_EUR, &settings);
+        book.apply_snapshot(None, levels(&[(103, 1), (101, 1), (102, 1)]).into_iter(), levels(&[(97, 1), (99, 1), (98, 1)]).into_iter());
+        let ob = book.latest_order_book().unwrap();
+        assert_eq!(ob.asks, levels(&[(101, 1), (102, 1)]));
+        assert_eq!(ob.bids, levels(&[(99, 1), (98, 1)]));
+        assert_eq!(ob.asks[0].0, BigDecimal::from(101));
+        assert_eq!(ob.bids[0].0, BigDecimal::from(99));
+    }
+
+    #[test]
+    fn should_emit_books_by_settings() {
+        let settings = BookSettings { depth: 1, throttle_ms: 0, emission: BookEmission::AnyChange };
+        let mut book = LiveAggregatedOrderBook::new(Pair::BTC_EUR, &settings);
+        book.apply_snapshot(None, levels(&[(101, 1), (102, 1)]).into_iter(), levels(&[(99, 1), (98, 1)]).into_iter());
+        assert_eq!(book.latest_order_book().unwrap().asks, levels(&[(101, 1)]));
+        // Beyond the depth but still sent
+        book.apply_update(BookUpdate { update_ids: None, asks: levels(&[(102, 2)]), bids: vec![] });
+        assert!(book.latest_order_book().is_some());
+
+        let full = book.full_order_book();
+        assert_eq!(full.asks, levels(&[(101, 1), (102, 2)]));
+        assert_eq!(full.bids, levels(&[(99, 1), (98, 1)]));
+
+        book.emission = BookEmission::TopOfBook;
+        book.apply_update(BookUpdate { update_ids: None, asks: levels(&[(102, 3)]), bids: vec![] });
+        assert!(book.latest_order_book().is_none());
+    }
+
+    #[test]
+    fn should_throttle_books_on_the_feed_clock() {
+        let settings = BookSettings { depth: 1, throttle_ms: 50, emission: BookEmission::AnyChange };
+        let books = LiveBooks::new(settings);
+        books.clock().set(1_000);
+        let first = books.with_book(Pair::BTC_EUR, |agg| {
+            agg.apply_snapshot(None, levels(&[(101, 1)]).into_iter(), levels(&[(99, 1)]).into_iter());
+            agg.latest_order_book()
+        });
+        assert_eq!(first.unwrap().timestamp, 1_000);
+        books.clock().set(1_049);
+        assert!(books.with_book(Pair::BTC_EUR, |agg| agg.latest_order_book()).is_none());
+        books.clock().set(1_050);
+        assert_eq!(books.with_book(Pair::BTC_EUR, |agg| agg.latest_order_book()).unwrap().timestamp, 1_050);
+    }
+
+    #[test]
+    fn should_flush_throttled_books() {
+        actix_rt::System::new("test").block_on(async {
+            let settings = BookSettings { depth: 1, throttle_ms: 50, emission: BookEmission::AnyChange };
+            let books = LiveBooks::new(settings);
+            let flushed = Rc::new(RefCell::new(vec![]));
+            let sink = flushed.clone();
+            books.on_flush(move |ob| sink.borrow_mut().push(ob));
+            let first = books.with_book(Pair::BTC_EUR, |agg| {
+                agg.apply_snapshot(None, levels(&[(101, 1)]).into_iter(), levels(&[(99, 1)]).into_iter());
+                agg.latest_order_book()
+            });
+            assert!(first.is_some());
+            let throttled = books.with_book(Pair::BTC_EUR, |agg| {
+                agg.apply_update(BookUpdate { update_ids: None, asks: levels(&[(100, 2)]), bids: vec![] });
+                agg.latest_order_book()
+            });
+            assert!(throttled.is_none());
+            assert!(flushed.borrow().is_empty());
+
+            tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+            assert_eq!(flushed.borrow().len(), 1);
+            assert_eq!(flushed.borrow()[0].asks, levels(&[(100, 2)]));
+        });
+    }
+
+    #[test]
+    fn should_parse_symbols() {
+        assert_eq!("ETH/BTC".parse::<Symbol>().unwrap(), Symbol::new("ETH", "BTC"));
+        assert_eq!("eth-btc".parse::<Symbol>().unwrap(), Symbol::new("ETH", "BTC"));
+        assert!("ETHBTC".parse::<Symbol>().is_err());
+    }
 }
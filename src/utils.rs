@@ -1,6 +1,6 @@
 use crate::error::*;
 use crate::exchange::Exchange;
-use crate::types::Pair;
+use crate::types::{self, Pair};
 
 pub fn pair_fn(xchg: Exchange) -> fn(&Pair) -> Option<&&str> {
     match xchg {
@@ -13,9 +13,37 @@ pub fn pair_fn(xchg: Exchange) -> fn(&Pair) -> Option<&&str> {
     }
 }
 
-pub fn pair_or(xchg: Exchange, pair: &Pair) -> Result<&&str>{
-    let pairs_fn : fn(&Pair) -> Option<&&str> = pair_fn(xchg);
-    match pairs_fn(pair) {
+pub fn pair_enum_fn(xchg: Exchange) -> fn(&str) -> Option<&Pair> {
+    match xchg {
+        Exchange::Bittrex => crate::bittrex::utils::get_pair_enum,
+        Exchange::Bitstamp => crate::bitstamp::utils::get_pair_enum,
+        Exchange::Gdax => crate::gdax::utils::get_pair_enum,
+        Exchange::Kraken => crate::kraken::utils::get_pair_enum,
+        Exchange::Poloniex => crate::poloniex::utils::get_pair_enum,
+        Exchange::Binance => crate::binance::utils::get_pair_enum,
+    }
+}
+
+/// Return the name used by the exchange for the pair.
+/// Pairs the exchange module does not know are searched in the markets loaded with
+/// `ExchangeApi::symbols`.
+pub fn pair_name(xchg: Exchange, pair: &Pair) -> Option<String> {
+    match pair_fn(xchg)(pair) {
+        Some(name) => Some(name.to_string()),
+        None => types::listed_pair_name(xchg, pair),
+    }
+}
+
+/// Return the pair associated to the name used by the exchange, see `pair_name`
+pub fn pair_enum(xchg: Exchange, name: &str) -> Option<Pair> {
+    match pair_enum_fn(xchg)(name) {
+        Some(pair) => Some(*pair),
+        None => types::listed_pair(xchg, name),
+    }
+}
+
+pub fn pair_or(xchg: Exchange, pair: &Pair) -> Result<String> {
+    match pair_name(xchg, pair) {
         Some(name) => Ok(name),
         None => Err(ErrorKind::PairUnsupported.into()),
     }
@@ -0,0 +1,411 @@
+//! Pre-trade checks run before an order is sent to the exchange.
+//! Orders that break the market rules or that the account cannot afford are rejected with the
+//! same `ErrorKind` the exchange would answer, without any call to the order endpoint.
+
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::error::*;
+use crate::exchange::ExchangeApi;
+use crate::helpers;
+use crate::types::*;
+
+/// Check an order against the market rules and the account balances.
+/// The price of a market order is its estimated fill price, such as the best price of the book.
+/// Checks are skipped when their data is missing: no market info, a rule the exchange does not
+/// publish, a market order without an estimated price, or a currency the `Currency` enum does
+/// not know.
+pub fn check_order(order_type: OrderType,
+                   pair: Pair,
+                   quantity: &Volume,
+                   price: Option<&Price>,
+                   market: Option<&MarketInfo>,
+                   balances: Option<&Balances>)
+                   -> Result<()> {
+    let is_limit = match order_type {
+        OrderType::BuyLimit | OrderType::SellLimit => true,
+        OrderType::BuyMarket | OrderType::SellMarket => false,
+    };
+    if is_limit && price.is_none() {
+        return Err(ErrorKind::MissingPrice.into());
+    }
+
+    if *quantity <= BigDecimal::zero() {
+        return Err(ErrorKind::InsufficientOrderSize.into());
+    }
+    if let Some(price) = price {
+        if *price <= BigDecimal::zero() {
+            return Err(ErrorKind::InvalidFieldValue("price".to_string()).into());
+        }
+    }
+
+    if let Some(market) = market {
+        check_market_rules(market, quantity, price, is_limit)?;
+    }
+
+    if let Some(balances) = balances {
+        check_funds(order_type, pair, quantity, price, balances)?;
+    }
+
+    Ok(())
+}
+
+fn check_market_rules(market: &MarketInfo, quantity: &Volume, price: Option<&Price>, is_limit: bool) -> Result<()> {
+    if let Some(ref min_quantity) = market.min_quantity {
+        if quantity < min_quantity {
+            return Err(ErrorKind::InsufficientOrderSize.into());
+        }
+    }
+    if let Some(ref max_quantity) = market.max_quantity {
+        if !max_quantity.is_zero() && quantity > max_quantity {
+            return Err(ErrorKind::InvalidFieldValue("quantity".to_string()).into());
+        }
+    }
+    if let Some(ref step) = market.quantity_step {
+        if helpers::floor_to_increment(quantity, step) != *quantity {
+            return Err(ErrorKind::InvalidFieldValue("quantity".to_string()).into());
+        }
+    }
+
+    if let Some(price) = price {
+        if let (Some(tick), true) = (&market.price_tick, is_limit) {
+            if helpers::floor_to_increment(price, tick) != *price {
+                return Err(ErrorKind::InvalidFieldValue("price".to_string()).into());
+            }
+        }
+        if let Some(ref min_notional) = market.min_notional {
+            if &(price * quantity) < min_notional {
+                return Err(ErrorKind::InsufficientOrderSize.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_funds(order_type: OrderType,
+               pair: Pair,
+               quantity: &Volume,
+               price: Option<&Price>,
+               balances: &Balances)
+               -> Result<()> {
+    let symbol = Symbol::from(pair);
+    // The cost of a market buy without an estimated price is unknown until it is filled
+    let (asset, needed) = match (order_type, price) {
+        (OrderType::BuyLimit, Some(price)) | (OrderType::BuyMarket, Some(price)) => (symbol.quote, price * quantity),
+        (OrderType::SellLimit, _) | (OrderType::SellMarket, _) => (symbol.base, quantity.clone()),
+        _ => return Ok(()),
+    };
+
+    let currency = match asset.currency() {
+        Some(currency) => currency,
+        None => return Ok(()),
+    };
+    let available = balances.get(&currency).cloned().unwrap_or_else(BigDecimal::zero);
+    if available < needed {
+        return Err(ErrorKind::InsufficientFunds.into());
+    }
+    Ok(())
+}
+
+/// Validation stage in front of `ExchangeApi::add_order` and `ExchangeApi::place_order`, run by
+/// the apis of `Coinnect::new` (see `ValidatedApi`).
+/// Market rules are loaded once from `ExchangeApi::markets`, balances are fetched before every
+/// order unless disabled. Market orders are priced at the best price of the ticker, they are
+/// rejected when it cannot be fetched.
+#[derive(Debug)]
+pub struct OrderValidator {
+    markets: Option<Markets>,
+    check_balances: bool,
+}
+
+impl OrderValidator {
+    pub fn new() -> OrderValidator {
+        OrderValidator {
+            markets: None,
+            check_balances: true,
+        }
+    }
+
+    /// Balances cost one more request per order, this function enables or disables the
+    /// InsufficientFunds check.
+    pub fn set_check_balances(&mut self, check_balances: bool) {
+        self.check_balances = check_balances
+    }
+
+    /// Forget the cached market rules, they are loaded again on the next order
+    pub fn reload_markets(&mut self) {
+        self.markets = None
+    }
+
+    /// Check the order, see `check_order`. The price of market orders is ignored.
+    pub async fn validate<A>(&mut self,
+                             api: &mut A,
+                             order_type: OrderType,
+                             pair: Pair,
+                             quantity: &Volume,
+                             price: Option<&Price>)
+                             -> Result<()>
+        where A: ExchangeApi + ?Sized {
+        let price = match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => CheckedPrice::Limit(price),
+            OrderType::BuyMarket | OrderType::SellMarket => CheckedPrice::Market(None),
+        };
+        self.check(api, order_type, pair, quantity, price).await
+    }
+
+    /// Check the order of `ExchangeApi::place_order`, see `check_order`. Stop orders sent as
+    /// market orders are priced at their stop price.
+    pub async fn validate_request<A>(&mut self, api: &mut A, order: &OrderRequest) -> Result<()>
+        where A: ExchangeApi + ?Sized {
+        order.check_prices()?;
+        let (limit_type, market_type) = match order.side {
+            OrderSide::Buy => (OrderType::BuyLimit, OrderType::BuyMarket),
+            OrderSide::Sell => (OrderType::SellLimit, OrderType::SellMarket),
+        };
+        let (order_type, price) = match (order.kind, &order.price) {
+            (OrderKind::Market, _) => (market_type, CheckedPrice::Market(None)),
+            (_, Some(price)) => (limit_type, CheckedPrice::Limit(Some(price))),
+            (_, None) => (market_type, CheckedPrice::Market(order.stop_price.as_ref())),
+        };
+        self.check(api, order_type, order.pair, &order.quantity, price).await
+    }
+
+    async fn check<A>(&mut self,
+                      api: &mut A,
+                      order_type: OrderType,
+                      pair: Pair,
+                      quantity: &Volume,
+                      price: CheckedPrice<'_>)
+                      -> Result<()>
+        where A: ExchangeApi + ?Sized {
+        let limit_price = match price {
+            CheckedPrice::Limit(price) => price,
+            CheckedPrice::Market(_) => None,
+        };
+        // Check what does not need a request first
+        check_order(order_type, pair, quantity, limit_price, None, None)?;
+
+        if self.markets.is_none() {
+            self.markets = match api.markets().await {
+                Ok(markets) => Some(markets),
+                Err(Error(ErrorKind::Unsupported(_), _)) => Some(Markets::new()),
+                Err(e) => return Err(e),
+            };
+        }
+        let market = self.markets.as_ref().and_then(|markets| markets.get(&Symbol::from(pair)));
+
+        let price = match price {
+            CheckedPrice::Limit(price) | CheckedPrice::Market(price @ Some(_)) => price.cloned(),
+            CheckedPrice::Market(None) => {
+                let ticker = api.ticker(pair).await?;
+                match order_type {
+                    OrderType::BuyMarket => Some(ticker.lowest_ask),
+                    _ => Some(ticker.highest_bid),
+                }
+            }
+        };
+
+        let balances = if self.check_balances {
+            Some(api.balances().await?)
+        } else {
+            None
+        };
+
+        check_order(order_type, pair, quantity, price.as_ref(), market, balances.as_ref())
+    }
+
+    /// Validate the order then place it with `ExchangeApi::add_order`
+    pub async fn add_order<A>(&mut self,
+                              api: &mut A,
+                              order_type: OrderType,
+                              pair: Pair,
+                              quantity: Volume,
+                              price: Option<Price>)
+                              -> Result<OrderInfo>
+        where A: ExchangeApi + ?Sized {
+        self.validate(api, order_type, pair, &quantity, price.as_ref()).await?;
+        api.add_order(order_type, pair, quantity, price).await
+    }
+
+    /// Validate the order then place it with `ExchangeApi::place_order`
+    pub async fn place_order<A>(&mut self, api: &mut A, order: OrderRequest) -> Result<OrderInfo>
+        where A: ExchangeApi + ?Sized {
+        self.validate_request(api, &order).await?;
+        api.place_order(order).await
+    }
+}
+
+/// Price an order is checked with
+#[derive(Debug, Clone, Copy)]
+enum CheckedPrice<'a> {
+    /// The limit price
+    Limit(Option<&'a Price>),
+    /// The estimated fill price of a market order, the best price of the ticker when None
+    Market(Option<&'a Price>),
+}
+
+impl Default for OrderValidator {
+    fn default() -> Self {
+        OrderValidator::new()
+    }
+}
+
+/// An exchange api whose orders are checked by an `OrderValidator` before being sent, as
+/// returned by `Coinnect::new`. Use `Coinnect::new_unvalidated` to send orders unchecked.
+#[derive(Debug)]
+pub struct ValidatedApi {
+    api: Box<dyn ExchangeApi + Send>,
+    validator: OrderValidator,
+}
+
+impl ValidatedApi {
+    pub fn new(api: Box<dyn ExchangeApi + Send>) -> ValidatedApi {
+        ValidatedApi { api, validator: OrderValidator::new() }
+    }
+
+    pub fn validator(&mut self) -> &mut OrderValidator {
+        &mut self.validator
+    }
+
+    /// Return the api without the validation stage
+    pub fn into_inner(self) -> Box<dyn ExchangeApi + Send> {
+        self.api
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for ValidatedApi {
+    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
+        self.api.ticker(pair).await
+    }
+
+    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
+        self.api.orderbook(pair).await
+    }
+
+    async fn add_order(&mut self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+        self.validator.add_order(&mut *self.api, order_type, pair, quantity, price).await
+    }
+
+    async fn place_order(&mut self, order: OrderRequest) -> Result<OrderInfo> {
+        self.validator.place_order(&mut *self.api, order).await
+    }
+
+    async fn balances(&mut self) -> Result<Balances> {
+        self.api.balances().await
+    }
+
+    async fn cancel_order(&mut self, pair: Pair, order: &OrderInfo) -> Result<OrderInfo> {
+        self.api.cancel_order(pair, order).await
+    }
+
+    async fn order_status(&mut self, pair: Pair, order: &OrderInfo) -> Result<Order> {
+        self.api.order_status(pair, order).await
+    }
+
+    async fn open_orders(&mut self, pair: Pair) -> Result<Vec<Order>> {
+        self.api.open_orders(pair).await
+    }
+
+    async fn recent_trades(&mut self, pair: Pair, since: Option<i64>) -> Result<Vec<Trade>> {
+        self.api.recent_trades(pair, since).await
+    }
+
+    async fn candles(&mut self, pair: Pair, interval: Interval, start: Option<i64>, end: Option<i64>) -> Result<Vec<Candle>> {
+        self.api.candles(pair, interval, start, end).await
+    }
+
+    async fn symbols(&mut self) -> Result<SymbolMap> {
+        self.api.symbols().await
+    }
+
+    async fn markets(&mut self) -> Result<Markets> {
+        self.api.markets().await
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+    use crate::test_util::decimal;
+
+    fn btc_eur_market() -> MarketInfo {
+        let mut market = MarketInfo::new(Symbol::new("BTC", "EUR"), "XXBTZEUR");
+        market.price_tick = Some(decimal("0.1"));
+        market.quantity_step = Some(decimal("0.001"));
+        market.min_quantity = Some(decimal("0.002"));
+        market.min_notional = Some(decimal("10"));
+        market
+    }
+
+    #[test]
+    fn should_require_a_price_for_limit_orders() {
+        let result = check_order(OrderType::BuyLimit, Pair::BTC_EUR, &decimal("1"), None, None, None);
+        match result {
+            Err(Error(ErrorKind::MissingPrice, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(check_order(OrderType::BuyMarket, Pair::BTC_EUR, &decimal("1"), None, None, None).is_ok());
+    }
+
+    #[test]
+    fn should_apply_market_rules() {
+        let market = btc_eur_market();
+        let check = |quantity: &str, price: &str| {
+            check_order(OrderType::SellLimit, Pair::BTC_EUR, &decimal(quantity), Some(&decimal(price)), Some(&market), None)
+        };
+
+        assert!(check("0.005", "7000.1").is_ok());
+        match check("0.001", "7000") {
+            Err(Error(ErrorKind::InsufficientOrderSize, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match check("0.0055", "7000") {
+            Err(Error(ErrorKind::InvalidFieldValue(ref field), _)) if field == "quantity" => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match check("0.005", "7000.05") {
+            Err(Error(ErrorKind::InvalidFieldValue(ref field), _)) if field == "price" => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match check("0.002", "100") {
+            Err(Error(ErrorKind::InsufficientOrderSize, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_check_market_orders_at_their_estimated_price() {
+        let market = btc_eur_market();
+        match check_order(OrderType::BuyMarket, Pair::BTC_EUR, &decimal("0.002"), Some(&decimal("100")), Some(&market), None) {
+            Err(Error(ErrorKind::InsufficientOrderSize, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        // The estimate is not a limit price, it does not have to be on the price tick
+        assert!(check_order(OrderType::BuyMarket, Pair::BTC_EUR, &decimal("0.002"), Some(&decimal("7000.05")), Some(&market), None).is_ok());
+
+        let mut balances = Balances::new();
+        balances.insert(Currency::EUR, decimal("10"));
+        match check_order(OrderType::BuyMarket, Pair::BTC_EUR, &decimal("0.002"), Some(&decimal("7000")), None, Some(&balances)) {
+            Err(Error(ErrorKind::InsufficientFunds, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_check_available_funds() {
+        let mut balances = Balances::new();
+        balances.insert(Currency::EUR, decimal("100"));
+        balances.insert(Currency::BTC, decimal("0.5"));
+
+        assert!(check_order(OrderType::BuyLimit, Pair::BTC_EUR, &decimal("0.01"), Some(&decimal("7000")), None, Some(&balances)).is_ok());
+        match check_order(OrderType::BuyLimit, Pair::BTC_EUR, &decimal("1"), Some(&decimal("7000")), None, Some(&balances)) {
+            Err(Error(ErrorKind::InsufficientFunds, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match check_order(OrderType::SellMarket, Pair::BTC_EUR, &decimal("1"), None, None, Some(&balances)) {
+            Err(Error(ErrorKind::InsufficientFunds, _)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "mock_server")]
+
+#[cfg(test)]
+mod mock_server_tests {
+    use actix_web::http::Method;
+    use awc::error::WsProtocolError;
+    use awc::ws::{Frame, Message};
+    use futures::{SinkExt, StreamExt};
+    use serde_json::json;
+
+    use coinnect_rt::bitstamp::{BitstampApi, BitstampCreds};
+    use coinnect_rt::bittrex::{BittrexApi, BittrexCreds};
+    use coinnect_rt::error::ErrorKind;
+    use coinnect_rt::exchange::Exchange;
+    use coinnect_rt::helpers;
+    use coinnect_rt::kraken::{KrakenApi, KrakenCreds};
+    use coinnect_rt::mock_server::{MockResponse, MockServer};
+    use coinnect_rt::poloniex::{PoloniexApi, PoloniexCreds};
+    use coinnect_rt::transport::HttpClient;
+
+    #[test]
+    fn kraken_private_calls_are_signed() {
+        actix_rt::System::new("test").block_on(async {
+            let server = MockServer::start(Exchange::Kraken).unwrap();
+            server.set_credentials("key", "c2VjcmV0", "");
+            server.on(Method::GET, "/0/public/Time", MockResponse::json(json!({"error": [], "result": {"unixtime": 1534614248}})));
+            server.on(Method::POST, "/0/private/Balance", MockResponse::json(json!({"error": [], "result": {"XXBT": "1.5"}})));
+
+            let mut api = KrakenApi::new(KrakenCreds::new("mock", "key", "c2VjcmV0")).unwrap();
+            api.set_base_url(&server.url());
+            let time = api.get_server_time().await.unwrap();
+            assert_eq!(time["result"]["unixtime"], 1534614248);
+            let balance = api.get_account_balance().await.unwrap();
+            assert_eq!(balance["result"]["XXBT"], "1.5");
+
+            let mut api = KrakenApi::new(KrakenCreds::new("mock", "key", "b3RoZXI=")).unwrap();
+            api.set_base_url(&server.url());
+            let rejected = api.get_account_balance().await.unwrap();
+            assert_eq!(rejected["error"][0], "EAPI:Invalid signature");
+            assert_eq!(server.requests().len(), 3);
+            server.stop().await;
+        });
+    }
+
+    #[test]
+    fn bitstamp_rejects_bad_credentials() {
+        actix_rt::System::new("test").block_on(async {
+            let server = MockServer::start(Exchange::Bitstamp).unwrap();
+            server.set_credentials("key", "secret", "42");
+            server.on(Method::POST, "/api/v2/balance/", MockResponse::json(json!({"btc_balance": "0.5"})));
+
+            let mut api = BitstampApi::new(BitstampCreds::new("mock", "key", "secret", "42")).unwrap();
+            api.set_base_url(&server.url());
+            assert_eq!(api.return_balances().await.unwrap()["btc_balance"], "0.5");
+
+            let mut api = BitstampApi::new(BitstampCreds::new("mock", "key", "secret", "43")).unwrap();
+            api.set_base_url(&server.url());
+            match api.return_balances().await {
+                Err(e) => match e.kind() {
+                    ErrorKind::BadCredentials => (),
+                    kind => panic!("unexpected error {:?}", kind),
+                },
+                Ok(r) => panic!("accepted {:?}", r),
+            }
+            server.stop().await;
+        });
+    }
+
+    #[test]
+    fn poloniex_private_calls_are_signed() {
+        actix_rt::System::new("test").block_on(async {
+            let server = MockServer::start(Exchange::Poloniex).unwrap();
+            server.set_credentials("key", "secret", "");
+            server.on(Method::POST, "/tradingApi", MockResponse::json(json!({"BTC": "0.5"})));
+
+            let mut api = PoloniexApi::new(PoloniexCreds::new("mock", "key", "secret")).unwrap();
+            api.set_base_url(&server.url());
+            assert_eq!(api.return_balances().await.unwrap()["BTC"], "0.5");
+
+            let mut api = PoloniexApi::new(PoloniexCreds::new("mock", "key", "other")).unwrap();
+            api.set_base_url(&server.url());
+            assert_eq!(api.return_balances().await.unwrap()["error"], "Invalid API key/secret pair.");
+            server.stop().await;
+        });
+    }
+
+    #[test]
+    fn bittrex_private_calls_are_signed() {
+        actix_rt::System::new("test").block_on(async {
+            let server = MockServer::start(Exchange::Bittrex).unwrap();
+            server.set_credentials("key", "secret", "");
+            server.on(Method::POST, "/api/v1.1/account/getbalances", MockResponse::json(json!({"success": true, "message": "", "result": [{"Currency": "BTC", "Balance": 0.5}]})));
+
+            let mut api = BittrexApi::new(BittrexCreds::new("mock", "key", "secret")).unwrap();
+            api.set_base_url(&server.url());
+            assert_eq!(api.get_balances().await.unwrap()["result"][0]["Currency"], "BTC");
+
+            let mut api = BittrexApi::new(BittrexCreds::new("mock", "key", "other")).unwrap();
+            api.set_base_url(&server.url());
+            assert_eq!(api.get_balances().await.unwrap()["message"], "INVALID_SIGNATURE");
+            server.stop().await;
+        });
+    }
+
+    #[test]
+    fn websocket_answers_scripted_frames() {
+        actix_rt::System::new("test").block_on(async {
+            let server = MockServer::start(Exchange::Kraken).unwrap();
+            server.on_ws_connect(vec![r#"{"event":"systemStatus","status":"online"}"#.to_string()]);
+            server.on_ws_message("subscribe", vec![r#"{"event":"subscriptionStatus","status":"subscribed"}"#.to_string()]);
+
+            let mut ws = helpers::new_ws_client(server.ws_url()).await.unwrap();
+            let text = |frame: Option<Result<Frame, WsProtocolError>>| match frame {
+                Some(Ok(Frame::Text(bytes))) => String::from_utf8(bytes.to_vec()).unwrap(),
+                other => panic!("unexpected frame {:?}", other),
+            };
+            assert!(text(ws.next().await).contains("systemStatus"));
+            ws.send(Message::Text(r#"{"event":"subscribe"}"#.to_string())).await.unwrap();
+            assert!(text(ws.next().await).contains("subscribed"));
+            server.send_ws(r#"{"event":"heartbeat"}"#);
+            assert!(text(ws.next().await).contains("heartbeat"));
+            assert_eq!(server.ws_messages(), vec![r#"{"event":"subscribe"}"#.to_string()]);
+            server.stop().await;
+        });
+    }
+}